@@ -0,0 +1,320 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::bucket::expired_buckets;
+use crate::exit::{self, Exit};
+use crate::fsutil::dir_size;
+use crate::identity::Identity;
+use crate::journal::{self, Journal, JournalOptOut};
+use crate::lock::LockArgs;
+use crate::output::{stderr, stdout};
+use crate::units::{format_size, parse_retention, Retention};
+
+#[derive(clap::Args, Debug)]
+pub struct ArchiveArgs {
+    /// Directory containing the month directories
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Archive months that ended more than this long ago (e.g. 90d, 12m, 3y)
+    #[arg(long, value_parser = parse_retention)]
+    older_than: Retention,
+
+    /// Archive format
+    #[arg(long, value_enum, default_value = "tar.zst")]
+    format: ArchiveFormat,
+
+    /// Encrypt each archive with the system `age` before the originals are
+    /// removed: age:<recipient> for a public key, or age:@<file> for a file
+    /// of recipients
+    #[arg(long, value_name = "SCHEME", value_parser = parse_encryption)]
+    encrypt: Option<Encryption>,
+
+    /// Show what would be archived without changing anything
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Record every archived month in .chronoban/journal.jsonl inside the
+    /// directory
+    #[arg(long)]
+    journal: bool,
+
+    #[command(flatten)]
+    unjournaled: JournalOptOut,
+
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+pub async fn run(args: ArchiveArgs) -> Result<Exit> {
+    let path = tokio::fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    let _lock = if args.dry_run { None } else { args.lock.acquire(&path).await? };
+    // The originals are deleted once archived
+    let journal = match args.dry_run {
+        true => None,
+        false => {
+            args.unjournaled.require("archive", args.journal)?;
+            args.journal.then(|| Journal::open(&path, &journal::new_run_id(), &Identity::current())).transpose()?
+        }
+    };
+
+    let cutoff = args.older_than.cutoff(Local::now().date_naive()).context("--older-than reaches back past any date")?;
+    let buckets = expired_buckets(&path, cutoff).await?;
+
+    stdout!("Archiving month directories in: {}", path.display());
+    stdout!("Cutoff: months ending before {}", cutoff);
+    if args.dry_run {
+        stdout!("🔍 DRY RUN MODE - Nothing will be archived");
+    }
+    stdout!();
+
+    let mut archived = 0;
+    let mut errors = 0;
+    let mut original_bytes = 0;
+    let mut archive_bytes = 0;
+
+    for bucket in buckets {
+        if exit::interrupted() {
+            break;
+        }
+        let size = dir_size(&bucket).await;
+        let target = archive_path(&bucket, args.format);
+        let target = match &args.encrypt {
+            Some(encryption) => encryption.path(&target),
+            None => target,
+        };
+        if args.dry_run {
+            stdout!("🗜️  Would archive: {} ({}) -> {}", bucket.display(), format_size(size), target.display());
+            archived += 1;
+            original_bytes += size;
+            continue;
+        }
+
+        let result = async {
+            if target.exists() {
+                anyhow::bail!("Archive already exists: {}", target.display());
+            }
+            let mut archive = create_verified(&bucket, args.format).await?;
+            if let Some(encryption) = &args.encrypt {
+                archive = encryption.encrypt(&archive).await?;
+            }
+            tokio::fs::remove_dir_all(&bucket)
+                .await
+                .with_context(|| format!("Failed to delete {:?} after archiving", bucket))?;
+            Ok::<_, anyhow::Error>(archive)
+        };
+
+        match result.await {
+            Ok(archive) => {
+                if let Some(journal) = &journal {
+                    journal.record_prune(&bucket, "archive", Some(&archive))?;
+                }
+                let packed = tokio::fs::metadata(&archive).await.map(|m| m.len()).unwrap_or(0);
+                stdout!("🗜️  Archived: {} ({}) -> {} ({})",
+                    bucket.display(), format_size(size), archive.display(), format_size(packed));
+                archived += 1;
+                original_bytes += size;
+                archive_bytes += packed;
+            }
+            Err(e) => {
+                stderr!("❌ Error archiving {}: {:#}", bucket.display(), e);
+                errors += 1;
+            }
+        }
+    }
+
+    stdout!("\n📊 Summary:");
+    stdout!("  Months archived: {}", archived);
+    stdout!("  Original size: {}", format_size(original_bytes));
+    if !args.dry_run {
+        stdout!("  Archive size: {}", format_size(archive_bytes));
+    }
+    stdout!("  Errors: {}", errors);
+
+    Ok(Exit::from_failures(errors))
+}
+
+/// Archive formats, produced by the system `tar`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormat {
+    #[value(name = "tar")]
+    Tar,
+    #[value(name = "tar.gz")]
+    TarGz,
+    #[value(name = "tar.xz")]
+    TarXz,
+    #[value(name = "tar.zst")]
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::TarZst => "tar.zst",
+        }
+    }
+
+    fn compress_flag(self) -> Option<&'static str> {
+        match self {
+            ArchiveFormat::Tar => None,
+            ArchiveFormat::TarGz => Some("--gzip"),
+            ArchiveFormat::TarXz => Some("--xz"),
+            ArchiveFormat::TarZst => Some("--zstd"),
+        }
+    }
+}
+
+/// How `--encrypt` seals an archive.
+#[derive(Clone, Debug)]
+pub enum Encryption {
+    /// age, to a recipient or a file of them.
+    Age(AgeRecipient),
+}
+
+#[derive(Clone, Debug)]
+pub enum AgeRecipient {
+    Key(String),
+    File(PathBuf),
+}
+
+fn parse_encryption(s: &str) -> Result<Encryption, String> {
+    let (scheme, recipient) = s.split_once(':').ok_or_else(|| format!("expected age:<recipient>, not {:?}", s))?;
+    match scheme {
+        "age" if recipient.is_empty() => Err("age: needs a recipient".to_string()),
+        "age" => Ok(Encryption::Age(match recipient.strip_prefix('@') {
+            Some(file) => AgeRecipient::File(PathBuf::from(file)),
+            None => AgeRecipient::Key(recipient.to_string()),
+        })),
+        other => Err(format!("unknown encryption {:?}, expected age", other)),
+    }
+}
+
+impl Encryption {
+    /// Where the encrypted copy of `archive` goes.
+    pub fn path(&self, archive: &Path) -> PathBuf {
+        match self {
+            Encryption::Age(_) => {
+                let mut name = archive.as_os_str().to_owned();
+                name.push(".age");
+                PathBuf::from(name)
+            }
+        }
+    }
+
+    /// Encrypt `archive`, which has already been verified, and replace it
+    /// with the encrypted copy.
+    pub async fn encrypt(&self, archive: &Path) -> Result<PathBuf> {
+        let sealed = self.path(archive);
+        let Encryption::Age(recipient) = self;
+        let mut age = Command::new("age");
+        match recipient {
+            AgeRecipient::Key(key) => age.arg("-r").arg(key),
+            AgeRecipient::File(file) => age.arg("-R").arg(file),
+        };
+        let status = age.arg("-o").arg(&sealed).arg(archive).status().await;
+        let sealed_ok = match status {
+            Ok(status) if status.success() => tokio::fs::metadata(&sealed).await.is_ok_and(|m| m.len() > 0),
+            _ => false,
+        };
+        // Neither the plaintext archive nor a half-written one is kept
+        let _ = tokio::fs::remove_file(archive).await;
+        if !sealed_ok {
+            let _ = tokio::fs::remove_file(&sealed).await;
+            status.context("Failed to run age")?;
+            anyhow::bail!("age failed to encrypt {}", archive.display());
+        }
+        Ok(sealed)
+    }
+}
+
+/// Where the archive of `dir` is written: next to it, named after it.
+pub fn archive_path(dir: &Path, format: ArchiveFormat) -> PathBuf {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    dir.with_file_name(format!("{}.{}", name, format.extension()))
+}
+
+/// Pack `dir` into an archive beside it and verify that the archive lists
+/// every entry of the directory. The directory itself is left untouched.
+pub async fn create_verified(dir: &Path, format: ArchiveFormat) -> Result<PathBuf> {
+    let parent = dir.parent().context("Cannot archive the filesystem root")?;
+    let name = dir.file_name().context("Directory has no name")?;
+    let archive = archive_path(dir, format);
+
+    if archive.exists() {
+        anyhow::bail!("Archive already exists: {}", archive.display());
+    }
+
+    let mut tar = Command::new("tar");
+    tar.arg("-c");
+    if let Some(flag) = format.compress_flag() {
+        tar.arg(flag);
+    }
+    tar.arg("-f").arg(&archive).arg("-C").arg(parent).arg(name);
+    let status = tar.status().await.context("Failed to run tar")?;
+    if !status.success() {
+        let _ = tokio::fs::remove_file(&archive).await;
+        anyhow::bail!("tar exited with {} while archiving {}", status, dir.display());
+    }
+
+    if let Err(e) = verify(dir, &archive, format).await {
+        let _ = tokio::fs::remove_file(&archive).await;
+        return Err(e);
+    }
+
+    Ok(archive)
+}
+
+/// Compare the archive listing against the directory tree it was built from.
+async fn verify(dir: &Path, archive: &Path, format: ArchiveFormat) -> Result<()> {
+    let mut tar = Command::new("tar");
+    tar.arg("-t");
+    if let Some(flag) = format.compress_flag() {
+        tar.arg(flag);
+    }
+    let output = tar
+        .arg("-f")
+        .arg(archive)
+        .output()
+        .await
+        .context("Failed to run tar")?;
+    if !output.status.success() {
+        anyhow::bail!("Archive failed to read back: {}", archive.display());
+    }
+
+    let listed: BTreeSet<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.trim_end_matches('/').to_string())
+        .collect();
+
+    let parent = dir.parent().unwrap_or(dir);
+    let expected: BTreeSet<String> = walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(parent)
+                .ok()
+                .map(|p| p.to_string_lossy().into_owned())
+        })
+        .collect();
+
+    let missing: Vec<_> = expected.difference(&listed).collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Archive {} is missing {} entries (first: {})",
+            archive.display(),
+            missing.len(),
+            missing[0]
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,269 @@
+//! `chronoban bench`: time runs at several `--jobs` values and suggest one,
+//! since the best setting depends on the storage far more than the CPU: a
+//! local SSD keeps gaining from more workers long after NFS has stopped.
+//!
+//! ```sh
+//! chronoban bench ~/Downloads                  # a synthetic tree made there
+//! chronoban bench /mnt/nas --dest /mnt/archive # copying across filesystems
+//! chronoban bench ~/Downloads --existing       # planning the real tree
+//! ```
+//!
+//! Each trial is an ordinary run of chronoban in a child process, so it
+//! measures exactly what a real run would do. The synthetic tree is made
+//! afresh in a scratch directory for every trial and removed afterwards;
+//! `--existing` only plans, with `--dry-run`, and leaves the tree alone.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::exit::{self, Exit};
+use crate::json::{self, Value};
+use crate::output::{self, status, stdout, OutputFormat};
+use crate::schema::SCHEMA_VERSION;
+use crate::units::{format_size, parse_size};
+
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    /// Directory to benchmark in; the synthetic tree is made inside it, on
+    /// the same filesystem
+    #[arg(default_value = ".")]
+    dir: PathBuf,
+
+    /// Where the buckets go, to time moves onto another filesystem
+    #[arg(long, value_name = "DIR", conflicts_with = "existing")]
+    dest: Option<PathBuf>,
+
+    /// Plan runs over the directory as it is instead of a synthetic tree;
+    /// nothing is moved, so only scanning is timed
+    #[arg(long)]
+    existing: bool,
+
+    /// Files in the synthetic tree
+    #[arg(long, value_name = "N", default_value = "1000")]
+    files: usize,
+
+    /// Size of each synthetic file
+    #[arg(long, value_name = "SIZE", default_value = "64K", value_parser = parse_size)]
+    file_size: u64,
+
+    /// Job counts to try
+    #[arg(short = 'j', long, value_name = "N,...", value_delimiter = ',', default_value = "1,2,4,8,16,32")]
+    jobs: Vec<usize>,
+
+    /// Config file for the runs; by default none, so routes and hooks don't
+    /// skew the timings
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+/// One trial's timings.
+struct Trial {
+    jobs: usize,
+    entries: u64,
+    bytes: u64,
+    scan: Duration,
+    /// Zero when only planning.
+    moving: Duration,
+}
+
+impl Trial {
+    fn total(&self) -> Duration {
+        self.scan + self.moving
+    }
+
+    fn entries_per_sec(&self) -> f64 {
+        self.entries as f64 / self.total().as_secs_f64().max(1e-6)
+    }
+
+    fn to_json(&self) -> Value {
+        json::object([
+            ("jobs", self.jobs.into()),
+            ("entries", self.entries.into()),
+            ("bytes", self.bytes.into()),
+            ("scan_secs", self.scan.as_secs_f64().into()),
+            ("move_secs", self.moving.as_secs_f64().into()),
+            ("entries_per_sec", self.entries_per_sec().into()),
+        ])
+    }
+}
+
+/// A directory made for the benchmark, removed however it ends.
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub async fn run(args: BenchArgs) -> Result<Exit> {
+    let dir = std::fs::canonicalize(&args.dir).with_context(|| format!("Failed to access directory: {:?}", args.dir))?;
+    let mut jobs = args.jobs.clone();
+    jobs.retain(|&n| n > 0);
+    jobs.sort_unstable();
+    jobs.dedup();
+    if jobs.is_empty() {
+        anyhow::bail!("--jobs needs at least one count above zero");
+    }
+    if !args.existing && args.files == 0 {
+        anyhow::bail!("--files must be at least 1");
+    }
+    let exe = std::env::current_exe().context("Failed to find the chronoban executable")?;
+    let pid = std::process::id();
+    let scratch = Scratch(dir.join(format!(".chronoban-bench-{}", pid)));
+    std::fs::create_dir(&scratch.0).with_context(|| format!("Failed to create {:?}", scratch.0))?;
+    let config = match &args.config {
+        Some(config) => config.clone(),
+        None => {
+            let empty = scratch.0.join("config.toml");
+            std::fs::write(&empty, "").with_context(|| format!("Failed to write {:?}", empty))?;
+            empty
+        }
+    };
+    let dest = match &args.dest {
+        Some(dest) => {
+            let dest = std::fs::canonicalize(dest).with_context(|| format!("Failed to access directory: {:?}", dest))?;
+            Some(Scratch(dest.join(format!(".chronoban-bench-{}", pid))))
+        }
+        None => None,
+    };
+
+    status!("⏱️  Benchmarking {}", dir.display());
+    if args.existing {
+        status!("   Planning the tree as it is, with --dry-run");
+    } else {
+        status!("   {} files of {} per trial{}", args.files, format_size(args.file_size),
+            dest.as_ref().map(|d| format!(", moved to {}", d.0.display())).unwrap_or_default());
+    }
+
+    let mut trials = Vec::with_capacity(jobs.len());
+    for &n in &jobs {
+        if exit::interrupted() {
+            break;
+        }
+        let mut command = vec!["-q".into(), "--output".into(), "json".into(), "--no-lock".into()];
+        command.extend(["--config".into(), config.clone().into_os_string()]);
+        command.extend(["-j".into(), n.to_string().into()]);
+        let trial = if args.existing {
+            command.extend(["-n".into(), "-d".into(), dir.clone().into_os_string()]);
+            let (summary, elapsed) = child(&exe, &command)?;
+            let planned = summary.get("plan").and_then(|p| p.get("entries")).and_then(Value::as_array).map_or(0, |e| e.len());
+            Trial { jobs: n, entries: planned as u64, bytes: 0, scan: elapsed, moving: Duration::ZERO }
+        } else {
+            let source = scratch.0.join(format!("trial-{}", n));
+            let _source = Scratch(source.clone());
+            generate(&source, args.files, args.file_size)?;
+            command.extend(["-d".into(), source.clone().into_os_string()]);
+            if let Some(dest) = &dest {
+                command.extend(["--dest".into(), dest.0.join(format!("trial-{}", n)).into_os_string()]);
+            }
+            let (summary, elapsed) = child(&exe, &command)?;
+            let moving = Duration::from_secs_f64(summary.get("elapsed_secs").and_then(Value::as_f64).unwrap_or(0.0));
+            if let Some(dest) = &dest {
+                let _ = std::fs::remove_dir_all(dest.0.join(format!("trial-{}", n)));
+            }
+            Trial {
+                jobs: n,
+                entries: summary.get("moved").and_then(Value::as_u64).unwrap_or(0),
+                bytes: summary.get("bytes_moved").and_then(Value::as_u64).unwrap_or(0),
+                scan: elapsed.saturating_sub(moving),
+                moving,
+            }
+        };
+        status!("   -j {:<3} {:>9.1?} total, {:>9.0} entries/s", n, trial.total(), trial.entries_per_sec());
+        trials.push(trial);
+    }
+    if trials.is_empty() {
+        return Ok(Exit::Interrupted);
+    }
+    let best = recommend(&trials);
+
+    match args.output {
+        OutputFormat::Text => {
+            stdout!("\n📊 Results:");
+            stdout!("  {:>5}  {:>10}  {:>10}  {:>12}  {:>12}", "jobs", "scan", "move", "entries/s", "bytes/s");
+            for trial in &trials {
+                let bytes_per_sec = (trial.bytes as f64 / trial.moving.as_secs_f64().max(1e-6)) as u64;
+                let (moving, rate) = match args.existing {
+                    true => ("-".to_string(), "-".to_string()),
+                    false => (format!("{:.1?}", trial.moving), format_size(bytes_per_sec) + "/s"),
+                };
+                stdout!("  {:>5}  {:>10.1?}  {:>10}  {:>12.0}  {:>12}", trial.jobs, trial.scan, moving, trial.entries_per_sec(), rate);
+            }
+            let fastest = trials.iter().min_by_key(|t| t.total()).map_or(best, |t| t.jobs);
+            if fastest == best {
+                stdout!("\n✅ Recommended: -j {}", best);
+            } else {
+                stdout!("\n✅ Recommended: -j {} (within 5% of the fastest, -j {}, with fewer workers)", best, fastest);
+            }
+        }
+        OutputFormat::Json => {
+            output::flush();
+            println!("{}", json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("dir", dir.as_path().into()),
+                ("synthetic", (!args.existing).into()),
+                ("trials", Value::Array(trials.iter().map(Trial::to_json).collect())),
+                ("recommended_jobs", best.into()),
+            ]));
+        }
+    }
+    Ok(Exit::from_failures(0))
+}
+
+/// The fewest jobs that come within 5% of the fastest trial: past that,
+/// more workers only add contention.
+fn recommend(trials: &[Trial]) -> usize {
+    let fastest = trials.iter().map(Trial::total).min().unwrap_or_default();
+    trials
+        .iter()
+        .filter(|t| t.total().as_secs_f64() <= fastest.as_secs_f64() * 1.05)
+        .map(|t| t.jobs)
+        .min()
+        .unwrap_or(1)
+}
+
+/// `files` files of `size` bytes in a new `dir`, dated over the two years
+/// before last year so none is too recent to move.
+fn generate(dir: &Path, files: usize, size: u64) -> Result<()> {
+    std::fs::create_dir(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let block = vec![0u8; size.min(1 << 20) as usize];
+    let newest = SystemTime::now() - Duration::from_secs(365 * 24 * 60 * 60);
+    let spread = 2 * 365 * 24 * 60 * 60 / files.max(1) as u64;
+    for i in 0..files {
+        let path = dir.join(format!("bench-{:06}.dat", i));
+        let mut file = std::fs::File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+        let mut left = size;
+        while left > 0 {
+            let n = left.min(block.len() as u64);
+            file.write_all(&block[..n as usize]).with_context(|| format!("Failed to write {:?}", path))?;
+            left -= n;
+        }
+        let modified = newest - Duration::from_secs(spread * i as u64);
+        file.set_times(std::fs::FileTimes::new().set_modified(modified))
+            .with_context(|| format!("Failed to date {:?}", path))?;
+    }
+    Ok(())
+}
+
+/// Run chronoban with `args` and return its JSON summary and how long it took.
+fn child(exe: &Path, args: &[std::ffi::OsString]) -> Result<(Value, Duration)> {
+    let started = Instant::now();
+    let out = std::process::Command::new(exe)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .context("Failed to run chronoban for a trial")?;
+    let elapsed = started.elapsed();
+    let text = String::from_utf8_lossy(&out.stdout);
+    let summary = text.lines().rev().find_map(|line| json::parse(line).ok());
+    match summary {
+        Some(summary) if out.status.success() => Ok((summary, elapsed)),
+        _ => anyhow::bail!("A trial run failed ({}): {}", out.status, String::from_utf8_lossy(&out.stderr).trim()),
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::{Context, Result};
+use chrono::format::{self, Item, Parsed, StrftimeItems};
+use chrono::{DateTime, Months, NaiveDate, NaiveTime};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::months::{strip_name, Locale, MonthNames};
+
+pub fn is_year_month_dir(path: &Path) -> bool {
+    if let Some(name) = path.file_name().and_then(|n| n.to_str()).map(strip_name) {
+        // Check if it matches YYYY-MM pattern
+        if name.len() == 7 && name.chars().nth(4) == Some('-') {
+            let parts: Vec<&str> = name.split('-').collect();
+            if parts.len() == 2 {
+                return parts[0].parse::<u32>().is_ok() && parts[1].parse::<u32>().is_ok();
+            }
+        }
+    }
+    false
+}
+
+/// The first day of the month a `YYYY-MM` bucket name stands for, with or
+/// without a month name after it.
+pub fn parse_year_month(name: &str) -> Option<NaiveDate> {
+    let (year, month) = strip_name(name).split_once('-')?;
+    if year.len() != 4 || month.len() != 2 {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)
+}
+
+/// Month directories whose whole month lies before `cutoff`, oldest first.
+pub async fn expired_buckets(base: &Path, cutoff: NaiveDate) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(base)
+        .await
+        .with_context(|| format!("Failed to read directory: {:?}", base))?;
+
+    let mut buckets = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let Some(month) = entry.file_name().to_str().and_then(parse_year_month) else {
+            continue;
+        };
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+        let month_end = month + Months::new(1);
+        if month_end <= cutoff {
+            buckets.push(entry.path());
+        }
+    }
+    buckets.sort();
+    Ok(buckets)
+}
+
+/// The bucket sizes chronoban knows by name, for `--granularity`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Granularity {
+    /// 2024
+    Year,
+    /// 2024-Q2
+    Quarter,
+    /// 2024-05
+    Month,
+    /// 2024-W20, by ISO week
+    Week,
+    /// 2024-05-17
+    Day,
+}
+
+impl Granularity {
+    pub const ALL: [Granularity; 5] =
+        [Granularity::Year, Granularity::Quarter, Granularity::Month, Granularity::Week, Granularity::Day];
+
+    pub fn format(self) -> BucketFormat {
+        let pattern = match self {
+            Granularity::Year => "%Y",
+            Granularity::Quarter => "%Y-Q%q",
+            Granularity::Month => "%Y-%m",
+            Granularity::Week => "%G-W%V",
+            Granularity::Day => "%Y-%m-%d",
+        };
+        parse_bucket_format(pattern).expect("granularity formats are valid")
+    }
+}
+
+/// Whether a top-level name is a bucket of any granularity, so a tree
+/// organized by year, say, keeps its buckets when later runs file by month.
+/// The name must read back exactly as it renders, which rules out stray
+/// numbers such as `42`. A month name after the number is allowed.
+pub fn is_granularity_bucket_name(name: &str) -> bool {
+    let name = strip_name(name);
+    Granularity::ALL.iter().any(|granularity| {
+        let format = granularity.format();
+        format.parse_dir(Path::new(name)).is_some_and(|date| format.render_date(date) == Path::new(name))
+    })
+}
+
+/// Coarser buckets that sparse months are folded into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coarse {
+    Quarter,
+    Year,
+}
+
+/// A strftime-style bucket layout such as `%Y-%m` or `%Y/%m`. Each `/`
+/// separates one directory level.
+#[derive(Clone, Debug)]
+pub struct BucketFormat {
+    pattern: String,
+    components: Vec<String>,
+    /// Names written after the levels holding the month, for `--month-names`.
+    month_names: Option<&'static Locale>,
+}
+
+impl BucketFormat {
+    /// Render the bucket directory, relative to the base, for a date.
+    pub fn render<Tz: chrono::TimeZone>(&self, datetime: &DateTime<Tz>) -> PathBuf
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        let Some(locale) = self.month_names else {
+            return PathBuf::from(datetime.format(&self.pattern).to_string());
+        };
+        use chrono::Datelike;
+        let name = locale.names[datetime.month0() as usize];
+        self.components
+            .iter()
+            .map(|component| match datetime.format(component).to_string() {
+                part if component.contains("%m") => format!("{} {}", part, name),
+                part => part,
+            })
+            .collect()
+    }
+
+    /// Name the month after its number, as `--month-names` asks. The format
+    /// needs a `%m` to put the name after.
+    pub fn with_month_names(mut self, names: MonthNames) -> Result<BucketFormat, String> {
+        self.month_names = match names {
+            MonthNames::Numeric => None,
+            MonthNames::Named(_) if !self.pattern.contains("%m") => {
+                return Err(format!("--month-names needs a --format with %m, not {:?}", self.pattern));
+            }
+            MonthNames::Named(locale) => Some(locale),
+        };
+        Ok(self)
+    }
+
+    pub fn render_date(&self, date: NaiveDate) -> PathBuf {
+        self.render(&date.and_time(NaiveTime::MIN).and_utc())
+    }
+
+    /// Number of directory levels a bucket spans.
+    pub fn depth(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Whether a directory name directly below the base could be (the top
+    /// level of) a bucket in this format. Month names are accepted whether
+    /// or not this format writes them, so switching `--month-names` keeps
+    /// the buckets already made.
+    pub fn is_bucket_name(&self, name: &str) -> bool {
+        let mut parsed = Parsed::new();
+        format::parse(&mut parsed, unnamed(name, &self.components[0]), StrftimeItems::new(&self.components[0])).is_ok()
+    }
+
+    /// Parse a bucket path relative to the base back into the first day it covers.
+    pub fn parse_dir(&self, relative: &Path) -> Option<NaiveDate> {
+        let parts: Vec<_> = relative.iter().map(|p| p.to_str()).collect::<Option<_>>()?;
+        if parts.len() != self.components.len() {
+            return None;
+        }
+
+        let mut parsed = Parsed::new();
+        for (part, component) in parts.iter().zip(&self.components) {
+            format::parse(&mut parsed, unnamed(part, component), StrftimeItems::new(component)).ok()?;
+        }
+        if let Ok(date) = parsed.to_naive_date() {
+            return Some(date);
+        }
+        // Coarser layouts only pin down the year, quarter, month or week.
+        if parsed.isoweek().is_some() {
+            if parsed.weekday().is_none() {
+                parsed.set_weekday(chrono::Weekday::Mon).ok()?;
+            }
+            return parsed.to_naive_date().ok();
+        }
+        if parsed.month().is_none() && parsed.ordinal().is_none() {
+            let quarter = parsed.quarter().unwrap_or(1);
+            parsed.set_month(i64::from((quarter - 1) * 3 + 1)).ok()?;
+        }
+        if parsed.day().is_none() && parsed.ordinal().is_none() {
+            parsed.set_day(1).ok()?;
+        }
+        parsed.to_naive_date().ok()
+    }
+
+    /// Whether months can be folded into quarters and years: the format
+    /// must be month-granular.
+    pub fn can_coalesce(&self) -> bool {
+        self.pattern.contains("%m") && !["%d", "%e", "%j", "%U", "%W", "%V"].iter().any(|c| self.pattern.contains(c))
+    }
+
+    /// The pattern with its month replaced: `%Y-%m` becomes `%Y-Q2` for a
+    /// quarter, or `%Y` for a year (dropping the separator next to `%m`).
+    fn coarse_pattern(&self, coarse: Coarse, quarter: u32) -> String {
+        match coarse {
+            Coarse::Quarter => self.pattern.replacen("%m", &format!("Q{}", quarter), 1),
+            Coarse::Year => {
+                let at = self.pattern.find("%m").unwrap();
+                let (before, after) = (&self.pattern[..at], &self.pattern[at + 2..]);
+                let is_sep = |c: char| "-_./ ".contains(c);
+                if before.ends_with(is_sep) {
+                    format!("{}{}", &before[..before.len() - 1], after)
+                } else if after.starts_with(is_sep) {
+                    format!("{}{}", before, &after[1..])
+                } else {
+                    format!("{}{}", before, after)
+                }
+            }
+        }
+    }
+
+    /// Render the coarser bucket a date falls into. Only meaningful when
+    /// [`can_coalesce`](Self::can_coalesce) holds.
+    pub fn render_coarse<Tz: chrono::TimeZone>(&self, datetime: &DateTime<Tz>, coarse: Coarse) -> PathBuf
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        use chrono::Datelike;
+        let quarter = (datetime.month() - 1) / 3 + 1;
+        PathBuf::from(datetime.format(&self.coarse_pattern(coarse, quarter)).to_string())
+    }
+
+    /// Whether a top-level name could be a coalesced quarter or year bucket.
+    pub fn is_coarse_name(&self, name: &str) -> bool {
+        let matches = |pattern: String| {
+            let first = pattern.split('/').next().unwrap_or_default().to_string();
+            let mut parsed = Parsed::new();
+            format::parse(&mut parsed, name, StrftimeItems::new(&first)).is_ok()
+        };
+        (1..=4).any(|q| matches(self.coarse_pattern(Coarse::Quarter, q)))
+            || matches(self.coarse_pattern(Coarse::Year, 1))
+    }
+}
+
+/// A level of a bucket name without the month name a `%m` may be followed by.
+fn unnamed<'a>(part: &'a str, component: &str) -> &'a str {
+    if component.contains("%m") { strip_name(part) } else { part }
+}
+
+impl std::fmt::Display for BucketFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Parse and validate a bucket format: it must render to a relative path and
+/// parse back, or chronoban could not recognize its own buckets later.
+pub fn parse_bucket_format(s: &str) -> Result<BucketFormat, String> {
+    if StrftimeItems::new(s).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid date format: {:?}", s));
+    }
+    let components: Vec<String> = s.split('/').map(String::from).collect();
+    if components.iter().any(|c| c.is_empty() || c == "." || c == "..") {
+        return Err(format!("format must be a relative path without empty parts: {:?}", s));
+    }
+
+    let format = BucketFormat {
+        pattern: s.to_string(),
+        components,
+        month_names: None,
+    };
+    let sample = NaiveDate::from_ymd_opt(2024, 5, 17).unwrap();
+    let rendered = format.render_date(sample);
+    match format.parse_dir(&rendered) {
+        Some(date) if format.render_date(date) == rendered => Ok(format),
+        _ => Err(format!(
+            "format {:?} cannot be parsed back from directory names (it needs at least a year)",
+            s
+        )),
+    }
+}
+
+/// Directories at the format's depth whose relative path parses as a bucket.
+pub async fn find_buckets(base: &Path, format: &BucketFormat) -> Result<Vec<(PathBuf, NaiveDate)>> {
+    let base = base.to_path_buf();
+    let format = format.clone();
+    let buckets = tokio::task::spawn_blocking(move || {
+        let mut buckets: Vec<_> = walkdir::WalkDir::new(&base)
+            .min_depth(format.depth())
+            .max_depth(format.depth())
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || e.file_type().is_dir())
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir())
+            .filter_map(|e| {
+                let relative = e.path().strip_prefix(&base).ok()?;
+                let date = format.parse_dir(relative)?;
+                Some((e.path().to_path_buf(), date))
+            })
+            .collect();
+        buckets.sort();
+        buckets
+    })
+    .await?;
+    Ok(buckets)
+}
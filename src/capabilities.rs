@@ -0,0 +1,212 @@
+//! What the platform and the filesystem under a directory support, probed
+//! rather than assumed, for scripts that adapt to it and for bug reports.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::fsutil::is_other_filesystem;
+use crate::json;
+use crate::output::{stdout, OutputFormat};
+use crate::schema::SCHEMA_VERSION;
+use crate::trash::trash_dir;
+
+#[derive(clap::Args, Debug)]
+pub struct CapabilitiesArgs {
+    /// Directory whose filesystem to probe
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+/// One probed capability: `Ok` with how it was found, or `Err` with why
+/// it's missing.
+struct Capability {
+    key: &'static str,
+    label: &'static str,
+    found: Result<String, String>,
+}
+
+pub async fn run(args: CapabilitiesArgs) -> Result<()> {
+    let base = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    let capabilities = tokio::task::spawn_blocking({
+        let base = base.clone();
+        move || probe(&base)
+    })
+    .await??;
+
+    match args.output {
+        OutputFormat::Text => {
+            stdout!("🧪 Capabilities of {} ({} {}, chronoban {}):", base.display(),
+                std::env::consts::OS, std::env::consts::ARCH, env!("CARGO_PKG_VERSION"));
+            for capability in &capabilities {
+                match &capability.found {
+                    Ok(detail) => stdout!("  ✅ {}: {}", capability.label, detail),
+                    Err(reason) => stdout!("  ❌ {}: {}", capability.label, reason),
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let fields = capabilities.iter().map(|capability| {
+                let (supported, detail) = match &capability.found {
+                    Ok(detail) => (true, detail),
+                    Err(reason) => (false, reason),
+                };
+                (capability.key, json::object([
+                    ("supported", supported.into()),
+                    ("detail", detail.as_str().into()),
+                ]))
+            });
+            println!("{}", json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("directory", base.as_path().into()),
+                ("os", std::env::consts::OS.into()),
+                ("arch", std::env::consts::ARCH.into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+                ("capabilities", json::object(fields)),
+            ]));
+        }
+    }
+    Ok(())
+}
+
+/// Scratch files in the probed directory, removed however probing ends.
+struct Scratch {
+    paths: [PathBuf; 2],
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn probe(base: &Path) -> Result<Vec<Capability>> {
+    let pid = std::process::id();
+    let scratch = Scratch {
+        paths: [
+            base.join(format!(".chronoban-probe-{}", pid)),
+            base.join(format!(".chronoban-probe-{}-2", pid)),
+        ],
+    };
+    let [file, other] = &scratch.paths;
+    std::fs::write(file, b"")
+        .with_context(|| format!("Failed to create a probe file in {:?}", base))?;
+
+    Ok(vec![
+        Capability { key: "birth_time", label: "Birth time", found: birth_time(file) },
+        Capability { key: "xattrs", label: "Extended attributes", found: xattrs(file) },
+        Capability { key: "reflink", label: "Reflink copies", found: reflink(file, other) },
+        Capability { key: "renameat2", label: "Atomic no-replace rename", found: renameat2(file, other) },
+        Capability { key: "trash", label: "Trash", found: trash(base) },
+        Capability { key: "notifications", label: "Desktop notifications", found: notifications() },
+    ])
+}
+
+fn birth_time(file: &Path) -> Result<String, String> {
+    match std::fs::metadata(file).and_then(|m| m.created()) {
+        Ok(_) => Ok("recorded by the filesystem".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn xattrs(file: &Path) -> Result<String, String> {
+    let path = c_path(file)?;
+    // SAFETY: both strings are NUL-terminated and the value is a valid
+    // one-byte buffer.
+    let rc = unsafe { libc::setxattr(path.as_ptr(), c"user.chronoban.probe".as_ptr(), b"1".as_ptr().cast(), 1, 0) };
+    if rc == 0 {
+        Ok("user attributes can be set".to_string())
+    } else {
+        Err(std::io::Error::last_os_error().to_string())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn xattrs(_file: &Path) -> Result<String, String> {
+    Err("only probed on Linux".to_string())
+}
+
+/// Clone `file` into a new file with the FICLONE ioctl, as btrfs and XFS
+/// allow.
+#[cfg(target_os = "linux")]
+fn reflink(file: &Path, clone: &Path) -> Result<String, String> {
+    use std::os::unix::io::AsRawFd;
+    const FICLONE: libc::c_ulong = 0x4004_9409;
+
+    let src = std::fs::File::open(file).map_err(|e| e.to_string())?;
+    let dst = std::fs::File::create(clone).map_err(|e| e.to_string())?;
+    // SAFETY: both descriptors stay open for the duration of the call.
+    let rc = unsafe { libc::ioctl(dst.as_raw_fd(), FICLONE as _, src.as_raw_fd()) };
+    drop(dst);
+    let _ = std::fs::remove_file(clone);
+    if rc == 0 {
+        Ok("files can be cloned".to_string())
+    } else {
+        Err(std::io::Error::last_os_error().to_string())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_file: &Path, _clone: &Path) -> Result<String, String> {
+    Err("only probed on Linux".to_string())
+}
+
+/// Rename `file` with RENAME_NOREPLACE and back, through the raw syscall
+/// since older C libraries lack a wrapper.
+#[cfg(target_os = "linux")]
+fn renameat2(file: &Path, renamed: &Path) -> Result<String, String> {
+    let rename = |from: &Path, to: &Path| -> Result<(), String> {
+        let (from, to) = (c_path(from)?, c_path(to)?);
+        // SAFETY: both paths are NUL-terminated and outlive the call.
+        let rc = unsafe {
+            libc::syscall(libc::SYS_renameat2, libc::AT_FDCWD, from.as_ptr(), libc::AT_FDCWD, to.as_ptr(),
+                libc::RENAME_NOREPLACE)
+        };
+        if rc == 0 { Ok(()) } else { Err(std::io::Error::last_os_error().to_string()) }
+    };
+    rename(file, renamed)?;
+    rename(renamed, file)?;
+    Ok("renames can refuse to replace".to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn renameat2(_file: &Path, _renamed: &Path) -> Result<String, String> {
+    Err("only available on Linux".to_string())
+}
+
+/// Pruning moves months into the trash by renaming, so the trash has to be
+/// on the same filesystem.
+fn trash(base: &Path) -> Result<String, String> {
+    let dir = trash_dir().map_err(|e| e.to_string())?;
+    let Some(existing) = dir.ancestors().find(|d| d.exists()) else {
+        return Err(format!("{} can't be created", dir.display()));
+    };
+    if is_other_filesystem(existing, base) {
+        return Err(format!("{} is on another filesystem", dir.display()));
+    }
+    Ok(dir.display().to_string())
+}
+
+fn notifications() -> Result<String, String> {
+    let command = if cfg!(target_os = "macos") { "osascript" } else { "notify-send" };
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(command))
+        .find(|candidate| candidate.is_file())
+        .map(|found| found.display().to_string())
+        .ok_or_else(|| format!("{} not found on PATH", command))
+}
+
+#[cfg(target_os = "linux")]
+fn c_path(path: &Path) -> Result<std::ffi::CString, String> {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())
+}
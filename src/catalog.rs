@@ -0,0 +1,185 @@
+//! A catalog of every file chronoban has organized, kept with `--catalog`:
+//! where each one came from, where it went, its size, checksum and times.
+//! Unlike the journal it can live anywhere and span many directories, and
+//! `chronoban catalog` answers questions from it without rescanning, such
+//! as where a file ended up or which contents were filed more than once.
+//!
+//! The catalog is JSON lines, one entry per move, appended as runs go.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::hash::{hash_tree, to_hex, Digest};
+use crate::json::{self, Value};
+use crate::output::{stdout, OutputFormat};
+use crate::plan::{format_time, PlanEntry};
+use crate::schema::SCHEMA_VERSION;
+use crate::units::format_size;
+
+pub struct Catalog {
+    file: Mutex<File>,
+    run_id: String,
+}
+
+impl Catalog {
+    pub fn open(path: &Path, run_id: &str) -> Result<Catalog> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory: {:?}", dir))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open catalog: {:?}", path))?;
+        Ok(Catalog { file: Mutex::new(file), run_id: run_id.to_string() })
+    }
+
+    /// Record a finished move of `entry`. The checksum is taken from a
+    /// verified copy if there was one, and computed from the target if not.
+    pub fn record(&self, entry: &PlanEntry, sha256: Option<Digest>) -> Result<()> {
+        let sha256 = sha256.or_else(|| hash_tree(&entry.destination).ok());
+        let record = json::object([
+            ("schema_version", SCHEMA_VERSION.into()),
+            ("run_id", self.run_id.as_str().into()),
+            ("time", Local::now().to_rfc3339().into()),
+            ("source", entry.source.as_path().into()),
+            ("destination", entry.destination.as_path().into()),
+            ("size", entry.size.into()),
+            ("sha256", sha256.as_ref().map(to_hex).into()),
+            ("modified", entry.modified.map(format_time).into()),
+            ("accessed", entry.accessed.map(format_time).into()),
+        ]);
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", record).context("Failed to write catalog")?;
+        file.flush().context("Failed to write catalog")?;
+        Ok(())
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CatalogArgs {
+    /// The catalog file written with --catalog
+    catalog: PathBuf,
+
+    /// Only files whose original or current path contains this text
+    #[arg(long)]
+    path: Option<String>,
+
+    /// Only files with this SHA-256 checksum
+    #[arg(long, value_name = "HEX")]
+    sha256: Option<String>,
+
+    /// Only contents filed more than once, grouped by checksum
+    #[arg(long, conflicts_with = "sha256")]
+    duplicates: bool,
+
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+/// Where a recorded file is now, following it through later moves.
+#[derive(Clone, Debug)]
+pub struct Filed {
+    /// Where it was before chronoban first moved it.
+    pub original: String,
+    /// The record of its latest move.
+    pub record: Value,
+}
+
+impl Filed {
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.record.get(key).and_then(Value::as_str)
+    }
+
+    fn to_json(&self) -> Value {
+        let mut fields = vec![("original".to_string(), self.original.as_str().into())];
+        if let Value::Object(record) = &self.record {
+            fields.extend(record.iter().filter(|(key, _)| key != "schema_version").cloned());
+        }
+        Value::Object(fields)
+    }
+}
+
+/// Follow the moves in JSON-lines `text` (a catalog, or a journal with
+/// `to` as "target"), keyed by where each file is now. A move out of a
+/// path already moved into carries the original along.
+pub fn follow_moves(text: &str, to: &str) -> BTreeMap<String, Filed> {
+    let mut filed: BTreeMap<String, Filed> = BTreeMap::new();
+    // A line cut short by a crash is of no use; skip it
+    for record in text.lines().filter_map(|line| json::parse(line).ok()) {
+        let (Some(source), Some(destination)) = (
+            record.get("source").and_then(Value::as_str),
+            record.get(to).and_then(Value::as_str),
+        ) else {
+            continue;
+        };
+        let original = filed.remove(source).map_or_else(|| source.to_string(), |earlier| earlier.original);
+        filed.insert(destination.to_string(), Filed { original, record });
+    }
+    filed
+}
+
+/// Read the catalog at `path`.
+pub fn load(path: &Path) -> Result<BTreeMap<String, Filed>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read catalog: {:?}", path))?;
+    Ok(follow_moves(&text, "destination"))
+}
+
+pub fn run(args: CatalogArgs) -> Result<()> {
+    let filed = load(&args.catalog)?;
+
+    let mut files: Vec<&Filed> = filed
+        .values()
+        .filter(|f| {
+            args.path.as_ref().is_none_or(|text| {
+                f.original.contains(text.as_str()) || f.field("destination").is_some_and(|d| d.contains(text.as_str()))
+            })
+        })
+        .filter(|f| {
+            args.sha256.as_ref().is_none_or(|hex| f.field("sha256").is_some_and(|s| s.eq_ignore_ascii_case(hex)))
+        })
+        .collect();
+
+    if args.duplicates {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for f in &files {
+            if let Some(sha256) = f.field("sha256") {
+                *counts.entry(sha256).or_default() += 1;
+            }
+        }
+        files.retain(|f| f.field("sha256").is_some_and(|s| counts[s] > 1));
+        files.sort_by(|a, b| a.field("sha256").cmp(&b.field("sha256")));
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            let mut group = None;
+            for f in &files {
+                let sha256 = f.field("sha256").unwrap_or("-");
+                if args.duplicates && group != Some(sha256) {
+                    stdout!("🔁 {}:", sha256);
+                    group = Some(sha256);
+                }
+                let size = f.record.get("size").and_then(Value::as_u64).unwrap_or_default();
+                stdout!("  📇 {} (from {}, {})", f.field("destination").unwrap_or_default(), f.original,
+                    format_size(size));
+            }
+            if files.is_empty() {
+                stdout!("Nothing in the catalog matches");
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("files", Value::Array(files.iter().map(|f| f.to_json()).collect())),
+            ]));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,603 @@
+//! Command-line interface.
+
+use anyhow::Result;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use crate::exit::{self, Exit};
+use crate::journal::JournalOptOut;
+use crate::email::EmailArgs;
+use crate::limits::LimitArgs;
+use crate::lock::LockArgs;
+use crate::units::{parse_duration, parse_instant, parse_size};
+use crate::{
+    archive, bench, bucket, capabilities, catalog, completions, conflicts, datesrc, dedupe, diff, find, flatten, fsutil, history, identity, manpage, mapping, migrate, marker, months, notify, organize,
+    output, preflight, preset, profile, provider, prune, replan, route, scan, schema, stats, template, tui, unicode,
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "chronoban")]
+#[command(about = "Organize files into YYYY-MM directories based on modification time", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Plain output without emoji or colour (also with NO_COLOR or when not
+    /// writing to a terminal)
+    #[arg(long, global = true)]
+    plain: bool,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compress old month directories into verified archives in place
+    Archive(archive::ArchiveArgs),
+
+    /// Time runs at several --jobs values on this storage and recommend one
+    Bench(bench::BenchArgs),
+
+    /// Search the catalog kept with --catalog
+    Catalog(catalog::CatalogArgs),
+
+    /// Report what this platform and the directory's filesystem support
+    Capabilities(capabilities::CapabilitiesArgs),
+
+    /// Print a shell completion script
+    Completions(completions::CompletionsArgs),
+
+    /// List the entries journaled runs skipped for a conflict, and settle
+    /// them in bulk
+    Conflicts(conflicts::ConflictsArgs),
+
+    /// Report what chronoban did to a directory since a time or run, from
+    /// its journal
+    Diff(diff::DiffArgs),
+
+    /// Find which bucket files went into, by their old or current name
+    Find(find::FindArgs),
+
+    /// Move everything out of month directories back into the base directory
+    Flatten(flatten::FlattenArgs),
+
+    /// List the runs a journaled directory has seen
+    History(history::HistoryArgs),
+
+    /// Print the man page
+    Manpage(manpage::ManpageArgs),
+
+    /// Move files from one bucket layout to another in place
+    Migrate(migrate::MigrateArgs),
+
+    /// Print the plan a run would follow as JSON, or with --diff compare it
+    /// against a saved one
+    Plan(replan::PlanArgs),
+
+    /// Remove, trash or archive month directories past a retention threshold
+    Prune(prune::PruneArgs),
+
+    /// Organize the folders of profiles defined in the config file
+    Run(profile::RunArgs),
+
+    /// Report what a run would organize, per bucket, by date source and by
+    /// age, without moving anything
+    Scan(scan::ScanArgs),
+
+    /// Print the JSON Schema of chronoban's machine-readable output
+    Schema(schema::SchemaArgs),
+
+    /// Show what a run recorded in the history and journal did
+    Show(history::ShowArgs),
+
+    /// Show files, bytes and the largest file per bucket of an organized tree
+    Stats(stats::StatsArgs),
+
+    /// Review the planned moves by month and choose which to make
+    Tui(tui::TuiArgs),
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct Args {
+    /// Directory to organize
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    pub path: PathBuf,
+
+    /// Directories to organize together in one run, instead of --dir
+    #[arg(value_name = "DIR", conflicts_with = "path")]
+    pub paths: Vec<PathBuf>,
+
+    /// Where buckets are created; required with several directories
+    /// (defaults to the directory being organized). An s3://bucket/prefix
+    /// uploads into dated keys instead, and user@host:/path over SSH
+    #[arg(long, value_name = "DIR")]
+    pub dest: Option<PathBuf>,
+
+    /// Bucket layout as a strftime format; `/` creates nested directories
+    #[arg(short = 'f', long, default_value = "%Y-%m", value_parser = bucket::parse_bucket_format)]
+    pub format: bucket::BucketFormat,
+
+    /// Follow the month number in bucket names with its name: numeric
+    /// (none), locale (the language of LANG) or a language such as de, for
+    /// 2024-05 Mai
+    #[arg(long, value_name = "NAMES", default_value = "numeric", value_parser = months::parse_month_names)]
+    pub month_names: months::MonthNames,
+
+    /// Bucket by a named layout instead of a format: year (2024), quarter
+    /// (2024-Q2), month (2024-05), week (2024-W20) or day (2024-05-17)
+    #[arg(long, value_enum, conflicts_with_all = ["format", "template", "preset"])]
+    pub granularity: Option<bucket::Granularity>,
+
+    /// Put months that would receive fewer than N entries into a quarter
+    /// bucket (e.g. 2024-Q2) instead, or a year bucket if the quarter is
+    /// still sparse
+    #[arg(long, value_name = "N", conflicts_with = "template")]
+    pub auto_coalesce: Option<usize>,
+
+    /// Full destination template instead of a date format, e.g.
+    /// '{year}/{month}/{ext}/{name}' or '%Y-%m/{ext}'. Placeholders: year,
+    /// month, day, ext, category, mime_category, camera, owner, parent, name,
+    /// stem
+    #[arg(long, conflicts_with_all = ["format", "routes"], value_parser = template::parse_template)]
+    pub template: Option<template::Template>,
+
+    /// Options for a common chore, e.g. 'messenger' for WhatsApp and
+    /// Telegram exports (day buckets, dates from file names)
+    #[arg(long, conflicts_with_all = ["format", "template"])]
+    pub preset: Option<preset::Preset>,
+
+    /// Descend into subdirectories and organize the files inside them
+    /// instead of moving top-level entries whole
+    #[arg(short = 'r', long)]
+    pub recursive: bool,
+
+    /// With --recursive, only organize files at least this deep
+    /// (1 = directly inside the directory)
+    #[arg(long, default_value = "1", requires = "recursive",
+        value_parser = clap::value_parser!(u64).range(1..))]
+    pub min_depth: u64,
+
+    /// With --recursive, only organize files at most this deep
+    #[arg(long, requires = "recursive",
+        value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_depth: Option<u64>,
+
+    /// Never cross into other filesystems, such as network mounts or
+    /// bind-mounted snapshots below the directory
+    #[arg(long)]
+    pub one_file_system: bool,
+
+    /// With --recursive, keep each file's path below the directory inside
+    /// its bucket instead of flattening it
+    #[arg(long, visible_alias = "preserve-structure", requires = "recursive", conflicts_with = "template")]
+    pub keep_structure: bool,
+
+    /// With --recursive, remove the directories the moves left empty,
+    /// deepest first; the directory being organized always stays
+    #[arg(long, requires = "recursive")]
+    pub prune_empty: bool,
+
+    /// Route files into a subfolder of their bucket by extension or MIME type,
+    /// e.g. 'jpg,png=Photos' or 'image/*=Photos'
+    #[arg(long = "route", value_name = "EXTS=FOLDER", value_parser = route::parse_route)]
+    pub routes: Vec<route::Route>,
+
+    /// Organize only files, only directories, or both
+    #[arg(long, value_enum, default_value = "both")]
+    pub only: organize::Only,
+
+    /// Organize only entries owned by this user, by name or uid (Unix)
+    #[arg(long, value_name = "USER", value_parser = identity::parse_user)]
+    pub owner: Option<u32>,
+
+    /// Organize only entries whose group is this one, by name or gid (Unix)
+    #[arg(long, value_name = "GROUP", value_parser = identity::parse_group)]
+    pub group: Option<u32>,
+
+    /// What to do with zero-byte files, such as leftovers of failed downloads
+    #[arg(long, value_enum, default_value = "organize")]
+    pub empty: organize::EmptyFiles,
+
+    /// Skip symbolic links, move the links themselves, or follow them and
+    /// organize copies of what they point to
+    #[arg(long, value_enum, default_value = "move")]
+    pub symlinks: organize::Symlinks,
+
+    /// Leave entries matching this gitignore-style pattern where they are,
+    /// e.g. 'README.txt' or 'current/' (repeatable); directories holding a
+    /// .chronokeep file are kept too
+    #[arg(long, value_name = "PATTERN")]
+    pub keep: Vec<String>,
+
+    /// Leave the N most recently modified entries of each directory where
+    /// they are, however old they are
+    #[arg(long, value_name = "N")]
+    pub keep_latest: Option<usize>,
+
+    /// Include dotfiles and dot-directories, which are skipped by default
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Move sockets, FIFOs and device nodes too, instead of skipping them
+    #[arg(long)]
+    pub include_special: bool,
+
+    /// Copy entries into their buckets and leave the originals in place,
+    /// keeping permissions, extended attributes and (as root) ownership
+    #[arg(long, conflicts_with = "assert_idempotent")]
+    pub copy: bool,
+
+    /// All or nothing: check every planned move first, and if any move
+    /// fails, put back the ones already made (implies --journal)
+    #[arg(long, conflicts_with = "resume")]
+    pub atomic: bool,
+
+    /// Whenever an entry is copied, with --copy or to cross filesystems,
+    /// compare SHA-256 checksums of the original and the copy before
+    /// removing the original or counting it done, and journal the checksum
+    #[arg(long)]
+    pub verify_checksum: bool,
+
+    /// Date videos and audio by when they were recorded, from MP4/MOV
+    /// headers, ID3 tags and Vorbis comments, and .eml messages by their
+    /// Date header, instead of the file time
+    #[arg(long)]
+    pub embedded_dates: bool,
+
+    /// Move sidecar files (.xmp, .aae, .srt, .thm, .lrv, and .json named
+    /// after the whole file) into the same bucket as the photo or video they
+    /// belong to, dated by it, and only once it has been moved
+    #[arg(long)]
+    pub sidecars: bool,
+
+    /// Write destination names in this Unicode normalization form, and treat
+    /// names that differ only in normalization as the same name
+    #[arg(long, value_name = "FORM", value_enum)]
+    pub normalize: Option<unicode::Form>,
+
+    /// Group files taken close together into event folders inside their
+    /// bucket, named after the day each event starts (2024-05/2024-05-17_event)
+    #[arg(long, conflicts_with = "template")]
+    pub events: bool,
+
+    /// With --events, the longest pause between two files of one event
+    /// (e.g. 4h, 30m)
+    #[arg(long, value_name = "DURATION", default_value = "4h", value_parser = parse_duration, requires = "events")]
+    pub gap: Duration,
+
+    /// Show how FILE would be dated, by which provider and why, and the
+    /// bucket that puts it in, without organizing anything
+    #[arg(long, value_name = "FILE")]
+    pub explain: Option<PathBuf>,
+
+    /// Detect file types from their content instead of trusting extensions
+    #[arg(long)]
+    pub detect_type: bool,
+
+    /// What to do with files whose contents already exist in their bucket
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub dedupe: Option<dedupe::DedupeMode>,
+
+    /// Config file (defaults to ~/.config/chronoban/config.toml if present)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Perform a dry run without moving files
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Only process items older than N days
+    #[arg(short = 'a', long, default_value = "0")]
+    pub min_age_days: u64,
+
+    /// Only process items older than this, in finer units than
+    /// --min-age-days (e.g. 90min, 36h, 2w)
+    #[arg(long, value_parser = parse_duration, conflicts_with = "min_age_days")]
+    pub min_age: Option<Duration>,
+
+    /// Only process items younger than this (e.g. 30d)
+    #[arg(long, value_parser = parse_duration)]
+    pub max_age: Option<Duration>,
+
+    /// What to do with items dated after the reference time, which have no
+    /// age to compare with --min-age
+    #[arg(long, value_enum, default_value = "organize")]
+    pub future: organize::FutureDates,
+
+    /// Leave items dated in the current month, which is still filling up,
+    /// until the month is over
+    #[arg(long)]
+    pub complete_months_only: bool,
+
+    /// Treat dates before this as implausible, such as the 1970-01-01 some
+    /// backups restore files with (e.g. 1990-01-01)
+    #[arg(long, value_parser = parse_instant)]
+    pub min_date: Option<SystemTime>,
+
+    /// Treat dates after this as implausible (e.g. 2100-01-01)
+    #[arg(long, value_parser = parse_instant)]
+    pub max_date: Option<SystemTime>,
+
+    /// What to do with items whose date falls outside --min-date and
+    /// --max-date
+    #[arg(long, value_enum, default_value = "skip")]
+    pub implausible: organize::Implausible,
+
+    /// After the run, write a CSV file with one row per entry: its path,
+    /// what was done, the target, date source, size and any error
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Write how long walking, reading metadata, dating and moving took, as
+    /// a Chrome trace for chrome://tracing, Perfetto or speedscope
+    #[arg(long, value_name = "FILE")]
+    pub trace_file: Option<PathBuf>,
+
+    /// Move items left without a plausible date into this folder of the
+    /// destination (e.g. _unsorted) instead of leaving them behind
+    #[arg(long, value_name = "DIR")]
+    pub unknown_dir: Option<PathBuf>,
+
+    /// With --implausible fallback, the date providers to try in turn
+    #[arg(long, value_delimiter = ',', default_value = "exif,media,filename",
+        value_parser = provider::parse_provider_name)]
+    pub date_fallback: Vec<String>,
+
+    /// Reference time for ages, instead of when the run starts (e.g.
+    /// 2024-03-31 or 2024-03-31T23:59:00Z)
+    #[arg(long, value_parser = parse_instant)]
+    pub now: Option<SystemTime>,
+
+    /// Date directories by their own timestamp, or by the newest or oldest
+    /// file inside them
+    #[arg(long, value_enum, default_value = "self")]
+    pub dir_time: organize::DirTime,
+
+    /// Which of an entry's own times dates it
+    #[arg(long, value_enum, default_value = "mtime")]
+    pub time: fsutil::MetadataTime,
+
+    /// Use access time instead of modification time (--time atime)
+    #[arg(long, conflicts_with = "time")]
+    pub use_atime: bool,
+
+    /// Which date to use when a date in the file name disagrees with the
+    /// metadata date; without it such files are skipped
+    #[arg(long, value_enum)]
+    pub prefer: Option<datesrc::Prefer>,
+
+    /// How far a file name date may be from the metadata date before they
+    /// count as disagreeing (e.g. 1d, 12h)
+    #[arg(long, default_value = "1d", value_parser = parse_duration)]
+    pub date_tolerance: Duration,
+
+    /// Reset access times after chronoban reads directories or file contents
+    #[arg(long)]
+    pub restore_atime: bool,
+
+    /// Set each entry's original access and modification times again after
+    /// moving or copying it, in case the filesystem changed them
+    #[arg(long)]
+    pub preserve_times: bool,
+
+    /// Maximum number of concurrent move operations (defaults to CPU count)
+    #[arg(short = 'j', long)]
+    pub jobs: Option<usize>,
+
+    /// Plan, perform and report entries in a fixed order, one at a time, so
+    /// two runs over the same tree print the same thing
+    #[arg(long, value_enum, conflicts_with = "jobs")]
+    pub sort: Option<organize::Sort>,
+
+    /// Number of times to retry a failed move before counting it as an error
+    #[arg(long, default_value = "0")]
+    pub retries: u32,
+
+    /// Initial delay between retries, doubled after each attempt (e.g. 500ms, 2s)
+    #[arg(long, default_value = "500ms", value_parser = parse_duration)]
+    pub retry_delay: Duration,
+
+    /// Move at most N entries this run
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Which entries to process first when a run is limited
+    #[arg(long, value_enum, default_value = "oldest")]
+    pub order: organize::Order,
+
+    /// Once the destination has less than SIZE free (e.g. 5G), start no
+    /// more moves that copy onto it instead of failing entry after entry
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub stop_at_free: Option<u64>,
+
+    /// Likewise once it has fewer than N inodes free
+    #[arg(long, value_name = "N")]
+    pub stop_at_free_inodes: Option<u64>,
+
+    /// What to do at --stop-at-free: stop cleanly, or pause until space
+    /// is freed
+    #[arg(long, value_enum, default_value = "stop")]
+    pub on_low_space: preflight::LowSpace,
+
+    /// Never move directories larger than this wholesale without confirmation (e.g. 10G)
+    #[arg(long, value_parser = parse_size)]
+    pub max_dir_size: Option<u64>,
+
+    /// Skip entries whose size or mtime changes within this window (e.g. 5s)
+    #[arg(long, value_parser = parse_duration)]
+    pub settle: Option<Duration>,
+
+    /// Skip entries that another process has open (Linux only)
+    #[arg(long)]
+    pub skip_open: bool,
+
+    /// Keep running, organizing again every DURATION (e.g. 10m) until
+    /// interrupted. Without a DURATION, runs follow the cron expression in
+    /// the config file's [schedule] table. A pass due while another run
+    /// still holds the directory is skipped
+    #[arg(long, visible_alias = "every", value_name = "DURATION", value_parser = parse_duration,
+        num_args = 0..=1, conflicts_with_all = ["dry_run", "assert_idempotent", "resume", "apply_map"])]
+    pub watch: Option<Option<Duration>>,
+
+    /// With --watch, delay each run by a random amount up to DURATION, so
+    /// many machines on one schedule don't all start at once
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "watch")]
+    pub jitter: Option<Duration>,
+
+    /// Run the watcher as a systemd service: report readiness and status
+    /// with sd_notify, log with journald priorities, and reload the config
+    /// file on SIGHUP
+    #[arg(long, requires = "watch")]
+    pub service: bool,
+
+    /// With --watch, serve Prometheus metrics on this address, e.g.
+    /// 127.0.0.1:9321
+    #[arg(long, value_name = "ADDR", requires = "watch")]
+    pub metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Ask which planned moves to make before making them (`chronoban tui`)
+    #[arg(skip)]
+    pub review: bool,
+
+    /// Stop after planning and print or compare the plan (`chronoban plan`)
+    #[arg(skip)]
+    pub planning: Option<replan::Planning>,
+
+    /// The config profile whose routes and date sources to use (`chronoban
+    /// run`)
+    #[arg(skip)]
+    pub profile: Option<String>,
+
+    /// Shell command run before each move, with {src} and {dst} replaced by
+    /// the paths; if it fails the entry is not moved
+    #[arg(long, value_name = "COMMAND")]
+    pub pre_hook: Option<String>,
+
+    /// Shell command run after each move, with {src} and {dst} replaced by
+    /// the paths
+    #[arg(long, value_name = "COMMAND")]
+    pub post_hook: Option<String>,
+
+    /// When the run finishes or fails, POST its JSON summary to this http(s)
+    /// URL, or show a desktop notification with 'desktop' (repeatable)
+    #[arg(long, value_name = "URL", value_parser = notify::parse_target)]
+    pub notify: Vec<notify::Target>,
+
+    /// Shell command run once the run is done, with CHRONOBAN_RUN_ID,
+    /// CHRONOBAN_BASE, CHRONOBAN_MOVED, CHRONOBAN_SKIPPED and
+    /// CHRONOBAN_ERRORS set
+    #[arg(long, value_name = "COMMAND")]
+    pub on_complete: Option<String>,
+
+    /// How buckets chronoban creates are marked as its own; existing
+    /// directories without a marker get a warning before files go in
+    #[arg(long, value_enum, default_value = "dotfile")]
+    pub marker: marker::MarkerKind,
+
+    /// Record every move in .chronoban/journal.jsonl inside the directory
+    #[arg(long)]
+    pub journal: bool,
+
+    /// Continue the last journaled run if it was interrupted, moving what it
+    /// had planned but not done instead of planning again (implies --journal)
+    #[arg(long)]
+    pub resume: bool,
+
+    #[command(flatten)]
+    pub unjournaled: JournalOptOut,
+
+    /// Also record every file organized, with where it came from, its size,
+    /// checksum and times, in this catalog file, which `chronoban catalog`
+    /// can search; one catalog can serve many directories
+    #[arg(long, value_name = "FILE")]
+    pub catalog: Option<PathBuf>,
+
+    /// Instead of planning, move entries as a CSV mapping says, such as an
+    /// `exiftool -csv` export or a photo organizer's list of renames;
+    /// relative paths are taken from the directory being organized
+    #[arg(long, value_name = "CSV", conflicts_with = "resume")]
+    pub apply_map: Option<PathBuf>,
+
+    /// Columns of --apply-map holding each entry's path and its destination,
+    /// by header name or number (defaults to names like SourceFile and
+    /// Destination)
+    #[arg(long, value_name = "SOURCE,DEST", requires = "apply_map", value_parser = mapping::parse_map_columns)]
+    pub map_columns: Option<mapping::MapColumns>,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+
+    #[command(flatten)]
+    pub limits: LimitArgs,
+
+    #[command(flatten)]
+    pub email: EmailArgs,
+
+    /// Print only the summary, plus warnings and errors
+    #[arg(short = 'q', long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Explain how entries are placed; repeat (-vv) to also explain skips
+    #[arg(short = 'v', long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Format of the run summary on stdout
+    #[arg(long, value_enum, default_value = "text")]
+    pub output: output::OutputFormat,
+
+    /// Exit non-zero when any entry was skipped for a conflict, not only
+    /// when moves fail, and refuse to move anything if some entry can't be
+    /// classified (unreadable, or a socket, FIFO or device without
+    /// --include-special)
+    #[arg(long)]
+    pub strict: bool,
+
+    /// After a successful run, re-plan and fail if any further moves would occur
+    #[arg(long, conflicts_with_all = ["dry_run", "limit"])]
+    pub assert_idempotent: bool,
+}
+
+/// Run the command line and turn the outcome into an exit code.
+pub async fn main() -> ExitCode {
+    match run().await {
+        Ok(exit) => exit.into(),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            Exit::Fatal.into()
+        }
+    }
+}
+
+pub async fn run() -> Result<Exit> {
+    let cli = Cli::parse();
+    output::force_plain(cli.plain);
+    exit::handle_interrupts();
+
+    match cli.command {
+        Some(Command::Archive(args)) => archive::run(args).await,
+        Some(Command::Bench(args)) => bench::run(args).await,
+        Some(Command::Catalog(args)) => catalog::run(args).map(|()| Exit::Success),
+        Some(Command::Capabilities(args)) => capabilities::run(args).await.map(|()| Exit::Success),
+        Some(Command::Completions(args)) => completions::run(args, Cli::command()).map(|()| Exit::Success),
+        Some(Command::Conflicts(args)) => conflicts::run(args).await,
+        Some(Command::Diff(args)) => diff::run(args).await.map(|()| Exit::Success),
+        Some(Command::Find(args)) => find::run(args).await.map(|()| Exit::Success),
+        Some(Command::Flatten(args)) => flatten::run(args).await,
+        Some(Command::History(args)) => history::run(args).await.map(|()| Exit::Success),
+        Some(Command::Manpage(args)) => manpage::run(args, Cli::command()).map(|()| Exit::Success),
+        Some(Command::Migrate(args)) => migrate::run(args).await,
+        Some(Command::Plan(args)) => replan::run(args).await,
+        Some(Command::Prune(args)) => prune::run(args).await,
+        Some(Command::Run(args)) => profile::run(args).await,
+        Some(Command::Scan(args)) => scan::run(args).await,
+        Some(Command::Schema(args)) => schema::run(args).map(|()| Exit::Success),
+        Some(Command::Show(args)) => history::show(args).await.map(|()| Exit::Success),
+        Some(Command::Stats(args)) => stats::run(args).await.map(|()| Exit::Success),
+        Some(Command::Tui(args)) => tui::run(args).await,
+        None => organize::run(cli.args).await,
+    }
+}
@@ -0,0 +1,350 @@
+//! `chronoban completions`: shell completion scripts, written out from the
+//! command-line definition itself so they can't fall behind the flags.
+
+use anyhow::Result;
+use clap::builder::PossibleValue;
+use clap::{ArgAction, ValueHint};
+use std::fmt::Write as _;
+
+#[derive(clap::Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to complete for
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum Shell {
+    /// Source it, or install it as /usr/share/bash-completion/completions/chronoban
+    Bash,
+    /// Install it as _chronoban in a directory on $fpath
+    Zsh,
+    /// Install it as ~/.config/fish/completions/chronoban.fish
+    Fish,
+    /// Dot-source it from $PROFILE
+    Powershell,
+}
+
+/// What a value can be completed with.
+enum Values {
+    Choices(Vec<(String, String)>),
+    Paths,
+    Anything,
+}
+
+struct Opt {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    /// `None` for flags.
+    value: Option<(String, Values)>,
+    repeats: bool,
+}
+
+struct Positional {
+    name: String,
+    values: Values,
+    many: bool,
+}
+
+/// The top-level command (named "") or one subcommand.
+struct Level {
+    name: String,
+    options: Vec<Opt>,
+    positionals: Vec<Positional>,
+    commands: Vec<(String, String)>,
+}
+
+pub fn run(args: CompletionsArgs, mut command: clap::Command) -> Result<()> {
+    command.build();
+    let levels = levels(&command);
+    let name = command.get_name().to_string();
+    let script = match args.shell {
+        Shell::Bash => bash(&name, &levels),
+        Shell::Zsh => zsh(&name, &levels),
+        Shell::Fish => fish(&name, &levels),
+        Shell::Powershell => powershell(&name, &levels),
+    };
+    print!("{}", script);
+    Ok(())
+}
+
+fn levels(command: &clap::Command) -> Vec<Level> {
+    let mut levels = vec![level("", command)];
+    levels.extend(subcommands(command).map(|sub| level(sub.get_name(), sub)));
+    levels
+}
+
+fn subcommands(command: &clap::Command) -> impl Iterator<Item = &clap::Command> {
+    command.get_subcommands().filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+fn level(name: &str, command: &clap::Command) -> Level {
+    let mut options = Vec::new();
+    let mut positionals = Vec::new();
+    for arg in command.get_arguments().filter(|a| !a.is_hide_set()) {
+        let help = arg.get_help().map(|h| h.to_string().replace('\n', " ")).unwrap_or_default();
+        let value_name = || {
+            arg.get_value_names()
+                .map(|names| names.join(" "))
+                .unwrap_or_else(|| arg.get_id().to_string().to_uppercase())
+        };
+        let values = || {
+            let choices: Vec<PossibleValue> = arg.get_possible_values().into_iter().filter(|v| !v.is_hide_set()).collect();
+            if !choices.is_empty() {
+                return Values::Choices(choices.iter().map(|v| {
+                    (v.get_name().to_string(), v.get_help().map(|h| h.to_string()).unwrap_or_default())
+                }).collect());
+            }
+            match arg.get_value_hint() {
+                ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath | ValueHint::ExecutablePath => Values::Paths,
+                _ => Values::Anything,
+            }
+        };
+        let repeats = matches!(arg.get_action(), ArgAction::Append | ArgAction::Count);
+        if arg.is_positional() {
+            positionals.push(Positional { name: value_name(), values: values(), many: repeats });
+            continue;
+        }
+        let value = arg.get_action().takes_values().then(|| (value_name(), values()));
+        let aliases = arg.get_visible_aliases().unwrap_or_default();
+        options.push(Opt { long: arg.get_long().map(String::from), short: arg.get_short(), help: help.clone(),
+            value, repeats });
+        for alias in aliases {
+            let value = arg.get_action().takes_values().then(|| (value_name(), values()));
+            options.push(Opt { long: Some(alias.to_string()), short: None, help: help.clone(), value, repeats });
+        }
+    }
+    let commands = subcommands(command)
+        .map(|sub| (sub.get_name().to_string(), sub.get_about().map(|a| a.to_string()).unwrap_or_default()))
+        .collect();
+    Level { name: name.to_string(), options, positionals, commands }
+}
+
+impl Opt {
+    /// Every spelling, `--long` and `-s`.
+    fn spellings(&self) -> Vec<String> {
+        let mut spellings: Vec<String> = self.long.iter().map(|l| format!("--{}", l)).collect();
+        spellings.extend(self.short.map(|s| format!("-{}", s)));
+        spellings
+    }
+}
+
+fn bash(name: &str, levels: &[Level]) -> String {
+    let function = format!("_{}", name.replace('-', "_"));
+    let commands: Vec<&str> = levels[0].commands.iter().map(|(c, _)| c.as_str()).collect();
+    let mut out = String::new();
+    let _ = writeln!(out, "{}() {{", function);
+    out.push_str("    local cur prev cmd word opts words files\n");
+    out.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    out.push_str("    cmd=\"\"\n");
+    out.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+    out.push_str("        [[ \"$word\" == -* ]] && continue\n");
+    let _ = writeln!(out, "        case \"$word\" in {}) cmd=\"$word\" ;; esac", commands.join("|"));
+    out.push_str("        break\n    done\n\n    case \"$cmd\" in\n");
+    for level in levels {
+        let _ = writeln!(out, "        {})", if level.name.is_empty() { "\"\"" } else { &level.name });
+        out.push_str("            case \"$prev\" in\n");
+        for opt in &level.options {
+            let Some((_, values)) = &opt.value else { continue };
+            let action = match values {
+                Values::Choices(choices) => {
+                    let names: Vec<&str> = choices.iter().map(|(n, _)| n.as_str()).collect();
+                    format!("COMPREPLY=($(compgen -W '{}' -- \"$cur\"))", names.join(" "))
+                }
+                Values::Paths => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+                Values::Anything => "COMPREPLY=()".to_string(),
+            };
+            let _ = writeln!(out, "                {}) {}; return ;;", opt.spellings().join("|"), action);
+        }
+        out.push_str("            esac\n");
+        let spellings: Vec<String> = level.options.iter().flat_map(Opt::spellings).collect();
+        let _ = writeln!(out, "            opts='{}'", spellings.join(" "));
+        let mut words: Vec<&str> = level.commands.iter().map(|(c, _)| c.as_str()).collect();
+        let mut files = false;
+        for positional in &level.positionals {
+            match &positional.values {
+                Values::Choices(choices) => words.extend(choices.iter().map(|(n, _)| n.as_str())),
+                Values::Paths => files = true,
+                Values::Anything => {}
+            }
+        }
+        let _ = writeln!(out, "            words='{}'", words.join(" "));
+        let _ = writeln!(out, "            files={}", if files { 1 } else { 0 });
+        out.push_str("            ;;\n");
+    }
+    out.push_str("    esac\n\n");
+    out.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    out.push_str("        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))\n");
+    out.push_str("    else\n");
+    out.push_str("        COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n");
+    out.push_str("        (( files )) && COMPREPLY+=($(compgen -f -- \"$cur\"))\n");
+    out.push_str("    fi\n}\n\n");
+    let _ = writeln!(out, "complete -o filenames -F {} {}", function, name);
+    out
+}
+
+/// `text` inside a single-quoted zsh word, also escaping the characters
+/// `_arguments` and `_describe` treat specially.
+fn zsh_quote(text: &str) -> String {
+    text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:")
+}
+
+fn zsh_action(values: &Values) -> String {
+    match values {
+        Values::Choices(choices) => {
+            let names: Vec<String> = choices.iter().map(|(n, _)| zsh_quote(n)).collect();
+            format!("({})", names.join(" "))
+        }
+        Values::Paths => "_files".to_string(),
+        Values::Anything => " ".to_string(),
+    }
+}
+
+fn zsh_specs(level: &Level, indent: &str) -> String {
+    let mut out = String::new();
+    for opt in &level.options {
+        let repeat = if opt.repeats { "*" } else { "" };
+        for spelling in opt.spellings() {
+            let value = match &opt.value {
+                Some((name, values)) => format!(":{}:{}", zsh_quote(name), zsh_action(values)),
+                None => String::new(),
+            };
+            let equals = if opt.value.is_some() && spelling.starts_with("--") { "=" } else { "" };
+            let _ = writeln!(out, "{}'{}{}{}[{}]{}' \\", indent, repeat, spelling, equals, zsh_quote(&opt.help), value);
+        }
+    }
+    out
+}
+
+fn zsh(name: &str, levels: &[Level]) -> String {
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut out = String::new();
+    let _ = writeln!(out, "#compdef {}\n", name);
+    let _ = writeln!(out, "{}() {{", function);
+    out.push_str("    local curcontext=\"$curcontext\" state line ret=1\n");
+    out.push_str("    local -a commands\n    commands=(\n");
+    for (command, about) in &levels[0].commands {
+        let _ = writeln!(out, "        '{}:{}'", command, zsh_quote(about));
+    }
+    out.push_str("    )\n\n");
+    out.push_str("    if (( CURRENT > 2 )) && [[ -n ${commands[(r)${words[2]}:*]} ]]; then\n");
+    out.push_str("        local cmd=$words[2]\n        shift words\n        (( CURRENT-- ))\n");
+    out.push_str("        case $cmd in\n");
+    for level in &levels[1..] {
+        let _ = writeln!(out, "            ({})", level.name);
+        out.push_str("                _arguments -s -S \\\n");
+        out.push_str(&zsh_specs(level, "                    "));
+        for (i, positional) in level.positionals.iter().enumerate() {
+            let position = if positional.many { "*".to_string() } else { (i + 1).to_string() };
+            let _ = writeln!(out, "                    '{}:{}:{}' \\", position, zsh_quote(&positional.name),
+                zsh_action(&positional.values));
+        }
+        out.push_str("                    && ret=0\n                ;;\n");
+    }
+    out.push_str("        esac\n    else\n");
+    out.push_str("        _arguments -C -s -S \\\n");
+    out.push_str(&zsh_specs(&levels[0], "            "));
+    out.push_str("            '1: :->first' \\\n            '*:DIR:_files' \\\n            && ret=0\n");
+    out.push_str("        if [[ $state == first ]]; then\n");
+    out.push_str("            _describe -t commands 'command' commands && ret=0\n");
+    out.push_str("            _files && ret=0\n");
+    out.push_str("        fi\n    fi\n    return ret\n}\n\n");
+    let _ = writeln!(out, "{} \"$@\"", function);
+    out
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(name: &str, levels: &[Level]) -> String {
+    let mut out = String::new();
+    for level in levels {
+        let condition = if level.name.is_empty() {
+            "__fish_use_subcommand".to_string()
+        } else {
+            format!("__fish_seen_subcommand_from {}", level.name)
+        };
+        if !level.name.is_empty() && !level.positionals.iter().any(|p| matches!(p.values, Values::Paths)) {
+            let _ = writeln!(out, "complete -c {} -n '{}' -f", name, condition);
+        }
+        for opt in &level.options {
+            let mut line = format!("complete -c {} -n '{}'", name, condition);
+            if let Some(short) = opt.short {
+                let _ = write!(line, " -s {}", short);
+            }
+            if let Some(long) = &opt.long {
+                let _ = write!(line, " -l {}", long);
+            }
+            match &opt.value {
+                Some((_, Values::Choices(choices))) => {
+                    let names: Vec<&str> = choices.iter().map(|(n, _)| n.as_str()).collect();
+                    let _ = write!(line, " -x -a {}", fish_quote(&names.join(" ")));
+                }
+                Some((_, Values::Paths)) => line.push_str(" -r -F"),
+                Some((_, Values::Anything)) => line.push_str(" -x"),
+                None => {}
+            }
+            if !opt.help.is_empty() {
+                let _ = write!(line, " -d {}", fish_quote(&opt.help));
+            }
+            let _ = writeln!(out, "{}", line);
+        }
+        for positional in &level.positionals {
+            if let Values::Choices(choices) = &positional.values {
+                for (choice, help) in choices {
+                    let _ = writeln!(out, "complete -c {} -n '{}' -a {} -d {}", name, condition, fish_quote(choice),
+                        fish_quote(help));
+                }
+            }
+        }
+        for (command, about) in &level.commands {
+            let _ = writeln!(out, "complete -c {} -n '{}' -a {} -d {}", name, condition, command, fish_quote(about));
+        }
+    }
+    out
+}
+
+fn powershell_quote(text: &str) -> String {
+    let text = if text.is_empty() { " " } else { text };
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn powershell(name: &str, levels: &[Level]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{", name);
+    out.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n\n");
+    out.push_str("    $completions = @{\n");
+    for level in levels {
+        let _ = writeln!(out, "        '{}' = @(", level.name);
+        let mut items: Vec<(String, &str)> = Vec::new();
+        for opt in &level.options {
+            items.extend(opt.spellings().into_iter().map(|s| (s, opt.help.as_str())));
+        }
+        for positional in &level.positionals {
+            if let Values::Choices(choices) = &positional.values {
+                items.extend(choices.iter().map(|(c, h)| (c.clone(), h.as_str())));
+            }
+        }
+        items.extend(level.commands.iter().map(|(c, a)| (c.clone(), a.as_str())));
+        for (item, help) in items {
+            let _ = writeln!(out, "            ,@({}, {})", powershell_quote(&item), powershell_quote(help));
+        }
+        out.push_str("        )\n");
+    }
+    out.push_str("    }\n\n");
+    out.push_str("    $command = ''\n");
+    out.push_str("    foreach ($element in $commandAst.CommandElements | Select-Object -Skip 1) {\n");
+    out.push_str("        $text = $element.ToString()\n");
+    out.push_str("        if ($text -eq $wordToComplete) { break }\n");
+    out.push_str("        if ($text -like '-*') { continue }\n");
+    out.push_str("        if ($completions.ContainsKey($text)) { $command = $text }\n");
+    out.push_str("        break\n    }\n\n");
+    out.push_str("    $completions[$command] | Where-Object { $_[0] -like \"$wordToComplete*\" } | ForEach-Object {\n");
+    out.push_str("        $type = if ($_[0] -like '-*') { 'ParameterName' } else { 'ParameterValue' }\n");
+    out.push_str("        [System.Management.Automation.CompletionResult]::new($_[0], $_[0], $type, $_[1])\n");
+    out.push_str("    }\n}\n");
+    out
+}
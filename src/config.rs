@@ -0,0 +1,212 @@
+//! The optional TOML config file.
+//!
+//! Besides the settings every run uses, it can name profiles, each a folder
+//! with its own way of organizing, for `chronoban run`:
+//!
+//! ```toml
+//! [profile.photos]
+//! path = "~/Pictures/Inbox"
+//! dest = "~/Pictures"
+//! format = "%Y/%m"
+//!
+//! [profile.photos.dates]
+//! "*.jpg" = ["exif", "mtime"]
+//!
+//! [profile.downloads]
+//! path = "~/Downloads"
+//!
+//! [profile.downloads.routes]
+//! Documents = ["pdf", "docx"]
+//! ```
+//!
+//! A profile's `[routes]` and `[dates]` replace the top-level ones; without
+//! them it uses those.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::bucket::{parse_bucket_format, BucketFormat};
+use crate::email::EmailArgs;
+use crate::hooks::BucketHooks;
+use crate::json::Value;
+use crate::limits::LimitArgs;
+use crate::provider::{self, ChainConfig};
+use crate::route::Route;
+use crate::schedule::ScheduleConfig;
+use crate::toml;
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub routes: Vec<Route>,
+    pub limits: LimitArgs,
+    pub hooks: BucketHooks,
+    pub schedule: ScheduleConfig,
+    pub dates: Vec<ChainConfig>,
+    pub email: EmailArgs,
+    /// `[profile.NAME]` tables, in the order the file gives them.
+    pub profiles: Vec<Profile>,
+}
+
+/// A folder `chronoban run --profile NAME` organizes, and how.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    /// One directory, or several organized together.
+    pub paths: Vec<PathBuf>,
+    pub dest: Option<PathBuf>,
+    pub format: Option<BucketFormat>,
+    pub routes: Option<Vec<Route>>,
+    pub dates: Option<Vec<ChainConfig>>,
+}
+
+/// `$XDG_CONFIG_HOME/chronoban/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|dir| dir.join("chronoban").join("config.toml"))
+}
+
+impl Config {
+    /// Load an explicitly given config, or the default one if it exists.
+    pub fn load(explicit: Option<&Path>) -> Result<Config> {
+        let path = match explicit {
+            Some(path) => path.to_path_buf(),
+            None => match default_path().filter(|p| p.exists()) {
+                Some(path) => path,
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config: {:?}", path))?;
+        let doc = toml::parse(&text)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid config: {:?}", path))?;
+        Config::from_value(&doc).with_context(|| format!("Invalid config: {:?}", path))
+    }
+
+    /// The profile called `name`.
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles.iter().find(|p| p.name == name).with_context(|| {
+            let known: Vec<&str> = self.profiles.iter().map(|p| p.name.as_str()).collect();
+            match known.is_empty() {
+                true => format!("No profile {:?}: the config file has no [profile.NAME] tables", name),
+                false => format!("No profile {:?} in the config file (it has {})", name, known.join(", ")),
+            }
+        })
+    }
+
+    /// Use profile `name`'s routes and date sources in place of the
+    /// top-level ones, where it has its own.
+    pub fn use_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self.profile(name)?.clone();
+        if let Some(routes) = profile.routes {
+            self.routes = routes;
+        }
+        if let Some(dates) = profile.dates {
+            self.dates = dates;
+        }
+        Ok(())
+    }
+
+    fn from_value(doc: &Value) -> Result<Config> {
+        let mut config = Config::default();
+
+        if let Some(routes) = doc.get("routes") {
+            config.routes = routes_from_config(routes, "routes")?;
+        }
+
+        if let Some(limits) = doc.get("limits") {
+            config.limits = LimitArgs::from_config(limits)?;
+        }
+
+        if let Some(hooks) = doc.get("hooks") {
+            config.hooks = BucketHooks::from_config(hooks)?;
+        }
+
+        if let Some(dates) = doc.get("dates") {
+            config.dates = provider::chains_from_config(dates)?;
+        }
+
+        if let Some(schedule) = doc.get("schedule") {
+            config.schedule = ScheduleConfig::from_config(schedule)?;
+        }
+
+        if let Some(email) = doc.get("email") {
+            config.email = EmailArgs::from_config(email)?;
+        }
+
+        if let Some(profiles) = doc.get("profile") {
+            let profiles = profiles.as_object().context("[profile] must hold [profile.NAME] tables")?;
+            for (name, table) in profiles {
+                config.profiles.push(profile_from_config(name, table)?);
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn routes_from_config(table: &Value, key: &str) -> Result<Vec<Route>> {
+    let table = table.as_object().with_context(|| format!("[{}] must be a table", key))?;
+    let mut routes = Vec::new();
+    for (folder, extensions) in table {
+        let extensions = string_list(extensions)
+            .with_context(|| format!("{}.{} must be a list of extensions", key, folder))?;
+        routes.push(Route::new(folder, extensions).map_err(anyhow::Error::msg)?);
+    }
+    Ok(routes)
+}
+
+fn profile_from_config(name: &str, table: &Value) -> Result<Profile> {
+    let key = format!("profile.{}", name);
+    table.as_object().with_context(|| format!("[{}] must be a table", key))?;
+    let paths = match table.get("path") {
+        Some(Value::String(path)) => vec![expand_home(path)],
+        Some(paths) => string_list(paths)
+            .filter(|paths| !paths.is_empty())
+            .with_context(|| format!("{}.path must be a directory or a list of them", key))?
+            .iter()
+            .map(|path| expand_home(path))
+            .collect(),
+        None => anyhow::bail!("[{}] needs a path to organize", key),
+    };
+    let dest = match table.get("dest") {
+        Some(dest) => Some(expand_home(dest.as_str().with_context(|| format!("{}.dest must be a string", key))?)),
+        None => None,
+    };
+    let format = match table.get("format") {
+        Some(format) => {
+            let format = format.as_str().with_context(|| format!("{}.format must be a string", key))?;
+            Some(parse_bucket_format(format).map_err(|e| anyhow::anyhow!("{}.format: {}", key, e))?)
+        }
+        None => None,
+    };
+    let routes = match table.get("routes") {
+        Some(routes) => Some(routes_from_config(routes, &format!("{}.routes", key))?),
+        None => None,
+    };
+    let dates = match table.get("dates") {
+        Some(dates) => Some(provider::chains_from_config(dates).with_context(|| format!("In [{}.dates]", key))?),
+        None => None,
+    };
+    Ok(Profile { name: name.to_string(), paths, dest, format, routes, dates })
+}
+
+/// `path` with a leading `~/` standing for the home directory, as a shell
+/// would read it.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn string_list(value: &Value) -> Option<Vec<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|v| v.as_str().map(String::from))
+        .collect()
+}
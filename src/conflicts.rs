@@ -0,0 +1,434 @@
+//! `chronoban conflicts`: the entries journaled runs skipped because of a
+//! conflict and that are still where they were, so they don't pile up
+//! unnoticed. `--resolve` settles the ones whose target already exists:
+//!
+//! ```sh
+//! chronoban conflicts -d ~/Pictures
+//! chronoban conflicts -d ~/Pictures --resolve keep-newest --dry-run
+//! ```
+//!
+//! Nothing is deleted: whichever copy loses is set aside in
+//! `.chronoban/conflicts`. Resolutions are journaled as moves, which is also
+//! how a later run that moves the entry takes it off the list.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+use crate::exit::{self, Exit};
+use crate::fsutil::unique_path;
+use crate::identity::Identity;
+use crate::journal::{self, journal_path, Journal, STATE_DIR};
+use crate::json::{self, Value};
+use crate::lock::LockArgs;
+use crate::mover::{move_entry, Retry};
+use crate::output::{self, stderr, stdout, OutputFormat};
+use crate::plan::Conflict;
+use crate::schema::SCHEMA_VERSION;
+use crate::units::format_size;
+
+#[derive(clap::Args, Debug)]
+pub struct ConflictsArgs {
+    /// Directory whose journal to read: where the runs created buckets
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Settle the conflicts over a target that already exists
+    #[arg(long, value_enum, value_name = "HOW")]
+    resolve: Option<Resolution>,
+
+    /// Show what resolving would do without changing anything
+    #[arg(short = 'n', long, requires = "resolve")]
+    dry_run: bool,
+
+    #[arg(long, value_enum, default_value = "text", conflicts_with = "resolve")]
+    output: OutputFormat,
+
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum Resolution {
+    /// Move each entry in beside its target under a numbered name such as
+    /// `report (2).pdf`
+    RenameAll,
+    /// Keep whichever was modified last; set the other aside
+    KeepNewest,
+    /// Keep the larger of the two; set the other aside
+    KeepLargest,
+    /// Ask about each conflict in turn
+    Interactive,
+}
+
+/// What to do about one conflict.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Choice {
+    Rename,
+    /// The entry replaces the target, which is set aside.
+    KeepEntry,
+    /// The target stays and the entry is set aside.
+    KeepTarget,
+    Leave,
+}
+
+struct Outstanding {
+    source: PathBuf,
+    target: PathBuf,
+    conflict: String,
+    run_id: String,
+}
+
+impl Outstanding {
+    /// Whether `--resolve` can settle it: the other kinds need a different
+    /// date or dedupe setting rather than a choice between two files.
+    fn resolvable(&self) -> bool {
+        Conflict::parse(&self.conflict) == Some(Conflict::TargetExists)
+    }
+
+    fn to_json(&self) -> Value {
+        json::object([
+            ("source", self.source.as_path().into()),
+            ("target", self.target.as_path().into()),
+            ("conflict", self.conflict.as_str().into()),
+            ("run_id", self.run_id.as_str().into()),
+        ])
+    }
+}
+
+pub async fn run(args: ConflictsArgs) -> Result<Exit> {
+    let base = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    if !journal_path(&base).exists() {
+        anyhow::bail!("No journal in {}; conflicts are only recorded by runs with --journal", base.display());
+    }
+    let _lock = match args.resolve {
+        Some(_) if !args.dry_run => args.lock.acquire(&base).await?,
+        _ => None,
+    };
+    let conflicts = outstanding(&base)?;
+
+    let Some(resolution) = args.resolve else {
+        list(&base, &conflicts, args.output);
+        return Ok(Exit::Success);
+    };
+    if resolution == Resolution::Interactive && !io::stdin().is_terminal() {
+        anyhow::bail!("--resolve interactive asks on the terminal; pick rename-all, keep-newest or keep-largest");
+    }
+    let journal = match args.dry_run {
+        true => None,
+        false => Some(Journal::open(&base, &journal::new_run_id(), &Identity::current())?),
+    };
+    let aside = base.join(STATE_DIR).join("conflicts");
+
+    let (mut resolved, mut left, mut errors) = (0, 0, 0);
+    for conflict in &conflicts {
+        if exit::interrupted() {
+            break;
+        }
+        if !conflict.resolvable() {
+            stderr!("⏭️  {} ({}): needs a change of settings, not a choice between files",
+                conflict.source.display(), conflict.conflict);
+            left += 1;
+            continue;
+        }
+        let choice = match resolution {
+            Resolution::RenameAll => Choice::Rename,
+            Resolution::KeepNewest => keep_if(conflict, |m| m.modified().unwrap_or(SystemTime::UNIX_EPOCH)),
+            Resolution::KeepLargest => keep_if(conflict, |m| m.len()),
+            Resolution::Interactive => match ask(conflict)? {
+                Some(choice) => choice,
+                None => break,
+            },
+        };
+        if choice == Choice::Leave {
+            left += 1;
+            continue;
+        }
+        match settle(conflict, choice, &aside, args.dry_run, journal.as_ref()).await {
+            Ok(()) => resolved += 1,
+            Err(e) => {
+                stderr!("❌ Error: {:#}", e);
+                errors += 1;
+            }
+        }
+    }
+
+    stdout!("\n📊 Summary:");
+    stdout!("  Resolved: {}", resolved);
+    stdout!("  Left as they are: {}", left);
+    stdout!("  Errors: {}", errors);
+    Ok(Exit::from_failures(errors))
+}
+
+/// The conflicts the journal records that nothing has moved since and whose
+/// entry is still there, by source path.
+fn outstanding(base: &Path) -> Result<Vec<Outstanding>> {
+    let path = journal_path(base);
+    let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read journal: {:?}", path))?;
+    let mut waiting: BTreeMap<PathBuf, Outstanding> = BTreeMap::new();
+    for record in text.lines().filter_map(|line| json::parse(line).ok()) {
+        let field = |key| record.get(key).and_then(Value::as_str);
+        let Some(source) = field("source").map(PathBuf::from) else {
+            continue;
+        };
+        match field("event") {
+            Some("conflict") => {
+                let conflict = Outstanding {
+                    target: PathBuf::from(field("target").unwrap_or_default()),
+                    conflict: field("conflict").unwrap_or_default().to_string(),
+                    run_id: field("run_id").unwrap_or_default().to_string(),
+                    source: source.clone(),
+                };
+                waiting.insert(source, conflict);
+            }
+            Some("move" | "upload" | "dedupe" | "delete") => {
+                waiting.remove(&source);
+            }
+            _ => {}
+        }
+    }
+    Ok(waiting
+        .into_values()
+        .filter(|c| std::fs::symlink_metadata(&c.source).is_ok())
+        // Once the target is gone the next run moves the entry anyway
+        .filter(|c| !c.resolvable() || std::fs::symlink_metadata(&c.target).is_ok())
+        .collect())
+}
+
+fn list(base: &Path, conflicts: &[Outstanding], format: OutputFormat) {
+    if format == OutputFormat::Json {
+        output::flush();
+        println!("{}", json::object([
+            ("schema_version", SCHEMA_VERSION.into()),
+            ("base", base.into()),
+            ("conflicts", Value::Array(conflicts.iter().map(Outstanding::to_json).collect())),
+        ]));
+        return;
+    }
+    if conflicts.is_empty() {
+        stdout!("✅ No outstanding conflicts in {}", base.display());
+        return;
+    }
+    stdout!("⚠️  {} outstanding conflicts in {}:", conflicts.len(), base.display());
+    for conflict in conflicts {
+        stdout!("  {} -> {} ({})", conflict.source.display(), conflict.target.display(), conflict.conflict);
+        if conflict.resolvable() {
+            stdout!("      entry: {}   target: {}", describe(&conflict.source), describe(&conflict.target));
+        }
+    }
+    stdout!("\nSettle them with --resolve rename-all, keep-newest, keep-largest or interactive");
+}
+
+/// Size and modification time, for choosing between the two.
+fn describe(path: &Path) -> String {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return "missing".to_string();
+    };
+    let modified = metadata
+        .modified()
+        .map(|time| chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| "?".to_string());
+    format!("{}, modified {}", format_size(metadata.len()), modified)
+}
+
+/// Keep the entry if `key` ranks it above the target; on a tie the target
+/// stays, as nothing would be gained by swapping them.
+fn keep_if<K: Ord>(conflict: &Outstanding, key: impl Fn(&std::fs::Metadata) -> K) -> Choice {
+    match (std::fs::symlink_metadata(&conflict.source), std::fs::symlink_metadata(&conflict.target)) {
+        (Ok(entry), Ok(target)) if key(&entry) > key(&target) => Choice::KeepEntry,
+        (Ok(_), Ok(_)) => Choice::KeepTarget,
+        _ => Choice::Leave,
+    }
+}
+
+/// Ask what to do on the terminal; `None` to stop asking.
+fn ask(conflict: &Outstanding) -> Result<Option<Choice>> {
+    stderr!("\n⚠️  {} -> {}", conflict.source.display(), conflict.target.display());
+    stderr!("   entry:  {}", describe(&conflict.source));
+    stderr!("   target: {}", describe(&conflict.target));
+    loop {
+        output::flush();
+        eprint!("   [r]ename, keep [e]ntry, keep [t]arget, [s]kip or [q]uit? ");
+        let _ = io::stderr().flush();
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        return Ok(Some(match answer.trim() {
+            "r" | "rename" => Choice::Rename,
+            "e" | "entry" => Choice::KeepEntry,
+            "t" | "target" => Choice::KeepTarget,
+            "s" | "skip" | "" => Choice::Leave,
+            "q" | "quit" => return Ok(None),
+            _ => continue,
+        }));
+    }
+}
+
+async fn settle(
+    conflict: &Outstanding,
+    choice: Choice,
+    aside: &Path,
+    dry_run: bool,
+    journal: Option<&Journal>,
+) -> Result<()> {
+    let (source, target) = (conflict.source.as_path(), conflict.target.as_path());
+    let dir = target.parent().unwrap_or(target);
+    let name = |path: &Path| path.file_name().unwrap_or_default().to_os_string();
+    let moves = match choice {
+        Choice::Rename => vec![(source.to_path_buf(), unique_path(dir, &name(target)))],
+        Choice::KeepEntry => {
+            vec![(target.to_path_buf(), unique_path(aside, &name(target))), (source.to_path_buf(), target.to_path_buf())]
+        }
+        Choice::KeepTarget => vec![(source.to_path_buf(), unique_path(aside, &name(source)))],
+        Choice::Leave => return Ok(()),
+    };
+    for (from, to) in moves {
+        if dry_run {
+            stdout!("📦 Would move: {} -> {}", from.display(), to.display());
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        let method = move_entry(&from, &to, Retry { retries: 0, delay: Duration::ZERO }, false).await?;
+        if let Some(journal) = journal {
+            journal.record_move(&from, &to, method)?;
+        }
+        stdout!("✅ Moved: {} -> {}", from.display(), to.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: ConflictsArgs,
+    }
+
+    /// A base directory for one test, removed afterwards.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir = std::env::temp_dir().join(format!("chronoban-conflicts-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join("2022-03")).unwrap();
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// `entry` and its target, written with the contents and ages given,
+    /// and a journaled conflict between them.
+    fn conflict(base: &Path, entry: (&str, u64), target: (&str, u64)) -> (PathBuf, PathBuf) {
+        let (source, destination) = (base.join("report.txt"), base.join("2022-03").join("report.txt"));
+        for (path, (contents, age)) in [(&source, entry), (&destination, target)] {
+            std::fs::write(path, contents).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(age * 86400);
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        }
+        Journal::open(base, "run-1", &Identity::current())
+            .unwrap()
+            .record_conflict(&source, &destination, Conflict::TargetExists)
+            .unwrap();
+        (source, destination)
+    }
+
+    async fn resolve(base: &Path, how: &str) -> Exit {
+        let dir = base.to_str().unwrap();
+        run(Cli::parse_from(["conflicts", "-d", dir, "--no-lock", "--resolve", how]).args).await.unwrap()
+    }
+
+    /// What is set aside in the state directory, by contents.
+    fn aside(base: &Path) -> Vec<String> {
+        let mut aside: Vec<String> = std::fs::read_dir(base.join(STATE_DIR).join("conflicts"))
+            .into_iter()
+            .flatten()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        aside.sort();
+        aside
+    }
+
+    #[tokio::test]
+    async fn keep_newest_replaces_an_older_target() {
+        let scratch = Scratch::new("newest");
+        let base = scratch.0.as_path();
+        let (source, target) = conflict(base, ("new", 1), ("old", 30));
+
+        assert_eq!(resolve(base, "keep-newest").await, Exit::Success);
+        assert!(!source.exists());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(aside(base), ["old"]);
+        assert!(outstanding(base).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keep_newest_keeps_a_newer_target() {
+        let scratch = Scratch::new("newest-target");
+        let base = scratch.0.as_path();
+        let (source, target) = conflict(base, ("old", 30), ("new", 1));
+
+        assert_eq!(resolve(base, "keep-newest").await, Exit::Success);
+        assert!(!source.exists());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(aside(base), ["old"]);
+        assert!(outstanding(base).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keep_largest_keeps_the_bigger_file() {
+        let scratch = Scratch::new("largest");
+        let base = scratch.0.as_path();
+        let (source, target) = conflict(base, ("the longer one", 30), ("short", 1));
+
+        assert_eq!(resolve(base, "keep-largest").await, Exit::Success);
+        assert!(!source.exists());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "the longer one");
+        assert_eq!(aside(base), ["short"]);
+        assert!(outstanding(base).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keep_largest_leaves_the_target_on_a_tie() {
+        let scratch = Scratch::new("largest-tie");
+        let base = scratch.0.as_path();
+        let (source, target) = conflict(base, ("entry", 1), ("targt", 30));
+
+        assert_eq!(resolve(base, "keep-largest").await, Exit::Success);
+        assert!(!source.exists());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "targt");
+        assert_eq!(aside(base), ["entry"]);
+    }
+
+    #[test]
+    fn outstanding_drops_conflicts_moved_since() {
+        let scratch = Scratch::new("outstanding");
+        let base = scratch.0.as_path();
+        let (source, target) = conflict(base, ("entry", 1), ("target", 1));
+        assert_eq!(outstanding(base).unwrap().len(), 1);
+
+        let journal = Journal::open(base, "run-2", &Identity::current()).unwrap();
+        journal.record_move(&source, &base.join("2022-03/report (2).txt"), crate::mover::MoveMethod::Renamed).unwrap();
+        assert!(outstanding(base).unwrap().is_empty());
+        assert!(target.exists());
+    }
+}
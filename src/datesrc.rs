@@ -0,0 +1,105 @@
+//! Dates encoded in file names, such as `IMG_20240314_120001.jpg` or
+//! `2024-03-14 notes.txt`.
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+/// Which date wins when a file name and the file's metadata disagree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Prefer {
+    Filename,
+    Metadata,
+}
+
+/// The first plausible date in a name: a year from 1970 to 2099, month and
+/// day, optionally separated by `-`, `_` or `.`, optionally followed by a
+/// time. Digits directly around the match rule it out, so counters and ids
+/// are not mistaken for dates.
+pub fn date_from_name(name: &str) -> Option<NaiveDateTime> {
+    let bytes = name.as_bytes();
+    (0..bytes.len()).find_map(|start| {
+        if start > 0 && bytes[start - 1].is_ascii_digit() {
+            return None;
+        }
+        parse_at(&bytes[start..])
+    })
+}
+
+fn parse_at(b: &[u8]) -> Option<NaiveDateTime> {
+    let mut pos = 0;
+    let year = digits(b, &mut pos, 4)?;
+    if !(1970..=2099).contains(&year) {
+        return None;
+    }
+    let sep = separator(b, pos, b"-_.");
+    pos += sep.is_some() as usize;
+    let month = digits(b, &mut pos, 2)?;
+    if separator(b, pos, b"-_.") != sep {
+        return None;
+    }
+    pos += sep.is_some() as usize;
+    let day = digits(b, &mut pos, 2)?;
+    let date = NaiveDate::from_ymd_opt(year as i32, month, day)?;
+
+    let date_end = pos;
+    let time = parse_time(b, &mut pos);
+    let end = if time.is_some() { pos } else { date_end };
+    if b.get(end).is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(date.and_time(time.unwrap_or(NaiveTime::MIN)))
+}
+
+/// `HHMMSS`, `HH-MM-SS`, `HH.MM.SS` or `HH:MM:SS`, optionally after one
+/// separator.
+fn parse_time(b: &[u8], pos: &mut usize) -> Option<NaiveTime> {
+    let mut p = *pos;
+    p += separator(b, p, b"-_ T").is_some() as usize;
+    let hour = digits(b, &mut p, 2)?;
+    let sep = separator(b, p, b"-_.:");
+    p += sep.is_some() as usize;
+    let minute = digits(b, &mut p, 2)?;
+    if separator(b, p, b"-_.:") != sep {
+        return None;
+    }
+    p += sep.is_some() as usize;
+    let second = digits(b, &mut p, 2)?;
+    let time = NaiveTime::from_hms_opt(hour, minute, second)?;
+    *pos = p;
+    Some(time)
+}
+
+fn digits(b: &[u8], pos: &mut usize, n: usize) -> Option<u32> {
+    let s = b.get(*pos..*pos + n)?;
+    if !s.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    *pos += n;
+    std::str::from_utf8(s).ok()?.parse().ok()
+}
+
+fn separator(b: &[u8], pos: usize, allowed: &[u8]) -> Option<u8> {
+    b.get(pos).copied().filter(|c| allowed.contains(c))
+}
+
+/// The date in a messenger media export name: WhatsApp's
+/// `IMG-20240314-WA0001.jpg` or Telegram's `photo_2024-03-14_12-00-01.jpg`.
+/// Other names yield nothing, so stray files in an export keep their
+/// metadata date.
+pub fn messenger_date(name: &str) -> Option<NaiveDateTime> {
+    const WHATSAPP: [&str; 6] = ["IMG-", "VID-", "AUD-", "PTT-", "STK-", "DOC-"];
+    const TELEGRAM: [&str; 6] = ["photo_", "video_", "file_", "audio_", "voice_", "sticker_"];
+
+    if let Some(rest) = WHATSAPP.iter().find_map(|p| name.strip_prefix(p)) {
+        let (date, counter) = rest.split_at_checked(8)?;
+        if !date.bytes().all(|b| b.is_ascii_digit()) || !counter.starts_with("-WA") {
+            return None;
+        }
+        return parse_at(date.as_bytes());
+    }
+    let rest = TELEGRAM.iter().find_map(|p| name.strip_prefix(p))?;
+    let b = rest.as_bytes();
+    if b.get(4) != Some(&b'-') {
+        return None;
+    }
+    parse_at(b)
+}
@@ -0,0 +1,123 @@
+//! Recognizing entries that already exist, byte for byte, in their bucket.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::fsutil::restore_atime;
+use crate::hash::{hash_file, Digest};
+use crate::marker::is_reserved;
+use crate::output::stderr;
+
+/// What to do with a file whose contents are already in its bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DedupeMode {
+    /// Say so, but move it anyway
+    Report,
+    /// Leave it where it is
+    Skip,
+    /// Put a hard link to the existing copy at the destination instead
+    Hardlink,
+    /// Delete it
+    Delete,
+}
+
+/// Files already in each bucket, grouped by size so only same-sized files
+/// are ever hashed. Buckets are indexed on first use and hashes are cached
+/// for the rest of the run.
+pub struct DuplicateFinder {
+    buckets: HashMap<PathBuf, HashMap<u64, Vec<PathBuf>>>,
+    hashes: HashMap<PathBuf, Option<Digest>>,
+    restore_atime: bool,
+}
+
+impl DuplicateFinder {
+    pub fn new(restore_atime: bool) -> Self {
+        DuplicateFinder {
+            buckets: HashMap::new(),
+            hashes: HashMap::new(),
+            restore_atime,
+        }
+    }
+
+    /// An existing file in `bucket` with the same contents as `path`. Empty
+    /// files are never considered duplicates of each other.
+    pub async fn find(&mut self, bucket: &Path, path: &Path, size: u64) -> Option<PathBuf> {
+        if size == 0 {
+            return None;
+        }
+
+        if !self.buckets.contains_key(bucket) {
+            let index = index_bucket(bucket.to_path_buf()).await;
+            self.buckets.insert(bucket.to_path_buf(), index);
+        }
+        let existing = self.buckets[bucket].get(&size)?.clone();
+
+        let digest = self.hash(path).await?;
+        for other in existing {
+            if self.hash(&other).await == Some(digest) {
+                return Some(other);
+            }
+        }
+        None
+    }
+
+    async fn hash(&mut self, path: &Path) -> Option<Digest> {
+        if let Some(digest) = self.hashes.get(path) {
+            return *digest;
+        }
+        let owned = path.to_path_buf();
+        let restore = self.restore_atime;
+        let digest = tokio::task::spawn_blocking(move || {
+            let atime = std::fs::metadata(&owned).and_then(|m| m.accessed()).ok();
+            let digest = hash_file(&owned);
+            if let (true, Some(atime)) = (restore, atime) {
+                restore_atime(&owned, atime);
+            }
+            digest
+        })
+        .await
+        .ok()
+        .and_then(|r| match r {
+            Ok(digest) => Some(digest),
+            Err(e) => {
+                stderr!("⚠️  Cannot hash {}: {}", path.display(), e);
+                None
+            }
+        });
+        self.hashes.insert(path.to_path_buf(), digest);
+        digest
+    }
+}
+
+async fn index_bucket(bucket: PathBuf) -> HashMap<u64, Vec<PathBuf>> {
+    tokio::task::spawn_blocking(move || {
+        let mut index: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        let entries = WalkDir::new(&bucket).into_iter().filter_entry(|e| !is_reserved(e.file_name()));
+        for entry in entries.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Ok(metadata) = entry.metadata() {
+                index.entry(metadata.len()).or_default().push(entry.into_path());
+            }
+        }
+        index
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Replace a duplicate with a hard link at its destination pointing to the
+/// existing copy, keeping the file's name in the bucket.
+pub fn hardlink(source: &Path, existing: &Path, target: &Path) -> std::io::Result<()> {
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::hard_link(existing, target)?;
+    if let Err(e) = crate::fsutil::remove_entry(source) {
+        let _ = std::fs::remove_file(target);
+        return Err(e);
+    }
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! What chronoban did to a directory since some point, read back from its
+//! journal: entries organized, buckets created, duplicates and months
+//! removed, and anything that looks wrong now, such as runs that never
+//! finished or files no longer where they were put.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::hash::{hash_tree, to_hex};
+use crate::journal::journal_path;
+use crate::json::{self, Value};
+use crate::output::{stdout, OutputFormat};
+use crate::schema::SCHEMA_VERSION;
+use crate::units::{parse_duration, parse_instant};
+
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// Directory whose journal to read
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Where to start: a duration back from now (e.g. 1d, 12h), a time
+    /// (e.g. 2024-03-31), or a run id, to report the runs after it
+    #[arg(long, value_parser = parse_since)]
+    since: Since,
+
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+#[derive(Clone, Debug)]
+pub enum Since {
+    Time(DateTime<Local>),
+    Run(String),
+}
+
+fn parse_since(s: &str) -> Result<Since, String> {
+    if let Ok(ago) = parse_duration(s) {
+        let ago = chrono::Duration::from_std(ago).map_err(|e| e.to_string())?;
+        return Ok(Since::Time(Local::now() - ago));
+    }
+    if let Ok(time) = parse_instant(s) {
+        return Ok(Since::Time(time.into()));
+    }
+    match s.trim() {
+        "" => Err("expected a duration, a time or a run id".to_string()),
+        run_id => Ok(Since::Run(run_id.to_string())),
+    }
+}
+
+/// What the journal says happened after the starting point.
+#[derive(Debug, Default)]
+struct Report {
+    runs: Vec<String>,
+    organized: usize,
+    /// Of those, how many were copied rather than renamed.
+    copied: usize,
+    /// Entries organized into each bucket, and whether the bucket is new.
+    buckets: BTreeMap<PathBuf, (usize, bool)>,
+    deduplicated: usize,
+    deleted_empty: usize,
+    pruned: Vec<(PathBuf, String)>,
+    unfinished: Vec<String>,
+    missing: Vec<PathBuf>,
+    changed: Vec<PathBuf>,
+}
+
+impl Report {
+    fn is_empty(&self) -> bool {
+        self.organized == 0 && self.deduplicated == 0 && self.deleted_empty == 0 && self.pruned.is_empty()
+    }
+}
+
+pub async fn run(args: DiffArgs) -> Result<()> {
+    let base = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    let path = journal_path(&base);
+    let text = match fs::read_to_string(&path).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!("No journal in {}; runs only leave one with --journal", base.display())
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to read journal: {:?}", path)),
+    };
+    // A line cut short by a crash is of no use; skip it
+    let records: Vec<Value> = text.lines().filter_map(|line| json::parse(line).ok()).collect();
+
+    let start = match &args.since {
+        Since::Time(since) => records.iter().position(|r| time(r).is_some_and(|t| t >= *since)),
+        Since::Run(since) => {
+            if !records.iter().any(|r| run_id(r) == since) {
+                anyhow::bail!("No run {} in the journal of {}", since, base.display());
+            }
+            // Run ids sort chronologically
+            records.iter().position(|r| run_id(r) > since.as_str())
+        }
+    };
+    let (before, after) = records.split_at(start.unwrap_or(records.len()));
+
+    let report = tokio::task::spawn_blocking({
+        let (before, after) = (before.to_vec(), after.to_vec());
+        move || compare(&before, &after)
+    })
+    .await?;
+
+    match args.output {
+        OutputFormat::Text => print_text(&base, &args.since, &report),
+        OutputFormat::Json => print_json(&base, &args.since, &report),
+    }
+    Ok(())
+}
+
+fn compare(before: &[Value], after: &[Value]) -> Report {
+    // Plans know each entry's bucket; a move on its own only knows the target
+    let mut buckets_of = HashMap::new();
+    let mut known = HashSet::new();
+    for record in before.iter().chain(after) {
+        if let Some(entries) = record.get("plan").and_then(|p| p.get("entries")).and_then(Value::as_array) {
+            for entry in entries {
+                if let (Some(source), Some(bucket)) = (str_field(entry, "source"), str_field(entry, "bucket")) {
+                    buckets_of.insert(PathBuf::from(source), PathBuf::from(bucket));
+                }
+            }
+        }
+    }
+    for record in before {
+        if let Some((source, target)) = moved(record) {
+            known.insert(bucket_of(&buckets_of, &source, &target));
+        }
+    }
+
+    let mut report = Report::default();
+    let mut finished = HashSet::new();
+    let mut planned = Vec::new();
+    // Where each entry organized in the window should be now
+    let mut placed: BTreeMap<PathBuf, Option<String>> = BTreeMap::new();
+    for record in after {
+        let run = run_id(record).to_string();
+        if !report.runs.contains(&run) {
+            report.runs.push(run.clone());
+        }
+        match str_field(record, "event") {
+            Some("move") => {
+                let Some((source, target)) = moved(record) else { continue };
+                report.organized += 1;
+                if str_field(record, "method") == Some("copy") {
+                    report.copied += 1;
+                }
+                let bucket = bucket_of(&buckets_of, &source, &target);
+                let created = !known.contains(&bucket);
+                report.buckets.entry(bucket).or_insert((0, created)).0 += 1;
+                placed.remove(&source);
+                placed.insert(target, str_field(record, "sha256").map(String::from));
+            }
+            Some("rollback") => {
+                let (Some(source), Some(target)) = (str_field(record, "source"), str_field(record, "target")) else {
+                    continue;
+                };
+                let (source, target) = (Path::new(source), PathBuf::from(target));
+                report.organized = report.organized.saturating_sub(1);
+                let bucket = bucket_of(&buckets_of, source, &target);
+                if let Some((count, _)) = report.buckets.get_mut(&bucket) {
+                    *count = count.saturating_sub(1);
+                    if *count == 0 {
+                        report.buckets.remove(&bucket);
+                    }
+                }
+                placed.remove(&target);
+            }
+            Some("dedupe") => {
+                report.deduplicated += 1;
+                if let Some(source) = str_field(record, "source") {
+                    placed.remove(Path::new(source));
+                }
+            }
+            Some("delete") => {
+                report.deleted_empty += 1;
+                if let Some(source) = str_field(record, "source") {
+                    placed.remove(Path::new(source));
+                }
+            }
+            Some("prune") => {
+                if let Some(bucket) = str_field(record, "bucket") {
+                    let bucket = PathBuf::from(bucket);
+                    placed.retain(|path, _| !path.starts_with(&bucket));
+                    report.pruned.push((bucket, str_field(record, "action").unwrap_or_default().to_string()));
+                }
+            }
+            Some("plan") => planned.push(run),
+            Some("finish") => {
+                finished.insert(run);
+            }
+            _ => {}
+        }
+    }
+    report.unfinished = planned.into_iter().filter(|run| !finished.contains(run)).collect();
+
+    for (path, sha256) in placed {
+        if std::fs::symlink_metadata(&path).is_err() {
+            report.missing.push(path);
+        } else if let Some(sha256) = sha256 {
+            if hash_tree(&path).is_ok_and(|digest| to_hex(&digest) != sha256) {
+                report.changed.push(path);
+            }
+        }
+    }
+    report
+}
+
+fn print_text(base: &Path, since: &Since, report: &Report) {
+    let label = match since {
+        Since::Time(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        Since::Run(run_id) => format!("run {}", run_id),
+    };
+    stdout!("📰 Since {} in {}: {} runs", label, base.display(), report.runs.len());
+    if report.is_empty() && report.unfinished.is_empty() {
+        stdout!("✨ Nothing was organized");
+        return;
+    }
+    if report.organized > 0 {
+        stdout!("📦 Organized {} entries ({} copied)", report.organized, report.copied);
+        for (bucket, (entries, created)) in &report.buckets {
+            let new = if *created { " (new)" } else { "" };
+            stdout!("  {}: {} entries{}", relative(base, bucket).display(), entries, new);
+        }
+    }
+    if report.deduplicated > 0 || report.deleted_empty > 0 {
+        stdout!("🧹 Removed {} duplicates and {} empty files", report.deduplicated, report.deleted_empty);
+    }
+    for (bucket, action) in &report.pruned {
+        stdout!("🗑️  Pruned {} ({})", relative(base, bucket).display(), action);
+    }
+    for run_id in &report.unfinished {
+        stdout!("⚠️  Run {} did not finish; --resume picks it up", run_id);
+    }
+    for path in &report.missing {
+        stdout!("⚠️  No longer where it was put: {}", path.display());
+    }
+    for path in &report.changed {
+        stdout!("⚠️  Changed since its checksum was verified: {}", path.display());
+    }
+}
+
+fn print_json(base: &Path, since: &Since, report: &Report) {
+    let since = match since {
+        Since::Time(time) => time.to_rfc3339(),
+        Since::Run(run_id) => run_id.clone(),
+    };
+    let buckets = report.buckets.iter().map(|(bucket, (entries, created))| {
+        json::object([
+            ("bucket", relative(base, bucket).into()),
+            ("entries", (*entries).into()),
+            ("created", (*created).into()),
+        ])
+    });
+    let pruned = report.pruned.iter().map(|(bucket, action)| {
+        json::object([("bucket", relative(base, bucket).into()), ("action", action.as_str().into())])
+    });
+    let anomalies = report
+        .unfinished
+        .iter()
+        .map(|run_id| json::object([("kind", "unfinished".into()), ("run_id", run_id.as_str().into())]))
+        .chain(report.missing.iter().map(|path| {
+            json::object([("kind", "missing".into()), ("path", path.as_path().into())])
+        }))
+        .chain(report.changed.iter().map(|path| {
+            json::object([("kind", "changed".into()), ("path", path.as_path().into())])
+        }));
+    println!("{}", json::object([
+        ("schema_version", SCHEMA_VERSION.into()),
+        ("base", base.into()),
+        ("since", since.into()),
+        ("runs", report.runs.clone().into()),
+        ("organized", report.organized.into()),
+        ("copied", report.copied.into()),
+        ("buckets", Value::Array(buckets.collect())),
+        ("deduplicated", report.deduplicated.into()),
+        ("deleted_empty", report.deleted_empty.into()),
+        ("pruned", Value::Array(pruned.collect())),
+        ("anomalies", Value::Array(anomalies.collect())),
+    ]));
+}
+
+fn time(record: &Value) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(str_field(record, "time")?).ok().map(Into::into)
+}
+
+fn run_id(record: &Value) -> &str {
+    str_field(record, "run_id").unwrap_or_default()
+}
+
+fn str_field<'a>(record: &'a Value, key: &str) -> Option<&'a str> {
+    record.get(key).and_then(Value::as_str)
+}
+
+fn moved(record: &Value) -> Option<(PathBuf, PathBuf)> {
+    if str_field(record, "event") != Some("move") {
+        return None;
+    }
+    Some((str_field(record, "source")?.into(), str_field(record, "target")?.into()))
+}
+
+fn bucket_of(buckets: &HashMap<PathBuf, PathBuf>, source: &Path, target: &Path) -> PathBuf {
+    match buckets.get(source) {
+        Some(bucket) => bucket.clone(),
+        None => target.parent().unwrap_or(target).to_path_buf(),
+    }
+}
+
+fn relative<'a>(base: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(base).unwrap_or(path)
+}
@@ -0,0 +1,235 @@
+//! Mailing the run summary, with `--email-report`, for machines with no
+//! other way to say a nightly run is failing. Plain SMTP to a relay such as
+//! a local MTA is spoken directly; TLS and logins go through curl, as there
+//! is no TLS here. Mail that can't be delivered is warned about but never
+//! fails the run.
+//!
+//! ```toml
+//! [email]
+//! to = ["me@example.com"]
+//! smtp = "smtps://mail.example.com"
+//! user = "me@example.com"   # password from CHRONOBAN_SMTP_PASSWORD
+//! ```
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+use crate::identity::Identity;
+use crate::json::Value;
+use crate::output::stderr;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where the SMTP password comes from, so it stays out of argument lists.
+const PASSWORD_VAR: &str = "CHRONOBAN_SMTP_PASSWORD";
+
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct EmailArgs {
+    /// Mail the summary and any errors to this address after each run, or
+    /// when a run fails (repeatable)
+    #[arg(long, value_name = "ADDR")]
+    pub email_report: Vec<String>,
+
+    /// Mail server: smtp://host[:port] for plain SMTP, smtps://host[:port]
+    /// for TLS (default smtp://localhost)
+    #[arg(long, value_name = "URL", value_parser = parse_server)]
+    pub smtp: Option<String>,
+
+    /// Upgrade a smtp:// connection with STARTTLS
+    #[arg(long)]
+    pub smtp_starttls: bool,
+
+    /// Log in as this user, with the password in CHRONOBAN_SMTP_PASSWORD
+    #[arg(long, value_name = "USER")]
+    pub smtp_user: Option<String>,
+
+    /// Sender address (default chronoban@ this host)
+    #[arg(long, value_name = "ADDR")]
+    pub smtp_from: Option<String>,
+}
+
+pub fn parse_server(s: &str) -> Result<String, String> {
+    if s.starts_with("smtp://") || s.starts_with("smtps://") {
+        Ok(s.trim_end_matches('/').to_string())
+    } else {
+        Err(format!("expected a smtp:// or smtps:// URL: {:?}", s))
+    }
+}
+
+impl EmailArgs {
+    /// Read the `[email]` table of the config file.
+    pub fn from_config(table: &Value) -> Result<EmailArgs> {
+        let string = |key: &str| -> Result<Option<String>> {
+            match table.get(key) {
+                Some(v) => Ok(Some(v.as_str().with_context(|| format!("email.{} must be a string", key))?.to_string())),
+                None => Ok(None),
+            }
+        };
+        let to = match table.get("to") {
+            Some(Value::String(s)) => vec![s.clone()],
+            Some(v) => v
+                .as_array()
+                .and_then(|items| items.iter().map(|v| v.as_str().map(String::from)).collect())
+                .context("email.to must be an address or a list of them")?,
+            None => Vec::new(),
+        };
+        let smtp = match string("smtp")? {
+            Some(s) => Some(parse_server(&s).map_err(anyhow::Error::msg).context("email.smtp")?),
+            None => None,
+        };
+        let starttls = match table.get("starttls") {
+            Some(v) => v.as_bool().context("email.starttls must be true or false")?,
+            None => false,
+        };
+        Ok(EmailArgs { email_report: to, smtp, smtp_starttls: starttls, smtp_user: string("user")?, smtp_from: string("from")? })
+    }
+
+    /// These settings, with anything left unset taken from `fallback`.
+    pub fn or(self, fallback: EmailArgs) -> EmailArgs {
+        EmailArgs {
+            email_report: if self.email_report.is_empty() { fallback.email_report } else { self.email_report },
+            smtp: self.smtp.or(fallback.smtp),
+            smtp_starttls: self.smtp_starttls || fallback.smtp_starttls,
+            smtp_user: self.smtp_user.or(fallback.smtp_user),
+            smtp_from: self.smtp_from.or(fallback.smtp_from),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.email_report.is_empty()
+    }
+}
+
+/// Mail `body` to the report's recipients, warning if it can't be sent.
+pub async fn send(settings: &EmailArgs, subject: &str, body: &str) {
+    if !settings.is_enabled() {
+        return;
+    }
+    let from = settings.smtp_from.clone().unwrap_or_else(|| format!("chronoban@{}", Identity::current().host));
+    let message = message(&from, &settings.email_report, subject, body);
+    let server = settings.smtp.as_deref().unwrap_or("smtp://localhost");
+    let sent = if server.starts_with("smtps://") || settings.smtp_starttls || settings.smtp_user.is_some() {
+        curl(settings, server, &from, &message).await
+    } else {
+        let address = &server["smtp://".len()..];
+        let address = if address.contains(':') { address.to_string() } else { format!("{}:25", address) };
+        tokio::time::timeout(TIMEOUT, smtp(&address, &from, &settings.email_report, &message))
+            .await
+            .context("timed out")
+            .and_then(|sent| sent)
+    };
+    if let Err(e) = sent {
+        stderr!("⚠️  Could not mail the report to {}: {:#}", settings.email_report.join(", "), e);
+    }
+}
+
+/// The whole message, with CRLF line endings.
+fn message(from: &str, to: &[String], subject: &str, body: &str) -> String {
+    let mut text = format!(
+        "From: chronoban <{}>\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        from, to.join(", "), subject, Local::now().to_rfc2822());
+    for line in body.lines() {
+        text.push_str(line);
+        text.push_str("\r\n");
+    }
+    text
+}
+
+/// Deliver `message` over plain SMTP.
+async fn smtp(address: &str, from: &str, to: &[String], message: &str) -> Result<()> {
+    let stream = TcpStream::connect(address).await.with_context(|| format!("Failed to reach {}", address))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    expect(&mut reader, "220").await?;
+
+    let mut command = async |line: String, code: &str| -> Result<()> {
+        writer.write_all(line.as_bytes()).await?;
+        writer.write_all(b"\r\n").await?;
+        expect(&mut reader, code).await.with_context(|| format!("after {}", line.split(':').next().unwrap_or(&line)))
+    };
+    command(format!("EHLO {}", Identity::current().host), "250").await?;
+    command(format!("MAIL FROM:<{}>", from), "250").await?;
+    for recipient in to {
+        command(format!("RCPT TO:<{}>", recipient), "25").await?;
+    }
+    command("DATA".to_string(), "354").await?;
+    // A line holding only "." would end the message early
+    let mut stuffed = String::with_capacity(message.len());
+    for line in message.split_inclusive("\r\n") {
+        if line.starts_with('.') {
+            stuffed.push('.');
+        }
+        stuffed.push_str(line);
+    }
+    stuffed.push('.');
+    command(stuffed, "250").await?;
+    command("QUIT".to_string(), "221").await?;
+    Ok(())
+}
+
+/// Read one reply, which may span lines, and check its code starts with
+/// `code`.
+async fn expect(reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>, code: &str) -> Result<()> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            anyhow::bail!("server closed the connection");
+        }
+        // "250-..." continues, "250 ..." ends
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        if !line.starts_with(code) {
+            anyhow::bail!("server answered {:?}", line.trim_end());
+        }
+        return Ok(());
+    }
+}
+
+/// Deliver `message` through curl, which speaks TLS. The login is handed
+/// over on stdin and the message in a temporary file, keeping both out of
+/// the process list.
+async fn curl(settings: &EmailArgs, server: &str, from: &str, message: &str) -> Result<()> {
+    let file = std::env::temp_dir().join(format!("chronoban-mail-{}.eml", std::process::id()));
+    std::fs::write(&file, message).with_context(|| format!("Failed to write {:?}", file))?;
+
+    let mut config = format!("url = \"{}\"\nmail-from = \"{}\"\n", server, from);
+    for recipient in &settings.email_report {
+        config.push_str(&format!("mail-rcpt = \"{}\"\n", recipient));
+    }
+    if settings.smtp_starttls {
+        config.push_str("ssl-reqd\n");
+    }
+    if let Some(user) = &settings.smtp_user {
+        let password = std::env::var(PASSWORD_VAR).with_context(|| format!("--smtp-user needs {} set", PASSWORD_VAR))?;
+        config.push_str(&format!("user = \"{}:{}\"\n", user, password.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+
+    let result = async {
+        let mut child = Command::new("curl")
+            .args(["-fsS", "-m", &TIMEOUT.as_secs().to_string(), "-K", "-", "--upload-file"])
+            .arg(&file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start curl")?;
+        if let Some(mut pipe) = child.stdin.take() {
+            pipe.write_all(config.as_bytes()).await?;
+        }
+        let status = tokio::time::timeout(TIMEOUT, child.wait()).await.context("timed out")??;
+        if !status.success() {
+            anyhow::bail!("curl exited with {}", status);
+        }
+        Ok(())
+    }
+    .await;
+    let _ = std::fs::remove_file(&file);
+    result
+}
@@ -0,0 +1,332 @@
+//! Dates recorded inside files, for `--embedded-dates` and the `media`,
+//! `email` and `document` date providers: when a video or audio recording
+//! was made, from MP4/MOV movie headers, ID3 tags and Vorbis comments, when
+//! an `.eml` message was sent, and when a PDF was created. Copying a file
+//! off a phone or exporting mail gives it a new mtime; the date inside it
+//! stays put.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::plan::DateSource;
+
+/// Tags live near the start of a file; this bounds the read.
+const READ_LIMIT: u64 = 256 * 1024;
+
+/// Seconds from 1904-01-01, the QuickTime epoch, to 1970-01-01.
+const QUICKTIME_EPOCH_OFFSET: u64 = 2_082_844_800;
+
+/// The date recorded inside `path`, if it is a kind of file that carries
+/// one and the date is set.
+pub fn recorded(path: &Path) -> Option<(SystemTime, DateSource)> {
+    if is_email(path) {
+        return Some((email(path)?, DateSource::Email));
+    }
+    Some((media(path)?, DateSource::Media))
+}
+
+fn is_email(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("eml"))
+}
+
+/// When an `.eml` message was sent.
+pub fn email(path: &Path) -> Option<SystemTime> {
+    if !is_email(path) {
+        return None;
+    }
+    let mut buf = Vec::new();
+    File::open(path).ok()?.take(READ_LIMIT).read_to_end(&mut buf).ok()?;
+    email_sent(&String::from_utf8_lossy(&buf))
+}
+
+/// When a video or audio file was recorded.
+pub fn media(path: &Path) -> Option<SystemTime> {
+    let mut file = File::open(path).ok()?;
+    let mut magic = [0; 12];
+    file.read_exact(&mut magic).ok()?;
+    file.seek(SeekFrom::Start(0)).ok()?;
+
+    let time = if &magic[4..8] == b"ftyp" {
+        movie_created(&mut file)?
+    } else {
+        let mut buf = Vec::new();
+        file.take(READ_LIMIT).read_to_end(&mut buf).ok()?;
+        let date = if buf.starts_with(b"ID3") {
+            id3_date(&buf)?
+        } else if buf.starts_with(b"fLaC") {
+            vorbis_date(flac_comments(&buf)?)?
+        } else if buf.starts_with(b"OggS") {
+            vorbis_date(ogg_comments(&buf)?)?
+        } else {
+            return None;
+        };
+        SystemTime::from(Local.from_local_datetime(&date).earliest()?)
+    };
+    Some(time)
+}
+
+/// When a PDF was created, from the CreationDate of its document
+/// information. That dictionary is usually near the end of the file, where
+/// the trailer points to it, so both ends are searched.
+pub fn document(path: &Path) -> Option<SystemTime> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let mut head = Vec::new();
+    (&mut file).take(READ_LIMIT).read_to_end(&mut head).ok()?;
+    if !head.starts_with(b"%PDF-") {
+        return None;
+    }
+    let mut tail = Vec::new();
+    if len > READ_LIMIT {
+        file.seek(SeekFrom::Start(len.saturating_sub(READ_LIMIT).max(READ_LIMIT))).ok()?;
+        file.read_to_end(&mut tail).ok()?;
+    }
+    pdf_creation_date(&tail).or_else(|| pdf_creation_date(&head))
+}
+
+/// `/CreationDate (D:YYYYMMDDHHmmSS+HH'mm')`; everything after the year is
+/// optional.
+fn pdf_creation_date(buf: &[u8]) -> Option<SystemTime> {
+    const KEY: &[u8] = b"/CreationDate";
+    let at = buf.windows(KEY.len()).position(|w| w == KEY)? + KEY.len();
+    let rest = &buf[at..buf.len().min(at + 64)];
+    let start = rest.windows(2).position(|w| w == b"D:")? + 2;
+    let text: String = rest[start..]
+        .iter()
+        .take_while(|&&b| b != b')')
+        .map(|&b| b as char)
+        .collect();
+    let digits: String = text.chars().take_while(char::is_ascii_digit).collect();
+    let field = |from: usize, default: u32| {
+        digits.get(from..from + 2).and_then(|d| d.parse().ok()).unwrap_or(default)
+    };
+    let year: i32 = digits.get(..4)?.parse().ok()?;
+    let date = NaiveDate::from_ymd_opt(year, field(4, 1), field(6, 1))?
+        .and_hms_opt(field(8, 0), field(10, 0), field(12, 0))?;
+    // The zone: Z, or +HH'mm' / -HH'mm'; without one the time is local
+    let zone = &text[digits.len()..];
+    let offset = match zone.chars().next() {
+        Some('Z') => Some(0),
+        Some(sign @ ('+' | '-')) => {
+            let numbers: Vec<i32> = zone[1..].split('\'').filter_map(|n| n.parse().ok()).collect();
+            let minutes = numbers.first().copied().unwrap_or(0) * 60 + numbers.get(1).copied().unwrap_or(0);
+            Some(if sign == '-' { -minutes } else { minutes })
+        }
+        _ => None,
+    };
+    let time = match offset.and_then(|minutes| chrono::FixedOffset::east_opt(minutes * 60)) {
+        Some(zone) => SystemTime::from(zone.from_local_datetime(&date).single()?),
+        None => SystemTime::from(Local.from_local_datetime(&date).earliest()?),
+    };
+    Some(time)
+}
+
+/// The creation time in an MP4/MOV file's `moov/mvhd` box. The movie box
+/// may come after the media data, so top-level boxes are skipped over
+/// rather than read.
+fn movie_created(file: &mut File) -> Option<SystemTime> {
+    let len = file.metadata().ok()?.len();
+    let moov = find_box(file, 0, len, b"moov")?;
+    let mvhd = find_box(file, moov.0, moov.1, b"mvhd")?;
+    let mut header = [0; 12];
+    file.seek(SeekFrom::Start(mvhd.0)).ok()?;
+    file.read_exact(&mut header).ok()?;
+    // Version, flags, then the creation time in 32 or 64 bits
+    let seconds = match header[0] {
+        0 => u64::from(u32::from_be_bytes(header[4..8].try_into().ok()?)),
+        1 => u64::from_be_bytes(header[4..12].try_into().ok()?),
+        _ => return None,
+    };
+    // Unset times are 0, i.e. 1904, and some tools write their own epoch
+    let unix = seconds.checked_sub(QUICKTIME_EPOCH_OFFSET).filter(|s| *s > 0)?;
+    Some(UNIX_EPOCH + Duration::from_secs(unix))
+}
+
+/// The `Date:` header of a message, read as RFC 2822. Headers end at the
+/// first blank line and may be folded onto indented continuation lines.
+fn email_sent(message: &str) -> Option<SystemTime> {
+    let mut date: Option<String> = None;
+    for line in message.lines() {
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            if let Some(date) = &mut date {
+                date.push(' ');
+                date.push_str(line.trim());
+            }
+            continue;
+        }
+        if date.is_some() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("date") {
+                date = Some(value.trim().to_string());
+            }
+        }
+    }
+    let date = date?;
+    // Mailers often append the zone's name as a comment: "+0100 (CET)"
+    let date = match date.find('(') {
+        Some(at) => date[..at].trim(),
+        None => date.as_str(),
+    };
+    DateTime::parse_from_rfc2822(date).ok().map(SystemTime::from)
+}
+
+/// The body (start, end) of the first `kind` box between `start` and `end`.
+fn find_box(file: &mut File, start: u64, end: u64, kind: &[u8; 4]) -> Option<(u64, u64)> {
+    let mut pos = start;
+    while pos + 8 <= end {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(pos)).ok()?;
+        file.read_exact(&mut header).ok()?;
+        let (size, body) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            // A 64-bit size follows the type
+            1 => {
+                let mut large = [0; 8];
+                file.read_exact(&mut large).ok()?;
+                (u64::from_be_bytes(large), pos + 16)
+            }
+            // The box runs to the end
+            0 => (end - pos, pos + 8),
+            size => (u64::from(size), pos + 8),
+        };
+        if size < body - pos {
+            return None;
+        }
+        if &header[4..8] == kind {
+            return Some((body, (pos + size).min(end)));
+        }
+        pos += size;
+    }
+    None
+}
+
+/// The recording date in an ID3v2.3 or v2.4 tag: TDRC (or TDOR), or the
+/// TYER, TDAT and TIME frames of v2.3.
+fn id3_date(buf: &[u8]) -> Option<NaiveDateTime> {
+    let version = *buf.get(3)?;
+    if !(3..=4).contains(&version) {
+        return None;
+    }
+    let size = syncsafe(buf.get(6..10)?) as usize;
+    let tag = buf.get(10..(10 + size).min(buf.len()))?;
+
+    let mut frames = std::collections::HashMap::new();
+    let mut pos = 0;
+    while pos + 10 <= tag.len() && tag[pos] != 0 {
+        let id = &tag[pos..pos + 4];
+        let raw = &tag[pos + 4..pos + 8];
+        let len = if version == 4 { syncsafe(raw) } else { u32::from_be_bytes(raw.try_into().ok()?) } as usize;
+        let Some(body) = tag.get(pos + 10..pos + 10 + len) else { break };
+        if id[0] == b'T' {
+            frames.insert(id.to_vec(), id3_text(body));
+        }
+        pos += 10 + len;
+    }
+
+    for id in [&b"TDRC"[..], b"TDOR"] {
+        if let Some(date) = frames.get(id).and_then(|text| parse_date(text)) {
+            return Some(date);
+        }
+    }
+    // v2.3 splits the date: TYER is YYYY, TDAT is DDMM and TIME is HHMM
+    let year = frames.get(&b"TYER"[..])?;
+    let day_month = frames.get(&b"TDAT"[..])?;
+    let (day, month) = (day_month.get(..2)?, day_month.get(2..4)?);
+    let time = frames.get(&b"TIME"[..]).and_then(|t| Some(format!("T{}:{}:00", t.get(..2)?, t.get(2..4)?)));
+    parse_date(&format!("{}-{}-{}{}", year, month, day, time.unwrap_or_default()))
+}
+
+fn syncsafe(b: &[u8]) -> u32 {
+    b.iter().fold(0, |n, &byte| (n << 7) | u32::from(byte & 0x7f))
+}
+
+/// A text frame's first string, in whichever ID3 encoding it declares.
+fn id3_text(body: &[u8]) -> String {
+    let Some((&encoding, text)) = body.split_first() else {
+        return String::new();
+    };
+    let text = match encoding {
+        1 | 2 => {
+            let big_endian = encoding == 2 || text.starts_with(&[0xfe, 0xff]);
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|c| if big_endian { u16::from_be_bytes([c[0], c[1]]) } else { u16::from_le_bytes([c[0], c[1]]) })
+                .filter(|&u| u != 0xfeff)
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    };
+    text.split('\0').next().unwrap_or_default().trim().to_string()
+}
+
+/// The VORBIS_COMMENT block of a FLAC file.
+fn flac_comments(buf: &[u8]) -> Option<&[u8]> {
+    let mut pos = 4;
+    loop {
+        let header = buf.get(pos..pos + 4)?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        if header[0] & 0x7f == 4 {
+            return buf.get(pos + 4..pos + 4 + len);
+        }
+        if header[0] & 0x80 != 0 {
+            return None;
+        }
+        pos += 4 + len;
+    }
+}
+
+/// The comment header of an Ogg Vorbis or Opus stream. It follows the
+/// identification header, normally on the second page.
+fn ogg_comments(buf: &[u8]) -> Option<&[u8]> {
+    for marker in [&b"\x03vorbis"[..], b"OpusTags"] {
+        if let Some(at) = buf.windows(marker.len()).position(|w| w == marker) {
+            return buf.get(at + marker.len()..);
+        }
+    }
+    None
+}
+
+/// The DATE field of a Vorbis comment block.
+fn vorbis_date(block: &[u8]) -> Option<NaiveDateTime> {
+    let u32_at = |at: usize| -> Option<usize> {
+        Some(u32::from_le_bytes(block.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+    let vendor = u32_at(0)?;
+    let mut pos = 4 + vendor;
+    let count = u32_at(pos)?;
+    pos += 4;
+    for _ in 0..count {
+        let len = u32_at(pos)?;
+        let comment = block.get(pos + 4..pos + 4 + len)?;
+        pos += 4 + len;
+        let comment = String::from_utf8_lossy(comment);
+        if let Some((key, value)) = comment.split_once('=') {
+            if key.eq_ignore_ascii_case("DATE") {
+                return parse_date(value);
+            }
+        }
+    }
+    None
+}
+
+/// A tag date: `YYYY-MM-DD`, optionally with a time after `T` or a space. A
+/// bare year is usually a release year rather than when a recording was
+/// made, so it doesn't count.
+fn parse_date(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    let date = NaiveDate::parse_from_str(text.get(..10)?, "%Y-%m-%d").ok()?;
+    let rest = text[10..].trim_start_matches(['T', ' ']);
+    let time = ["%H:%M:%S", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(rest.get(..rest.len().min(8))?, format).ok())
+        .unwrap_or(NaiveTime::MIN);
+    Some(date.and_time(time))
+}
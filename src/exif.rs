@@ -0,0 +1,200 @@
+//! Just enough EXIF to name the camera a photo came from, from the Make and
+//! Model tags of IFD0, and to tell when it was taken, in JPEGs and
+//! TIFF-based raw files (CR2, NEF, ARW, DNG, ...).
+
+use chrono::NaiveDateTime;
+use std::io::Read;
+use std::path::Path;
+
+/// EXIF lives near the start of a file; this bounds the read.
+const READ_LIMIT: u64 = 256 * 1024;
+
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TYPE_ASCII: u16 = 2;
+const TYPE_LONG: u16 = 4;
+
+/// The camera that took a photo, as a safe directory name such as
+/// `Fujifilm X-T5`.
+pub fn camera(path: &Path) -> Option<String> {
+    let mut buf = Vec::new();
+    std::fs::File::open(path).ok()?.take(READ_LIMIT).read_to_end(&mut buf).ok()?;
+    let tiff = Tiff::new(tiff_block(&buf)?)?;
+    let ifd0 = tiff.u32_at(4)? as usize;
+    let make = tiff.string(ifd0, TAG_MAKE);
+    let model = tiff.string(ifd0, TAG_MODEL);
+    camera_name(make.as_deref(), model.as_deref())
+}
+
+/// When a photo was taken: DateTimeOriginal from the Exif IFD, or failing
+/// that the DateTime of IFD0. EXIF times carry no zone; they are the
+/// camera's local time.
+pub fn taken(path: &Path) -> Option<NaiveDateTime> {
+    let mut buf = Vec::new();
+    std::fs::File::open(path).ok()?.take(READ_LIMIT).read_to_end(&mut buf).ok()?;
+    let tiff = Tiff::new(tiff_block(&buf)?)?;
+    let ifd0 = tiff.u32_at(4)? as usize;
+    let original = tiff
+        .entry(ifd0, TAG_EXIF_IFD)
+        .filter(|&entry| tiff.u16_at(entry + 2) == Some(TYPE_LONG))
+        .and_then(|entry| tiff.u32_at(entry + 8))
+        .and_then(|exif| tiff.string(exif as usize, TAG_DATE_TIME_ORIGINAL));
+    let text = original.or_else(|| tiff.string(ifd0, TAG_DATE_TIME))?;
+    NaiveDateTime::parse_from_str(text.trim(), "%Y:%m:%d %H:%M:%S").ok()
+}
+
+/// The TIFF structure holding the EXIF data: the whole file for TIFF-based
+/// formats, or the payload of a JPEG's `Exif` APP1 segment.
+fn tiff_block(buf: &[u8]) -> Option<&[u8]> {
+    if buf.starts_with(b"II*\0") || buf.starts_with(b"MM\0*") {
+        return Some(buf);
+    }
+    if !buf.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= buf.len() {
+        if buf[pos] != 0xff {
+            return None;
+        }
+        let marker = buf[pos + 1];
+        // Start of scan: image data follows, no more metadata.
+        if marker == 0xda {
+            return None;
+        }
+        let len = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]) as usize;
+        let body = buf.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xe1 && body.starts_with(b"Exif\0\0") {
+            return Some(&body[6..]);
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+/// A TIFF structure in its byte order.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Tiff<'a>> {
+        let big_endian = match data.get(..2)? {
+            b"II" => false,
+            b"MM" => true,
+            _ => return None,
+        };
+        Some(Tiff { data, big_endian })
+    }
+
+    fn u16_at(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    }
+
+    fn u32_at(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+
+    /// Where the entry for `tag` is in the IFD at `ifd`.
+    fn entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let entries = self.u16_at(ifd)? as usize;
+        (0..entries).map(|i| ifd + 2 + i * 12).find(|&entry| self.u16_at(entry) == Some(tag))
+    }
+
+    fn string(&self, ifd: usize, tag: u16) -> Option<String> {
+        let entry = self.entry(ifd, tag)?;
+        if self.u16_at(entry + 2)? != TYPE_ASCII {
+            return None;
+        }
+        let count = self.u32_at(entry + 4)? as usize;
+        // Values of up to four bytes are stored in the entry itself.
+        let start = if count <= 4 { entry + 8 } else { self.u32_at(entry + 8)? as usize };
+        let raw = self.data.get(start..start.checked_add(count)?)?;
+        Some(String::from_utf8_lossy(raw).trim_end_matches('\0').to_string())
+    }
+}
+
+/// Corporate suffixes that only make directory names longer.
+const MAKE_SUFFIXES: &[&str] = &[
+    " corporation",
+    " imaging corp.",
+    " corp.",
+    " co., ltd.",
+    " co.,ltd.",
+    " optical co.,ltd",
+    " company",
+];
+
+/// Combine Make and Model into one name, dropping the make where the model
+/// already starts with it (`Canon` + `Canon EOS R5`).
+fn camera_name(make: Option<&str>, model: Option<&str>) -> Option<String> {
+    let make = make.map(tidy_make).filter(|m| !m.is_empty());
+    let model = model.map(str::trim).filter(|m| !m.is_empty());
+    let name = match (make, model) {
+        (Some(make), Some(model)) => {
+            let brand = make.split_whitespace().next().unwrap_or_default().to_lowercase();
+            if model.to_lowercase().starts_with(&brand) {
+                model.to_string()
+            } else {
+                format!("{} {}", make, model)
+            }
+        }
+        (Some(make), None) => make,
+        (None, Some(model)) => model.to_string(),
+        (None, None) => return None,
+    };
+    let safe = sanitize(&name);
+    (!safe.is_empty()).then_some(safe)
+}
+
+/// `FUJIFILM` to `Fujifilm`, `NIKON CORPORATION` to `Nikon`.
+fn tidy_make(make: &str) -> String {
+    let mut make = make.trim().to_string();
+    for suffix in MAKE_SUFFIXES {
+        if let Some(start) = make.len().checked_sub(suffix.len()) {
+            if make.is_char_boundary(start) && make[start..].eq_ignore_ascii_case(suffix) {
+                make.truncate(start);
+            }
+        }
+    }
+    if make.len() > 3 && !make.chars().any(|c| c.is_lowercase()) {
+        make = make
+            .split(' ')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_string() + &chars.as_str().to_lowercase(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+    make
+}
+
+/// Make an arbitrary string safe as a single directory name: no path
+/// separators or control characters, collapsed whitespace, bounded length,
+/// and never `.` or `..`.
+fn sanitize(s: &str) -> String {
+    let replaced: String = s
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let mut out = replaced.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((cut, _)) = out.char_indices().nth(64) {
+        out.truncate(cut);
+    }
+    out.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string()
+}
@@ -0,0 +1,71 @@
+//! How a run ended, as a process exit code, and Ctrl-C (or SIGTERM)
+//! handling that lets in-flight moves finish before stopping.
+
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::output::stderr;
+
+/// Exit codes, so scripts and CI can tell a clean run from a partial one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    Success = 0,
+    /// The run could not start or was aborted by an error.
+    Fatal = 1,
+    /// Some entries failed (or, with `--strict`, hit a conflict).
+    Partial = 2,
+    Interrupted = 3,
+}
+
+impl Exit {
+    /// The outcome of a run that had `failures` per-entry problems.
+    pub fn from_failures(failures: usize) -> Exit {
+        if interrupted() {
+            Exit::Interrupted
+        } else if failures > 0 {
+            Exit::Partial
+        } else {
+            Exit::Success
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> ExitCode {
+        ExitCode::from(exit as u8)
+    }
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C was pressed. Long loops check this between entries.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// On the first Ctrl-C, ask the run to stop after the moves already under
+/// way; on the second, exit at once.
+pub fn handle_interrupts() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                std::process::exit(Exit::Interrupted as i32);
+            }
+            stderr!("\n🛑 Interrupted: finishing moves in progress (Ctrl-C again to abort)");
+        }
+    });
+    // Service managers stop with SIGTERM; treat it like a first Ctrl-C
+    #[cfg(unix)]
+    tokio::spawn(async {
+        use tokio::signal::unix::{signal, SignalKind};
+        let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+            return;
+        };
+        while terminate.recv().await.is_some() {
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                std::process::exit(Exit::Interrupted as i32);
+            }
+            stderr!("🛑 Terminated: finishing moves in progress");
+        }
+    });
+}
@@ -0,0 +1,199 @@
+//! Content-type detection from magic bytes, for files whose extension is
+//! missing or wrong.
+
+use std::io::Read;
+use std::path::Path;
+
+/// A detected file type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileType {
+    pub mime: &'static str,
+    /// Canonical extension, used to match extension-based routes.
+    pub extension: &'static str,
+    pub category: Category,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+    Font,
+    Executable,
+    Text,
+}
+
+impl Category {
+    pub const ALL: [Category; 8] = [
+        Category::Image,
+        Category::Video,
+        Category::Audio,
+        Category::Document,
+        Category::Archive,
+        Category::Font,
+        Category::Executable,
+        Category::Text,
+    ];
+
+    /// Directory name used for the category in destination templates.
+    pub fn folder_name(self) -> &'static str {
+        match self {
+            Category::Image => "images",
+            Category::Video => "videos",
+            Category::Audio => "audio",
+            Category::Document => "documents",
+            Category::Archive => "archives",
+            Category::Font => "fonts",
+            Category::Executable => "executables",
+            Category::Text => "text",
+        }
+    }
+
+    /// Best guess from a canonical lowercase extension, for when content
+    /// isn't sniffed.
+    pub fn from_extension(ext: &str) -> Option<Category> {
+        use Category::*;
+        Some(match ext {
+            "jpg" | "png" | "gif" | "webp" | "heic" | "heif" | "avif" | "tif" | "bmp" | "svg"
+            | "psd" | "raw" | "cr2" | "cr3" | "nef" | "arw" | "dng" | "ico" => Image,
+            "mp4" | "mov" | "mkv" | "avi" | "webm" | "m4v" | "wmv" | "3gp" => Video,
+            "mp3" | "flac" | "wav" | "ogg" | "m4a" | "aac" | "opus" => Audio,
+            "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "ods" | "odp"
+            | "rtf" | "ps" | "epub" | "sqlite" => Document,
+            "zip" | "tar" | "gz" | "xz" | "zst" | "bz2" | "7z" | "rar" => Archive,
+            "ttf" | "otf" | "woff" | "woff2" => Font,
+            "exe" | "dll" | "so" | "dylib" | "elf" | "msi" | "deb" | "rpm" | "dmg" | "appimage" => Executable,
+            "txt" | "md" | "csv" | "json" | "yaml" | "toml" | "xml" | "html" | "log" => Text,
+            _ => return None,
+        })
+    }
+}
+
+/// How many leading bytes detection looks at.
+const SNIFF_LEN: usize = 512;
+
+/// Read the start of a file and identify it. Only files are inspected.
+pub fn detect(path: &Path) -> Option<FileType> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut buf = [0u8; SNIFF_LEN];
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return None,
+        }
+    }
+    detect_bytes(&buf[..len])
+}
+
+const fn ft(mime: &'static str, extension: &'static str, category: Category) -> FileType {
+    FileType {
+        mime,
+        extension,
+        category,
+    }
+}
+
+pub fn detect_bytes(b: &[u8]) -> Option<FileType> {
+    use Category::*;
+
+    let starts = |sig: &[u8]| b.starts_with(sig);
+    let at = |offset: usize, sig: &[u8]| b.len() >= offset + sig.len() && &b[offset..offset + sig.len()] == sig;
+
+    // ISO base media (MP4, MOV, HEIC, AVIF, ...) is identified by its brand.
+    if at(4, b"ftyp") && b.len() >= 12 {
+        return Some(match &b[8..12] {
+            b"heic" | b"heix" | b"hevc" | b"heim" | b"heis" | b"mif1" | b"msf1" => {
+                ft("image/heic", "heic", Image)
+            }
+            b"avif" | b"avis" => ft("image/avif", "avif", Image),
+            b"crx " => ft("image/x-canon-cr3", "cr3", Image),
+            b"qt  " => ft("video/quicktime", "mov", Video),
+            b"M4A " | b"M4B " => ft("audio/mp4", "m4a", Audio),
+            b"3gp4" | b"3gp5" | b"3g2a" => ft("video/3gpp", "3gp", Video),
+            _ => ft("video/mp4", "mp4", Video),
+        });
+    }
+    if at(0, b"RIFF") && b.len() >= 12 {
+        match &b[8..12] {
+            b"WEBP" => return Some(ft("image/webp", "webp", Image)),
+            b"WAVE" => return Some(ft("audio/wav", "wav", Audio)),
+            b"AVI " => return Some(ft("video/x-msvideo", "avi", Video)),
+            _ => {}
+        }
+    }
+
+    let table: &[(&[u8], FileType)] = &[
+        (b"\xFF\xD8\xFF", ft("image/jpeg", "jpg", Image)),
+        (b"\x89PNG\r\n\x1a\n", ft("image/png", "png", Image)),
+        (b"GIF87a", ft("image/gif", "gif", Image)),
+        (b"GIF89a", ft("image/gif", "gif", Image)),
+        (b"BM", ft("image/bmp", "bmp", Image)),
+        (b"8BPS", ft("image/vnd.adobe.photoshop", "psd", Image)),
+        (b"\x00\x00\x01\x00", ft("image/x-icon", "ico", Image)),
+        (b"II*\x00", ft("image/tiff", "tif", Image)),
+        (b"MM\x00*", ft("image/tiff", "tif", Image)),
+        (b"\x1A\x45\xDF\xA3", ft("video/x-matroska", "mkv", Video)),
+        (b"ID3", ft("audio/mpeg", "mp3", Audio)),
+        (b"\xFF\xFB", ft("audio/mpeg", "mp3", Audio)),
+        (b"\xFF\xF3", ft("audio/mpeg", "mp3", Audio)),
+        (b"fLaC", ft("audio/flac", "flac", Audio)),
+        (b"OggS", ft("audio/ogg", "ogg", Audio)),
+        (b"%PDF-", ft("application/pdf", "pdf", Document)),
+        (b"{\\rtf", ft("application/rtf", "rtf", Document)),
+        (b"%!PS", ft("application/postscript", "ps", Document)),
+        (b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", ft("application/x-ole-storage", "doc", Document)),
+        (b"PK\x03\x04", ft("application/zip", "zip", Archive)),
+        (b"\x1F\x8B", ft("application/gzip", "gz", Archive)),
+        (b"BZh", ft("application/x-bzip2", "bz2", Archive)),
+        (b"\xFD7zXZ\x00", ft("application/x-xz", "xz", Archive)),
+        (b"\x28\xB5\x2F\xFD", ft("application/zstd", "zst", Archive)),
+        (b"7z\xBC\xAF\x27\x1C", ft("application/x-7z-compressed", "7z", Archive)),
+        (b"Rar!\x1A\x07", ft("application/vnd.rar", "rar", Archive)),
+        (b"wOFF", ft("font/woff", "woff", Font)),
+        (b"wOF2", ft("font/woff2", "woff2", Font)),
+        (b"OTTO", ft("font/otf", "otf", Font)),
+        (b"\x7FELF", ft("application/x-elf", "elf", Executable)),
+        (b"MZ", ft("application/x-msdownload", "exe", Executable)),
+        (b"SQLite format 3\x00", ft("application/vnd.sqlite3", "sqlite", Document)),
+        (b"<?xml", ft("application/xml", "xml", Text)),
+    ];
+    // A tar header starts with a file name, which could look like anything.
+    if at(257, b"ustar") {
+        return Some(ft("application/x-tar", "tar", Archive));
+    }
+    if let Some((_, t)) = table.iter().find(|(sig, _)| starts(sig)) {
+        // Office documents are zip files; peek at the first member names.
+        if t.extension == "zip" {
+            return Some(office_type(b).unwrap_or(*t));
+        }
+        return Some(*t);
+    }
+
+    let head = String::from_utf8_lossy(&b[..b.len().min(64)]).to_ascii_lowercase();
+    if head.trim_start().starts_with("<!doctype html") || head.trim_start().starts_with("<html") {
+        return Some(ft("text/html", "html", Text));
+    }
+    None
+}
+
+fn office_type(b: &[u8]) -> Option<FileType> {
+    use Category::*;
+    let contains = |needle: &[u8]| b.windows(needle.len()).any(|w| w == needle);
+    if contains(b"word/") {
+        Some(ft("application/vnd.openxmlformats-officedocument.wordprocessingml.document", "docx", Document))
+    } else if contains(b"xl/") {
+        Some(ft("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", "xlsx", Document))
+    } else if contains(b"ppt/") {
+        Some(ft("application/vnd.openxmlformats-officedocument.presentationml.presentation", "pptx", Document))
+    } else if contains(b"mimetypeapplication/epub+zip") {
+        Some(ft("application/epub+zip", "epub", Document))
+    } else if contains(b"mimetypeapplication/vnd.oasis.opendocument.text") {
+        Some(ft("application/vnd.oasis.opendocument.text", "odt", Document))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,165 @@
+//! `chronoban find`: which bucket something ended up in, by the name it
+//! had or has. Searches the directory's journal, a catalog, or the buckets
+//! on disk for trees organized without either.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use walkdir::WalkDir;
+
+use crate::bucket::{find_buckets, parse_bucket_format, BucketFormat};
+use crate::catalog::{self, follow_moves};
+use crate::ignore::Patterns;
+use crate::journal::journal_path;
+use crate::json::{self, Value};
+use crate::marker::is_reserved;
+use crate::output::{stdout, OutputFormat};
+use crate::schema::SCHEMA_VERSION;
+
+#[derive(clap::Args, Debug)]
+pub struct FindArgs {
+    /// Gitignore-style pattern for the name, e.g. 'IMG_2041*' or '*.pdf'
+    pattern: String,
+
+    /// Directory containing the buckets
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Bucket layout of the tree
+    #[arg(short = 'f', long, default_value = "%Y-%m", value_parser = parse_bucket_format)]
+    format: BucketFormat,
+
+    /// Search this catalog instead of the directory's journal
+    #[arg(long, value_name = "FILE")]
+    catalog: Option<PathBuf>,
+
+    /// Search the buckets on disk instead of the journal (the default when
+    /// there is no journal)
+    #[arg(long, conflicts_with = "catalog")]
+    tree: bool,
+
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+/// A file found, where it is now and where it was before chronoban moved it
+/// if a record says so.
+struct Found {
+    path: PathBuf,
+    bucket: Option<String>,
+    original: Option<PathBuf>,
+    exists: bool,
+}
+
+pub async fn run(args: FindArgs) -> Result<()> {
+    let base = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    let pattern = Patterns::new([args.pattern.as_str()]);
+
+    let journal = journal_path(&base);
+    let found = if let Some(catalog) = &args.catalog {
+        recorded(catalog::load(catalog)?, &base, &args.format, &pattern)
+    } else if !args.tree && journal.exists() {
+        let text = fs::read_to_string(&journal)
+            .await
+            .with_context(|| format!("Failed to read journal: {:?}", journal))?;
+        recorded(follow_moves(&text, "target"), &base, &args.format, &pattern)
+    } else {
+        let buckets = find_buckets(&base, &args.format).await?;
+        tokio::task::spawn_blocking({
+            let base = base.clone();
+            move || on_disk(&base, buckets.into_iter().map(|(dir, _)| dir).collect(), &pattern)
+        })
+        .await?
+    };
+
+    match args.output {
+        OutputFormat::Text => {
+            for found in &found {
+                let bucket = found.bucket.as_deref().unwrap_or("-");
+                let mut line = format!("🔎 {}: {}", bucket, found.path.display());
+                if let Some(original) = found.original.as_ref().filter(|o| **o != found.path) {
+                    line.push_str(&format!(" (was {})", original.display()));
+                }
+                if !found.exists {
+                    line.push_str(", no longer there");
+                }
+                stdout!("{}", line);
+            }
+            if found.is_empty() {
+                stdout!("Nothing matches {:?}", args.pattern);
+            }
+        }
+        OutputFormat::Json => {
+            let matches = found.iter().map(|found| {
+                json::object([
+                    ("path", found.path.as_path().into()),
+                    ("bucket", found.bucket.as_deref().into()),
+                    ("original", found.original.as_deref().into()),
+                    ("exists", found.exists.into()),
+                ])
+            });
+            println!("{}", json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("pattern", args.pattern.as_str().into()),
+                ("matches", Value::Array(matches.collect())),
+            ]));
+        }
+    }
+    Ok(())
+}
+
+/// Files from a journal or catalog whose original or current name matches.
+fn recorded(
+    filed: impl IntoIterator<Item = (String, catalog::Filed)>,
+    base: &Path,
+    format: &BucketFormat,
+    pattern: &Patterns,
+) -> Vec<Found> {
+    let named = |path: &Path| path.file_name().is_some_and(|name| pattern.matches(Path::new(name), false));
+    filed
+        .into_iter()
+        .map(|(path, filed)| (PathBuf::from(path), PathBuf::from(filed.original)))
+        .filter(|(path, original)| named(path) || named(original))
+        .map(|(path, original)| Found {
+            bucket: bucket_of(base, format, &path),
+            exists: std::fs::symlink_metadata(&path).is_ok(),
+            path,
+            original: Some(original),
+        })
+        .collect()
+}
+
+/// Entries inside the buckets whose name matches.
+fn on_disk(base: &Path, buckets: Vec<PathBuf>, pattern: &Patterns) -> Vec<Found> {
+    let mut found = Vec::new();
+    for bucket in buckets {
+        let name = bucket.strip_prefix(base).unwrap_or(&bucket).display().to_string();
+        let entries = WalkDir::new(&bucket)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| !is_reserved(e.file_name()))
+            .filter_map(|e| e.ok());
+        for entry in entries {
+            if pattern.matches(Path::new(entry.file_name()), entry.file_type().is_dir()) {
+                found.push(Found {
+                    path: entry.into_path(),
+                    bucket: Some(name.clone()),
+                    original: None,
+                    exists: true,
+                });
+            }
+        }
+    }
+    found
+}
+
+/// The bucket `path` lies in, if it is below `base` in a directory the
+/// format parses.
+fn bucket_of(base: &Path, format: &BucketFormat, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(base).ok()?;
+    let bucket: PathBuf = relative.components().take(format.depth()).collect();
+    format.parse_dir(&bucket)?;
+    Some(bucket.display().to_string())
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+
+use crate::bucket::is_year_month_dir;
+use crate::exit::{self, Exit};
+use crate::fsutil::unique_path;
+use crate::identity::Identity;
+use crate::journal::{self, Journal};
+use crate::lock::LockArgs;
+use crate::marker::{self, is_reserved};
+use crate::mover::MoveMethod;
+use crate::output::{stderr, stdout};
+
+#[derive(clap::Args, Debug)]
+pub struct FlattenArgs {
+    /// Directory containing the month directories
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// What to do when an entry with the same name already exists in the base
+    #[arg(long, value_enum, default_value = "skip")]
+    on_conflict: FlattenConflict,
+
+    /// Record every move in .chronoban/journal.jsonl inside the directory
+    #[arg(long)]
+    journal: bool,
+
+    /// Show what would be moved without changing anything
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum FlattenConflict {
+    /// Leave the entry in its month directory
+    Skip,
+    /// Move it under a numbered name such as `report (2).pdf`
+    Rename,
+}
+
+pub async fn run(args: FlattenArgs) -> Result<Exit> {
+    let base = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    let _lock = if args.dry_run { None } else { args.lock.acquire(&base).await? };
+
+    let journal = if args.journal && !args.dry_run {
+        Some(Arc::new(Journal::open(&base, &journal::new_run_id(), &Identity::current())?))
+    } else {
+        None
+    };
+
+    stdout!("Flattening month directories in: {}", base.display());
+    if args.dry_run {
+        stdout!("🔍 DRY RUN MODE - No files will be moved");
+    }
+    stdout!();
+
+    let mut moved = 0;
+    let mut skipped = 0;
+    let mut errors = 0;
+    let mut removed_dirs = 0;
+
+    for bucket in month_dirs(&base).await? {
+        if exit::interrupted() {
+            break;
+        }
+        let mut entries = fs::read_dir(&bucket)
+            .await
+            .with_context(|| format!("Failed to read directory: {:?}", bucket))?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let source = entry.path();
+            let name = entry.file_name();
+            if is_reserved(&name) {
+                continue;
+            }
+            let target = match args.on_conflict {
+                FlattenConflict::Skip if base.join(&name).exists() => {
+                    stderr!("⚠️  Target already exists, skipping: {} -> {}",
+                        source.display(), base.join(&name).display());
+                    skipped += 1;
+                    continue;
+                }
+                FlattenConflict::Skip => base.join(&name),
+                FlattenConflict::Rename => unique_path(&base, &name),
+            };
+
+            if args.dry_run {
+                stdout!("📦 Would move: {} -> {}", source.display(), target.display());
+                moved += 1;
+                continue;
+            }
+
+            match move_out(&source, &target, journal.as_deref()).await {
+                Ok(()) => {
+                    stdout!("✅ Moved: {} -> {}", source.display(), target.display());
+                    moved += 1;
+                }
+                Err(e) => {
+                    stderr!("❌ Error: {:#}", e);
+                    errors += 1;
+                }
+            }
+        }
+
+        // Only succeeds once the bucket is empty, which is exactly when it should go.
+        if !args.dry_run {
+            marker::remove_if_only(&bucket);
+        }
+        if !args.dry_run && fs::remove_dir(&bucket).await.is_ok() {
+            removed_dirs += 1;
+        }
+    }
+
+    stdout!("\n📊 Summary:");
+    stdout!("  Files moved: {}", moved);
+    stdout!("  Files skipped: {}", skipped);
+    stdout!("  Month directories removed: {}", removed_dirs);
+    stdout!("  Errors: {}", errors);
+
+    Ok(Exit::from_failures(errors))
+}
+
+async fn month_dirs(base: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(base)
+        .await
+        .with_context(|| format!("Failed to read directory: {:?}", base))?;
+
+    let mut dirs = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if is_year_month_dir(&entry.path()) && entry.file_type().await?.is_dir() {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+async fn move_out(source: &Path, target: &Path, journal: Option<&Journal>) -> Result<()> {
+    fs::rename(source, target)
+        .await
+        .with_context(|| format!("Failed to move {:?} to {:?}", source, target))?;
+    if let Some(journal) = journal {
+        journal.record_move(source, target, MoveMethod::Renamed)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,479 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::output::stderr;
+use crate::plan::DateSource;
+
+/// Which of an entry's own timestamps dates it (`--time`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MetadataTime {
+    /// Last modification
+    Mtime,
+    /// Last access
+    Atime,
+    /// Creation: the birth time on Linux (through statx), macOS and the
+    /// BSDs, and the CreationTime of NTFS and FAT on Windows
+    Btime,
+}
+
+impl MetadataTime {
+    pub fn of(self, metadata: &std::fs::Metadata) -> io::Result<SystemTime> {
+        match self {
+            MetadataTime::Mtime => metadata.modified(),
+            MetadataTime::Atime => metadata.accessed(),
+            MetadataTime::Btime => metadata.created(),
+        }
+    }
+
+    pub fn source(self) -> DateSource {
+        match self {
+            MetadataTime::Mtime => DateSource::Modified,
+            MetadataTime::Atime => DateSource::Accessed,
+            MetadataTime::Btime => DateSource::Created,
+        }
+    }
+}
+
+/// Total size of all files below `path`, not following symlinks.
+pub async fn dir_size(path: &Path) -> u64 {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        walkdir::WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.metadata().ok())
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+            .sum()
+    })
+    .await
+    .unwrap_or(0)
+}
+
+/// The newest (or oldest) `time` of any file below `path`, or `None` if it
+/// holds no files.
+pub async fn content_time(path: &Path, time: MetadataTime, newest: bool) -> Option<SystemTime> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let times = walkdir::WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.metadata().ok())
+            .filter(|m| m.is_file())
+            .filter_map(|m| time.of(&m).ok());
+        if newest {
+            times.max()
+        } else {
+            times.min()
+        }
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Paths currently held open by any process we are allowed to inspect.
+#[cfg(target_os = "linux")]
+pub fn open_file_paths() -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for proc_entry in std::fs::read_dir("/proc")?.flatten() {
+        let is_pid = proc_entry
+            .file_name()
+            .to_str()
+            .is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()));
+        if !is_pid {
+            continue;
+        }
+        // Other users' processes are unreadable without privileges; skip them.
+        let Ok(fds) = std::fs::read_dir(proc_entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if let Ok(target) = std::fs::read_link(fd.path()) {
+                if target.is_absolute() {
+                    paths.push(target);
+                }
+            }
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_file_paths() -> io::Result<Vec<PathBuf>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--skip-open is only supported on Linux",
+    ))
+}
+
+/// Put back an access time that chronoban's own reads may have bumped.
+pub fn restore_atime(path: &Path, atime: SystemTime) {
+    if let Err(e) = set_times(path, std::fs::FileTimes::new().set_accessed(atime)) {
+        stderr!("⚠️  Failed to restore atime for {}: {}", path.display(), e);
+    }
+}
+
+/// Set both of an entry's times back to what they were before it was moved
+/// or copied, for filesystems that bump them on the way.
+pub fn preserve_times(path: &Path, accessed: SystemTime, modified: SystemTime) {
+    let times = std::fs::FileTimes::new().set_accessed(accessed).set_modified(modified);
+    if let Err(e) = set_times(path, times) {
+        stderr!("⚠️  Failed to preserve times for {}: {}", path.display(), e);
+    }
+}
+
+/// Set the given times of a file or directory. Windows only changes them
+/// through a handle opened to write attributes, which a read-only file
+/// allows and a directory needs backup semantics to be opened for at all.
+pub fn set_times(path: &Path, times: std::fs::FileTimes) -> io::Result<()> {
+    #[cfg(windows)]
+    let file = {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        std::fs::OpenOptions::new()
+            .access_mode(FILE_WRITE_ATTRIBUTES)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)?
+    };
+    #[cfg(not(windows))]
+    let file = std::fs::File::open(path)?;
+    file.set_times(times)
+}
+
+/// Remove a file, symlink or whole directory. Windows refuses to delete
+/// anything with the read-only attribute, which elsewhere only guards the
+/// contents, so there the attribute is cleared when it is what stood in
+/// the way.
+///
+/// Deleting is the only place the attribute gets in the way, because a
+/// destination is never written over: a target that already exists is a
+/// conflict and skipped, and `chronoban conflicts --resolve` renames the
+/// losing copy aside, which Windows allows for read-only files.
+pub fn remove_entry(path: &Path) -> io::Result<()> {
+    let remove = || match std::fs::symlink_metadata(path)? {
+        metadata if metadata.is_dir() => std::fs::remove_dir_all(path),
+        _ => std::fs::remove_file(path),
+    };
+    let result = remove();
+    #[cfg(windows)]
+    if result.as_ref().is_err_and(|e| e.kind() == io::ErrorKind::PermissionDenied) {
+        clear_readonly(path);
+        return remove();
+    }
+    result
+}
+
+#[cfg(windows)]
+fn clear_readonly(path: &Path) {
+    for entry in walkdir::WalkDir::new(path).follow_links(false).into_iter().flatten() {
+        let Ok(metadata) = entry.metadata() else { continue };
+        let mut permissions = metadata.permissions();
+        if permissions.readonly() {
+            permissions.set_readonly(false);
+            let _ = std::fs::set_permissions(entry.path(), permissions);
+        }
+    }
+}
+
+/// Explain how the mount's atime policy interacts with `--use-atime`.
+pub fn warn_atime_mount(path: &Path) {
+    let Some(options) = mount_options(path) else {
+        return;
+    };
+    if options.iter().any(|o| o == "noatime") {
+        stderr!("⚠️  {} is mounted noatime: access times are never updated,", path.display());
+        stderr!("   so --use-atime will bucket by a stale or creation-era time.");
+    } else if options.iter().any(|o| o == "relatime") {
+        stderr!("⚠️  {} is mounted relatime: reading a file updates its access time", path.display());
+        stderr!("   at most once a day, so inspections may shift it into a later bucket on");
+        stderr!("   future runs. Use --restore-atime to undo chronoban's own reads.");
+    } else {
+        stderr!("⚠️  {} updates access times on every read: inspecting files will", path.display());
+        stderr!("   change their bucket on future runs. Use --restore-atime to undo");
+        stderr!("   chronoban's own reads.");
+    }
+}
+
+/// Options of the mount containing `path`, from the longest matching mount point.
+#[cfg(target_os = "linux")]
+fn mount_options(path: &Path) -> Option<Vec<String>> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = unescape_mount_field(fields.nth(1)?);
+            let options = fields.nth(1)?;
+            Some((PathBuf::from(mount_point), options))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, options)| options.split(',').map(String::from).collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mount_options(_path: &Path) -> Option<Vec<String>> {
+    None
+}
+
+/// `/proc/self/mounts` escapes whitespace and backslashes as octal.
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> String {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\012", "\n")
+        .replace("\\134", "\\")
+}
+
+/// The first of `name`, `name (2)`, `name (3)`, … that does not exist in `dir`.
+/// The counter goes before the extension so the file type stays recognizable.
+pub fn unique_path(dir: &Path, name: &std::ffi::OsStr) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let as_path = Path::new(name);
+    let stem = as_path.file_stem().unwrap_or(name).to_string_lossy();
+    let ext = as_path.extension().map(|e| e.to_string_lossy());
+    (2..)
+        .map(|n| match &ext {
+            Some(ext) => dir.join(format!("{} ({}).{}", stem, n, ext)),
+            None => dir.join(format!("{} ({})", stem, n)),
+        })
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// Login name of a file's owner, or the numeric uid when it has none.
+#[cfg(unix)]
+pub fn owner_name(metadata: &std::fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let uid = metadata.uid();
+    Some(crate::identity::user_name(uid).unwrap_or_else(|| uid.to_string()))
+}
+
+#[cfg(not(unix))]
+pub fn owner_name(_metadata: &std::fs::Metadata) -> Option<String> {
+    None
+}
+
+/// The inode number, which tells a file replaced under the same name from
+/// the one that was there.
+#[cfg(unix)]
+pub fn inode(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+pub fn inode(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Whether the entry belongs to the user and group asked for, if any.
+#[cfg(unix)]
+pub fn owned_by(metadata: &std::fs::Metadata, owner: Option<u32>, group: Option<u32>) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    owner.is_none_or(|uid| metadata.uid() == uid) && group.is_none_or(|gid| metadata.gid() == gid)
+}
+
+#[cfg(not(unix))]
+pub fn owned_by(_metadata: &std::fs::Metadata, _owner: Option<u32>, _group: Option<u32>) -> bool {
+    true
+}
+
+/// What kind of special file this is, for the types chronoban doesn't know
+/// how to organize: sockets, FIFOs and devices.
+#[cfg(unix)]
+pub fn special_kind(file_type: std::fs::FileType) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_socket() {
+        Some("socket")
+    } else if file_type.is_fifo() {
+        Some("FIFO")
+    } else if file_type.is_block_device() {
+        Some("block device")
+    } else if file_type.is_char_device() {
+        Some("character device")
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+pub fn special_kind(_file_type: std::fs::FileType) -> Option<&'static str> {
+    None
+}
+
+/// Whether `path` lives on a different filesystem than `parent`, i.e. it is
+/// a mount point (or something below one).
+#[cfg(unix)]
+pub fn is_other_filesystem(path: &Path, parent: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::symlink_metadata(path), std::fs::metadata(parent)) {
+        (Ok(a), Ok(b)) => a.dev() != b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn is_other_filesystem(_path: &Path, _parent: &Path) -> bool {
+    false
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`.
+#[cfg(unix)]
+pub fn free_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is valid for writing.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Inodes available to unprivileged users on the filesystem holding
+/// `path`, or `None` where the filesystem doesn't count them.
+#[cfg(unix)]
+pub fn free_inodes(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is valid for writing.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 || stat.f_files == 0 {
+        return None;
+    }
+    Some(stat.f_favail as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_inodes(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Whether the current user may create and remove entries in `dir`.
+#[cfg(unix)]
+pub fn is_writable_dir(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(dir) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `dir` is NUL-terminated.
+    unsafe { libc::access(dir.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_writable_dir(dir: &Path) -> bool {
+    std::fs::metadata(dir).is_ok_and(|m| !m.permissions().readonly())
+}
+
+/// Copy `source`'s extended attributes onto `target`, not following
+/// symlinks; on macOS these include Finder tags and info. On Linux only
+/// the `user` namespace is open to everyone, so failing to set the others
+/// (security labels, ACLs without privileges) is not an error.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn copy_xattrs(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let source = std::ffi::CString::new(source.as_os_str().as_bytes())?;
+    let target = std::ffi::CString::new(target.as_os_str().as_bytes())?;
+    for name in xattr::list(&source)? {
+        // Gone since it was listed
+        let Ok(value) = xattr::get(&source, &name) else {
+            continue;
+        };
+        if let Err(e) = xattr::set(&target, &name, &value) {
+            if cfg!(target_os = "macos") || name.to_bytes().starts_with(b"user.") {
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn copy_xattrs(_source: &Path, _target: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Thin wrappers over the platform's extended attribute calls, which differ
+/// between Linux and macOS only in their flags.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod xattr {
+    use std::ffi::{CStr, CString};
+    use std::io;
+
+    /// Call `f` with a buffer, growing it to the size asked for by a first
+    /// call with an empty one.
+    fn sized(f: impl Fn(*mut libc::c_void, usize) -> isize) -> io::Result<Vec<u8>> {
+        loop {
+            let len = f(std::ptr::null_mut(), 0);
+            if len < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buf = vec![0u8; len as usize];
+            let got = f(buf.as_mut_ptr().cast(), buf.len());
+            if got >= 0 {
+                buf.truncate(got as usize);
+                return Ok(buf);
+            }
+            let err = io::Error::last_os_error();
+            // Grew in between the two calls; ask again
+            if err.raw_os_error() != Some(libc::ERANGE) {
+                return Err(err);
+            }
+        }
+    }
+
+    pub fn list(path: &CStr) -> io::Result<Vec<CString>> {
+        // SAFETY: the path is NUL-terminated and the buffer is valid for
+        // the length given.
+        #[cfg(target_os = "linux")]
+        let names = sized(|buf, len| unsafe { libc::llistxattr(path.as_ptr(), buf.cast(), len) })?;
+        #[cfg(target_os = "macos")]
+        let names = sized(|buf, len| unsafe {
+            libc::listxattr(path.as_ptr(), buf.cast(), len, libc::XATTR_NOFOLLOW)
+        })?;
+        Ok(names
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| CString::new(name).ok())
+            .collect())
+    }
+
+    pub fn get(path: &CStr, name: &CStr) -> io::Result<Vec<u8>> {
+        // SAFETY: as for `list`.
+        #[cfg(target_os = "linux")]
+        return sized(|buf, len| unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf, len) });
+        #[cfg(target_os = "macos")]
+        return sized(|buf, len| unsafe {
+            libc::getxattr(path.as_ptr(), name.as_ptr(), buf, len, 0, libc::XATTR_NOFOLLOW)
+        });
+    }
+
+    pub fn set(path: &CStr, name: &CStr, value: &[u8]) -> io::Result<()> {
+        let (value_ptr, len) = (value.as_ptr().cast(), value.len());
+        // SAFETY: both strings are NUL-terminated and the value is valid for
+        // its length.
+        #[cfg(target_os = "linux")]
+        let rc = unsafe { libc::lsetxattr(path.as_ptr(), name.as_ptr(), value_ptr, len, 0) };
+        #[cfg(target_os = "macos")]
+        let rc = unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value_ptr, len, 0, libc::XATTR_NOFOLLOW) };
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Past runs of a journaled directory: each run that gets to the end leaves
+//! a line in `.chronoban/runs.jsonl` with when it ran, how it was invoked and
+//! what came of it. `chronoban history` lists them and `chronoban show` goes
+//! through one, with what the journal says it did.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::journal::{journal_path, STATE_DIR};
+use crate::json::{self, Value};
+use crate::output::{self, stdout, OutputFormat};
+
+pub fn history_path(base: &Path) -> PathBuf {
+    base.join(STATE_DIR).join("runs.jsonl")
+}
+
+#[derive(clap::Args, Debug)]
+pub struct HistoryArgs {
+    /// Directory whose runs to list
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Only the most recent runs
+    #[arg(short = 'n', long, value_name = "N")]
+    last: Option<usize>,
+
+    /// JSON prints the recorded lines as they are
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ShowArgs {
+    /// The run, as listed by `chronoban history`
+    run_id: String,
+
+    /// Directory the run organized
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+/// Add a finished run. `run` is its summary, to which the start and end
+/// times and the command line are added.
+pub fn record(base: &Path, run: Value, started: DateTime<Local>) -> Result<()> {
+    let Value::Object(mut fields) = run else {
+        unreachable!("summaries are objects");
+    };
+    let command: Vec<String> = std::env::args().skip(1).collect();
+    let extra = [
+        ("started", started.to_rfc3339().into()),
+        ("finished", Local::now().to_rfc3339().into()),
+        ("args", command.into()),
+    ];
+    // After run_id, where a reader looks first
+    let at = fields.iter().position(|(key, _)| key == "run_id").map_or(0, |i| i + 1);
+    fields.splice(at..at, extra.into_iter().map(|(key, value)| (key.to_string(), value)));
+
+    let path = history_path(base);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open run history: {:?}", path))?;
+    writeln!(file, "{}", Value::Object(fields)).with_context(|| format!("Failed to write run history: {:?}", path))
+}
+
+pub async fn run(args: HistoryArgs) -> Result<()> {
+    let base = canonical(&args.path).await?;
+    let runs = read_lines(&history_path(&base))?;
+    if runs.is_empty() && !journal_path(&base).exists() {
+        anyhow::bail!("No run history in {}; runs only leave one with --journal", base.display());
+    }
+    let recorded: HashSet<&str> = runs.iter().filter_map(|r| str_field(r, "run_id")).collect();
+    // Runs that planned in the journal but never got to the end
+    let journal = read_lines(&journal_path(&base))?;
+    let mut unfinished: Vec<(&str, &str)> = Vec::new();
+    for line in &journal {
+        if let (Some("plan"), Some(run_id)) = (str_field(line, "event"), str_field(line, "run_id")) {
+            if !recorded.contains(run_id) && !unfinished.iter().any(|(id, _)| *id == run_id) {
+                unfinished.push((run_id, str_field(line, "time").unwrap_or_default()));
+            }
+        }
+    }
+    let skip = args.last.map_or(0, |last| runs.len().saturating_sub(last));
+
+    match args.output {
+        OutputFormat::Text => {
+            stdout!("🗂️  Runs in {}:", base.display());
+            if runs.is_empty() && unfinished.is_empty() {
+                stdout!("  None yet");
+            }
+            for run in &runs[skip..] {
+                stdout!("{}", line(run));
+            }
+            for (run_id, time) in unfinished {
+                stdout!("  {}  {}  did not finish", run_id, short_time(time));
+            }
+        }
+        OutputFormat::Json => {
+            output::flush();
+            for run in &runs[skip..] {
+                println!("{}", run);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn show(args: ShowArgs) -> Result<()> {
+    let base = canonical(&args.path).await?;
+    let runs = read_lines(&history_path(&base))?;
+    let run = runs.iter().find(|r| str_field(r, "run_id") == Some(args.run_id.as_str()));
+    let events: Vec<Value> = read_lines(&journal_path(&base))?
+        .into_iter()
+        .filter(|e| str_field(e, "run_id") == Some(args.run_id.as_str()))
+        .filter(|e| str_field(e, "event") != Some("plan"))
+        .collect();
+    if run.is_none() && events.is_empty() {
+        anyhow::bail!("No run {} in {}", args.run_id, base.display());
+    }
+
+    if args.output == OutputFormat::Json {
+        output::flush();
+        println!("{}", json::object([
+            ("run", run.cloned().unwrap_or(Value::Null)),
+            ("events", Value::Array(events)),
+        ]));
+        return Ok(());
+    }
+
+    stdout!("🗂️  Run {} in {}", args.run_id, base.display());
+    match run {
+        Some(run) => {
+            let started = str_field(run, "started").unwrap_or_default();
+            stdout!("  Started: {}", short_time(started));
+            stdout!("  Finished: {}", short_time(str_field(run, "finished").unwrap_or_default()));
+            if let Some(command) = run.get("args").and_then(Value::as_array) {
+                let command: Vec<&str> = command.iter().filter_map(Value::as_str).collect();
+                stdout!("  Command: chronoban {}", command.join(" "));
+            }
+            for (label, key) in [("Moved", "moved"), ("Skipped", "skipped"), ("Errors", "errors")] {
+                stdout!("  {}: {}", label, run.get(key).and_then(Value::as_u64).unwrap_or(0));
+            }
+            if run.get("interrupted").and_then(Value::as_bool) == Some(true) {
+                stdout!("  🛑 Interrupted before finishing");
+            }
+        }
+        None => stdout!("  ⚠️  Did not finish; --resume picks it up"),
+    }
+    if !events.is_empty() {
+        stdout!("\n📜 What it did:");
+    }
+    for event in &events {
+        let field = |key| str_field(event, key).unwrap_or_default();
+        match field("event") {
+            "move" => stdout!("  📦 {} -> {}", field("source"), field("target")),
+            "upload" => stdout!("  ☁️  {} -> {}", field("source"), field("uri")),
+            "dedupe" => stdout!("  🔗 {} (duplicate of {})", field("source"), field("duplicate_of")),
+            "delete" => stdout!("  🗑️  {} (empty)", field("source")),
+            "conflict" => stdout!("  ⚠️  {} -> {} skipped ({})", field("source"), field("target"), field("conflict")),
+            "rollback" => stdout!("  ⏪ {} -> {} (rolled back)", field("target"), field("source")),
+            "prune" => stdout!("  🗑️  Pruned {} ({})", field("bucket"), field("action")),
+            "finish" => stdout!("  ✅ Finished its plan"),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// One run, for the listing.
+fn line(run: &Value) -> String {
+    let count = |key| run.get(key).and_then(Value::as_u64).unwrap_or(0);
+    let mut text = format!(
+        "  {}  {}  moved {}, skipped {}, errors {}",
+        str_field(run, "run_id").unwrap_or("?"),
+        short_time(str_field(run, "started").unwrap_or_default()),
+        count("moved"),
+        count("skipped"),
+        count("errors"),
+    );
+    if run.get("interrupted").and_then(Value::as_bool) == Some(true) {
+        text.push_str(" (interrupted)");
+    }
+    text
+}
+
+fn short_time(time: &str) -> String {
+    match DateTime::parse_from_rfc3339(time) {
+        Ok(time) => time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+        Err(_) => time.to_string(),
+    }
+}
+
+async fn canonical(path: &Path) -> Result<PathBuf> {
+    tokio::fs::canonicalize(path).await.with_context(|| format!("Failed to access directory: {:?}", path))
+}
+
+/// The JSON lines of `path`, none if it doesn't exist. A line cut short by
+/// a crash is skipped.
+fn read_lines(path: &Path) -> Result<Vec<Value>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(text.lines().filter_map(|line| json::parse(line).ok()).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    }
+}
+
+fn str_field<'a>(record: &'a Value, key: &str) -> Option<&'a str> {
+    record.get(key).and_then(Value::as_str)
+}
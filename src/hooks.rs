@@ -0,0 +1,186 @@
+//! Commands from the config file's `[hooks]` table, run once for each
+//! bucket a run finishes moving into, e.g. to make thumbnails or poke an
+//! indexer:
+//!
+//! ```toml
+//! [hooks]
+//! bucket = ["exiftool -r -P -overwrite_original '-FileModifyDate<DateTimeOriginal' \"$1\""]
+//! jobs = 2
+//! ```
+//!
+//! Each command runs through the shell with the bucket as `$1` and in
+//! `CHRONOBAN_BUCKET`, and the entries moved into it on stdin, one per line.
+//!
+//! `--pre-hook` and `--post-hook` run around every single move instead,
+//! with `{src}` and `{dst}` replaced by the quoted paths, and `--on-complete`
+//! once when the run is done.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+use crate::json::Value;
+use crate::output::{self, stderr, verbose};
+
+#[derive(Clone, Debug, Default)]
+pub struct BucketHooks {
+    commands: Vec<String>,
+    /// How many hooks may run at once.
+    jobs: usize,
+}
+
+impl BucketHooks {
+    /// Read the `[hooks]` table of the config file.
+    pub fn from_config(table: &Value) -> Result<BucketHooks> {
+        let commands = match table.get("bucket") {
+            Some(Value::String(command)) => vec![command.clone()],
+            Some(list) => list
+                .as_array()
+                .and_then(|items| items.iter().map(|v| v.as_str().map(String::from)).collect())
+                .context("hooks.bucket must be a command or a list of commands")?,
+            None => Vec::new(),
+        };
+        let jobs = match table.get("jobs") {
+            Some(v) => v.as_u64().filter(|&n| n > 0).context("hooks.jobs must be a positive number")? as usize,
+            None => 1,
+        };
+        Ok(BucketHooks { commands, jobs })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+/// Hooks started as buckets complete, running alongside the remaining moves.
+pub struct HookRunner {
+    hooks: Arc<BucketHooks>,
+    slots: Arc<Semaphore>,
+    tasks: Vec<JoinHandle<usize>>,
+}
+
+impl HookRunner {
+    pub fn new(hooks: &BucketHooks) -> HookRunner {
+        HookRunner {
+            hooks: Arc::new(hooks.clone()),
+            slots: Arc::new(Semaphore::new(hooks.jobs.max(1))),
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Run every hook for a bucket that has received all its entries.
+    pub fn spawn(&mut self, bucket: PathBuf, entries: Vec<PathBuf>) {
+        if self.hooks.is_empty() || entries.is_empty() {
+            return;
+        }
+        let hooks = self.hooks.clone();
+        let slots = self.slots.clone();
+        self.tasks.push(tokio::spawn(async move {
+            let _slot = slots.acquire_owned().await;
+            let mut failed = 0;
+            for command in &hooks.commands {
+                match run_hook(command, &bucket, &entries).await {
+                    Ok(()) => verbose!("🪝 Hook done for {}: {}", bucket.display(), command),
+                    Err(e) => {
+                        stderr!("❌ Hook failed for {}: {:#}", bucket.display(), e);
+                        failed += 1;
+                    }
+                }
+            }
+            failed
+        }));
+    }
+
+    /// Wait for every hook started, returning how many failed.
+    pub async fn finish(self) -> usize {
+        let mut failed = 0;
+        for task in self.tasks {
+            failed += task.await.unwrap_or(1);
+        }
+        failed
+    }
+}
+
+async fn run_hook(command: &str, bucket: &Path, entries: &[PathBuf]) -> Result<()> {
+    let mut shell = shell(command);
+    shell.arg(bucket).env("CHRONOBAN_BUCKET", bucket);
+    let mut list = String::new();
+    for entry in entries {
+        list.push_str(&entry.to_string_lossy());
+        list.push('\n');
+    }
+    run(shell, command, Some(list)).await
+}
+
+/// Run a `--pre-hook` or `--post-hook` template for the move of `source`
+/// to `target`.
+pub async fn run_move_hook(template: &str, source: &Path, target: &Path) -> Result<()> {
+    let command = template.replace("{src}", &quote(source)).replace("{dst}", &quote(target));
+    let mut shell = shell(&command);
+    shell.env("CHRONOBAN_SOURCE", source).env("CHRONOBAN_TARGET", target);
+    run(shell, template, None).await
+}
+
+/// Run the `--on-complete` command, with the run's outcome in its
+/// environment.
+pub async fn run_on_complete(command: &str, run_id: &str, base: &Path, counts: [(&str, usize); 3]) -> Result<()> {
+    let mut shell = shell(command);
+    shell.env("CHRONOBAN_RUN_ID", run_id).env("CHRONOBAN_BASE", base);
+    for (name, count) in counts {
+        shell.env(format!("CHRONOBAN_{}", name.to_uppercase()), count.to_string());
+    }
+    run(shell, command, None).await
+}
+
+async fn run(mut shell: Command, command: &str, stdin: Option<String>) -> Result<()> {
+    shell.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() });
+    // Keep a JSON summary on stdout parseable
+    if output::progress_to_stderr() {
+        shell.stdout(std::io::stderr());
+    }
+    let mut child = shell
+        .spawn()
+        .with_context(|| format!("Failed to start {:?}", command))?;
+
+    if let (Some(mut pipe), Some(input)) = (child.stdin.take(), stdin) {
+        // A hook that doesn't read its stdin closes it early; that's fine
+        let _ = pipe.write_all(input.as_bytes()).await;
+    }
+
+    let status = child.wait().await.with_context(|| format!("Failed to run {:?}", command))?;
+    if !status.success() {
+        anyhow::bail!("{:?} exited with {}", command, status);
+    }
+    Ok(())
+}
+
+/// `path` quoted for the shell the hook runs in.
+#[cfg(unix)]
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+#[cfg(not(unix))]
+fn quote(path: &Path) -> String {
+    format!("\"{}\"", path.to_string_lossy())
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    // The name after the script becomes $0, so the bucket lands in $1
+    shell.arg("-c").arg(command).arg("chronoban-hook");
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
@@ -0,0 +1,129 @@
+use crate::json::{self, Value};
+
+/// Who ran chronoban, where, and with which version — recorded so shared
+/// archives keep an audit trail.
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub user: String,
+    pub host: String,
+    pub version: &'static str,
+}
+
+impl Identity {
+    pub fn current() -> Self {
+        Identity {
+            user: current_user(),
+            host: hostname(),
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json::object([
+            ("user", self.user.as_str().into()),
+            ("host", self.host.as_str().into()),
+            ("version", self.version.into()),
+        ])
+    }
+}
+
+fn current_user() -> String {
+    ["USER", "LOGNAME", "USERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(uid_fallback)
+}
+
+#[cfg(unix)]
+fn uid_fallback() -> String {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+    user_name(uid).unwrap_or_else(|| format!("uid:{}", uid))
+}
+
+/// Look up the login name for `uid` in the password database.
+#[cfg(unix)]
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: all pointers are valid for the duration of the call and
+    // `buf.len()` matches the buffer passed in.
+    let rc = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    // SAFETY: on success pw_name points to a NUL-terminated string inside `buf`.
+    let name = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// A user given by name or numeric id, for `--owner`.
+#[cfg(unix)]
+pub fn parse_user(s: &str) -> Result<u32, String> {
+    if let Ok(uid) = s.parse() {
+        return Ok(uid);
+    }
+    let name = std::ffi::CString::new(s).map_err(|e| e.to_string())?;
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: all pointers are valid for the duration of the call and
+    // `buf.len()` matches the buffer passed in.
+    let rc = unsafe { libc::getpwnam_r(name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return Err(format!("no such user {:?}", s));
+    }
+    Ok(pwd.pw_uid)
+}
+
+/// A group given by name or numeric id, for `--group`.
+#[cfg(unix)]
+pub fn parse_group(s: &str) -> Result<u32, String> {
+    if let Ok(gid) = s.parse() {
+        return Ok(gid);
+    }
+    let name = std::ffi::CString::new(s).map_err(|e| e.to_string())?;
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: as for getpwnam_r above.
+    let rc = unsafe { libc::getgrnam_r(name.as_ptr(), &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return Err(format!("no such group {:?}", s));
+    }
+    Ok(grp.gr_gid)
+}
+
+#[cfg(not(unix))]
+pub fn parse_user(_s: &str) -> Result<u32, String> {
+    Err("filtering by owner is only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+pub fn parse_group(_s: &str) -> Result<u32, String> {
+    Err("filtering by group is only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+fn uid_fallback() -> String {
+    "unknown".to_string()
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for `buf.len()` bytes and gethostname
+    // writes at most that many.
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if rc != 0 {
+        return "unknown".to_string();
+    }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown".to_string())
+}
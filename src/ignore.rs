@@ -0,0 +1,202 @@
+//! Gitignore-style patterns: `.chronobanignore` files in a directory for
+//! entries that are never organized, such as `node_modules` or `target/`,
+//! and `--keep` for entries pinned in place.
+//!
+//! The usual gitignore rules apply: `#` starts a comment, `!` re-includes,
+//! a trailing `/` matches only directories, a pattern with a `/` anywhere
+//! but the end is matched against the path from the directory, and one
+//! without is matched against names at any depth. `*`, `?`, `[...]` and
+//! `**` work as in git.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Name of the ignore file read from each directory being organized.
+pub const IGNORE_FILE: &str = ".chronobanignore";
+
+/// A directory holding a file of this name is pinned in place.
+pub const KEEP_MARKER: &str = ".chronokeep";
+
+#[derive(Clone, Debug, Default)]
+pub struct Patterns {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    glob: Vec<Token>,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path instead of the last name.
+    anchored: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Char(char),
+    /// `?`: any one character but `/`.
+    One,
+    /// `*`: anything without a `/`.
+    Star,
+    /// `**` not followed by `/`: anything at all.
+    Rest,
+    /// `**/`: nothing, or any run of directories.
+    AnyDirs,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+impl Patterns {
+    /// Read `base`'s ignore file; a missing one ignores nothing.
+    pub fn load(base: &Path) -> Result<Patterns> {
+        let path = base.join(IGNORE_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Ok(Patterns::parse(&text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Patterns::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read ignore file: {:?}", path)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Patterns {
+        Patterns::new(text.lines())
+    }
+
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Patterns {
+        Patterns { rules: patterns.into_iter().filter_map(parse_rule).collect() }
+    }
+
+    /// Whether the entry at `relative` (a path below the base) matches. The
+    /// last matching pattern decides, so `!` can exclude an earlier match.
+    pub fn matches(&self, relative: &Path, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let path: Vec<char> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+            .chars()
+            .collect();
+        let name_start = path.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+
+        let mut matched = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let text = if rule.anchored { &path[..] } else { &path[name_start..] };
+            if glob_match(&rule.glob, text) {
+                matched = !rule.negated;
+            }
+        }
+        matched
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let mut pattern = line.trim_end_matches('\r');
+    if !pattern.ends_with("\\ ") {
+        pattern = pattern.trim_end_matches(' ');
+    }
+    if pattern.is_empty() || pattern.starts_with('#') {
+        return None;
+    }
+    let negated = pattern.starts_with('!');
+    if negated {
+        pattern = &pattern[1..];
+    }
+    let dir_only = pattern.len() > 1 && pattern.ends_with('/');
+    if dir_only {
+        pattern = &pattern[..pattern.len() - 1];
+    }
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return None;
+    }
+    Some(Rule { glob: tokenize(pattern), negated, dir_only, anchored })
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[i + 1]));
+                i += 1;
+            }
+            '?' => tokens.push(Token::One),
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                i += 1;
+                if at_start && chars.get(i + 1) == Some(&'/') {
+                    tokens.push(Token::AnyDirs);
+                    i += 1;
+                } else {
+                    tokens.push(Token::Rest);
+                }
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => match parse_class(&chars[i + 1..]) {
+                Some((class, len)) => {
+                    tokens.push(class);
+                    i += len;
+                }
+                None => tokens.push(Token::Char('[')),
+            },
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// Parse the inside of a `[...]` class, returning it and how many characters
+/// it used up to and including the `]`, or `None` if it is never closed.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        first = false;
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+fn glob_match(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    match token {
+        Token::Char(c) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+        Token::One => text.first().is_some_and(|&c| c != '/') && glob_match(rest, &text[1..]),
+        Token::Class { negated, ranges } => text.first().is_some_and(|&c| {
+            c != '/' && ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c)) != *negated
+        }) && glob_match(rest, &text[1..]),
+        Token::Star => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match(rest, &text[i..])),
+        Token::Rest => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        Token::AnyDirs => {
+            glob_match(rest, text)
+                || (0..text.len()).any(|i| text[i] == '/' && glob_match(rest, &text[i + 1..]))
+        }
+    }
+}
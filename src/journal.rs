@@ -0,0 +1,409 @@
+//! Append-only record of every move, stored as JSON lines in the base
+//! directory's `.chronoban` state directory.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::fs::{File, OpenOptions};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::hash::to_hex;
+use crate::identity::Identity;
+use crate::json::{self, Value};
+use crate::mover::MoveMethod;
+use crate::plan::{Conflict, Plan};
+use crate::schema::SCHEMA_VERSION;
+
+/// Name of the per-directory state directory chronoban keeps its own files in.
+pub const STATE_DIR: &str = ".chronoban";
+
+pub fn journal_path(base: &Path) -> PathBuf {
+    base.join(STATE_DIR).join("journal.jsonl")
+}
+
+/// The way out of journaling for actions that destroy data.
+#[derive(clap::Args, Debug, Clone, Copy)]
+pub struct JournalOptOut {
+    /// Don't journal even actions that destroy data; needs --force for them
+    #[arg(long, conflicts_with = "journal")]
+    pub no_journal: bool,
+
+    /// With --no-journal, allow actions that destroy data
+    #[arg(long, requires = "no_journal")]
+    pub force: bool,
+}
+
+impl JournalOptOut {
+    /// Refuse `action`, which destroys data, unless it is being journaled or
+    /// the user opted out with `--no-journal --force`.
+    pub fn require(self, action: &str, journaled: bool) -> Result<()> {
+        if journaled || (self.no_journal && self.force) {
+            return Ok(());
+        }
+        if self.no_journal {
+            anyhow::bail!("{} destroys data and --no-journal leaves no record of it; add --force to go ahead", action);
+        }
+        anyhow::bail!("{} destroys data, so it needs --journal to keep a record of what it removed \
+            (or --no-journal --force to go without)", action);
+    }
+}
+
+pub struct Journal {
+    file: Mutex<File>,
+    path: PathBuf,
+    run_id: String,
+    identity: Identity,
+    /// Files earlier runs journaled as uploaded, read when first asked for.
+    uploaded: OnceLock<HashSet<(PathBuf, String, u64)>>,
+}
+
+impl Journal {
+    pub fn open(base: &Path, run_id: &str, identity: &Identity) -> Result<Self> {
+        let path = journal_path(base);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create state directory: {:?}", dir))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open journal: {:?}", path))?;
+        Ok(Journal {
+            file: Mutex::new(file),
+            path,
+            run_id: run_id.to_string(),
+            identity: identity.clone(),
+            uploaded: OnceLock::new(),
+        })
+    }
+
+    pub fn record_move(&self, source: &Path, target: &Path, method: MoveMethod) -> Result<()> {
+        let (method, bytes, sha256) = match method {
+            MoveMethod::Renamed => ("rename", None, None),
+            MoveMethod::Copied { bytes, sha256 } => ("copy", Some(bytes), sha256.as_ref().map(to_hex)),
+        };
+        self.append(json::object([
+            ("event", "move".into()),
+            ("source", source.into()),
+            ("target", target.into()),
+            ("method", method.into()),
+            ("bytes_copied", bytes.into()),
+            ("sha256", sha256.into()),
+        ]))
+    }
+
+    /// A duplicate removed instead of moved. `link` is the hard link left at
+    /// its destination, if any.
+    pub fn record_dedupe(&self, source: &Path, existing: &Path, link: Option<&Path>) -> Result<()> {
+        self.append(json::object([
+            ("event", "dedupe".into()),
+            ("source", source.into()),
+            ("duplicate_of", existing.into()),
+            ("link", link.into()),
+        ]))
+    }
+
+    /// An entry uploaded to a remote destination. `kept` says whether the
+    /// original was left in place, as it is with `--copy`.
+    pub fn record_upload(&self, source: &Path, uri: &str, bytes: u64, kept: bool) -> Result<()> {
+        self.append(json::object([
+            ("event", "upload".into()),
+            ("source", source.into()),
+            ("uri", uri.into()),
+            ("bytes_uploaded", bytes.into()),
+            ("kept", kept.into()),
+        ]))
+    }
+
+    /// One file of an upload sent in full, so an upload cut off partway
+    /// through a directory can skip it when tried again.
+    pub fn record_uploaded_file(&self, source: &Path, uri: &str, bytes: u64) -> Result<()> {
+        self.append(json::object([
+            ("event", "upload-file".into()),
+            ("source", source.into()),
+            ("uri", uri.into()),
+            ("bytes_uploaded", bytes.into()),
+        ]))
+    }
+
+    /// Whether an earlier run journaled `source`, at `bytes` long, as
+    /// already uploaded to `uri`.
+    pub fn uploaded_before(&self, source: &Path, uri: &str, bytes: u64) -> Result<bool> {
+        if self.uploaded.get().is_none() {
+            let text = std::fs::read_to_string(&self.path)
+                .with_context(|| format!("Failed to read journal: {:?}", self.path))?;
+            let mut uploaded = HashSet::new();
+            for record in text.lines().filter_map(|line| json::parse(line).ok()) {
+                let field = |key| record.get(key).and_then(Value::as_str);
+                if field("event") != Some("upload-file") || field("run_id") == Some(self.run_id.as_str()) {
+                    continue;
+                }
+                if let (Some(source), Some(uri), Some(bytes)) =
+                    (field("source"), field("uri"), record.get("bytes_uploaded").and_then(Value::as_u64))
+                {
+                    uploaded.insert((PathBuf::from(source), uri.to_string(), bytes));
+                }
+            }
+            let _ = self.uploaded.set(uploaded);
+        }
+        let uploaded = self.uploaded.get().expect("filled above");
+        Ok(uploaded.contains(&(source.to_path_buf(), uri.to_string(), bytes)))
+    }
+
+    /// An empty file deleted instead of moved.
+    pub fn record_delete(&self, source: &Path) -> Result<()> {
+        self.append(json::object([
+            ("event", "delete".into()),
+            ("source", source.into()),
+        ]))
+    }
+
+    /// A month directory pruned. `target` is where it went: the trash, the
+    /// archive that replaced it or the pending-delete area.
+    pub fn record_prune(&self, bucket: &Path, action: &str, target: Option<&Path>) -> Result<()> {
+        self.append(json::object([
+            ("event", "prune".into()),
+            ("bucket", bucket.into()),
+            ("action", action.into()),
+            ("target", target.into()),
+        ]))
+    }
+
+    /// The plan a run is about to execute, so an interrupted run can be
+    /// resumed without planning again.
+    pub fn record_plan(&self, plan: &Plan) -> Result<()> {
+        self.append(json::object([
+            ("event", "plan".into()),
+            ("plan", plan.to_json()),
+        ]))
+    }
+
+    /// An entry skipped because of a conflict, found by the plan or met
+    /// while moving, which `chronoban conflicts` lists until it is resolved
+    /// or moved.
+    pub fn record_conflict(&self, source: &Path, target: &Path, conflict: Conflict) -> Result<()> {
+        self.append(json::object([
+            ("event", "conflict".into()),
+            ("source", source.into()),
+            ("target", target.into()),
+            ("conflict", conflict.as_str().into()),
+        ]))
+    }
+
+    /// A move undone by `--atomic`: `target` was put back at `source`, or
+    /// removed when it was a copy.
+    pub fn record_rollback(&self, source: &Path, target: &Path) -> Result<()> {
+        self.append(json::object([
+            ("event", "rollback".into()),
+            ("source", source.into()),
+            ("target", target.into()),
+        ]))
+    }
+
+    /// The moves this run has journaled so far, in order, as source and
+    /// target.
+    pub fn moves(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let text = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read journal: {:?}", self.path))?;
+        let mut moves = Vec::new();
+        for record in text.lines().filter_map(|line| json::parse(line).ok()) {
+            let field = |key| record.get(key).and_then(Value::as_str);
+            if field("run_id") != Some(self.run_id.as_str()) || field("event") != Some("move") {
+                continue;
+            }
+            if let (Some(source), Some(target)) = (field("source"), field("target")) {
+                moves.push((PathBuf::from(source), PathBuf::from(target)));
+            }
+        }
+        Ok(moves)
+    }
+
+    /// The run got through its whole plan.
+    pub fn record_finish(&self) -> Result<()> {
+        self.append(json::object([("event", "finish".into())]))
+    }
+
+    /// Write one entry, stamped with the run, time and mover identity.
+    fn append(&self, entry: Value) -> Result<()> {
+        let Value::Object(fields) = entry else {
+            unreachable!("journal entries are objects");
+        };
+        let mut record = vec![
+            ("schema_version".to_string(), SCHEMA_VERSION.into()),
+            ("run_id".to_string(), self.run_id.as_str().into()),
+            ("time".to_string(), Local::now().to_rfc3339().into()),
+        ];
+        record.extend(fields);
+        record.push(("mover".to_string(), self.identity.to_json()));
+
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", Value::Object(record)).context("Failed to write journal")?;
+        file.flush().context("Failed to write journal")?;
+        Ok(())
+    }
+}
+
+/// The latest journaled run that recorded a plan but never finished,
+/// with the sources it already moved or removed.
+pub struct Unfinished {
+    pub run_id: String,
+    pub plan: Plan,
+    pub done: HashSet<PathBuf>,
+}
+
+/// Find the run to pick up with `--resume`, if the last planned run was
+/// interrupted.
+pub fn unfinished_run(base: &Path) -> Result<Option<Unfinished>> {
+    let path = journal_path(base);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read journal: {:?}", path)),
+    };
+
+    let mut last: Option<Unfinished> = None;
+    for line in text.lines() {
+        // A line cut short by a crash is of no use; skip it
+        let Ok(record) = json::parse(line) else {
+            continue;
+        };
+        let run_id = record.get("run_id").and_then(Value::as_str).unwrap_or_default();
+        match record.get("event").and_then(Value::as_str) {
+            Some("plan") => {
+                let plan = record
+                    .get("plan")
+                    .ok_or_else(|| "plan event without a plan".to_string())
+                    .and_then(Plan::from_json)
+                    .map_err(|e| anyhow::anyhow!("Bad plan in journal {:?}: {}", path, e))?;
+                last = Some(Unfinished { run_id: run_id.to_string(), plan, done: HashSet::new() });
+            }
+            Some("move" | "upload" | "dedupe" | "delete") => {
+                if let (Some(run), Some(source)) = (&mut last, record.get("source").and_then(Value::as_str)) {
+                    if run.run_id == run_id {
+                        run.done.insert(PathBuf::from(source));
+                    }
+                }
+            }
+            Some("finish") if last.as_ref().is_some_and(|run| run.run_id == run_id) => last = None,
+            _ => {}
+        }
+    }
+    Ok(last)
+}
+
+/// A run id that sorts chronologically and is unique per process.
+pub fn new_run_id() -> String {
+    format!("{}-{}", Local::now().format("%Y%m%dT%H%M%S"), std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::{DateSource, PlanEntry};
+    use std::time::SystemTime;
+
+    /// A base directory for one test, removed afterwards.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir = std::env::temp_dir().join(format!("chronoban-journal-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn entry(base: &Path, name: &str) -> PlanEntry {
+        PlanEntry {
+            source: base.join(name),
+            destination: base.join("2022-03").join(name),
+            bucket: base.join("2022-03"),
+            date: SystemTime::UNIX_EPOCH,
+            date_source: DateSource::Modified,
+            size: 0,
+            modified: None,
+            accessed: None,
+            inode: None,
+            conflict: None,
+            duplicate_of: None,
+            sidecar_of: None,
+        }
+    }
+
+    #[test]
+    fn replays_only_this_runs_moves() {
+        let scratch = Scratch::new("moves");
+        let base = scratch.0.as_path();
+        let identity = Identity::current();
+        let earlier = Journal::open(base, "run-1", &identity).unwrap();
+        earlier.record_move(&base.join("old"), &base.join("2021-01/old"), MoveMethod::Renamed).unwrap();
+
+        let journal = Journal::open(base, "run-2", &identity).unwrap();
+        journal.record_move(&base.join("a"), &base.join("2022-03/a"), MoveMethod::Renamed).unwrap();
+        journal.record_dedupe(&base.join("b"), &base.join("2022-03/b"), None).unwrap();
+        journal.record_move(&base.join("c"), &base.join("2022-03/c"), MoveMethod::Copied { bytes: 3, sha256: None })
+            .unwrap();
+        journal.record_rollback(&base.join("c"), &base.join("2022-03/c")).unwrap();
+
+        assert_eq!(journal.moves().unwrap(), vec![
+            (base.join("a"), base.join("2022-03/a")),
+            (base.join("c"), base.join("2022-03/c")),
+        ]);
+    }
+
+    #[test]
+    fn unfinished_run_knows_what_it_did() {
+        let scratch = Scratch::new("unfinished");
+        let base = scratch.0.as_path();
+        let identity = Identity::current();
+        let mut plan = Plan::new(base.to_path_buf());
+        plan.entries = ["a", "b", "c", "d"].iter().map(|name| entry(base, name)).collect();
+
+        let finished = Journal::open(base, "run-1", &identity).unwrap();
+        finished.record_plan(&plan).unwrap();
+        finished.record_finish().unwrap();
+        assert!(unfinished_run(base).unwrap().is_none());
+
+        let journal = Journal::open(base, "run-2", &identity).unwrap();
+        journal.record_plan(&plan).unwrap();
+        journal.record_move(&base.join("a"), &base.join("2022-03/a"), MoveMethod::Renamed).unwrap();
+        journal.record_dedupe(&base.join("b"), &base.join("2022-03/b"), None).unwrap();
+        journal.record_delete(&base.join("c")).unwrap();
+        // Another run's events don't count towards this one
+        Journal::open(base, "run-3", &identity).unwrap().record_delete(&base.join("d")).unwrap();
+
+        let run = unfinished_run(base).unwrap().unwrap();
+        assert_eq!(run.run_id, "run-2");
+        assert_eq!(run.plan, plan);
+        assert_eq!(run.done, ["a", "b", "c"].iter().map(|name| base.join(name)).collect());
+
+        journal.record_finish().unwrap();
+        assert!(unfinished_run(base).unwrap().is_none());
+    }
+
+    #[test]
+    fn uploaded_files_come_from_earlier_runs() {
+        let scratch = Scratch::new("uploaded");
+        let base = scratch.0.as_path();
+        let identity = Identity::current();
+        let file = base.join("album/a.jpg");
+        Journal::open(base, "run-1", &identity).unwrap().record_uploaded_file(&file, "s3://b/a.jpg", 5).unwrap();
+
+        let journal = Journal::open(base, "run-2", &identity).unwrap();
+        journal.record_uploaded_file(&base.join("album/b.jpg"), "s3://b/b.jpg", 5).unwrap();
+        assert!(journal.uploaded_before(&file, "s3://b/a.jpg", 5).unwrap());
+        assert!(!journal.uploaded_before(&file, "s3://b/a.jpg", 6).unwrap());
+        assert!(!journal.uploaded_before(&file, "s3://b/other.jpg", 5).unwrap());
+        assert!(!journal.uploaded_before(&base.join("album/b.jpg"), "s3://b/b.jpg", 5).unwrap());
+    }
+}
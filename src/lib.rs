@@ -0,0 +1,71 @@
+//! Organize files into dated bucket directories.
+//!
+//! The binary is a thin wrapper around [`cli::main`]. Embedders that want to
+//! inspect or drive a run themselves can work with [`plan::Plan`].
+
+mod archive;
+mod bench;
+mod bucket;
+mod capabilities;
+mod catalog;
+pub mod cli;
+mod completions;
+mod config;
+mod conflicts;
+mod datesrc;
+mod dedupe;
+mod diff;
+mod email;
+mod embedded;
+mod exif;
+mod exit;
+mod filetype;
+mod find;
+mod flatten;
+mod fsutil;
+mod hash;
+mod history;
+mod hooks;
+mod identity;
+mod ignore;
+mod journal;
+mod limits;
+mod lock;
+pub mod json;
+mod manpage;
+mod mapping;
+mod marker;
+mod metrics;
+mod migrate;
+mod months;
+mod mover;
+mod notify;
+mod organize;
+mod output;
+pub mod plan;
+mod preflight;
+mod preset;
+mod profile;
+mod provider;
+mod prune;
+mod remote;
+mod replan;
+mod report;
+mod route;
+mod s3;
+mod scan;
+mod schedule;
+pub mod schema;
+mod service;
+mod sidecar;
+mod ssh;
+mod stats;
+mod template;
+mod trace;
+mod tui;
+mod toml;
+mod trash;
+mod unicode;
+mod unicode_tables;
+mod units;
+mod winpath;
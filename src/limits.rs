@@ -0,0 +1,203 @@
+//! Resource limits for a run, so chronoban shares a small server politely
+//! with whatever else runs on it.
+
+use anyhow::{Context, Result};
+
+use crate::json::Value;
+use crate::mover;
+use crate::output::stderr;
+use crate::units::parse_size;
+
+#[derive(clap::Args, Debug, Clone, Copy, Default)]
+pub struct LimitArgs {
+    /// Scheduling niceness for the run (0-19, higher is politer)
+    #[arg(long, value_parser = clap::value_parser!(i32).range(-20..=19))]
+    pub nice: Option<i32>,
+
+    /// Cap on open file descriptors
+    #[arg(long, value_name = "N")]
+    pub max_open_files: Option<u64>,
+
+    /// Cap on memory the process may allocate (e.g. 512M)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_memory: Option<u64>,
+
+    /// Cap on how fast entries are copied, per second across all jobs
+    /// (e.g. 50M); renames within a filesystem aren't slowed
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub bandwidth: Option<u64>,
+
+    /// Only use the disk when nothing else wants it: the idle I/O class on
+    /// Linux, background priority on macOS
+    #[arg(long)]
+    pub nice_io: bool,
+}
+
+impl LimitArgs {
+    /// Read the `[limits]` table of the config file.
+    pub fn from_config(table: &Value) -> Result<LimitArgs> {
+        let nice = match table.get("nice") {
+            Some(v) => Some(
+                v.as_i64()
+                    .and_then(|n| i32::try_from(n).ok())
+                    .filter(|n| (-20..=19).contains(n))
+                    .context("limits.nice must be a number from -20 to 19")?,
+            ),
+            None => None,
+        };
+        let max_open_files = match table.get("open_files") {
+            Some(v) => Some(v.as_u64().context("limits.open_files must be a number")?),
+            None => None,
+        };
+        let max_memory = match table.get("memory") {
+            Some(Value::String(s)) => Some(parse_size(s).map_err(anyhow::Error::msg).context("limits.memory")?),
+            Some(v) => Some(v.as_u64().context("limits.memory must be a size such as \"512M\"")?),
+            None => None,
+        };
+        let bandwidth = match table.get("bandwidth") {
+            Some(Value::String(s)) => Some(parse_size(s).map_err(anyhow::Error::msg).context("limits.bandwidth")?),
+            Some(v) => Some(v.as_u64().context("limits.bandwidth must be a size such as \"50M\"")?),
+            None => None,
+        };
+        let nice_io = match table.get("nice_io") {
+            Some(v) => v.as_bool().context("limits.nice_io must be true or false")?,
+            None => false,
+        };
+        Ok(LimitArgs { nice, max_open_files, max_memory, bandwidth, nice_io })
+    }
+
+    /// Limits given on the command line win over the config file's.
+    pub fn or(self, fallback: LimitArgs) -> LimitArgs {
+        LimitArgs {
+            nice: self.nice.or(fallback.nice),
+            max_open_files: self.max_open_files.or(fallback.max_open_files),
+            max_memory: self.max_memory.or(fallback.max_memory),
+            bandwidth: self.bandwidth.or(fallback.bandwidth),
+            nice_io: self.nice_io || fallback.nice_io,
+        }
+    }
+
+    /// Apply the limits to this process for the rest of the run.
+    pub fn apply(self) -> Result<()> {
+        if let Some(nice) = self.nice {
+            set_nice(nice).context("Failed to set niceness")?;
+        }
+        if let Some(n) = self.max_open_files {
+            set_soft_limit(Resource::OpenFiles, n).context("Failed to limit open files")?;
+        }
+        if let Some(bytes) = self.max_memory {
+            set_soft_limit(Resource::Memory, bytes).context("Failed to limit memory")?;
+        }
+        if let Some(bytes) = self.bandwidth {
+            mover::limit_bandwidth(bytes);
+        }
+        if self.nice_io {
+            match set_idle_io() {
+                Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+                    stderr!("⚠️  --nice-io isn't supported here, running at normal I/O priority");
+                }
+                result => result.context("Failed to lower I/O priority")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Resource {
+    OpenFiles,
+    Memory,
+}
+
+/// Lower the soft limit, leaving the hard limit alone so it can't exceed it.
+#[cfg(unix)]
+fn set_soft_limit(resource: Resource, value: u64) -> std::io::Result<()> {
+    let resource = match resource {
+        Resource::OpenFiles => libc::RLIMIT_NOFILE,
+        Resource::Memory => libc::RLIMIT_DATA,
+    };
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    // SAFETY: `limit` is a valid rlimit for getrlimit to fill in.
+    if unsafe { libc::getrlimit(resource, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    limit.rlim_cur = (value as libc::rlim_t).min(limit.rlim_max);
+    // SAFETY: as above, and the new soft limit does not exceed the hard one.
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Linux keeps niceness per thread, and the runtime's worker threads exist
+/// already, so each one is reniced; threads started later inherit it.
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) -> std::io::Result<()> {
+    for task in std::fs::read_dir("/proc/self/task")?.flatten() {
+        let Some(tid) = task.file_name().to_str().and_then(|t| t.parse::<libc::id_t>().ok()) else {
+            continue;
+        };
+        // SAFETY: setpriority has no memory-safety preconditions.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_nice(nice: i32) -> std::io::Result<()> {
+    // SAFETY: setpriority has no memory-safety preconditions.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Put every thread in the idle I/O scheduling class; like niceness, the
+/// class is per thread and inherited by threads started later.
+#[cfg(target_os = "linux")]
+fn set_idle_io() -> std::io::Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+    for task in std::fs::read_dir("/proc/self/task")?.flatten() {
+        let Some(tid) = task.file_name().to_str().and_then(|t| t.parse::<libc::c_long>().ok()) else {
+            continue;
+        };
+        // SAFETY: ioprio_set takes plain integers and touches no memory.
+        let rc = unsafe {
+            libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT)
+        };
+        if rc != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Background priority, which throttles the process's disk and network I/O.
+#[cfg(target_os = "macos")]
+fn set_idle_io() -> std::io::Result<()> {
+    const PRIO_DARWIN_PROCESS: libc::c_int = 4;
+    const PRIO_DARWIN_BG: libc::c_int = 0x1000;
+    // SAFETY: setpriority has no memory-safety preconditions.
+    if unsafe { libc::setpriority(PRIO_DARWIN_PROCESS, 0, PRIO_DARWIN_BG) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn set_idle_io() -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "no idle I/O class on this system"))
+}
+
+#[cfg(not(unix))]
+fn set_soft_limit(_resource: Resource, _value: u64) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "resource limits need a Unix system"))
+}
+
+#[cfg(not(unix))]
+fn set_nice(_nice: i32) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "niceness needs a Unix system"))
+}
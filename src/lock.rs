@@ -0,0 +1,83 @@
+//! Advisory lock on a base directory, so overlapping runs (two cron
+//! invocations, say) don't race each other for the same targets.
+
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+use crate::journal::STATE_DIR;
+use crate::output::stderr;
+
+#[derive(clap::Args, Debug, Clone, Copy)]
+pub struct LockArgs {
+    /// Wait for another run on the same directory to finish instead of
+    /// failing straight away
+    #[arg(long, conflicts_with = "no_lock")]
+    pub wait: bool,
+
+    /// Don't take the directory lock
+    #[arg(long)]
+    pub no_lock: bool,
+}
+
+/// Another run holds the lock. A watcher skips its pass on this rather than
+/// counting it as a failure.
+#[derive(Debug)]
+pub struct Busy(pub PathBuf);
+
+impl std::fmt::Display for Busy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Another chronoban run is working on {:?} (use --wait to queue behind it)", self.0)
+    }
+}
+
+impl std::error::Error for Busy {}
+
+/// Held for as long as the run works on the directory; the lock is released
+/// when the file is closed.
+pub struct DirLock {
+    _file: File,
+}
+
+impl LockArgs {
+    /// Lock `base` as these options ask, or `None` with `--no-lock`.
+    pub async fn acquire(self, base: &Path) -> Result<Option<DirLock>> {
+        if self.no_lock {
+            return Ok(None);
+        }
+        let dir = base.join(STATE_DIR);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create state directory: {:?}", dir))?;
+        let path = dir.join("lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file: {:?}", path))?;
+
+        if try_lock(&file, false)? {
+            return Ok(Some(DirLock { _file: file }));
+        }
+        if !self.wait {
+            return Err(Busy(base.to_path_buf()).into());
+        }
+        stderr!("⏳ Waiting for another chronoban run on {} to finish...", base.display());
+        let file = tokio::task::spawn_blocking(move || try_lock(&file, true).map(|_| file)).await??;
+        Ok(Some(DirLock { _file: file }))
+    }
+}
+
+/// Take an exclusive lock on `file` (flock on Unix, LockFileEx on Windows),
+/// returning false if another process holds it and `block` is off.
+fn try_lock(file: &File, block: bool) -> Result<bool> {
+    if block {
+        file.lock().context("Failed to lock directory")?;
+        return Ok(true);
+    }
+    match file.try_lock() {
+        Ok(()) => Ok(true),
+        Err(TryLockError::WouldBlock) => Ok(false),
+        Err(TryLockError::Error(e)) => Err(e).context("Failed to lock directory"),
+    }
+}
@@ -0,0 +1,138 @@
+//! `chronoban manpage`: a roff man page covering every subcommand, written
+//! out from the command-line definition, for packagers to install as
+//! chronoban.1.
+
+use anyhow::Result;
+use std::fmt::Write as _;
+
+#[derive(clap::Args, Debug)]
+pub struct ManpageArgs {}
+
+pub fn run(_args: ManpageArgs, mut command: clap::Command) -> Result<()> {
+    command.build();
+    let name = command.get_name().to_string();
+    let mut out = String::new();
+    let _ = writeln!(out, ".TH {} 1 \"\" \"{} {}\"", name.to_uppercase(), name, env!("CARGO_PKG_VERSION"));
+
+    out.push_str(".SH NAME\n");
+    let about = command.get_about().map(|a| a.to_string()).unwrap_or_default();
+    let _ = writeln!(out, "{} \\- {}", name, escape(&about));
+
+    out.push_str(".SH SYNOPSIS\n");
+    let _ = writeln!(out, "{}", synopsis(&name, &command));
+    if command.has_subcommands() {
+        let _ = writeln!(out, ".br\n\\fB{}\\fR \\fICOMMAND\\fR [\\fIOPTIONS\\fR]", name);
+    }
+
+    if let Some(long_about) = command.get_long_about() {
+        out.push_str(".SH DESCRIPTION\n");
+        paragraphs(&mut out, &long_about.to_string());
+    }
+
+    out.push_str(".SH OPTIONS\n");
+    options(&mut out, &command);
+
+    let subcommands: Vec<&clap::Command> =
+        command.get_subcommands().filter(|s| !s.is_hide_set() && s.get_name() != "help").collect();
+    if !subcommands.is_empty() {
+        out.push_str(".SH COMMANDS\n");
+        for sub in subcommands {
+            let _ = writeln!(out, ".SS \"{} {}\"", name, sub.get_name());
+            let _ = writeln!(out, "{}", synopsis(&format!("{} {}", name, sub.get_name()), sub));
+            out.push_str(".PP\n");
+            let about = sub.get_long_about().or(sub.get_about()).map(|a| a.to_string()).unwrap_or_default();
+            paragraphs(&mut out, &about);
+            options(&mut out, sub);
+        }
+    }
+
+    out.push_str(".SH EXIT STATUS\n");
+    out.push_str("0 when everything succeeded, 1 when the run stopped with an error, 2 when some entries could not \
+        be moved and 3 when interrupted.\n");
+    print!("{}", out);
+    Ok(())
+}
+
+fn synopsis(name: &str, command: &clap::Command) -> String {
+    let mut line = format!("\\fB{}\\fR [\\fIOPTIONS\\fR]", escape(name));
+    for arg in command.get_positionals().filter(|a| !a.is_hide_set()) {
+        let value = value_name(arg);
+        let many = if matches!(arg.get_action(), clap::ArgAction::Append) { "..." } else { "" };
+        if arg.is_required_set() {
+            let _ = write!(line, " \\fI{}\\fR{}", value, many);
+        } else {
+            let _ = write!(line, " [\\fI{}\\fR]{}", value, many);
+        }
+    }
+    line
+}
+
+fn options(out: &mut String, command: &clap::Command) {
+    for arg in command.get_arguments().filter(|a| !a.is_hide_set() && !a.is_positional()) {
+        out.push_str(".TP\n");
+        let mut names: Vec<String> = Vec::new();
+        names.extend(arg.get_short().map(|s| format!("\\fB\\-{}\\fR", s)));
+        names.extend(arg.get_long().map(|l| format!("\\fB\\-\\-{}\\fR", escape(l))));
+        for alias in arg.get_visible_aliases().unwrap_or_default() {
+            names.push(format!("\\fB\\-\\-{}\\fR", escape(alias)));
+        }
+        let mut heading = names.join(", ");
+        if arg.get_action().takes_values() {
+            let value = format!("\\fI{}\\fR", value_name(arg));
+            let value = if arg.get_num_args().is_some_and(|n| n.min_values() == 0) {
+                format!("[{}]", value)
+            } else {
+                value
+            };
+            let _ = write!(heading, " {}", value);
+        }
+        let _ = writeln!(out, "{}", heading);
+        let help = arg.get_long_help().or(arg.get_help()).map(|h| h.to_string()).unwrap_or_default();
+        paragraphs(out, &help);
+
+        let defaults: Vec<String> = arg.get_default_values().iter().map(|v| v.to_string_lossy().into_owned()).collect();
+        if arg.get_action().takes_values() && !defaults.is_empty() {
+            let _ = writeln!(out, ".br\nDefault: {}", escape(&defaults.join(",")));
+        }
+        if arg.get_action().takes_values() {
+            let values: Vec<_> = arg.get_possible_values().into_iter().filter(|v| !v.is_hide_set()).collect();
+            if !values.is_empty() {
+                out.push_str(".RS\n");
+                for value in values {
+                    let help = value.get_help().map(|h| format!(": {}", h)).unwrap_or_default();
+                    let _ = writeln!(out, ".IP \\(bu 2\n\\fI{}\\fR{}", escape(value.get_name()), escape(&help));
+                }
+                out.push_str(".RE\n");
+            }
+        }
+    }
+}
+
+fn value_name(arg: &clap::Arg) -> String {
+    let name = arg
+        .get_value_names()
+        .map(|names| names.join(" "))
+        .unwrap_or_else(|| arg.get_id().to_string().to_uppercase());
+    escape(&name)
+}
+
+/// Text in paragraphs separated by blank lines, as roff paragraphs.
+fn paragraphs(out: &mut String, text: &str) {
+    for (i, paragraph) in text.split("\n\n").filter(|p| !p.trim().is_empty()).enumerate() {
+        if i > 0 {
+            out.push_str(".IP\n");
+        }
+        let _ = writeln!(out, "{}", escape(&paragraph.trim().replace('\n', " ")));
+    }
+}
+
+/// Escape text for roff: backslashes and dashes, and a leading dot or quote
+/// that would otherwise start a request.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
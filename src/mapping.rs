@@ -0,0 +1,159 @@
+//! Plans from the mapping exports of other tools, such as `exiftool -csv`
+//! or a photo organizer's list of renames: a CSV file with a header row and
+//! one row per entry, naming where it is and where it should go. Applying
+//! one runs the moves through the usual executor, so they are journaled and
+//! can be resumed like any other run.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::fsutil::inode;
+use crate::output::{debug, stderr, status};
+use crate::plan::{Conflict, DateSource, Plan, PlanEntry};
+
+/// Header names recognized without `--map-columns`, compared ignoring case.
+const SOURCE_NAMES: &[&str] = &["sourcefile", "source", "original", "from", "path"];
+const DESTINATION_NAMES: &[&str] = &["destination", "dest", "target", "to", "new path", "directory"];
+
+/// Which columns of a mapping hold the source and the destination.
+#[derive(Clone, Debug)]
+pub struct MapColumns {
+    source: Column,
+    destination: Column,
+}
+
+#[derive(Clone, Debug)]
+enum Column {
+    Name(String),
+    /// 1-based, as spreadsheets count.
+    Number(usize),
+}
+
+/// Parse `SOURCE,DEST`, each a header name or a column number.
+pub fn parse_map_columns(s: &str) -> Result<MapColumns, String> {
+    let (source, destination) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected SOURCE,DEST columns: {:?}", s))?;
+    let column = |c: &str| match c.trim() {
+        "" => Err(format!("empty column in {:?}", s)),
+        "0" => Err("columns are numbered from 1".to_string()),
+        c => Ok(c.parse().map(Column::Number).unwrap_or_else(|_| Column::Name(c.to_string()))),
+    };
+    Ok(MapColumns { source: column(source)?, destination: column(destination)? })
+}
+
+/// Build a plan from the mapping in `file`. Relative sources are taken
+/// from `source_dir` and relative destinations from `base`; a destination
+/// that is a directory, or comes from a `Directory` column, receives the
+/// entry under its own name.
+pub fn load(file: &Path, columns: Option<&MapColumns>, source_dir: &Path, base: &Path) -> Result<Plan> {
+    let text = std::fs::read_to_string(file).with_context(|| format!("Failed to read mapping: {:?}", file))?;
+    let mut rows = parse_csv(&text).into_iter();
+    let header = rows.next().with_context(|| format!("Mapping {:?} is empty", file))?;
+    let find = |column: Option<&Column>, names: &[&str], role: &str| -> Result<usize> {
+        let index = match column {
+            Some(Column::Number(n)) => Some(n - 1).filter(|&i| i < header.len()),
+            Some(Column::Name(name)) => header.iter().position(|h| h.trim().eq_ignore_ascii_case(name)),
+            None => names.iter().find_map(|name| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name))),
+        };
+        index.with_context(|| format!("Mapping {:?} has no {} column; name it with --map-columns", file, role))
+    };
+    let source_col = find(columns.map(|c| &c.source), SOURCE_NAMES, "source")?;
+    let dest_col = find(columns.map(|c| &c.destination), DESTINATION_NAMES, "destination")?;
+    let dest_is_dir = header[dest_col].trim().eq_ignore_ascii_case("directory");
+
+    let mut plan = Plan::new(base.to_path_buf());
+    let mut destinations = HashSet::new();
+    for (line, row) in rows.enumerate().map(|(i, row)| (i + 2, row)) {
+        let (Some(source), Some(dest)) = (row.get(source_col), row.get(dest_col)) else {
+            if row.iter().any(|field| !field.is_empty()) {
+                stderr!("⚠️  Mapping line {} is missing a column, skipping", line);
+                plan.skipped += 1;
+            }
+            continue;
+        };
+        if source.is_empty() || dest.is_empty() {
+            debug!("⏭️  Mapping line {} has no source or destination", line);
+            plan.skipped += 1;
+            continue;
+        }
+
+        let source = source_dir.join(source);
+        let metadata = match std::fs::symlink_metadata(&source) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                stderr!("⚠️  Can't map {} (line {}): {}", source.display(), line, e);
+                plan.skipped += 1;
+                continue;
+            }
+        };
+        let mut destination = base.join(dest);
+        if dest_is_dir || dest.ends_with('/') || destination.is_dir() {
+            destination.push(source.file_name().unwrap_or_default());
+        }
+        if destination == source {
+            debug!("⏭️  Already in place: {}", source.display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        let conflict = if destination.exists() || !destinations.insert(destination.clone()) {
+            stderr!("⚠️  Target already exists, skipping: {} -> {}", source.display(), destination.display());
+            Some(Conflict::TargetExists)
+        } else {
+            None
+        };
+        let modified = metadata.modified().ok();
+        plan.entries.push(PlanEntry {
+            bucket: destination.parent().unwrap_or(base).to_path_buf(),
+            source,
+            destination,
+            date: modified.unwrap_or_else(SystemTime::now),
+            date_source: DateSource::Modified,
+            size: metadata.len(),
+            modified,
+            accessed: metadata.accessed().ok(),
+            inode: inode(&metadata),
+            conflict,
+            duplicate_of: None,
+            sidecar_of: None,
+        });
+    }
+    status!("🗺️  Mapping {}: {} entries to move", file.display(), plan.moves().count());
+    Ok(plan)
+}
+
+/// Split CSV text into rows of fields. Quoted fields may hold commas,
+/// newlines and doubled quotes.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
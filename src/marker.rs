@@ -0,0 +1,127 @@
+//! Markers that tell buckets chronoban created apart from look-alike
+//! directories someone else made.
+
+use std::io;
+use std::path::Path;
+
+use crate::ignore::KEEP_MARKER;
+use crate::output::stderr;
+
+/// Dotfile left in buckets chronoban creates.
+pub const MARKER_FILE: &str = ".chronoban-bucket";
+
+/// Whether `name` is in the namespace chronoban keeps for its own files:
+/// anything starting with `.chronoban` (state, markers, manifests, ignore
+/// files, scratch files) and the `.chronokeep` pin. Such files are never
+/// organized, deduplicated or counted as bucket contents.
+pub fn is_reserved(name: &std::ffi::OsStr) -> bool {
+    let name = name.as_encoded_bytes();
+    name.starts_with(b".chronoban") || name == KEEP_MARKER.as_bytes()
+}
+
+/// Extended attribute set on buckets chronoban creates.
+#[cfg(target_os = "linux")]
+const MARKER_XATTR: &std::ffi::CStr = c"user.chronoban.bucket";
+
+/// How new buckets are marked as chronoban's own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MarkerKind {
+    /// A `.chronoban-bucket` file inside the bucket
+    Dotfile,
+    /// A `user.chronoban.bucket` extended attribute (Linux only)
+    Xattr,
+    /// Don't mark buckets or warn about unmarked ones
+    None,
+}
+
+impl MarkerKind {
+    fn mark(self, dir: &Path) -> io::Result<()> {
+        match self {
+            MarkerKind::Dotfile => std::fs::write(dir.join(MARKER_FILE), b""),
+            MarkerKind::Xattr => set_xattr(dir),
+            MarkerKind::None => Ok(()),
+        }
+    }
+}
+
+/// How `dir` is marked, if at all.
+pub fn detect(dir: &Path) -> Option<MarkerKind> {
+    if dir.join(MARKER_FILE).is_file() {
+        Some(MarkerKind::Dotfile)
+    } else if has_xattr(dir) {
+        Some(MarkerKind::Xattr)
+    } else {
+        None
+    }
+}
+
+/// Create `dir` and mark it, unless it already exists. Failures are left
+/// for the move into the bucket to report, or only warned about when just
+/// the marker could not be written.
+pub fn claim(dir: &Path, kind: MarkerKind) {
+    if dir.exists() || std::fs::create_dir_all(dir).is_err() {
+        return;
+    }
+    if let Err(e) = kind.mark(dir) {
+        stderr!("⚠️  Could not mark {} as a chronoban bucket: {}", dir.display(), e);
+    }
+}
+
+/// Warn about `dir` if it exists without a marker, before files are merged
+/// into it.
+pub fn warn_if_foreign(dir: &Path) {
+    if dir.is_dir() && detect(dir).is_none() {
+        stderr!("⚠️  {} was not created by chronoban (no marker), merging into it anyway",
+            dir.display());
+    }
+}
+
+/// Remove the marker dotfile if it's all that is left, so the emptied
+/// bucket itself can be removed.
+pub fn remove_if_only(dir: &Path) {
+    let Ok(mut entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let only_marker = matches!(entries.next(), Some(Ok(e)) if e.file_name() == MARKER_FILE)
+        && entries.next().is_none();
+    if only_marker {
+        let _ = std::fs::remove_file(dir.join(MARKER_FILE));
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_xattr(dir: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: both strings are NUL-terminated and the value is a valid
+    // one-byte buffer.
+    let rc = unsafe {
+        libc::setxattr(path.as_ptr(), MARKER_XATTR.as_ptr(), b"1".as_ptr().cast(), 1, 0)
+    };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn has_xattr(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: both strings are NUL-terminated; a null buffer of size 0 only
+    // asks for the value's length.
+    unsafe { libc::getxattr(path.as_ptr(), MARKER_XATTR.as_ptr(), std::ptr::null_mut(), 0) >= 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_xattr(_dir: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "extended attribute markers need Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn has_xattr(_dir: &Path) -> bool {
+    false
+}
@@ -0,0 +1,99 @@
+//! Counters for a long-running `--watch`, served in the Prometheus text
+//! format with `--metrics-addr`, so a watcher that keeps failing, or quietly
+//! stops running, can be alerted on.
+
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::output::{status, stderr};
+
+static RUNS: AtomicU64 = AtomicU64::new(0);
+static FAILED_RUNS: AtomicU64 = AtomicU64::new(0);
+static MOVED: AtomicU64 = AtomicU64::new(0);
+static SKIPPED: AtomicU64 = AtomicU64::new(0);
+static ERRORS: AtomicU64 = AtomicU64::new(0);
+static BYTES_MOVED: AtomicU64 = AtomicU64::new(0);
+static LAST_RUN: AtomicU64 = AtomicU64::new(0);
+static LAST_SUCCESS: AtomicU64 = AtomicU64::new(0);
+
+/// Count a run that got through its plan.
+pub fn record_run(moved: usize, skipped: usize, errors: usize, bytes_moved: u64) {
+    RUNS.fetch_add(1, Ordering::Relaxed);
+    MOVED.fetch_add(moved as u64, Ordering::Relaxed);
+    SKIPPED.fetch_add(skipped as u64, Ordering::Relaxed);
+    ERRORS.fetch_add(errors as u64, Ordering::Relaxed);
+    BYTES_MOVED.fetch_add(bytes_moved, Ordering::Relaxed);
+    LAST_RUN.store(now(), Ordering::Relaxed);
+    if errors == 0 {
+        LAST_SUCCESS.store(now(), Ordering::Relaxed);
+    }
+}
+
+/// Count a run that stopped with an error.
+pub fn record_failure() {
+    RUNS.fetch_add(1, Ordering::Relaxed);
+    FAILED_RUNS.fetch_add(1, Ordering::Relaxed);
+    LAST_RUN.store(now(), Ordering::Relaxed);
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Start answering every HTTP request on `addr` with the metrics, in the
+/// background.
+pub async fn serve(addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    status!("📈 Serving metrics on http://{}/metrics", addr);
+    tokio::spawn(async move {
+        loop {
+            let mut stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    stderr!("⚠️  Metrics connection failed: {}", e);
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                // Whatever was asked for, the answer is the same; read the
+                // request only so the client isn't cut off mid-send
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let body = render();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    body.len(), body);
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+fn render() -> String {
+    let metrics = [
+        ("chronoban_runs_total", "counter", "Organize runs done, failed or not", &RUNS),
+        ("chronoban_failed_runs_total", "counter", "Runs that stopped with an error", &FAILED_RUNS),
+        ("chronoban_moved_total", "counter", "Entries moved", &MOVED),
+        ("chronoban_skipped_total", "counter", "Entries skipped", &SKIPPED),
+        ("chronoban_errors_total", "counter", "Entries that failed to move", &ERRORS),
+        ("chronoban_moved_bytes_total", "counter", "Bytes moved", &BYTES_MOVED),
+        ("chronoban_last_run_timestamp_seconds", "gauge", "When the last run ended", &LAST_RUN),
+        ("chronoban_last_success_timestamp_seconds", "gauge", "When the last run without errors ended",
+            &LAST_SUCCESS),
+    ];
+    let mut out = String::new();
+    for (name, kind, help, value) in metrics {
+        let _ = write!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name,
+            value.load(Ordering::Relaxed));
+    }
+    out
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::bucket::{find_buckets, parse_bucket_format, BucketFormat};
+use crate::exit::{self, Exit};
+use crate::lock::LockArgs;
+use crate::marker::{self, is_reserved};
+use crate::output::{stderr, stdout};
+
+#[derive(clap::Args, Debug)]
+pub struct MigrateArgs {
+    /// Directory containing the buckets
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Current bucket layout, e.g. '%Y-%m'
+    #[arg(long, value_parser = parse_bucket_format)]
+    from: BucketFormat,
+
+    /// New bucket layout, e.g. '%Y/%m'. Dates are taken from the old bucket
+    /// names, so it should not be finer-grained than --from.
+    #[arg(long, value_parser = parse_bucket_format)]
+    to: BucketFormat,
+
+    /// Show what would be moved without changing anything
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+pub async fn run(args: MigrateArgs) -> Result<Exit> {
+    let base = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    let _lock = if args.dry_run { None } else { args.lock.acquire(&base).await? };
+
+    stdout!("Migrating buckets in: {}", base.display());
+    stdout!("Layout: {} -> {}", args.from, args.to);
+    if args.dry_run {
+        stdout!("🔍 DRY RUN MODE - No files will be moved");
+    }
+    stdout!();
+
+    let buckets = find_buckets(&base, &args.from).await?;
+
+    let mut moved = 0;
+    let mut skipped = 0;
+    let mut errors = 0;
+    let mut buckets_migrated = 0;
+
+    for (old_dir, date) in buckets {
+        if exit::interrupted() {
+            break;
+        }
+        let new_dir = base.join(args.to.render_date(date));
+        if new_dir == old_dir {
+            continue;
+        }
+
+        let mut entries = Vec::new();
+        let mut read = fs::read_dir(&old_dir)
+            .await
+            .with_context(|| format!("Failed to read directory: {:?}", old_dir))?;
+        while let Some(entry) = read.next_entry().await? {
+            if !is_reserved(&entry.file_name()) {
+                entries.push(entry.path());
+            }
+        }
+        let marker = marker::detect(&old_dir);
+        entries.sort();
+
+        for source in entries {
+            let target = new_dir.join(source.file_name().unwrap());
+            if target.exists() || new_dir.starts_with(&source) {
+                stderr!("⚠️  Target already exists, skipping: {} -> {}",
+                    source.display(), target.display());
+                skipped += 1;
+                continue;
+            }
+
+            if args.dry_run {
+                stdout!("📦 Would move: {} -> {}", source.display(), target.display());
+                moved += 1;
+                continue;
+            }
+
+            if let Some(kind) = marker {
+                marker::claim(&new_dir, kind);
+            }
+            let result = async {
+                fs::create_dir_all(&new_dir)
+                    .await
+                    .with_context(|| format!("Failed to create directory: {:?}", new_dir))?;
+                fs::rename(&source, &target)
+                    .await
+                    .with_context(|| format!("Failed to move {:?} to {:?}", source, target))
+            };
+            match result.await {
+                Ok(()) => {
+                    stdout!("✅ Moved: {} -> {}", source.display(), target.display());
+                    moved += 1;
+                }
+                Err(e) => {
+                    stderr!("❌ Error: {:#}", e);
+                    errors += 1;
+                }
+            }
+        }
+
+        if !args.dry_run {
+            marker::remove_if_only(&old_dir);
+            remove_empty_ancestors(&old_dir, &base).await;
+        }
+        buckets_migrated += 1;
+    }
+
+    stdout!("\n📊 Summary:");
+    stdout!("  Buckets migrated: {}", buckets_migrated);
+    stdout!("  Files moved: {}", moved);
+    stdout!("  Files skipped: {}", skipped);
+    stdout!("  Errors: {}", errors);
+
+    Ok(Exit::from_failures(errors))
+}
+
+/// Remove `dir` and then each parent that became empty, stopping at `base`.
+async fn remove_empty_ancestors(dir: &Path, base: &Path) {
+    let mut current = Some(dir);
+    while let Some(dir) = current {
+        if dir == base || fs::remove_dir(dir).await.is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
@@ -0,0 +1,87 @@
+//! Month names for `--month-names`, which follow the number in bucket names
+//! (`2024-05 Mai`). chrono only knows English names, so the languages
+//! offered are tabled here.
+
+/// A language's month names, January first.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Locale {
+    pub id: &'static str,
+    pub names: [&'static str; 12],
+}
+
+const LOCALES: &[Locale] = &[
+    Locale { id: "en", names: ["January", "February", "March", "April", "May", "June", "July", "August",
+        "September", "October", "November", "December"] },
+    Locale { id: "de", names: ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August",
+        "September", "Oktober", "November", "Dezember"] },
+    Locale { id: "fr", names: ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août",
+        "septembre", "octobre", "novembre", "décembre"] },
+    Locale { id: "es", names: ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto",
+        "septiembre", "octubre", "noviembre", "diciembre"] },
+    Locale { id: "it", names: ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto",
+        "settembre", "ottobre", "novembre", "dicembre"] },
+    Locale { id: "pt", names: ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto",
+        "setembro", "outubro", "novembro", "dezembro"] },
+    Locale { id: "nl", names: ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus",
+        "september", "oktober", "november", "december"] },
+    Locale { id: "sv", names: ["januari", "februari", "mars", "april", "maj", "juni", "juli", "augusti",
+        "september", "oktober", "november", "december"] },
+    Locale { id: "da", names: ["januar", "februar", "marts", "april", "maj", "juni", "juli", "august",
+        "september", "oktober", "november", "december"] },
+    Locale { id: "nb", names: ["januar", "februar", "mars", "april", "mai", "juni", "juli", "august",
+        "september", "oktober", "november", "desember"] },
+    Locale { id: "fi", names: ["tammikuu", "helmikuu", "maaliskuu", "huhtikuu", "toukokuu", "kesäkuu", "heinäkuu",
+        "elokuu", "syyskuu", "lokakuu", "marraskuu", "joulukuu"] },
+    Locale { id: "pl", names: ["styczeń", "luty", "marzec", "kwiecień", "maj", "czerwiec", "lipiec", "sierpień",
+        "wrzesień", "październik", "listopad", "grudzień"] },
+];
+
+/// How the month is written in bucket names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonthNames {
+    /// Just the number, as `%m` renders it.
+    Numeric,
+    Named(&'static Locale),
+}
+
+/// Parse `numeric`, `locale` (the language of LC_ALL, LC_TIME or LANG) or
+/// a locale id such as `de` or `de_AT.UTF-8`.
+pub fn parse_month_names(s: &str) -> Result<MonthNames, String> {
+    match s {
+        "numeric" => Ok(MonthNames::Numeric),
+        "locale" => {
+            let id = ["LC_ALL", "LC_TIME", "LANG"]
+                .iter()
+                .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+                .unwrap_or_default();
+            match id.as_str() {
+                "" | "C" | "POSIX" => Ok(MonthNames::Named(&LOCALES[0])),
+                id => find(id).map(MonthNames::Named).ok_or_else(|| {
+                    format!("no month names for the current locale {:?}; name one of {}", id, known())
+                }),
+            }
+        }
+        id => find(id).map(MonthNames::Named).ok_or_else(|| format!("no month names for {:?}; expected numeric, locale or one of {}", id, known())),
+    }
+}
+
+/// The language part of a locale id: `pt` of `pt_BR.UTF-8`. Norwegian
+/// `no` and `nn` share the Bokmål names.
+fn find(id: &str) -> Option<&'static Locale> {
+    let language = id.split(['_', '-', '.', '@']).next().unwrap_or(id).to_ascii_lowercase();
+    let language = if language == "no" || language == "nn" { "nb".to_string() } else { language };
+    LOCALES.iter().find(|locale| locale.id == language)
+}
+
+fn known() -> String {
+    LOCALES.iter().map(|l| l.id).collect::<Vec<_>>().join(", ")
+}
+
+/// `part` without a trailing ` <month name>` in any known language, so a
+/// named bucket parses like a numeric one.
+pub fn strip_name(part: &str) -> &str {
+    match part.rsplit_once(' ') {
+        Some((head, name)) if LOCALES.iter().any(|l| l.names.contains(&name)) => head,
+        _ => part,
+    }
+}
@@ -0,0 +1,617 @@
+//! Moving a single entry into its bucket: rename when possible, copy and
+//! delete when the bucket lives on another filesystem.
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::fs;
+
+use crate::fsutil::{copy_xattrs, remove_entry, set_times, special_kind};
+use crate::hash::{hash_tree, to_hex, Digest};
+use crate::output::{status, stderr};
+use crate::units::format_size;
+use crate::winpath::extended;
+
+/// How an entry reached its target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveMethod {
+    Renamed,
+    /// The rename crossed filesystems, so the data was copied and the source
+    /// removed. Carries the number of bytes copied, and the checksum both
+    /// sides were verified to have if that was asked for.
+    Copied { bytes: u64, sha256: Option<Digest> },
+}
+
+/// Move `source` to `target`, creating the target's parent directory. With
+/// `verify`, a copy across filesystems must match the source's checksum
+/// before the source is removed.
+pub async fn move_entry(source: &Path, target: &Path, retry: Retry, verify: bool) -> Result<MoveMethod> {
+    // Messages keep the paths as given
+    let (from, to) = (extended(source), extended(target));
+    if let Some(dir) = to.parent() {
+        retry.run(|| fs::create_dir_all(dir)).await
+            .with_context(|| format!("Failed to create directory: {:?}", dir))?;
+    }
+
+    let renamed = retry
+        .run(|| {
+            let (from, to) = (from.to_path_buf(), to.to_path_buf());
+            async move {
+                tokio::task::spawn_blocking(move || rename_new(&from, &to))
+                    .await
+                    .map_err(io::Error::other)?
+            }
+        })
+        .await;
+    match renamed {
+        Ok(()) => Ok(MoveMethod::Renamed),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let (bytes, sha256) = copy_then_remove(&from, &to, retry, verify)
+                .await
+                .with_context(|| format!("Failed to copy {:?} to {:?}", source, target))?;
+            Ok(MoveMethod::Copied { bytes, sha256 })
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to move {:?} to {:?}", source, target)),
+    }
+}
+
+/// Copy `source` to `target`, creating the target's parent directory and
+/// leaving the source in place.
+pub async fn copy_entry(source: &Path, target: &Path, retry: Retry, verify: bool) -> Result<MoveMethod> {
+    let (from, to) = (extended(source).into_owned(), extended(target).into_owned());
+    if let Some(dir) = to.parent() {
+        retry.run(|| fs::create_dir_all(dir)).await
+            .with_context(|| format!("Failed to create directory: {:?}", dir))?;
+    }
+    let copied = retry
+        .run(|| {
+            let (from, to) = (from.clone(), to.clone());
+            async move {
+                tokio::task::spawn_blocking(move || copy_verified(&from, &to, verify))
+                    .await
+                    .map_err(io::Error::other)?
+            }
+        })
+        .await;
+    let (bytes, sha256) = copied.with_context(|| format!("Failed to copy {:?} to {:?}", source, target))?;
+    Ok(MoveMethod::Copied { bytes, sha256 })
+}
+
+/// Replace the symlink `link` with a copy of what it points to, placed at
+/// `target`. The link's target itself is left alone.
+pub async fn materialize_link(link: &Path, target: &Path, retry: Retry, verify: bool) -> Result<MoveMethod> {
+    let to = extended(target).into_owned();
+    if let Some(dir) = to.parent() {
+        retry.run(|| fs::create_dir_all(dir)).await
+            .with_context(|| format!("Failed to create directory: {:?}", dir))?;
+    }
+    let resolved = fs::canonicalize(link)
+        .await
+        .with_context(|| format!("Failed to follow symlink {:?}", link))?;
+    let copying = to.clone();
+    let copied = tokio::task::spawn_blocking(move || copy_verified(&resolved, &copying, verify)).await?;
+    let (bytes, sha256) = copied.with_context(|| format!("Failed to copy the target of {:?}", link))?;
+    fs::remove_file(extended(link))
+        .await
+        .with_context(|| format!("Failed to remove symlink {:?}", link))?;
+    Ok(MoveMethod::Copied { bytes, sha256 })
+}
+
+/// After moving a symlink from `old` to `new`, rewrite a relative target so
+/// it still points at the same place. Absolute targets are left alone.
+#[cfg(unix)]
+pub fn retarget_symlink(old: &Path, new: &Path) -> io::Result<()> {
+    let target = std::fs::read_link(new)?;
+    let (Some(old_dir), Some(new_dir)) = (old.parent(), new.parent()) else {
+        return Ok(());
+    };
+    if target.is_absolute() || old_dir == new_dir {
+        return Ok(());
+    }
+    let rewritten = relative_to(&normalize(&old_dir.join(&target)), &normalize(new_dir));
+    // Swap the link atomically so it never dangles in between
+    let tmp = new.with_file_name(format!(".{}.chronoban-link",
+        new.file_name().unwrap_or_default().to_string_lossy()));
+    std::os::unix::fs::symlink(&rewritten, &tmp)?;
+    std::fs::rename(&tmp, new).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(not(unix))]
+pub fn retarget_symlink(_old: &Path, _new: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Resolve `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// `path` expressed relative to the directory `dir`; both are absolute.
+fn relative_to(path: &Path, dir: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut out = PathBuf::new();
+    for _ in dir.components().skip(common) {
+        out.push("..");
+    }
+    for component in path.components().skip(common) {
+        out.push(component);
+    }
+    out
+}
+
+/// Copy across filesystems, then remove the source. If the copy fails the
+/// source is left untouched.
+async fn copy_then_remove(source: &Path, target: &Path, retry: Retry, verify: bool) -> io::Result<(u64, Option<Digest>)> {
+    let (from, to) = (source.to_path_buf(), target.to_path_buf());
+    let copied = retry
+        .run(|| {
+            let (from, to) = (from.clone(), to.clone());
+            async move {
+                tokio::task::spawn_blocking(move || copy_verified(&from, &to, verify))
+                    .await
+                    .map_err(io::Error::other)?
+            }
+        })
+        .await?;
+
+    remove_any(source).await?;
+    Ok(copied)
+}
+
+/// [`copy_tree`], then with `verify` hash both sides. A copy that fails or
+/// doesn't match is removed again, so a retry starts over, but only if this
+/// call created it: whatever was already at `target` is never touched.
+fn copy_verified(source: &Path, target: &Path, verify: bool) -> io::Result<(u64, Option<Digest>)> {
+    let mut created = false;
+    let copied = copy_tree(source, target, &mut created).and_then(|bytes| {
+        if !verify {
+            return Ok((bytes, None));
+        }
+        let expected = hash_tree(source)?;
+        let actual = hash_tree(target)?;
+        if expected != actual {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("checksum mismatch: source {}, copy {}", to_hex(&expected), to_hex(&actual))));
+        }
+        Ok((bytes, Some(actual)))
+    });
+    if copied.is_err() && created {
+        let _ = remove_entry(target);
+    }
+    copied
+}
+
+/// Rename `source` to `target` unless something is already there. A plain
+/// rename replaces a file, or an empty directory, that appeared at the
+/// target since planning.
+fn rename_new(source: &Path, target: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let from = std::ffi::CString::new(source.as_os_str().as_bytes())?;
+        let to = std::ffi::CString::new(target.as_os_str().as_bytes())?;
+        // SAFETY: both paths are NUL-terminated and outlive the call.
+        let rc = unsafe {
+            libc::syscall(libc::SYS_renameat2, libc::AT_FDCWD, from.as_ptr(), libc::AT_FDCWD, to.as_ptr(),
+                libc::RENAME_NOREPLACE)
+        };
+        if rc == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        // Old kernels and some filesystems lack the flag; check instead
+        if !matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) {
+            return Err(e);
+        }
+    }
+    if std::fs::symlink_metadata(target).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "the target already exists"));
+    }
+    std::fs::rename(source, target)
+}
+
+/// Open a new file at `target` for writing, failing rather than truncating
+/// one that is already there.
+fn create_new(target: &Path) -> io::Result<std::fs::File> {
+    std::fs::OpenOptions::new().write(true).create_new(true).open(target)
+}
+
+/// Recursively copy a file, directory or symlink, keeping modification
+/// times, permissions, extended attributes and, when running as root,
+/// ownership. Nothing already at `target` is written over; `created` is set
+/// once the target exists because of this call.
+fn copy_tree(source: &Path, target: &Path, created: &mut bool) -> io::Result<u64> {
+    let metadata = std::fs::symlink_metadata(source)?;
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        copy_symlink(source, target)?;
+        *created = true;
+        copy_owner(target, &metadata);
+        return Ok(0);
+    }
+    // Reading a FIFO or device as a file would hang or copy garbage
+    if let Some(kind) = special_kind(file_type) {
+        return Err(io::Error::new(io::ErrorKind::Unsupported,
+            format!("can't copy a {} across filesystems: {}", kind, source.display())));
+    }
+
+    let bytes = if file_type.is_dir() {
+        std::fs::create_dir(target)?;
+        *created = true;
+        let mut total = 0;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            total += copy_tree(&entry.path(), &target.join(entry.file_name()), &mut false)?;
+        }
+        total
+    } else {
+        let to = create_new(target)?;
+        *created = true;
+        copy_file(source, to, metadata.len())?
+    };
+
+    // Set once the contents are in, so a read-only directory can be filled,
+    // and ownership before the mode, as chown clears setuid bits
+    copy_owner(target, &metadata);
+    if let Err(e) = copy_xattrs(source, target) {
+        // The target filesystem simply may not hold them
+        if e.kind() != io::ErrorKind::Unsupported {
+            stderr!("⚠️  Could not copy extended attributes of {}: {}", source.display(), e);
+        }
+    }
+    std::fs::set_permissions(target, metadata.permissions())?;
+    if let Ok(mtime) = metadata.modified() {
+        let times = std::fs::FileTimes::new().set_modified(mtime);
+        // A copy on Windows would otherwise be created now
+        #[cfg(windows)]
+        let times = match metadata.created() {
+            Ok(created) => std::os::windows::fs::FileTimesExt::set_created(times, created),
+            Err(_) => times,
+        };
+        let _ = set_times(target, times);
+    }
+    Ok(bytes)
+}
+
+/// Files at least this big are copied in parallel chunks, with progress
+/// reports, rather than in one long sequential copy.
+const LARGE_FILE: u64 = 256 * 1024 * 1024;
+const CHUNK_WORKERS: u64 = 4;
+const PROGRESS_EVERY: Duration = Duration::from_secs(5);
+
+/// Fill `to`, newly created for the copy, with the contents of `source`.
+fn copy_file(source: &Path, mut to: std::fs::File, len: u64) -> io::Result<u64> {
+    if len < LARGE_FILE {
+        if THROTTLE.get().is_some() {
+            return copy_throttled(source, to);
+        }
+        return io::copy(&mut std::fs::File::open(source)?, &mut to);
+    }
+    copy_large(source, to, len)
+}
+
+/// Shared by every copy in the run, so `--bandwidth` caps them together.
+static THROTTLE: OnceLock<Throttle> = OnceLock::new();
+
+/// Copy no more than `bytes_per_sec` for the rest of the run.
+pub fn limit_bandwidth(bytes_per_sec: u64) {
+    let _ = THROTTLE.set(Throttle {
+        rate: bytes_per_sec.max(1) as f64,
+        state: Mutex::new((Instant::now(), 0.0)),
+    });
+}
+
+/// A token bucket holding up to a second's worth of bytes.
+struct Throttle {
+    rate: f64,
+    /// When the bucket was last filled, and the bytes it held then, which
+    /// go negative when a copy has run ahead.
+    state: Mutex<(Instant, f64)>,
+}
+
+impl Throttle {
+    /// Account for `bytes` copied, sleeping as long as the rate asks.
+    fn take(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let (filled, tokens) = &mut *state;
+            let now = Instant::now();
+            *tokens = (*tokens + now.duration_since(*filled).as_secs_f64() * self.rate).min(self.rate);
+            *filled = now;
+            *tokens -= bytes as f64;
+            (*tokens < 0.0).then(|| Duration::from_secs_f64(-*tokens / self.rate))
+        };
+        if let Some(wait) = wait {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+fn throttle(bytes: usize) {
+    if let Some(throttle) = THROTTLE.get() {
+        throttle.take(bytes);
+    }
+}
+
+/// [`io::copy`] a buffer at a time, so the throttle can pace it.
+fn copy_throttled(source: &Path, mut to: std::fs::File) -> io::Result<u64> {
+    use std::io::{Read, Write};
+    let mut from = std::fs::File::open(source)?;
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut total = 0;
+    loop {
+        let n = from.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        throttle(n);
+        to.write_all(&buf[..n])?;
+        total += n as u64;
+    }
+    to.set_permissions(from.metadata()?.permissions())?;
+    Ok(total)
+}
+
+/// Copy a big file as `CHUNK_WORKERS` ranges at once, reporting how far it
+/// has got every few seconds.
+#[cfg(unix)]
+fn copy_large(source: &Path, to: std::fs::File, len: u64) -> io::Result<u64> {
+    use std::os::unix::fs::FileExt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    let from = std::fs::File::open(source)?;
+    to.set_len(len)?;
+    to.set_permissions(from.metadata()?.permissions())?;
+
+    let done = AtomicU64::new(0);
+    let stride = len.div_ceil(CHUNK_WORKERS);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..CHUNK_WORKERS)
+            .map(|i| {
+                let (from, to, done) = (&from, &to, &done);
+                scope.spawn(move || -> io::Result<()> {
+                    let end = ((i + 1) * stride).min(len);
+                    let mut pos = i * stride;
+                    let mut buf = vec![0u8; 1024 * 1024];
+                    while pos < end {
+                        let want = ((end - pos) as usize).min(buf.len());
+                        let n = from.read_at(&mut buf[..want], pos)?;
+                        if n == 0 {
+                            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                "file shrank while it was being copied"));
+                        }
+                        throttle(n);
+                        to.write_all_at(&buf[..n], pos)?;
+                        pos += n as u64;
+                        done.fetch_add(n as u64, Ordering::Relaxed);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        let mut reported = std::time::Instant::now();
+        while !workers.iter().all(|w| w.is_finished()) {
+            std::thread::sleep(Duration::from_millis(100));
+            if reported.elapsed() >= PROGRESS_EVERY {
+                let copied = done.load(Ordering::Relaxed);
+                status!("⏳ Copying {}: {} of {} ({}%)", source.display(),
+                    format_size(copied), format_size(len), copied * 100 / len);
+                reported = std::time::Instant::now();
+            }
+        }
+        for worker in workers {
+            worker.join().map_err(|_| io::Error::other("copy worker panicked"))??;
+        }
+        Ok(len)
+    })
+}
+
+#[cfg(not(unix))]
+fn copy_large(source: &Path, mut to: std::fs::File, _len: u64) -> io::Result<u64> {
+    if THROTTLE.get().is_some() {
+        return copy_throttled(source, to);
+    }
+    io::copy(&mut std::fs::File::open(source)?, &mut to)
+}
+
+/// Give a copy its original's owner and group, which only root may do.
+#[cfg(unix)]
+fn copy_owner(target: &Path, metadata: &std::fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+    if let Err(e) = std::os::unix::fs::lchown(target, Some(metadata.uid()), Some(metadata.gid())) {
+        stderr!("⚠️  Could not keep the owner of {}: {}", target.display(), e);
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_target: &Path, _metadata: &std::fs::Metadata) {}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(source)?, target)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(source: &Path, target: &Path) -> io::Result<()> {
+    io::copy(&mut std::fs::File::open(source)?, &mut create_new(target)?).map(|_| ())
+}
+
+/// [`remove_entry`] off the runtime, as a whole tree can take a while;
+/// something already gone counts as removed.
+pub async fn remove_any(path: &Path) -> io::Result<()> {
+    let path: PathBuf = path.to_path_buf();
+    match tokio::task::spawn_blocking(move || remove_entry(&path)).await? {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Retry {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl Retry {
+    /// Run an I/O operation, retrying transient failures with exponential backoff.
+    pub async fn run<F, Fut, T>(&self, mut op: F) -> io::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = io::Result<T>>,
+    {
+        let mut delay = self.delay;
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    stderr!("🔁 Retrying in {:?} (attempt {}/{}): {}",
+                        delay, attempt, self.retries, e);
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Errors that retrying cannot fix are reported immediately.
+fn is_transient(e: &io::Error) -> bool {
+    !matches!(
+        e.kind(),
+        io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::AlreadyExists
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::CrossesDevices
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory for one test, removed afterwards.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir = std::env::temp_dir().join(format!("chronoban-mover-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const NO_RETRY: Retry = Retry { retries: 0, delay: Duration::ZERO };
+
+    fn already_exists(e: &anyhow::Error) -> bool {
+        e.chain().filter_map(|c| c.downcast_ref::<io::Error>()).any(|e| e.kind() == io::ErrorKind::AlreadyExists)
+    }
+
+    #[tokio::test]
+    async fn never_writes_over_a_target() {
+        let scratch = Scratch::new("exists");
+        let (source, target) = (scratch.0.join("a.txt"), scratch.0.join("2022-03").join("a.txt"));
+        std::fs::write(&source, "ours").unwrap();
+        std::fs::create_dir(scratch.0.join("2022-03")).unwrap();
+        std::fs::write(&target, "theirs").unwrap();
+
+        for verify in [false, true] {
+            let e = copy_entry(&source, &target, NO_RETRY, verify).await.unwrap_err();
+            assert!(already_exists(&e), "{:#}", e);
+            let e = move_entry(&source, &target, NO_RETRY, verify).await.unwrap_err();
+            assert!(already_exists(&e), "{:#}", e);
+        }
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "theirs");
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "ours");
+    }
+
+    #[tokio::test]
+    async fn never_writes_into_an_existing_directory() {
+        let scratch = Scratch::new("exists-dir");
+        let (source, target) = (scratch.0.join("album"), scratch.0.join("2022-03").join("album"));
+        std::fs::create_dir(&source).unwrap();
+        std::fs::write(source.join("a.jpg"), "ours").unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+
+        let e = copy_entry(&source, &target, NO_RETRY, false).await.unwrap_err();
+        assert!(already_exists(&e), "{:#}", e);
+        assert!(target.exists());
+        assert!(!target.join("a.jpg").exists());
+        // An empty directory is what a plain rename would quietly replace
+        let e = move_entry(&source, &target, NO_RETRY, false).await.unwrap_err();
+        assert!(already_exists(&e), "{:#}", e);
+        assert!(source.join("a.jpg").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn removes_only_a_copy_it_created() {
+        let scratch = Scratch::new("partial");
+        let (source, target) = (scratch.0.join("album"), scratch.0.join("2022-03").join("album"));
+        std::fs::create_dir(&source).unwrap();
+        std::fs::write(source.join("a.jpg"), "jpeg").unwrap();
+        // A FIFO can't be copied, so the copy fails partway
+        let fifo = std::ffi::CString::new(source.join("pipe").into_os_string().into_encoded_bytes()).unwrap();
+        // SAFETY: the path is NUL-terminated.
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        assert!(copy_entry(&source, &target, NO_RETRY, false).await.is_err());
+        assert!(!target.exists());
+        assert!(source.join("a.jpg").exists());
+    }
+
+    #[tokio::test]
+    async fn copies_files_and_trees() {
+        let scratch = Scratch::new("copy");
+        let source = scratch.0.join("album");
+        std::fs::create_dir_all(source.join("raw")).unwrap();
+        std::fs::write(source.join("a.jpg"), "jpeg").unwrap();
+        std::fs::write(source.join("raw").join("a.cr2"), "negative").unwrap();
+
+        let target = scratch.0.join("2022-03").join("album");
+        let method = copy_entry(&source, &target, NO_RETRY, true).await.unwrap();
+        assert_eq!(method, MoveMethod::Copied { bytes: 12, sha256: Some(hash_tree(&source).unwrap()) });
+        assert_eq!(std::fs::read_to_string(target.join("raw").join("a.cr2")).unwrap(), "negative");
+
+        let moved = scratch.0.join("2022-04").join("album");
+        assert_eq!(move_entry(&source, &moved, NO_RETRY, false).await.unwrap(), MoveMethod::Renamed);
+        assert!(!source.exists());
+        assert_eq!(hash_tree(&moved).unwrap(), hash_tree(&target).unwrap());
+    }
+}
@@ -0,0 +1,131 @@
+//! Telling someone how a run went, with `--notify`: the JSON summary POSTed
+//! to a URL, such as a home server dashboard, or a desktop notification.
+//! A run that fails sends a short failure document instead. Notifications
+//! that can't be delivered are warned about but never fail the run.
+
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+use crate::json::{self, Value};
+use crate::output::stderr;
+use crate::schema::SCHEMA_VERSION;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// POST the summary here.
+    Url(String),
+    /// Show a desktop notification.
+    Desktop,
+}
+
+pub fn parse_target(s: &str) -> Result<Target, String> {
+    if s == "desktop" {
+        Ok(Target::Desktop)
+    } else if s.starts_with("http://") || s.starts_with("https://") {
+        Ok(Target::Url(s.to_string()))
+    } else {
+        Err(format!("expected an http(s) URL or 'desktop': {:?}", s))
+    }
+}
+
+/// Report a run that stopped with `error`.
+pub async fn failed(targets: &[Target], base: &std::path::Path, error: &anyhow::Error) {
+    let document = json::object([
+        ("schema_version", SCHEMA_VERSION.into()),
+        ("failed", true.into()),
+        ("base", base.into()),
+        ("error", format!("{:#}", error).into()),
+    ]);
+    send(targets, &document, &format!("Failed: {:#}", error)).await;
+}
+
+/// Send `document`, such as the summary `--output json` prints, with
+/// `headline` for desktop notifications.
+pub async fn send(targets: &[Target], document: &Value, headline: &str) {
+    for target in targets {
+        let sent = match target {
+            Target::Url(url) => post(url, &document.to_string()).await,
+            Target::Desktop => desktop(headline).await,
+        };
+        if let Err(e) = sent {
+            stderr!("⚠️  Could not notify {}: {:#}", describe(target), e);
+        }
+    }
+}
+
+fn describe(target: &Target) -> &str {
+    match target {
+        Target::Url(url) => url,
+        Target::Desktop => "the desktop",
+    }
+}
+
+/// POST `body` as JSON. Plain HTTP is spoken directly; HTTPS goes through
+/// curl, as there is no TLS here.
+async fn post(url: &str, body: &str) -> Result<()> {
+    if url.starts_with("https://") {
+        return run(Command::new("curl")
+            .args(["-fsS", "-m", &TIMEOUT.as_secs().to_string(), "-X", "POST"])
+            .args(["-H", "Content-Type: application/json", "--data-binary", "@-", url]), Some(body))
+        .await;
+    }
+
+    let rest = &url["http://".len()..];
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = if path.is_empty() { "/" } else { path };
+    let address = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: chronoban/{}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, authority, env!("CARGO_PKG_VERSION"), body.len(), body);
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(&address).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    let response = tokio::time::timeout(TIMEOUT, exchange)
+        .await
+        .context("timed out")?
+        .with_context(|| format!("Failed to reach {}", address))?;
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => anyhow::bail!("server answered {:?}", status),
+    }
+}
+
+async fn desktop(headline: &str) -> Result<()> {
+    if cfg!(target_os = "macos") {
+        let script = format!("display notification {:?} with title \"chronoban\"", headline);
+        run(Command::new("osascript").args(["-e", &script]), None).await
+    } else {
+        run(Command::new("notify-send").args(["chronoban", headline]), None).await
+    }
+}
+
+async fn run(command: &mut Command, stdin: Option<&str>) -> Result<()> {
+    command
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .kill_on_drop(true);
+    let program = command.as_std().get_program().to_string_lossy().into_owned();
+    let mut child = command.spawn().with_context(|| format!("Failed to start {}", program))?;
+    if let (Some(mut pipe), Some(input)) = (child.stdin.take(), stdin) {
+        pipe.write_all(input.as_bytes()).await?;
+    }
+    let status = tokio::time::timeout(TIMEOUT, child.wait()).await.context("timed out")??;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,2910 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, TimeZone};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::fs;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinError;
+use walkdir::WalkDir;
+
+use crate::cli::Args;
+use crate::bucket::{is_granularity_bucket_name, BucketFormat, Coarse};
+use crate::catalog::Catalog;
+use crate::config::Config;
+use crate::datesrc::{date_from_name, Prefer};
+use crate::dedupe::{self, DedupeMode, DuplicateFinder};
+use crate::email;
+use crate::embedded;
+use crate::exif;
+use crate::exit::{self, Exit};
+use crate::filetype::{self, Category, FileType};
+use crate::fsutil::{
+    content_time, dir_size, inode, is_other_filesystem, open_file_paths, owned_by, owner_name, preserve_times, remove_entry,
+    restore_atime, special_kind, warn_atime_mount, MetadataTime,
+};
+use crate::history;
+use crate::hooks::{self, BucketHooks, HookRunner};
+use crate::identity::Identity;
+use crate::ignore::{Patterns, IGNORE_FILE, KEEP_MARKER};
+use crate::journal::{self, Journal, STATE_DIR};
+use crate::lock;
+use crate::mapping;
+use crate::marker::{self, is_reserved, MarkerKind};
+use crate::json::{self, Value};
+use crate::metrics;
+use crate::months::MonthNames;
+use crate::mover::{copy_entry, materialize_link, move_entry, remove_any, retarget_symlink, MoveMethod, Retry};
+use crate::notify;
+use crate::output::{self, debug, status, stderr, stdout, verbose, OutputFormat, Verbosity};
+use crate::plan::{Conflict, DateSource, Plan, PlanEntry};
+use crate::preflight::{self, Floor, LowSpace};
+use crate::provider::{self, DateChains};
+use crate::remote::{key_of, Remote};
+use crate::replan;
+use crate::report::Report;
+use crate::route::{canonical_extension, Router};
+use crate::schedule::{Period, Schedule};
+use crate::schema::SCHEMA_VERSION;
+use crate::service;
+use crate::sidecar;
+use crate::stats::BucketStats;
+use crate::template::{self, Template};
+use crate::trace;
+use crate::tui;
+use crate::unicode::{self, equivalence_key, ExistingNames};
+use crate::units::format_size;
+use crate::winpath;
+
+pub async fn run(mut args: Args) -> Result<Exit> {
+    output::init(args.output, Verbosity::from_flags(args.quiet, args.verbose));
+    output::journald(args.service);
+    let _writer = output::Writer::start();
+    let Some(interval) = args.watch else {
+        return organize_once(&mut args).await;
+    };
+
+    let mut schedule = watch_schedule(&args, interval)?;
+    if let Some(addr) = args.metrics_addr {
+        metrics::serve(addr).await?;
+    }
+    let mut hangups = hangups();
+    if args.service {
+        service::notify("READY=1");
+    }
+    let mut next = schedule.first(Local::now());
+    let mut exit = Exit::Success;
+    loop {
+        if next > Local::now() {
+            status!("👀 Watching; next run at {}", next.format("%Y-%m-%d %H:%M:%S"));
+        }
+        let mut reload = false;
+        while Local::now() < next && !exit::interrupted() && !reload {
+            let left = (next - Local::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(250).min(left)) => {}
+                Some(()) = hangup(&mut hangups) => reload = true,
+            }
+        }
+        if exit::interrupted() {
+            if args.service {
+                service::notify("STOPPING=1");
+            }
+            // Being stopped is how a service's life normally ends
+            return Ok(match exit {
+                Exit::Fatal => exit,
+                _ if args.service => Exit::Success,
+                _ => Exit::Interrupted,
+            });
+        }
+        if reload {
+            // The config file is read afresh by every run
+            status!("🔄 Reloading: organizing again now");
+            if args.service {
+                service::notify(&service::reloading());
+                service::notify("READY=1");
+            }
+        }
+
+        // A watcher outlives a failed run; the next one may well succeed
+        exit = match organize_once(&mut args).await {
+            Ok(exit) => exit,
+            Err(e) if e.downcast_ref::<lock::Busy>().is_some() => {
+                let busy = e.downcast_ref::<lock::Busy>().unwrap();
+                status!("⏭️  Previous run still active on {}, skipping this one", busy.0.display());
+                exit
+            }
+            Err(e) => {
+                stderr!("❌ Error: {:#}", e);
+                Exit::Fatal
+            }
+        };
+        if args.service {
+            service::notify(&format!("STATUS=Last run {}", match exit {
+                Exit::Success => "succeeded",
+                Exit::Partial => "had errors",
+                Exit::Fatal => "failed",
+                Exit::Interrupted => "was interrupted",
+            }));
+        }
+        match watch_schedule(&args, interval) {
+            Ok(reloaded) => schedule = reloaded,
+            Err(e) => stderr!("⚠️  Keeping the previous schedule: {:#}", e),
+        }
+        next = schedule.after(Local::now());
+    }
+}
+
+/// When a watcher runs: at the `--watch` interval if one was given, or on
+/// the config file's cron schedule.
+fn watch_schedule(args: &Args, interval: Option<Duration>) -> Result<Schedule> {
+    let config = Config::load(args.config.as_deref())?.schedule;
+    let period = match (interval, config.cron) {
+        (Some(interval), _) => Period::Every(interval),
+        (None, Some(cron)) => Period::Cron(cron),
+        (None, None) => {
+            anyhow::bail!("--watch needs a DURATION, or a cron expression under [schedule] in the config file")
+        }
+    };
+    let jitter = args.jitter.or(config.jitter).unwrap_or_default();
+    Ok(Schedule { period, jitter })
+}
+
+/// SIGHUP, which asks a watcher to reload its config and run again now.
+#[cfg(unix)]
+type Hangups = Option<tokio::signal::unix::Signal>;
+#[cfg(not(unix))]
+type Hangups = ();
+
+#[cfg(unix)]
+fn hangups() -> Hangups {
+    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok()
+}
+
+#[cfg(not(unix))]
+fn hangups() -> Hangups {}
+
+#[cfg(unix)]
+async fn hangup(hangups: &mut Hangups) -> Option<()> {
+    match hangups {
+        Some(signal) => signal.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn hangup(_hangups: &mut Hangups) -> Option<()> {
+    std::future::pending().await
+}
+
+/// One run, with its outcome counted and, on failure, notified.
+async fn organize_once(args: &mut Args) -> Result<Exit> {
+    if args.trace_file.is_some() {
+        trace::enable();
+    }
+    let result = organize(args).await;
+    if let Some(file) = &args.trace_file {
+        if let Err(e) = trace::write(file) {
+            stderr!("⚠️  {:#}", e);
+        }
+    }
+    if let Err(e) = &result {
+        metrics::record_failure();
+        let base = args.dest.as_ref().or(args.paths.first()).unwrap_or(&args.path);
+        if !args.notify.is_empty() {
+            notify::failed(&args.notify, base, e).await;
+        }
+        if args.email.is_enabled() {
+            let subject = format!("chronoban failed on {}", base.display());
+            email::send(&args.email, &subject, &format!("The run stopped with an error:\n\n{:#}", e)).await;
+        }
+    }
+    result
+}
+
+async fn organize(args: &mut Args) -> Result<Exit> {
+    if let Some(preset) = args.preset {
+        preset.apply(args);
+    }
+    if let Some(granularity) = args.granularity {
+        args.format = granularity.format();
+    }
+    if args.use_atime {
+        args.time = MetadataTime::Atime;
+    }
+    if args.month_names != MonthNames::Numeric {
+        args.format = args.format.clone().with_month_names(args.month_names).map_err(anyhow::Error::msg)?;
+    }
+    if let Some(file) = args.explain.clone() {
+        return explain(&file, args).await;
+    }
+
+    // Sorted output needs moves to finish in the order they start
+    if args.sort.is_some() {
+        args.jobs = Some(1);
+    }
+    // Auto-detect CPU count if jobs not specified
+    if args.jobs.is_none() {
+        args.jobs = Some(
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4)
+        );
+    }
+
+    let given = if args.paths.is_empty() { vec![args.path.clone()] } else { args.paths.clone() };
+    let mut sources = Vec::with_capacity(given.len());
+    for dir in &given {
+        let source = existing_dir(dir).await?;
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    let remote = match args.dest.as_deref().map(Remote::parse) {
+        Some(Some(remote)) => Some(Arc::new(remote?)),
+        _ => None,
+    };
+    let path = match &args.dest {
+        // Planned as if the buckets were local, then uploaded
+        Some(_) if remote.is_some() => sources[0].clone(),
+        Some(dest) if args.dry_run && !dest.exists() => std::path::absolute(dest)?,
+        Some(dest) => {
+            tokio::fs::create_dir_all(dest)
+                .await
+                .with_context(|| format!("Failed to create directory: {:?}", dest))?;
+            existing_dir(dest).await?
+        }
+        None if sources.len() == 1 => sources[0].clone(),
+        None => anyhow::bail!("Organizing several directories needs --dest to say where buckets go"),
+    };
+
+    if !args.dry_run {
+        preflight::check_writable(&sources, &path)?;
+    }
+
+    // Held until the run ends; taken in sorted order so waiting runs can't
+    // deadlock on each other
+    let mut locks = Vec::new();
+    if !args.dry_run {
+        let dirs: BTreeSet<&PathBuf> = sources.iter().chain([&path]).collect();
+        for dir in dirs {
+            locks.push(args.lock.acquire(dir).await?);
+        }
+    }
+
+    if args.time == MetadataTime::Atime {
+        for source in &sources {
+            warn_atime_mount(source);
+        }
+    }
+
+    if args.recursive && args.only == Only::Dirs {
+        anyhow::bail!("--only dirs can't be combined with --recursive, which organizes files");
+    }
+
+    if args.auto_coalesce.is_some() && !args.format.can_coalesce() {
+        anyhow::bail!("--auto-coalesce needs a monthly --format (with %m and no day), not {:?}",
+            args.format.to_string());
+    }
+
+    if args.copy
+        && (matches!(args.dedupe, Some(DedupeMode::Hardlink | DedupeMode::Delete)) || args.empty == EmptyFiles::Delete)
+    {
+        anyhow::bail!("--copy leaves the originals alone, so it can't be combined with options that delete them");
+    }
+
+    if args.atomic {
+        if remote.is_some() {
+            anyhow::bail!("--atomic can't take back uploads, so it needs a local --dest");
+        }
+        if matches!(args.dedupe, Some(DedupeMode::Hardlink | DedupeMode::Delete))
+            || args.empty == EmptyFiles::Delete
+            || args.symlinks == Symlinks::Follow
+        {
+            anyhow::bail!("--atomic can only undo moves, so it can't be combined with options that delete or replace entries");
+        }
+        // Rolling back works from the journal
+        args.journal = true;
+    }
+
+    if !args.dry_run {
+        let journaled = args.journal || args.resume;
+        match args.dedupe {
+            Some(DedupeMode::Delete) => args.unjournaled.require("--dedupe delete", journaled)?,
+            Some(DedupeMode::Hardlink) => args.unjournaled.require("--dedupe hardlink", journaled)?,
+            _ => {}
+        }
+        if args.empty == EmptyFiles::Delete {
+            args.unjournaled.require("--empty delete", journaled)?;
+        }
+    }
+
+    let mut config = Config::load(args.config.as_deref())?;
+    if let Some(profile) = &args.profile {
+        config.use_profile(profile)?;
+    }
+    args.limits.or(config.limits).apply()?;
+    args.email = std::mem::take(&mut args.email).or(std::mem::take(&mut config.email));
+    let mut rules = Rules::new(args, config);
+    if remote.is_some() && args.assert_idempotent {
+        anyhow::bail!("--assert-idempotent looks at local buckets and can't check a remote --dest");
+    }
+    rules.remote = remote;
+
+    let listed: Vec<_> = sources.iter().map(|s| s.display().to_string()).collect();
+    if let Some(profile) = &args.profile {
+        status!("Profile: {}", profile);
+    }
+    status!("Organizing files in: {}", listed.join(", "));
+    if let Some(remote) = &rules.remote {
+        status!("Destination: {}", remote);
+    } else if sources != [path.clone()] {
+        status!("Destination: {}", path.display());
+    }
+    status!("Concurrency: {} jobs", args.jobs.unwrap());
+    if args.dry_run {
+        status!("🔍 DRY RUN MODE - No files will be moved");
+    }
+    status!();
+
+    if let Some(planning) = &args.planning {
+        let plan = plan_directory(&path, &sources, args, &rules).await?;
+        replan::report(planning, &plan, args).await?;
+        return Ok(Exit::Success);
+    }
+
+    let prepared = if args.resume {
+        resume_plan(&path)?
+    } else if let Some(map) = &args.apply_map {
+        if sources.len() > 1 {
+            anyhow::bail!("--apply-map takes paths from a single directory");
+        }
+        Some(mapping::load(map, args.map_columns.as_ref(), &sources[0], &path)?)
+    } else {
+        None
+    };
+    if args.resume && prepared.is_none() {
+        status!("Nothing to resume, the last journaled run finished");
+    }
+
+    let run_id = journal::new_run_id();
+    let started = Local::now();
+    let identity = Identity::current();
+    let journal = if (args.journal || args.resume) && !args.dry_run {
+        Some(Arc::new(Journal::open(&path, &run_id, &identity)?))
+    } else {
+        None
+    };
+
+    let catalog = match &args.catalog {
+        Some(catalog) if !args.dry_run => Some(Arc::new(Catalog::open(catalog, &run_id)?)),
+        _ => None,
+    };
+
+    let (plan, mut stats) = organize_directory(&path, &sources, args, &rules, prepared, journal, catalog).await?;
+    if let Some(command) = args.on_complete.as_deref().filter(|_| !args.dry_run) {
+        let counts = [("moved", stats.moved), ("skipped", stats.skipped), ("errors", stats.errors)];
+        if let Err(e) = hooks::run_on_complete(command, &run_id, &path, counts).await {
+            stderr!("❌ Hook failed: {:#}", e);
+            stats.errors += 1;
+        }
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            stdout!("\n📊 Summary:");
+            stdout!("  Files moved: {}", stats.moved);
+            stdout!("  Files skipped: {}", stats.skipped);
+            if stats.kept > 0 {
+                stdout!("  Kept in place: {}", stats.kept);
+            }
+            if args.owner.is_some() || args.group.is_some() {
+                stdout!("  Owned by others: {}", stats.not_owned);
+            }
+            stdout!("  Errors: {}", stats.errors);
+            if exit::interrupted() {
+                if args.journal || args.resume {
+                    stdout!("  🛑 Interrupted before finishing; run again with --resume to continue");
+                } else {
+                    stdout!("  🛑 Interrupted before finishing; run again to continue");
+                }
+            }
+            if let Some((why, left)) = &stats.low_space {
+                stdout!("  🛑 Stopped with {} moves left: {}", left, why);
+                stdout!("     Moved {} entries ({}) before stopping", stats.moved, format_size(stats.moved_bytes));
+            }
+            if !args.dry_run {
+                stdout!("  Data moved: {} in {:.1?} ({}/s)",
+                    format_size(stats.moved_bytes), stats.elapsed, format_size(stats.throughput()));
+            }
+            if stats.copied > 0 && !args.copy {
+                stdout!("  ⚠️  Copied across filesystems: {} ({})",
+                    stats.copied, format_size(stats.copied_bytes));
+            }
+            if stats.deduplicated > 0 {
+                stdout!("  Duplicates removed: {} ({} reclaimed)",
+                    stats.deduplicated, format_size(stats.reclaimed_bytes));
+            }
+            if stats.deleted > 0 {
+                stdout!("  Empty files deleted: {}", stats.deleted);
+            }
+            if args.prune_empty && !args.dry_run {
+                stdout!("  Empty directories removed: {}", stats.pruned);
+            }
+            if !stats.buckets.is_empty() {
+                stdout!("\n📅 Per bucket:");
+                for bucket in &stats.buckets {
+                    stdout!("{}", bucket.line());
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let mut summary = summary_json(&run_id, &identity, &path, &sources, args, &stats);
+            if args.dry_run {
+                if let Value::Object(fields) = &mut summary {
+                    fields.push(("plan".to_string(), plan.to_json()));
+                }
+            }
+            output::flush();
+            println!("{}", summary);
+        }
+    }
+
+    if (args.journal || args.resume) && !args.dry_run {
+        let mut run = summary_json(&run_id, &identity, &path, &sources, args, &stats);
+        if let Value::Object(fields) = &mut run {
+            let failures = stats.failures.iter().map(|(path, error)| {
+                json::object([("path", path.as_path().into()), ("error", error.as_str().into())])
+            });
+            fields.push(("failures".to_string(), Value::Array(failures.collect())));
+        }
+        if let Err(e) = history::record(&path, run, started) {
+            stderr!("⚠️  Could not record the run in its history: {:#}", e);
+        }
+    }
+
+    if args.assert_idempotent && !exit::interrupted() {
+        if stats.errors > 0 {
+            anyhow::bail!("Idempotency check not run: {} errors occurred", stats.errors);
+        }
+        status!();
+        assert_idempotent(&path, &sources, args, &rules).await?;
+    }
+
+    metrics::record_run(stats.moved, stats.skipped, stats.errors, stats.moved_bytes);
+    if !args.notify.is_empty() {
+        let headline = if stats.errors > 0 {
+            format!("{} errors organizing {}", stats.errors, path.display())
+        } else {
+            format!("Organized {} entries in {}", stats.moved, path.display())
+        };
+        let summary = summary_json(&run_id, &identity, &path, &sources, args, &stats);
+        notify::send(&args.notify, &summary, &headline).await;
+    }
+    if args.email.is_enabled() {
+        let subject = if stats.errors > 0 {
+            format!("chronoban: {} errors organizing {}", stats.errors, path.display())
+        } else {
+            format!("chronoban: organized {} entries in {}", stats.moved, path.display())
+        };
+        email::send(&args.email, &subject, &summary_text(&run_id, &identity, &path, &stats)).await;
+    }
+
+    let conflicts = if args.strict { plan.conflicts().count() } else { 0 };
+    let left = stats.low_space.as_ref().map_or(0, |(_, left)| *left);
+    Ok(Exit::from_failures(stats.errors + conflicts + left))
+}
+
+/// `dir` canonicalized, provided it is a directory.
+async fn existing_dir(dir: &Path) -> Result<PathBuf> {
+    let path = tokio::fs::canonicalize(dir)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", dir))?;
+    if !path.is_dir() {
+        anyhow::bail!("Path must be a directory: {:?}", path);
+    }
+    Ok(path)
+}
+
+struct Stats {
+    moved: usize,
+    skipped: usize,
+    /// Entries pinned in place.
+    kept: usize,
+    /// Entries `--owner` or `--group` left alone.
+    not_owned: usize,
+    errors: usize,
+    /// Moves that degraded to copy+delete because they crossed filesystems.
+    copied: usize,
+    copied_bytes: u64,
+    /// Duplicates hard-linked or deleted instead of moved.
+    deduplicated: usize,
+    reclaimed_bytes: u64,
+    /// Empty files deleted under `--empty delete`.
+    deleted: usize,
+    /// Directories left empty and removed under `--prune-empty`.
+    pruned: usize,
+    buckets: Vec<BucketStats>,
+    /// Each entry that failed to move, and why.
+    failures: Vec<(PathBuf, String)>,
+    /// Size of everything moved; directories moved by rename count their
+    /// own entry size unless a size limit made chronoban measure them.
+    moved_bytes: u64,
+    elapsed: Duration,
+    /// Why `--stop-at-free` stopped the run, and how many moves it left.
+    low_space: Option<(String, usize)>,
+}
+
+/// What a finished task did with its entry.
+enum Outcome {
+    DryRun,
+    Moved(MoveMethod),
+    Deduplicated(u64),
+    /// Sent to a remote destination, with the bytes uploaded.
+    Uploaded(u64),
+    Deleted,
+    /// A sidecar left behind because its primary didn't move.
+    Stayed,
+    /// Gone or different from what was planned, so left alone.
+    Changed(&'static str),
+}
+
+impl Outcome {
+    /// What `--report` calls it.
+    fn action(&self, copy: bool) -> &'static str {
+        match self {
+            Outcome::DryRun => "planned",
+            Outcome::Moved(_) if copy => "copied",
+            Outcome::Moved(_) => "moved",
+            Outcome::Deduplicated(_) => "deduplicated",
+            Outcome::Uploaded(_) => "uploaded",
+            Outcome::Deleted => "deleted",
+            Outcome::Stayed | Outcome::Changed(_) => "skipped",
+        }
+    }
+}
+
+impl Stats {
+    /// Average bytes per second over the execution phase.
+    fn throughput(&self) -> u64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            (self.moved_bytes as f64 / secs) as u64
+        } else {
+            0
+        }
+    }
+
+    /// Count a finished move task, returning whether it succeeded.
+    fn record(&mut self, result: Result<Result<Outcome>, tokio::task::JoinError>, size: u64) -> bool {
+        match result {
+            Ok(Ok(Outcome::Deduplicated(bytes))) => {
+                self.deduplicated += 1;
+                self.reclaimed_bytes += bytes;
+                true
+            }
+            Ok(Ok(Outcome::Deleted)) => {
+                self.deleted += 1;
+                true
+            }
+            Ok(Ok(Outcome::Uploaded(bytes))) => {
+                self.moved += 1;
+                self.moved_bytes += bytes;
+                true
+            }
+            Ok(Ok(Outcome::Stayed | Outcome::Changed(_))) => {
+                self.skipped += 1;
+                false
+            }
+            Ok(Ok(outcome)) => {
+                self.moved += 1;
+                if let Outcome::Moved(MoveMethod::Copied { bytes, .. }) = outcome {
+                    self.copied += 1;
+                    self.copied_bytes += bytes;
+                    self.moved_bytes += bytes;
+                } else {
+                    self.moved_bytes += size;
+                }
+                true
+            }
+            Ok(Err(e)) => {
+                stderr!("❌ Error: {:#}", e);
+                self.errors += 1;
+                false
+            }
+            Err(e) => {
+                stderr!("❌ Task error: {}", e);
+                self.errors += 1;
+                false
+            }
+        }
+    }
+}
+
+/// The summary as mailed by `--email-report`, with every error.
+fn summary_text(run_id: &str, identity: &Identity, base: &Path, stats: &Stats) -> String {
+    let mut text = format!("Run {} on {} organizing {}\n\n", run_id, identity.host, base.display());
+    text.push_str(&format!("Files moved: {}\nFiles skipped: {}\nErrors: {}\nData moved: {} in {:.1?}\n",
+        stats.moved, stats.skipped, stats.errors, format_size(stats.moved_bytes), stats.elapsed));
+    if exit::interrupted() {
+        text.push_str("Interrupted before finishing\n");
+    }
+    if !stats.failures.is_empty() {
+        text.push_str("\nErrors:\n");
+        for (path, error) in &stats.failures {
+            text.push_str(&format!("  {}: {}\n", path.display(), error));
+        }
+    }
+    if !stats.buckets.is_empty() {
+        text.push_str("\nPer bucket:\n");
+        for bucket in &stats.buckets {
+            text.push_str(&format!("{}\n", bucket.line()));
+        }
+    }
+    text
+}
+
+fn summary_json(
+    run_id: &str,
+    identity: &Identity,
+    base: &Path,
+    sources: &[PathBuf],
+    args: &Args,
+    stats: &Stats,
+) -> Value {
+    json::object([
+        ("schema_version", SCHEMA_VERSION.into()),
+        ("run_id", run_id.into()),
+        ("base", base.into()),
+        ("sources", Value::Array(sources.iter().map(|s| s.as_path().into()).collect())),
+        ("dry_run", args.dry_run.into()),
+        ("interrupted", exit::interrupted().into()),
+        ("stopped_low_space", stats.low_space.as_ref().map(|(why, _)| why.as_str()).into()),
+        ("moved", stats.moved.into()),
+        ("skipped", stats.skipped.into()),
+        ("kept", stats.kept.into()),
+        ("not_owned", stats.not_owned.into()),
+        ("errors", stats.errors.into()),
+        ("copied_across_devices", stats.copied.into()),
+        ("copied_bytes", stats.copied_bytes.into()),
+        ("deduplicated", stats.deduplicated.into()),
+        ("reclaimed_bytes", stats.reclaimed_bytes.into()),
+        ("deleted_empty", stats.deleted.into()),
+        ("pruned_dirs", stats.pruned.into()),
+        ("bytes_moved", stats.moved_bytes.into()),
+        ("elapsed_secs", stats.elapsed.as_secs_f64().into()),
+        ("bytes_per_sec", stats.throughput().into()),
+        ("buckets", Value::Array(stats.buckets.iter().map(BucketStats::to_json).collect())),
+        ("mover", identity.to_json()),
+    ])
+}
+
+/// Processing order of planned moves, so limited runs work through the
+/// backlog predictably instead of in readdir order.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Order {
+    Oldest,
+    Newest,
+}
+
+/// A fixed order for `--sort`, so output can be compared between runs.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Sort {
+    /// By source path
+    Name,
+    /// By date, then source path
+    Time,
+}
+
+/// Which kinds of top-level entries get organized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Only {
+    Files,
+    Dirs,
+    Both,
+}
+
+/// How symbolic links are organized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Symlinks {
+    /// Leave them where they are
+    Skip,
+    /// Move the link itself, rewriting a relative target to stay correct
+    Move,
+    /// Put a copy of what the link points to in the bucket, in place of
+    /// the link
+    Follow,
+}
+
+/// What happens to zero-byte files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum EmptyFiles {
+    /// Bucket them like any other file
+    Organize,
+    /// Leave them where they are
+    Skip,
+    /// Delete them
+    Delete,
+    /// Move them into .chronoban/quarantine
+    Quarantine,
+}
+
+/// What happens to entries dated outside `--min-date` and `--max-date`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Implausible {
+    /// Leave them where they are, or move them into --unknown-dir
+    Skip,
+    /// Move them into .chronoban/quarantine
+    Quarantine,
+    /// Date them by the first --date-fallback provider with a plausible date
+    Fallback,
+}
+
+/// What happens to entries dated after the reference time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum FutureDates {
+    /// Organize them by their date, whatever the minimum age
+    Organize,
+    /// Leave them where they are
+    Skip,
+    /// Organize them as if dated at the reference time
+    Now,
+}
+
+/// Where a directory's date comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DirTime {
+    /// The directory's own timestamp
+    #[value(name = "self")]
+    Own,
+    /// The newest file anywhere inside it
+    Newest,
+    /// The oldest file anywhere inside it
+    Oldest,
+}
+
+impl Only {
+    fn includes(self, metadata: &std::fs::Metadata) -> bool {
+        match self {
+            Only::Files => !metadata.is_dir(),
+            Only::Dirs => metadata.is_dir(),
+            Only::Both => true,
+        }
+    }
+}
+
+/// Everything derived from the arguments and config file that decides where
+/// an entry goes.
+struct Rules {
+    layout: Layout,
+    router: Router,
+    /// `--keep` patterns.
+    keep: Patterns,
+    hooks: BucketHooks,
+    /// Date provider chains from the config file.
+    dates: Arc<DateChains>,
+    /// Where buckets are uploaded instead, with a remote `--dest`.
+    remote: Option<Arc<Remote>>,
+}
+
+/// How a destination is derived: a date-format bucket plus route subfolders,
+/// or a full template, which takes the place of both.
+#[derive(Clone)]
+enum Layout {
+    Format {
+        format: BucketFormat,
+        /// Quarter and year buckets may exist alongside month buckets.
+        coalesced: bool,
+    },
+    Template(Template),
+}
+
+impl Layout {
+    fn is_bucket_name(&self, name: &str) -> bool {
+        if is_granularity_bucket_name(name) {
+            return true;
+        }
+        match self {
+            Layout::Format { format, coalesced } => {
+                format.is_bucket_name(name) || (*coalesced && format.is_coarse_name(name))
+            }
+            Layout::Template(template) => template.is_bucket_name(name),
+        }
+    }
+}
+
+impl Rules {
+    fn new(args: &Args, config: Config) -> Rules {
+        let mut routes = args.routes.clone();
+        routes.extend(config.routes);
+        let layout = match &args.template {
+            Some(template) => Layout::Template(template.clone()),
+            None => Layout::Format {
+                format: args.format.clone(),
+                coalesced: args.auto_coalesce.is_some(),
+            },
+        };
+        Rules {
+            layout,
+            router: Router::new(routes),
+            keep: Patterns::new(args.keep.iter().map(String::as_str)),
+            hooks: config.hooks,
+            dates: Arc::new(DateChains::new(&config.dates, args.preset)),
+            remote: None,
+        }
+    }
+}
+
+async fn organize_directory(
+    base_path: &Path,
+    sources: &[PathBuf],
+    args: &Args,
+    rules: &Rules,
+    prepared: Option<Plan>,
+    journal: Option<Arc<Journal>>,
+    catalog: Option<Arc<Catalog>>,
+) -> Result<(Plan, Stats)> {
+    let mut plan = match prepared {
+        Some(plan) => plan,
+        None => plan_directory(base_path, sources, args, rules).await?,
+    };
+    if args.review {
+        plan = tui::review(plan)?;
+    }
+    if args.marker != MarkerKind::None {
+        let buckets: BTreeSet<_> = plan.moves().map(|m| &m.bucket).collect();
+        for bucket in buckets.into_iter().filter(|b| is_bucket(base_path, b)) {
+            marker::warn_if_foreign(bucket);
+        }
+    }
+    if rules.remote.is_none() {
+        preflight::check_space(&plan, base_path, args.copy, args.dry_run).await?;
+    }
+    if args.atomic {
+        preflight::check_atomic(&plan)?;
+    }
+    // Buckets that don't exist yet, which a rollback removes again
+    let created: BTreeSet<PathBuf> = if args.atomic {
+        plan.moves()
+            .flat_map(|entry| entry.destination.ancestors().skip(1))
+            .filter(|dir| *dir != base_path && dir.starts_with(base_path) && !dir.exists())
+            .map(Path::to_path_buf)
+            .collect()
+    } else {
+        BTreeSet::new()
+    };
+    if let Some(journal) = &journal {
+        journal.record_plan(&plan)?;
+        for entry in plan.conflicts() {
+            if let Some(conflict) = entry.conflict {
+                journal.record_conflict(&entry.source, &entry.destination, conflict)?;
+            }
+        }
+    }
+    let mut report = args.report.as_ref().map(|_| Report::default());
+    if let Some(report) = &mut report {
+        for entry in plan.conflicts() {
+            let why = entry.conflict.map(|c| c.as_str().to_string());
+            report.add(&entry.source, "skipped", Some(&entry.destination), entry.date_source, entry.size, why);
+        }
+    }
+    let mut stats =
+        execute_plan(base_path, &plan, args, rules, journal.clone(), catalog, report.as_mut()).await?;
+    if let (true, false, Some(journal)) = (args.atomic, args.dry_run, &journal) {
+        if stats.errors > 0 || exit::interrupted() {
+            let undone = roll_back(journal, &created, args).await?;
+            let why = if stats.errors > 0 { format!("{} moves failed", stats.errors) } else { "Interrupted".to_string() };
+            anyhow::bail!("{}, so --atomic put back all {} moves made; the directory is as it was", why, undone);
+        }
+    }
+    if args.prune_empty && !args.dry_run {
+        stats.pruned = prune_empty_dirs(&plan, sources).await;
+    }
+    if let (Some(report), Some(file)) = (report, &args.report) {
+        report.write(file)?;
+        status!("📝 Wrote report: {}", file.display());
+    }
+    if let Some(journal) = &journal {
+        if !exit::interrupted() {
+            journal.record_finish()?;
+        }
+    }
+    Ok((plan, stats))
+}
+
+/// Undo this run's journaled moves, newest first, and remove the buckets it
+/// created. Returns how many moves were undone.
+async fn roll_back(journal: &Journal, created: &BTreeSet<PathBuf>, args: &Args) -> Result<usize> {
+    let moves = journal.moves()?;
+    status!("⏪ Rolling back {} moves", moves.len());
+    let retry = Retry { retries: args.retries, delay: args.retry_delay };
+    let mut failed = Vec::new();
+    for (source, target) in moves.iter().rev() {
+        let undone = if args.copy {
+            remove_any(target).await.map_err(anyhow::Error::from)
+        } else {
+            let is_link = fs::symlink_metadata(target).await.is_ok_and(|m| m.is_symlink());
+            match move_entry(target, source, retry, false).await {
+                Ok(_) if is_link => retarget_symlink(target, source).map_err(anyhow::Error::from),
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            }
+        };
+        match undone {
+            Ok(()) => {
+                debug!("⏪ {} -> {}", target.display(), source.display());
+                journal.record_rollback(source, target)?;
+            }
+            Err(e) => failed.push(format!("{}: {:#}", target.display(), e)),
+        }
+    }
+    // Deepest first, so each is empty by the time it comes up
+    for dir in created.iter().rev() {
+        marker::remove_if_only(dir);
+        let _ = fs::remove_dir(dir).await;
+    }
+    // A rolled-back run is over; --resume must not pick it up again
+    journal.record_finish()?;
+    if !failed.is_empty() {
+        anyhow::bail!("--atomic could not put back {} of {} moves:\n  {}", failed.len(), moves.len(),
+            failed.join("\n  "));
+    }
+    Ok(moves.len())
+}
+
+/// Remove the directories the plan's moves emptied, deepest first, and
+/// then each parent that became empty in turn, up to but not including the
+/// source directory. Returns how many were removed.
+async fn prune_empty_dirs(plan: &Plan, sources: &[PathBuf]) -> usize {
+    let mut dirs: Vec<(&Path, &Path)> = plan
+        .moves()
+        .filter_map(|entry| {
+            let source = sources.iter().filter(|s| entry.source.starts_with(s)).max_by_key(|s| s.as_os_str().len())?;
+            Some((entry.source.parent()?, source.as_path()))
+        })
+        .collect();
+    dirs.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+    dirs.dedup();
+
+    let mut removed = 0;
+    for (dir, source) in dirs {
+        let mut current = Some(dir);
+        while let Some(dir) = current.filter(|d| d.starts_with(source) && *d != source) {
+            if fs::remove_dir(dir).await.is_err() {
+                break;
+            }
+            debug!("🧹 Removed empty directory: {}", dir.display());
+            removed += 1;
+            current = dir.parent();
+        }
+    }
+    if removed > 0 {
+        status!("🧹 Removed {} empty directories", removed);
+    }
+    removed
+}
+
+/// Whether `dir` is a bucket chronoban manages: not the base itself, nor
+/// anything in its state directory such as the quarantine.
+fn is_bucket(base_path: &Path, dir: &Path) -> bool {
+    dir != base_path && !dir.starts_with(base_path.join(STATE_DIR))
+}
+
+/// What is left of an interrupted run's plan: the moves it neither
+/// finished nor lost the source of since. Conflicts and errors were
+/// reported by that run already.
+fn resume_plan(base_path: &Path) -> Result<Option<Plan>> {
+    let Some(run) = journal::unfinished_run(base_path)? else {
+        return Ok(None);
+    };
+    let mut plan = Plan::new(base_path.to_path_buf());
+    for entry in run.plan.entries {
+        if entry.conflict.is_some() || run.done.contains(&entry.source) {
+            continue;
+        }
+        if std::fs::symlink_metadata(&entry.source).is_err() {
+            debug!("⏭️  Gone since the interrupted run: {}", entry.source.display());
+            plan.skipped += 1;
+            continue;
+        }
+        plan.entries.push(entry);
+    }
+    status!("⏯️  Resuming run {}: {} moves left", run.run_id, plan.entries.len());
+    Ok(Some(plan))
+}
+
+/// Plan moving everything in `sources` into buckets under `base_path`.
+async fn plan_directory(base_path: &Path, sources: &[PathBuf], args: &Args, rules: &Rules) -> Result<Plan> {
+    let _span = trace::span("plan", "plan");
+    let mut plan = Plan::new(base_path.to_path_buf());
+    let metadata_source = args.time.source();
+
+    let min_age = args.min_age.unwrap_or(Duration::from_secs(args.min_age_days * 24 * 60 * 60));
+    // Taken once, so every entry's age is measured from the same moment
+    // however long planning takes
+    let now = args.now.unwrap_or_else(SystemTime::now);
+    let this_month = DateTime::<Local>::from(now).with_day(1).map(|first| first.date_naive());
+    let plausible =
+        |time: SystemTime| args.min_date.is_none_or(|min| time >= min) && args.max_date.is_none_or(|max| time <= max);
+    let source_atimes: Vec<_> = sources
+        .iter()
+        .map(|s| std::fs::metadata(s).and_then(|m| m.accessed()).ok())
+        .collect();
+
+    // Entries that could not be classified, which --strict refuses to run past
+    let mut unhandled = Vec::new();
+
+    let mut candidates = Vec::new();
+    for source in sources {
+        let _walk = trace::span_for("walk", "plan", source);
+        let ignore = Patterns::load(source)?;
+        let mut found = if args.recursive {
+            walk_candidates(source, base_path, args, rules, ignore, &mut plan, &mut unhandled).await
+        } else {
+            top_level_candidates(source, base_path, args, rules, &ignore, &mut plan).await?
+        };
+        if let Some(n) = args.keep_latest {
+            keep_latest(&mut found, n, args.time, &mut plan);
+        }
+        candidates.extend(found.into_iter().map(|path| (source.as_path(), path)));
+    }
+    let mut placed = Vec::with_capacity(candidates.len());
+    // Empty files under --empty, kept out of the buckets and coalescing
+    let mut quarantined = Vec::new();
+    let mut deletions = Vec::new();
+
+    for (source, path) in candidates {
+        if exit::interrupted() {
+            break;
+        }
+        let reading = trace::span_for("metadata", "plan", &path);
+        let metadata = match fs::symlink_metadata(&path).await {
+            Ok(m) => m,
+            Err(e) => {
+                stderr!("❌ Error reading metadata for {:?}: {}", path, e);
+                plan.errors += 1;
+                unhandled.push((path, e.to_string()));
+                continue;
+            }
+        };
+        let metadata = match args.symlinks {
+            _ if !metadata.is_symlink() => metadata,
+            Symlinks::Move => metadata,
+            Symlinks::Skip => {
+                debug!("⏭️  Symlink (--symlinks skip): {}", path.display());
+                plan.skipped += 1;
+                continue;
+            }
+            // Dated and typed by what the link points to
+            Symlinks::Follow => match fs::metadata(&path).await {
+                Ok(m) => m,
+                Err(e) => {
+                    stderr!("⚠️  Can't follow symlink, skipping: {}: {}", path.display(), e);
+                    plan.skipped += 1;
+                    continue;
+                }
+            },
+        };
+        if !args.only.includes(&metadata) {
+            debug!("⏭️  Not selected by --only: {}", path.display());
+            plan.skipped += 1;
+            continue;
+        }
+        if !owned_by(&metadata, args.owner, args.group) {
+            debug!("⏭️  Owned by someone else: {}", path.display());
+            plan.not_owned += 1;
+            continue;
+        }
+        if let Some(kind) = special_kind(metadata.file_type()) {
+            if !args.include_special {
+                debug!("⏭️  Special file ({}), use --include-special to move it: {}", kind, path.display());
+                unhandled.push((path, format!("unsupported file type ({})", kind)));
+                plan.skipped += 1;
+                continue;
+            }
+        }
+
+        drop(reading);
+
+        let dating = trace::span_for("date", "plan", &path);
+        let chained = if metadata.is_file() {
+            let relative = path.strip_prefix(source).unwrap_or(&path);
+            chain_date(rules, relative, &path, &metadata, args.restore_atime).await
+        } else {
+            None
+        };
+        let (mut file_time, mut date_source, dates_conflict) = match chained {
+            // The config's order decides; there is nothing to disagree with
+            Some((time, source)) => (time, source, false),
+            None => {
+                let file_time = match args.time.of(&metadata) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        stderr!("❌ Error reading timestamp for {:?}: {}", path, e);
+                        plan.errors += 1;
+                        unhandled.push((path, e.to_string()));
+                        continue;
+                    }
+                };
+                let file_time = match args.dir_time {
+                    DirTime::Newest | DirTime::Oldest if metadata.is_dir() => {
+                        content_time(&path, args.time, args.dir_time == DirTime::Newest)
+                            .await
+                            .unwrap_or(file_time)
+                    }
+                    _ => file_time,
+                };
+                let embedded = if args.embedded_dates && metadata.is_file() {
+                    read_embedded_date(&path, metadata.accessed().ok(), args.restore_atime).await
+                } else {
+                    None
+                };
+                let (file_time, metadata_source) = embedded.unwrap_or((file_time, metadata_source));
+                if args.implausible == Implausible::Fallback && !plausible(file_time) {
+                    // The fallback decides, so there is nothing to disagree with
+                    (file_time, metadata_source, false)
+                } else {
+                    resolve_date(&path, file_time, metadata_source, args)
+                }
+            }
+        };
+        drop(dating);
+
+        // Where an entry without a usable date goes instead of a bucket
+        let mut set_aside = None;
+        if !plausible(file_time) {
+            let shown = DateTime::<Local>::from(file_time).format("%Y-%m-%d");
+            match args.implausible {
+                Implausible::Skip if args.unknown_dir.is_none() => {
+                    stderr!("⚠️  Implausible date {}, skipping: {}", shown, path.display());
+                    plan.skipped += 1;
+                    continue;
+                }
+                Implausible::Quarantine => {
+                    stderr!("⚠️  Implausible date {}, quarantining: {}", shown, path.display());
+                    set_aside = Some(quarantine(base_path, &path));
+                }
+                Implausible::Skip => {
+                    stderr!("⚠️  Implausible date {}, setting aside: {}", shown, path.display());
+                    set_aside = Some(unknown_date(base_path, source, &path, args));
+                }
+                Implausible::Fallback => match fallback_date(&path, &metadata, args, plausible) {
+                    Some((time, source)) => {
+                        verbose!("📅 Implausible date {}, dated by {} instead: {}", shown, source.as_str(),
+                            path.display());
+                        (file_time, date_source) = (time, source);
+                    }
+                    None if args.unknown_dir.is_some() => {
+                        stderr!("⚠️  Implausible date {} and no plausible fallback, setting aside: {}", shown,
+                            path.display());
+                        set_aside = Some(unknown_date(base_path, source, &path, args));
+                    }
+                    None => {
+                        stderr!("⚠️  Implausible date {} and no plausible fallback, skipping: {}", shown, path.display());
+                        plan.skipped += 1;
+                        continue;
+                    }
+                },
+            }
+        }
+
+        match now.duration_since(file_time) {
+            Ok(age) if age < min_age => {
+                debug!("⏭️  Too new ({} old, minimum {}): {}", format_age(age), format_age(min_age), path.display());
+                plan.skipped += 1;
+                continue;
+            }
+            Ok(age) if args.max_age.is_some_and(|max| age > max) => {
+                debug!("⏭️  Too old ({} old, --max-age {}): {}",
+                    format_age(age), format_age(args.max_age.unwrap()), path.display());
+                plan.skipped += 1;
+                continue;
+            }
+            Ok(_) => {}
+            Err(ahead) => match args.future {
+                FutureDates::Organize => {
+                    verbose!("🔮 Dated {} in the future: {}", format_age(ahead.duration()), path.display());
+                }
+                FutureDates::Skip => {
+                    debug!("⏭️  Dated {} in the future: {}", format_age(ahead.duration()), path.display());
+                    plan.skipped += 1;
+                    continue;
+                }
+                FutureDates::Now => {
+                    verbose!("🔮 Dated {} in the future, organizing as of now: {}",
+                        format_age(ahead.duration()), path.display());
+                    file_time = now;
+                }
+            },
+        }
+        if args.complete_months_only
+            && this_month.is_some_and(|month| DateTime::<Local>::from(file_time).date_naive() >= month)
+        {
+            debug!("⏭️  Month still open: {}", path.display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        if metadata.is_file() && metadata.len() == 0 && args.empty != EmptyFiles::Organize {
+            match args.empty {
+                EmptyFiles::Skip => {
+                    debug!("⏭️  Empty file: {}", path.display());
+                    plan.skipped += 1;
+                }
+                EmptyFiles::Delete => deletions.push(PlanEntry {
+                    source: path.clone(),
+                    destination: path,
+                    bucket: base_path.to_path_buf(),
+                    date: file_time,
+                    date_source,
+                    size: 0,
+                    modified: metadata.modified().ok(),
+                    accessed: metadata.accessed().ok(),
+                    inode: inode(&metadata),
+                    conflict: None,
+                    duplicate_of: None,
+                    sidecar_of: None,
+                }),
+                _ => {
+                    let (bucket, target) = quarantine(base_path, &path);
+                    quarantined.push(Placed {
+                        path,
+                        metadata,
+                        date: file_time,
+                        date_source,
+                        dates_conflict,
+                        bucket,
+                        target,
+                        sidecar_of: None,
+                    });
+                }
+            }
+            continue;
+        }
+        if let Some((bucket, target)) = set_aside {
+            quarantined.push(Placed {
+                path,
+                metadata,
+                date: file_time,
+                date_source,
+                dates_conflict,
+                bucket,
+                target,
+                sidecar_of: None,
+            });
+            continue;
+        }
+
+        // Convert to DateTime
+        let datetime: DateTime<Local> = file_time.into();
+
+        let detected = if args.detect_type && metadata.is_file() {
+            sniff(&path, metadata.accessed().ok(), args.restore_atime).await
+        } else {
+            None
+        };
+
+        // Create target path
+        let (bucket, target) = match &rules.layout {
+            Layout::Format { format, .. } => {
+                let bucket = base_path.join(format.render(&datetime));
+                let mut target_dir = bucket.clone();
+                if metadata.is_file() {
+                    if let Some(folder) = rules.router.folder_for(&path, detected.as_ref()) {
+                        target_dir.push(folder);
+                    }
+                }
+                let target = if args.keep_structure {
+                    target_dir.join(path.strip_prefix(source).unwrap())
+                } else {
+                    target_dir.join(path.file_name().unwrap())
+                };
+                (bucket, target)
+            }
+            Layout::Template(template) => {
+                let extension = match detected {
+                    Some(t) => Some(t.extension.to_string()),
+                    None if metadata.is_file() => path
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .map(|e| canonical_extension(&e.to_ascii_lowercase()).to_string()),
+                    None => None,
+                };
+                let category = detected
+                    .map(|t| t.category)
+                    .or_else(|| extension.as_deref().and_then(Category::from_extension));
+                let owner = if template.needs_owner() { owner_name(&metadata) } else { None };
+                let camera = if template.needs_camera() && metadata.is_file() {
+                    read_camera(&path, metadata.accessed().ok(), args.restore_atime).await
+                } else {
+                    None
+                };
+                let relative = template.render(&template::Fields {
+                    path: &path,
+                    datetime: &datetime,
+                    extension: extension.as_deref(),
+                    category,
+                    mime: detected.map(|t| t.mime),
+                    owner: owner.as_deref(),
+                    camera: camera.as_deref(),
+                });
+                // Progress is grouped by the template's first directory level
+                let bucket = match relative.components().count() {
+                    1 => base_path.to_path_buf(),
+                    _ => base_path.join(relative.iter().next().unwrap()),
+                };
+                (bucket, base_path.join(relative))
+            }
+        };
+
+        // Names Windows can't create are changed rather than failing to move
+        let target = if cfg!(windows) { winpath::sanitize(base_path, target) } else { target };
+        let target = match args.normalize {
+            Some(form) => unicode::normalize_path(base_path, target, form),
+            None => target,
+        };
+
+        placed.push(Placed {
+            path,
+            metadata,
+            date: file_time,
+            date_source,
+            dates_conflict,
+            bucket,
+            target,
+            sidecar_of: None,
+        });
+    }
+
+    if args.strict && !unhandled.is_empty() {
+        stderr!("❌ {} entries could not be classified:", unhandled.len());
+        for (path, reason) in &unhandled {
+            stderr!("  {}: {}", path.display(), reason);
+        }
+        anyhow::bail!("Refusing to move anything with unclassified entries (--strict)");
+    }
+
+    if let (Some(min_files), Layout::Format { format, .. }) = (args.auto_coalesce, &rules.layout) {
+        coalesce(base_path, format, min_files, &mut placed);
+    }
+    if args.sidecars {
+        placed = attach_sidecars(placed);
+    }
+    if let (true, Layout::Format { .. }) = (args.events, &rules.layout) {
+        group_events(args.gap, &mut placed);
+    }
+    placed.extend(quarantined);
+
+    let mut destinations = HashSet::new();
+    let mut duplicates = DuplicateFinder::new(args.restore_atime);
+    // Under --normalize, names that differ only in normalization collide
+    let mut existing = args.normalize.map(|_| ExistingNames::default());
+
+    for Placed { path, metadata, date: file_time, date_source, dates_conflict, bucket, target, sidecar_of } in placed {
+        // Already where it belongs
+        if target == path {
+            debug!("⏭️  Already in place: {}", path.display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        let key = if args.normalize.is_some() { equivalence_key(&target) } else { target.clone() };
+        let equivalent_exists =
+            !target.exists() && existing.as_mut().is_some_and(|names| names.contains_equivalent(&target));
+        let target_taken = target.exists() || equivalent_exists || !destinations.insert(key);
+        let duplicate_of = match args.dedupe {
+            Some(_) if metadata.is_file() => duplicates.find(&bucket, &path, metadata.len()).await,
+            _ => None,
+        };
+        if let Some(existing) = &duplicate_of {
+            status!("🔁 Duplicate of {}: {}", existing.display(), path.display());
+        }
+        // Removing a duplicate whose identical copy is the target itself is
+        // fine; otherwise an occupied target is a conflict.
+        let replaces_target = duplicate_of.as_ref() == Some(&target)
+            && matches!(args.dedupe, Some(DedupeMode::Hardlink | DedupeMode::Delete));
+
+        // The entry stays in the plan either way, so conflicts are visible
+        // to anything reading it
+        let conflict = if dates_conflict {
+            Some(Conflict::ConflictingDates)
+        } else if duplicate_of.is_some() && args.dedupe == Some(DedupeMode::Skip) {
+            Some(Conflict::Duplicate)
+        } else if equivalent_exists {
+            stderr!("⚠️  Target already exists in another Unicode normalization, skipping: {} -> {}",
+                path.display(), target.display());
+            Some(Conflict::TargetExists)
+        } else if target_taken && !replaces_target {
+            stderr!("⚠️  Target already exists, skipping: {} -> {}",
+                path.display(), target.display());
+            Some(Conflict::TargetExists)
+        } else {
+            None
+        };
+
+        let mut size = metadata.len();
+        if metadata.is_dir() && conflict.is_none() {
+            if let Some(limit) = args.max_dir_size {
+                size = dir_size(&path).await;
+                if size > limit && !confirm_large_dir(&path, size, limit) {
+                    debug!("⏭️  Over --max-dir-size, not confirmed: {}", path.display());
+                    plan.skipped += 1;
+                    continue;
+                }
+            }
+        }
+
+        verbose!("📝 {}: {} {} -> {}", path.display(), date_source.as_str(),
+            DateTime::<Local>::from(file_time).format("%Y-%m-%d %H:%M:%S"), target.display());
+
+        plan.entries.push(PlanEntry {
+            source: path,
+            destination: target,
+            bucket,
+            date: file_time,
+            date_source,
+            size,
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            inode: inode(&metadata),
+            conflict,
+            duplicate_of,
+            sidecar_of,
+        });
+    }
+
+    plan.entries.extend(deletions);
+
+    if args.restore_atime {
+        for (source, atime) in sources.iter().zip(source_atimes) {
+            if let Some(atime) = atime {
+                restore_atime(source, atime);
+            }
+        }
+    }
+
+    if args.skip_open {
+        skip_open_files(&mut plan);
+    }
+
+    if let Some(window) = args.settle {
+        settle(&mut plan, window).await;
+    }
+
+    if args.sidecars {
+        sidecar::keep_together(&mut plan);
+    }
+
+    let (mut moves, mut conflicts): (Vec<_>, Vec<_>) =
+        plan.entries.drain(..).partition(|e| e.conflict.is_none());
+
+    match args.order {
+        Order::Oldest => moves.sort_by_key(|m| m.date),
+        Order::Newest => moves.sort_by_key(|m| std::cmp::Reverse(m.date)),
+    }
+
+    if let Some(limit) = args.limit {
+        // Sidecars follow their primary, and go wherever it goes
+        let mut keep = limit;
+        while moves.get(keep).is_some_and(|m| m.sidecar_of.is_some()) {
+            keep += 1;
+        }
+        if moves.len() > keep {
+            let deferred = moves.len() - keep;
+            moves.truncate(keep);
+            plan.skipped += deferred;
+            status!("⏸️  Deferring {} entries to a later run (--limit {})", deferred, limit);
+        }
+    }
+
+    if let Some(sort) = args.sort {
+        sort_entries(&mut moves, sort);
+        sort_entries(&mut conflicts, sort);
+    }
+
+    plan.entries = moves;
+    plan.entries.extend(conflicts);
+
+    Ok(plan)
+}
+
+fn sort_entries(entries: &mut [PlanEntry], sort: Sort) {
+    match sort {
+        Sort::Name => entries.sort_by(|a, b| a.source.cmp(&b.source)),
+        Sort::Time => entries.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.source.cmp(&b.source))),
+    }
+}
+
+/// An entry whose destination has been decided but not yet checked.
+struct Placed {
+    path: PathBuf,
+    metadata: std::fs::Metadata,
+    date: SystemTime,
+    date_source: DateSource,
+    /// The name and metadata disagree about the date; see [`resolve_date`].
+    dates_conflict: bool,
+    bucket: PathBuf,
+    target: PathBuf,
+    sidecar_of: Option<PathBuf>,
+}
+
+/// Put the files of each bucket that follow one another by no more than
+/// `gap` into an event folder named after the day the event starts. Files
+/// with nothing close to them stay loose in the bucket.
+fn group_events(gap: Duration, placed: &mut [Placed]) {
+    let mut by_bucket: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    for (i, entry) in placed.iter().enumerate() {
+        if entry.metadata.is_file() {
+            by_bucket.entry(entry.bucket.clone()).or_default().push(i);
+        }
+    }
+    for (bucket, mut members) in by_bucket {
+        members.sort_by_key(|&i| placed[i].date);
+        let mut days: HashMap<String, usize> = HashMap::new();
+        let events: Vec<&[usize]> = members
+            .chunk_by(|&a, &b| placed[b].date.duration_since(placed[a].date).unwrap_or_default() <= gap)
+            .collect();
+        for event in events {
+            // A photo and its sidecars alone are no event
+            if event.iter().filter(|&&i| placed[i].sidecar_of.is_none()).count() < 2 {
+                continue;
+            }
+            let day = DateTime::<Local>::from(placed[event[0]].date).format("%Y-%m-%d").to_string();
+            let count = days.entry(day.clone()).or_default();
+            *count += 1;
+            let name = match *count {
+                1 => format!("{}_event", day),
+                n => format!("{}_event-{}", day, n),
+            };
+            debug!("🎉 {} files in {}", event.len(), name);
+            for &i in event {
+                let relative = placed[i].target.strip_prefix(&bucket).unwrap().to_path_buf();
+                placed[i].target = bucket.join(&name).join(relative);
+            }
+        }
+    }
+}
+
+/// Give each sidecar its primary's date and bucket, and place it right
+/// after the primary so sorting and `--limit` keep the two together.
+fn attach_sidecars(placed: Vec<Placed>) -> Vec<Placed> {
+    let is_sidecar = |entry: &Placed| entry.metadata.is_file() && sidecar::is_sidecar(&entry.path);
+    let mut by_name = HashMap::new();
+    let mut by_stem: HashMap<PathBuf, usize> = HashMap::new();
+    for (i, entry) in placed.iter().enumerate() {
+        if entry.metadata.is_file() && !is_sidecar(entry) {
+            by_name.insert(entry.path.clone(), i);
+            // RAW and JPEG pairs share a stem; the first name claims it
+            by_stem
+                .entry(entry.path.with_extension(""))
+                .and_modify(|j| if entry.path < placed[*j].path { *j = i })
+                .or_insert(i);
+        }
+    }
+    let mut sidecars: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, entry) in placed.iter().enumerate().filter(|(_, entry)| is_sidecar(entry)) {
+        let named = sidecar::primary_path(&entry.path);
+        let primary = by_name
+            .get(&named)
+            .or_else(|| by_stem.get(&named).filter(|_| sidecar::names_stem(&entry.path)));
+        if let Some(&primary) = primary {
+            sidecars.entry(primary).or_default().push(i);
+        }
+    }
+
+    let mut slots: Vec<Option<Placed>> = placed.into_iter().map(Some).collect();
+    let attached: HashSet<usize> = sidecars.values().flatten().copied().collect();
+    let mut ordered = Vec::with_capacity(slots.len());
+    for i in 0..slots.len() {
+        if attached.contains(&i) {
+            continue;
+        }
+        let primary = slots[i].take().unwrap();
+        let followers: Vec<Placed> = sidecars
+            .remove(&i)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|j| {
+                let sidecar = slots[j].take().unwrap();
+                verbose!("📎 Sidecar of {}: {}", primary.path.display(), sidecar.path.display());
+                Placed {
+                    date: primary.date,
+                    date_source: primary.date_source,
+                    dates_conflict: primary.dates_conflict,
+                    bucket: primary.bucket.clone(),
+                    target: primary.target.with_file_name(sidecar.path.file_name().unwrap()),
+                    sidecar_of: Some(primary.path.clone()),
+                    ..sidecar
+                }
+            })
+            .collect();
+        ordered.push(primary);
+        ordered.extend(followers);
+    }
+    ordered
+}
+
+/// Fold months that would receive fewer than `min_files` entries into their
+/// quarter, and quarters still below it into their year.
+fn coalesce(base: &Path, format: &BucketFormat, min_files: usize, placed: &mut [Placed]) {
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    for entry in placed.iter() {
+        *counts.entry(entry.bucket.clone()).or_default() += 1;
+    }
+
+    for coarse in [Coarse::Quarter, Coarse::Year] {
+        let mut moved: HashMap<PathBuf, usize> = HashMap::new();
+        let sparse: Vec<bool> = placed.iter().map(|e| counts[&e.bucket] < min_files).collect();
+        for (entry, sparse) in placed.iter_mut().zip(sparse) {
+            if !sparse {
+                continue;
+            }
+            let datetime: DateTime<Local> = entry.date.into();
+            let bucket = base.join(format.render_coarse(&datetime, coarse));
+            if let Ok(rest) = entry.target.strip_prefix(&entry.bucket) {
+                entry.target = bucket.join(rest);
+            }
+            entry.bucket = bucket.clone();
+            *moved.entry(bucket).or_default() += 1;
+        }
+        if moved.is_empty() {
+            return;
+        }
+        counts.extend(moved);
+    }
+}
+
+/// The top-level entries of a source directory, files and directories
+/// alike.
+async fn top_level_candidates(
+    source: &Path,
+    dest: &Path,
+    args: &Args,
+    rules: &Rules,
+    ignore: &Patterns,
+    plan: &mut Plan,
+) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(source)
+        .await
+        .with_context(|| format!("Failed to read directory: {:?}", source))?;
+
+    let mut candidates = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        // chronoban's own files and the destination are never organized
+        let name = entry.file_name();
+        if is_reserved(&name) || entry.path() == dest {
+            continue;
+        }
+        if args.unknown_dir.as_ref().is_some_and(|unknown| entry.path() == dest.join(unknown)) {
+            debug!("⏭️  Folder for entries without a date: {}", entry.path().display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        let is_dir = entry.file_type().await.is_ok_and(|t| t.is_dir());
+        if ignore.matches(Path::new(&name), is_dir) {
+            debug!("⏭️  Ignored by {}: {}", IGNORE_FILE, entry.path().display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        if is_kept(&rules.keep, Path::new(&name), &entry.path(), is_dir) {
+            verbose!("📌 Kept in place: {}", entry.path().display());
+            plan.kept += 1;
+            continue;
+        }
+
+        if !args.hidden && is_hidden(&name) {
+            debug!("⏭️  Hidden, use --hidden to include it: {}", entry.path().display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        if args.one_file_system && is_other_filesystem(&entry.path(), source) {
+            debug!("⏭️  Mount point (--one-file-system): {}", entry.path().display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        // Skip existing bucket directories
+        if name.to_str().is_some_and(|n| rules.layout.is_bucket_name(n)) {
+            debug!("⏭️  Existing bucket: {}", entry.path().display());
+            plan.skipped += 1;
+            continue;
+        }
+
+        candidates.push(entry.path());
+    }
+    Ok(candidates)
+}
+
+fn is_hidden(name: &std::ffi::OsStr) -> bool {
+    name.as_encoded_bytes().starts_with(b".")
+}
+
+/// Whether an entry is pinned by `--keep` or, for a directory, a
+/// `.chronokeep` marker inside it.
+fn is_kept(keep: &Patterns, relative: &Path, path: &Path, is_dir: bool) -> bool {
+    keep.matches(relative, is_dir) || (is_dir && path.join(KEEP_MARKER).exists())
+}
+
+/// Drop the `n` newest candidates, by their `--time`, so they stay where
+/// they are.
+fn keep_latest(candidates: &mut Vec<PathBuf>, n: usize, by: MetadataTime, plan: &mut Plan) {
+    let time = |path: &PathBuf| by.of(&std::fs::symlink_metadata(path).ok()?).ok();
+    let mut dated: Vec<_> = candidates.drain(..).map(|path| (time(&path), path)).collect();
+    // Newest first; entries without a time sort last and are never kept
+    dated.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+    for (i, (time, path)) in dated.into_iter().enumerate() {
+        if i < n && time.is_some() {
+            verbose!("📌 Kept as one of the {} newest: {}", n, path.display());
+            plan.kept += 1;
+        } else {
+            candidates.push(path);
+        }
+    }
+}
+
+/// Every non-directory of a source between --min-depth and --max-depth,
+/// never looking inside existing buckets, ignored or kept directories,
+/// chronoban's state or the destination.
+async fn walk_candidates(
+    source: &Path,
+    dest: &Path,
+    args: &Args,
+    rules: &Rules,
+    ignore: Patterns,
+    plan: &mut Plan,
+    unhandled: &mut Vec<(PathBuf, String)>,
+) -> Vec<PathBuf> {
+    let base = source.to_path_buf();
+    let dest = dest.to_path_buf();
+    let layout = rules.layout.clone();
+    let keep = rules.keep.clone();
+    let unknown = args.unknown_dir.as_ref().map(|unknown| dest.join(unknown));
+    // Depth is filtered by hand: walkdir's own min_depth hides the shallow
+    // directories from filter_entry, which would walk into buckets.
+    let min_depth = args.min_depth as usize;
+    let hidden = args.hidden;
+    let mut walker = WalkDir::new(source).min_depth(1).same_file_system(args.one_file_system);
+    if let Some(max) = args.max_depth {
+        walker = walker.max_depth(max as usize);
+    }
+
+    let (candidates, skipped, kept, errors) = tokio::task::spawn_blocking(move || {
+        let mut candidates = Vec::new();
+        let mut skipped = 0;
+        let mut kept = 0;
+        let mut errors = Vec::new();
+        let entries = walker.into_iter().filter_entry(|entry| {
+            if entry.path() == dest || is_reserved(entry.file_name()) {
+                return false;
+            }
+            if unknown.as_deref() == Some(entry.path()) {
+                debug!("⏭️  Folder for entries without a date: {}", entry.path().display());
+                skipped += 1;
+                return false;
+            }
+            if !hidden && is_hidden(entry.file_name()) {
+                debug!("⏭️  Hidden, use --hidden to include it: {}", entry.path().display());
+                skipped += 1;
+                return false;
+            }
+            let relative = entry.path().strip_prefix(&base).unwrap_or(entry.path());
+            let is_dir = entry.file_type().is_dir();
+            if ignore.matches(relative, is_dir) {
+                debug!("⏭️  Ignored by {}: {}", IGNORE_FILE, entry.path().display());
+                skipped += 1;
+                return false;
+            }
+            if is_kept(&keep, relative, entry.path(), is_dir) {
+                verbose!("📌 Kept in place: {}", entry.path().display());
+                kept += 1;
+                return false;
+            }
+            if entry.depth() != 1 || !entry.file_type().is_dir() {
+                return true;
+            }
+            let name = entry.file_name().to_str().unwrap_or_default();
+            if layout.is_bucket_name(name) {
+                debug!("⏭️  Existing bucket: {}", entry.path().display());
+                skipped += 1;
+                return false;
+            }
+            true
+        });
+        for entry in entries {
+            match entry {
+                Ok(entry) if entry.file_type().is_dir() || entry.depth() < min_depth => {}
+                Ok(entry) => candidates.push(entry.into_path()),
+                Err(e) => {
+                    stderr!("❌ Error reading {:?}: {}", e.path().unwrap_or(&base), e);
+                    errors.push((e.path().unwrap_or(&base).to_path_buf(), e.to_string()));
+                }
+            }
+        }
+        (candidates, skipped, kept, errors)
+    })
+    .await
+    .unwrap_or_default();
+
+    plan.skipped += skipped;
+    plan.kept += kept;
+    plan.errors += errors.len();
+    unhandled.extend(errors);
+    candidates
+}
+
+/// Pick the date an entry is bucketed by. A date in the file name within
+/// --date-tolerance of the metadata date agrees with it; beyond that --prefer
+/// decides, and without it the entry is flagged instead of silently trusting
+/// either.
+fn resolve_date(
+    path: &Path,
+    metadata_time: SystemTime,
+    metadata_source: DateSource,
+    args: &Args,
+) -> (SystemTime, DateSource, bool) {
+    let name_time = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .and_then(|name| match args.preset {
+            Some(preset) => preset.date_from_name(name),
+            None => date_from_name(name),
+        })
+        .and_then(|dt| Local.from_local_datetime(&dt).earliest())
+        .map(SystemTime::from);
+    let Some(name_time) = name_time else {
+        return (metadata_time, metadata_source, false);
+    };
+
+    let apart = match name_time.duration_since(metadata_time) {
+        Ok(d) => d,
+        Err(e) => e.duration(),
+    };
+    match args.prefer {
+        Some(Prefer::Filename) => (name_time, DateSource::Filename, false),
+        Some(Prefer::Metadata) => (metadata_time, metadata_source, false),
+        None if apart <= args.date_tolerance => (metadata_time, metadata_source, false),
+        None => {
+            stderr!("⚠️  Name says {} but {} says {}, skipping (choose with --prefer): {}",
+                DateTime::<Local>::from(name_time).format("%Y-%m-%d"),
+                metadata_source.as_str(),
+                DateTime::<Local>::from(metadata_time).format("%Y-%m-%d"),
+                path.display());
+            (metadata_time, metadata_source, true)
+        }
+    }
+}
+
+/// Where a quarantined entry goes.
+fn quarantine(base_path: &Path, path: &Path) -> (PathBuf, PathBuf) {
+    let bucket = base_path.join(STATE_DIR).join("quarantine");
+    let target = bucket.join(path.file_name().unwrap());
+    (bucket, target)
+}
+
+/// Where an entry goes under `--unknown-dir`, laid out like a bucket.
+fn unknown_date(base_path: &Path, source: &Path, path: &Path, args: &Args) -> (PathBuf, PathBuf) {
+    let bucket = base_path.join(args.unknown_dir.as_deref().unwrap());
+    let target = if args.keep_structure {
+        bucket.join(path.strip_prefix(source).unwrap())
+    } else {
+        bucket.join(path.file_name().unwrap())
+    };
+    (bucket, target)
+}
+
+/// The first date a `--date-fallback` provider finds that `plausible`
+/// accepts.
+fn fallback_date(
+    path: &Path,
+    metadata: &std::fs::Metadata,
+    args: &Args,
+    plausible: impl Fn(SystemTime) -> bool,
+) -> Option<(SystemTime, DateSource)> {
+    let found = args
+        .date_fallback
+        .iter()
+        .filter_map(|name| provider::provider(name, args.preset))
+        .filter_map(|provider| provider.date(path, metadata).ok())
+        .find(|&(time, _)| plausible(time));
+    if let (true, Ok(atime)) = (args.restore_atime, metadata.accessed()) {
+        restore_atime(path, atime);
+    }
+    found
+}
+
+/// Coarse human age for skip explanations.
+fn format_age(age: Duration) -> String {
+    let hours = age.as_secs() / 3600;
+    if hours >= 48 {
+        format!("{}d", hours / 24)
+    } else if hours > 0 {
+        format!("{}h", hours)
+    } else {
+        format!("{}m", age.as_secs() / 60)
+    }
+}
+
+/// Detect a file's type from its content. Reading it bumps the access time,
+/// which `--restore-atime` puts back.
+async fn sniff(path: &Path, atime: Option<SystemTime>, restore: bool) -> Option<FileType> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let detected = filetype::detect(&path);
+        if let (true, Some(atime)) = (restore, atime) {
+            restore_atime(&path, atime);
+        }
+        detected
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Read the camera name from a photo's EXIF data, putting the access time
+/// back afterwards like [`sniff`].
+async fn read_camera(path: &Path, atime: Option<SystemTime>, restore: bool) -> Option<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let camera = exif::camera(&path);
+        if let (true, Some(atime)) = (restore, atime) {
+            restore_atime(&path, atime);
+        }
+        camera
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// The date the config's fallback chain for a file finds, if a chain
+/// covers it. Providers may read the file; the access time is put back
+/// afterwards like [`sniff`].
+async fn chain_date(
+    rules: &Rules,
+    relative: &Path,
+    path: &Path,
+    metadata: &std::fs::Metadata,
+    restore: bool,
+) -> Option<(SystemTime, DateSource)> {
+    rules.dates.find(relative)?;
+    let (dates, relative, path, metadata) = (rules.dates.clone(), relative.to_path_buf(), path.to_path_buf(),
+        metadata.clone());
+    tokio::task::spawn_blocking(move || {
+        let found = dates.find(&relative)?.resolve(&path, &metadata);
+        if let (true, Ok(atime)) = (restore, metadata.accessed()) {
+            restore_atime(&path, atime);
+        }
+        found
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// `--explain`: how a file would be dated, provider by provider, and the
+/// bucket that puts it in.
+async fn explain(file: &Path, args: &Args) -> Result<Exit> {
+    let path = std::path::absolute(file)?;
+    let metadata = fs::metadata(&path).await.with_context(|| format!("Failed to access file: {:?}", file))?;
+    let mut config = Config::load(args.config.as_deref())?;
+    if let Some(profile) = &args.profile {
+        config.use_profile(profile)?;
+    }
+    let rules = Rules::new(args, config);
+    let show = |time: SystemTime| DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string();
+
+    stdout!("🔎 {}", path.display());
+    let dir = if args.paths.is_empty() { args.path.clone() } else { args.paths[0].clone() };
+    let dir = std::path::absolute(dir)?;
+    let relative = path.strip_prefix(&dir).unwrap_or(Path::new(path.file_name().unwrap_or_default()));
+    let chain = rules.dates.find(relative).filter(|_| metadata.is_file());
+    let (time, source) = if let Some(chain) = chain {
+        stdout!("  Dated by the [dates] chain for {:?}: {}", chain.pattern, chain.provider_names().join(", "));
+        let attempts = chain.attempts(&path, &metadata);
+        let winner = attempts.iter().position(|a| a.outcome.is_ok());
+        for (i, attempt) in attempts.iter().enumerate() {
+            match &attempt.outcome {
+                Ok((time, _)) if Some(i) == winner => stdout!("    {}: {}  <- used", attempt.provider, show(*time)),
+                Ok((time, _)) => stdout!("    {}: {} (not needed)", attempt.provider, show(*time)),
+                Err(why) => stdout!("    {}: nothing, {}", attempt.provider, why),
+            }
+        }
+        match winner.map(|i| &attempts[i].outcome) {
+            Some(Ok(found)) => *found,
+            _ => {
+                stdout!("  No provider found a date; dated by the file time instead");
+                (args.time.of(&metadata)?, args.time.source())
+            }
+        }
+    } else {
+        stdout!("  No [dates] chain covers it; dated by its file time, checked against its name");
+        let (time, source) = (args.time.of(&metadata)?, args.time.source());
+        stdout!("    {}: {}", source.as_str(), show(time));
+        let (time, source) = match embedded::recorded(&path).filter(|_| args.embedded_dates) {
+            Some((recorded, embedded)) => {
+                stdout!("    {} (--embedded-dates): {}, in place of {}", embedded.as_str(), show(recorded),
+                    source.as_str());
+                (recorded, embedded)
+            }
+            None => (time, source),
+        };
+        let (time, source, conflict) = resolve_date(&path, time, source, args);
+        if conflict {
+            stdout!("  The dates disagree, so a run would skip it; choose one with --prefer");
+            return Ok(Exit::Success);
+        }
+        (time, source)
+    };
+
+    let datetime: DateTime<Local> = time.into();
+    match &rules.layout {
+        Layout::Format { format, .. } => stdout!("📅 {} by {}, into {}", show(time), source.as_str(),
+            format.render(&datetime).display()),
+        Layout::Template(_) => stdout!("📅 {} by {}", show(time), source.as_str()),
+    }
+    Ok(Exit::Success)
+}
+
+/// Read the date recorded inside a file, putting the access time back
+/// afterwards like [`sniff`].
+async fn read_embedded_date(
+    path: &Path,
+    atime: Option<SystemTime>,
+    restore: bool,
+) -> Option<(SystemTime, DateSource)> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let recorded = embedded::recorded(&path);
+        if let (true, Some(atime)) = (restore, atime) {
+            restore_atime(&path, atime);
+        }
+        recorded
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Ask before moving an oversized directory. Without a terminal to ask on,
+/// the directory is left alone.
+fn confirm_large_dir(path: &Path, size: u64, limit: u64) -> bool {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    stderr!("⚠️  Directory is {} (limit {}): {}",
+        format_size(size), format_size(limit), path.display());
+    if !io::stdin().is_terminal() {
+        stderr!("   Skipping; re-run interactively or raise --max-dir-size to move it.");
+        return false;
+    }
+
+    output::flush();
+    eprint!("   Move it anyway? [y/N] ");
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// Wait out the settle window once for the whole plan, then drop every entry
+/// whose size or mtime moved in the meantime — it is still being written.
+async fn settle(plan: &mut Plan, window: Duration) {
+    if plan.moves().next().is_none() {
+        return;
+    }
+
+    status!("⏳ Waiting {:?} for files to settle...", window);
+    tokio::time::sleep(window).await;
+
+    let mut settled = Vec::with_capacity(plan.entries.len());
+    for planned in plan.entries.drain(..) {
+        if planned.conflict.is_some() {
+            settled.push(planned);
+            continue;
+        }
+        let unchanged = match fs::symlink_metadata(&planned.source).await {
+            Ok(m) => m.len() == planned.size && m.modified().ok() == planned.modified,
+            Err(_) => false,
+        };
+        if unchanged {
+            settled.push(planned);
+        } else {
+            stderr!("⏳ Still changing, skipping: {}", planned.source.display());
+            plan.skipped += 1;
+        }
+    }
+    plan.entries = settled;
+}
+
+fn skip_open_files(plan: &mut Plan) {
+    let open = match open_file_paths() {
+        Ok(open) => open,
+        Err(e) => {
+            stderr!("⚠️  Cannot detect open files: {}", e);
+            return;
+        }
+    };
+
+    let before = plan.entries.len();
+    plan.entries.retain(|planned| {
+        let in_use = planned.conflict.is_none()
+            && open.iter().any(|p| p.starts_with(&planned.source));
+        if in_use {
+            stderr!("🔒 Open by another process, skipping: {}", planned.source.display());
+        }
+        !in_use
+    });
+    plan.skipped += before - plan.entries.len();
+}
+
+async fn execute_plan(
+    base_path: &Path,
+    plan: &Plan,
+    args: &Args,
+    rules: &Rules,
+    journal: Option<Arc<Journal>>,
+    catalog: Option<Arc<Catalog>>,
+    mut report: Option<&mut Report>,
+) -> Result<Stats> {
+    let mut stats = Stats {
+        moved: 0,
+        skipped: plan.skipped + plan.conflicts().count(),
+        kept: plan.kept,
+        not_owned: plan.not_owned,
+        errors: plan.errors,
+        copied: 0,
+        copied_bytes: 0,
+        deduplicated: 0,
+        reclaimed_bytes: 0,
+        deleted: 0,
+        pruned: 0,
+        buckets: Vec::new(),
+        failures: Vec::new(),
+        moved_bytes: 0,
+        elapsed: Duration::ZERO,
+        low_space: None,
+    };
+    let floor = Floor { bytes: args.stop_at_free, inodes: args.stop_at_free_inodes };
+    let watch_space = floor.is_set() && !args.dry_run && rules.remote.is_none();
+    let started = Instant::now();
+    let _span = trace::span("execute", "move");
+
+    let mut buckets = BucketTracker::new(plan.moves().filter(|m| !m.is_deletion()), base_path, args.dry_run);
+    let mut hooks = HookRunner::new(&rules.hooks);
+    let hook_failures = Arc::new(AtomicUsize::new(0));
+    let (queue, mut finished) = workers(args.jobs.unwrap());
+    let mut claimed = HashSet::new();
+    // Sidecars wait on their primary's move, which is started first
+    let primaries: HashSet<&Path> = plan.moves().filter_map(|m| m.sidecar_of.as_deref()).collect();
+    let mut moving: HashMap<&Path, watch::Receiver<Option<bool>>> = HashMap::new();
+
+    for planned in sidecar::in_order(plan.moves()) {
+        // Only moves that copy take up room; renames don't
+        if watch_space && stats.low_space.is_none() && !exit::interrupted()
+            && (args.copy || is_other_filesystem(&planned.source, base_path)) {
+            if let Some(why) = floor.breached(base_path) {
+                match args.on_low_space {
+                    LowSpace::Pause => floor.wait(base_path, &why).await,
+                    LowSpace::Stop => {
+                        stderr!("🛑 {}; starting no more moves", why);
+                        stats.low_space = Some((why, 0));
+                    }
+                }
+            }
+        }
+        // Let moves already under way finish, but start no new ones
+        if exit::interrupted() || stats.low_space.is_some() {
+            stats.skipped += 1;
+            let why = match &mut stats.low_space {
+                Some((_, left)) => {
+                    *left += 1;
+                    "low on space"
+                }
+                None => "interrupted",
+            };
+            if let Some(report) = report.as_deref_mut() {
+                report.add(&planned.source, "skipped", Some(&planned.destination), planned.date_source, planned.size,
+                    Some(why.to_string()));
+            }
+            // The bucket still completes, so what did arrive gets its hooks
+            if !planned.is_deletion() {
+                if let Some(entries) = buckets.finished(&planned.bucket, &planned.destination, planned.size, Ended::Skipped) {
+                    hooks.spawn(planned.bucket.clone(), entries);
+                }
+            }
+            continue;
+        }
+        let path = planned.source.clone();
+        let target_path = planned.destination.clone();
+        let bucket = planned.bucket.clone();
+        let size = planned.size;
+        let accessed = planned.accessed;
+        let dedupe = match (&planned.duplicate_of, args.dedupe) {
+            (Some(existing), Some(mode @ (DedupeMode::Hardlink | DedupeMode::Delete))) => {
+                Some((existing.clone(), mode))
+            }
+            _ => None,
+        };
+        // Deletions don't land in any bucket
+        let tracked = (!planned.is_deletion()).then(|| bucket.clone());
+        if let Some(bucket) = &tracked {
+            buckets.started(bucket);
+        }
+        if !args.dry_run && rules.remote.is_none() && is_bucket(base_path, &bucket) && claimed.insert(bucket.clone()) {
+            marker::claim(&bucket, args.marker);
+        }
+        let dry_run = args.dry_run;
+        let restore = if args.restore_atime { accessed } else { None };
+        let journal = journal.clone();
+        let cataloged = catalog.clone().map(|catalog| (catalog, planned.clone()));
+        let retry = Retry {
+            retries: args.retries,
+            delay: args.retry_delay,
+        };
+
+        // Spawn async task for moving
+        let delete = planned.is_deletion();
+        let symlinks = args.symlinks;
+        let copy = args.copy;
+        let preserve = args.preserve_times;
+        let verify = args.verify_checksum;
+        let (pre_hook, post_hook) = (args.pre_hook.clone(), args.post_hook.clone());
+        let hook_failures = hook_failures.clone();
+        let done = primaries.contains(planned.source.as_path()).then(|| {
+            let (done, moved) = watch::channel(None);
+            moving.insert(&planned.source, moved);
+            done
+        });
+        let after = planned.sidecar_of.as_deref().map(|primary| moving.get(primary).cloned());
+        let remote = rules.remote.clone();
+        let planned_as = (planned.inode, planned.modified);
+        let relative = target_path.strip_prefix(base_path).unwrap_or(&target_path).to_path_buf();
+        let run: Move = Box::pin(async move {
+            let _span = trace::span_for("move", "move", &path);
+            if let Some(primary) = after {
+                let moved = match primary {
+                    Some(mut done) => done.wait_for(Option::is_some).await.is_ok_and(|done| *done == Some(true)),
+                    None => false,
+                };
+                if !moved {
+                    status!("⏭️  Leaving sidecar in place, its file wasn't moved: {}", path.display());
+                    return Ok(Outcome::Stayed);
+                }
+            }
+            let result = async {
+                if delete {
+                    return delete_empty(&path, dry_run, journal.as_deref());
+                }
+                // Downloads and the like can change under a long run
+                if !dry_run {
+                    if let Some(why) = changed_since_planned(&path, planned_as, size, symlinks) {
+                        status!("⏭️  Skipping, {} since planning: {}", why, path.display());
+                        return Ok(Outcome::Changed(why));
+                    }
+                }
+                if let Some((existing, mode)) = dedupe {
+                    return remove_duplicate(&path, &existing, &target_path, mode, size, dry_run, journal.as_deref());
+                }
+                if let Some(remote) = remote {
+                    return upload(&path, &remote, &relative, copy, dry_run, journal.as_deref()).await;
+                }
+                let verb = if copy { "copy" } else { "move" };
+                if dry_run {
+                    status!("📦 Would {}: {} -> {}", verb, path.display(), target_path.display());
+                    Ok::<_, anyhow::Error>(Outcome::DryRun)
+                } else {
+                    if let Some(pre_hook) = &pre_hook {
+                        hooks::run_move_hook(pre_hook, &path, &target_path)
+                            .await
+                            .with_context(|| format!("Pre-hook failed, not moving {:?}", path))?;
+                    }
+                    let metadata = std::fs::symlink_metadata(&path);
+                    let is_link = metadata.as_ref().is_ok_and(|m| m.is_symlink());
+                    // Setting times goes through to a link's target, so links keep theirs
+                    let original_times = metadata
+                        .ok()
+                        .filter(|m| preserve && !m.is_symlink())
+                        .and_then(|m| Some((restore.or(m.accessed().ok())?, m.modified().ok()?)));
+                    let method = async {
+                        Ok::<_, anyhow::Error>(if copy {
+                            let from = if is_link && symlinks == Symlinks::Follow {
+                                fs::canonicalize(&path)
+                                    .await
+                                    .with_context(|| format!("Failed to follow symlink {:?}", path))?
+                            } else {
+                                path.clone()
+                            };
+                            let method = {
+                                let _span = trace::span_for("copy", "move", &path);
+                                copy_entry(&from, &target_path, retry, verify).await?
+                            };
+                            if is_link && symlinks != Symlinks::Follow {
+                                retarget_symlink(&path, &target_path)
+                                    .with_context(|| format!("Copied {:?} but failed to fix its target", target_path))?;
+                            }
+                            method
+                        } else if is_link && symlinks == Symlinks::Follow {
+                            materialize_link(&path, &target_path, retry, verify).await?
+                        } else {
+                            let method = {
+                                let _span = trace::span_for("rename", "move", &path);
+                                move_entry(&path, &target_path, retry, verify).await?
+                            };
+                            if let MoveMethod::Copied { bytes, .. } = method {
+                                stderr!("⚠️  Crossed filesystems, copied {} instead of renaming: {}",
+                                    format_size(bytes), path.display());
+                            }
+                            if is_link {
+                                retarget_symlink(&path, &target_path)
+                                    .with_context(|| format!("Moved {:?} but failed to fix its target", target_path))?;
+                            }
+                            method
+                        })
+                    }
+                    .await;
+                    // Something took the target since planning; it's left as it is
+                    let method = match method {
+                        Err(e) if target_exists(&e) => {
+                            stderr!("⚠️  Conflict, the target appeared since planning: {} -> {}",
+                                path.display(), target_path.display());
+                            if let Some(journal) = &journal {
+                                journal.record_conflict(&path, &target_path, Conflict::TargetExists)?;
+                            }
+                            return Ok(Outcome::Changed("the target appeared"));
+                        }
+                        method => method?,
+                    };
+
+                    if let Some((accessed, modified)) = original_times {
+                        preserve_times(&target_path, accessed, modified);
+                    } else if let Some(atime) = restore {
+                        restore_atime(&target_path, atime);
+                    }
+
+                    if let Some(journal) = &journal {
+                        journal.record_move(&path, &target_path, method)?;
+                    }
+                    if let Some((catalog, entry)) = cataloged {
+                        let sha256 = match method {
+                            MoveMethod::Copied { sha256, .. } => sha256,
+                            MoveMethod::Renamed => None,
+                        };
+                        tokio::task::spawn_blocking(move || catalog.record(&entry, sha256)).await??;
+                    }
+
+                    let done = if copy { "Copied" } else { "Moved" };
+                    status!("✅ {}: {} -> {}", done, path.display(), target_path.display());
+                    if let Some(post_hook) = &post_hook {
+                        if let Err(e) = hooks::run_move_hook(post_hook, &path, &target_path).await {
+                            stderr!("❌ Hook failed for {}: {:#}", target_path.display(), e);
+                            hook_failures.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    Ok(Outcome::Moved(method))
+                }
+            }
+            .await;
+            if let Some(done) = done {
+                done.send_replace(Some(result.is_ok()));
+            }
+            result
+        });
+
+        // Wait for room in the queue, taking in finished moves meanwhile
+        let mut job = Some(Job {
+            bucket: tracked,
+            source: planned.source.clone(),
+            destination: planned.destination.clone(),
+            date_source: planned.date_source,
+            size,
+            run,
+        });
+        while let Some(waiting) = job.take() {
+            tokio::select! {
+                permit = queue.reserve() => permit.expect("workers outlive the queue").send(waiting),
+                Some(done) = finished.recv() => {
+                    done.record(&mut stats, &mut buckets, &mut hooks, report.as_deref_mut(), args.copy);
+                    job = Some(waiting);
+                }
+            }
+        }
+    }
+
+    drop(queue);
+    while let Some(done) = finished.recv().await {
+        done.record(&mut stats, &mut buckets, &mut hooks, report.as_deref_mut(), args.copy);
+    }
+    stats.errors += hooks.finish().await + hook_failures.load(Ordering::Relaxed);
+
+    stats.buckets = buckets.breakdown();
+    stats.elapsed = started.elapsed();
+
+    Ok(stats)
+}
+
+type Move = Pin<Box<dyn Future<Output = Result<Outcome>> + Send>>;
+
+/// A move waiting for a worker, with what to account it under.
+struct Job {
+    bucket: Option<PathBuf>,
+    source: PathBuf,
+    destination: PathBuf,
+    date_source: DateSource,
+    size: u64,
+    run: Move,
+}
+
+struct Done {
+    bucket: Option<PathBuf>,
+    source: PathBuf,
+    destination: PathBuf,
+    date_source: DateSource,
+    size: u64,
+    result: Result<Result<Outcome>, JoinError>,
+}
+
+impl Done {
+    fn record(
+        self,
+        stats: &mut Stats,
+        buckets: &mut BucketTracker,
+        hooks: &mut HookRunner,
+        report: Option<&mut Report>,
+        copy: bool,
+    ) {
+        // An entry that changed since planning keeps its reason as the
+        // report's note, but is a skip rather than a failure
+        let (action, note, failed) = match &self.result {
+            Ok(Ok(Outcome::Changed(why))) => ("skipped", Some(format!("{} since planning", why)), false),
+            Ok(Ok(outcome)) => (outcome.action(copy), None, false),
+            Ok(Err(e)) => ("error", Some(format!("{:#}", e)), true),
+            Err(e) => ("error", Some(e.to_string()), true),
+        };
+        if let (true, Some(error)) = (failed, &note) {
+            stats.failures.push((self.source.clone(), error.clone()));
+        }
+        if let Some(report) = report {
+            report.add(&self.source, action, Some(&self.destination), self.date_source, self.size, note);
+        }
+        let ended = match stats.record(self.result, self.size) {
+            true => Ended::Arrived,
+            false if failed => Ended::Failed,
+            false => Ended::Skipped,
+        };
+        if let Some(bucket) = self.bucket {
+            if let Some(entries) = buckets.finished(&bucket, &self.destination, self.size, ended) {
+                hooks.spawn(bucket, entries);
+            }
+        }
+    }
+}
+
+/// `count` workers taking moves off a queue as they free up. Both channels
+/// hold at most `count` entries, so however large the plan, only a bounded
+/// number of moves and results are in memory at once.
+fn workers(count: usize) -> (mpsc::Sender<Job>, mpsc::Receiver<Done>) {
+    let (queue, jobs) = mpsc::channel::<Job>(count);
+    let (results, finished) = mpsc::channel(count);
+    let jobs = Arc::new(Mutex::new(jobs));
+    for lane in 1..=count {
+        let jobs = jobs.clone();
+        let results = results.clone();
+        tokio::spawn(async move {
+            loop {
+                let Some(job) = jobs.lock().await.recv().await else {
+                    break;
+                };
+                // A panicking move fails that entry, not the worker
+                let result = tokio::spawn(trace::in_lane(lane, job.run)).await;
+                let done = Done {
+                    bucket: job.bucket,
+                    source: job.source,
+                    destination: job.destination,
+                    date_source: job.date_source,
+                    size: job.size,
+                    result,
+                };
+                if results.send(done).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    (queue, finished)
+}
+
+/// Hard-link or delete a duplicate instead of moving it. When the identical
+/// copy already sits at the destination, linking reduces to a delete.
+fn remove_duplicate(
+    source: &Path,
+    existing: &Path,
+    target: &Path,
+    mode: DedupeMode,
+    size: u64,
+    dry_run: bool,
+    journal: Option<&Journal>,
+) -> Result<Outcome> {
+    let link = mode == DedupeMode::Hardlink && existing != target;
+    if dry_run {
+        if link {
+            status!("🔗 Would link duplicate: {} -> {}", source.display(), target.display());
+        } else {
+            status!("🗑️  Would delete duplicate: {}", source.display());
+        }
+        return Ok(Outcome::DryRun);
+    }
+
+    if link {
+        dedupe::hardlink(source, existing, target)
+            .with_context(|| format!("Failed to link {:?} to {:?}", target, existing))?;
+        status!("🔗 Linked duplicate: {} -> {}", source.display(), target.display());
+    } else {
+        remove_entry(source).with_context(|| format!("Failed to delete duplicate {:?}", source))?;
+        status!("🗑️  Deleted duplicate: {}", source.display());
+    }
+
+    if let Some(journal) = journal {
+        journal.record_dedupe(source, existing, link.then_some(target))?;
+    }
+    Ok(Outcome::Deduplicated(size))
+}
+
+/// Whether a move failed because something was already at its target,
+/// which the movers refuse to write over.
+fn target_exists(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|e| e.kind() == std::io::ErrorKind::AlreadyExists)
+}
+
+/// Why the entry at `path` no longer matches what was planned, if it
+/// doesn't: it vanished, was replaced by another file of the same name, or
+/// a file's size or modification time moved on. Errors reading it are left
+/// for the move to report.
+fn changed_since_planned(
+    path: &Path,
+    (planned_inode, planned_modified): (Option<u64>, Option<SystemTime>),
+    size: u64,
+    symlinks: Symlinks,
+) -> Option<&'static str> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(m) if m.is_symlink() && symlinks == Symlinks::Follow => std::fs::metadata(path),
+        other => other,
+    };
+    let metadata = match metadata {
+        Ok(m) => m,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Some("vanished"),
+        Err(_) => return None,
+    };
+    if planned_inode.is_some() && inode(&metadata) != planned_inode {
+        return Some("replaced");
+    }
+    // A directory's size and time say little; it is moved whole
+    let modified = metadata.modified().ok();
+    if metadata.is_file() && (metadata.len() != size || (planned_modified.is_some() && modified != planned_modified)) {
+        return Some("changed");
+    }
+    None
+}
+
+/// Upload an entry to a remote destination, removing the original once it
+/// is there unless it is only being copied.
+async fn upload(
+    path: &Path,
+    remote: &Remote,
+    relative: &Path,
+    copy: bool,
+    dry_run: bool,
+    journal: Option<&Journal>,
+) -> Result<Outcome> {
+    let key = key_of(relative)
+        .with_context(|| format!("{:?} isn't valid UTF-8, which remote keys need", relative))?;
+    let uri = remote.uri(&key);
+    if dry_run {
+        status!("☁️  Would upload: {} -> {}", path.display(), uri);
+        return Ok(Outcome::DryRun);
+    }
+    let metadata = std::fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read metadata for {:?}", path))?;
+    if metadata.is_symlink() {
+        anyhow::bail!("Not uploading symlink {:?}, remote destinations can't hold links", path);
+    }
+    let bytes = {
+        let _span = trace::span_for("upload", "move", path);
+        remote.upload(path, &key, journal).await?
+    };
+    if !copy {
+        remove_any(path).await.with_context(|| format!("Uploaded {:?} but failed to remove it", path))?;
+    }
+    if let Some(journal) = journal {
+        journal.record_upload(path, &uri, bytes, copy)?;
+    }
+    status!("☁️  Uploaded: {} -> {}", path.display(), uri);
+    Ok(Outcome::Uploaded(bytes))
+}
+
+/// Delete an empty file under `--empty delete`, provided it is still empty.
+fn delete_empty(path: &Path, dry_run: bool, journal: Option<&Journal>) -> Result<Outcome> {
+    if dry_run {
+        status!("🗑️  Would delete empty file: {}", path.display());
+        return Ok(Outcome::DryRun);
+    }
+    let metadata = std::fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read metadata for {:?}", path))?;
+    if !metadata.is_file() || metadata.len() != 0 {
+        anyhow::bail!("No longer an empty file, not deleting: {:?}", path);
+    }
+    remove_entry(path).with_context(|| format!("Failed to delete empty file {:?}", path))?;
+    if let Some(journal) = journal {
+        journal.record_delete(path)?;
+    }
+    status!("🗑️  Deleted empty file: {}", path.display());
+    Ok(Outcome::Deleted)
+}
+
+/// Per-bucket progress, so a line can be printed as soon as the last pending
+/// move into a bucket finishes. Gives long runs a skimmable structure.
+struct BucketTracker {
+    buckets: HashMap<PathBuf, BucketProgress>,
+    base: PathBuf,
+    dry_run: bool,
+}
+
+#[derive(Default)]
+struct BucketProgress {
+    remaining: usize,
+    done: BucketStats,
+    /// Where the entries that made it into the bucket are now.
+    entries: Vec<PathBuf>,
+    failed: usize,
+    skipped: usize,
+    started: Option<Instant>,
+}
+
+/// How an entry a bucket was waiting for ended.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ended {
+    Arrived,
+    /// Left alone, as a sidecar whose file stayed, an entry changed since
+    /// planning or one never started.
+    Skipped,
+    Failed,
+}
+
+impl BucketTracker {
+    fn new<'a>(moves: impl Iterator<Item = &'a PlanEntry>, base: &Path, dry_run: bool) -> Self {
+        let mut buckets: HashMap<PathBuf, BucketProgress> = HashMap::new();
+        for planned in moves {
+            buckets.entry(planned.bucket.clone()).or_default().remaining += 1;
+        }
+        BucketTracker {
+            buckets,
+            base: base.to_path_buf(),
+            dry_run,
+        }
+    }
+
+    fn started(&mut self, bucket: &Path) {
+        if let Some(progress) = self.buckets.get_mut(bucket) {
+            progress.started.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Count a finished entry, returning the bucket's entries for its hooks
+    /// if it was the last one still pending in a real run.
+    fn finished(&mut self, bucket: &Path, entry: &Path, size: u64, ended: Ended) -> Option<Vec<PathBuf>> {
+        let progress = self.buckets.get_mut(bucket)?;
+        progress.remaining -= 1;
+        match ended {
+            Ended::Arrived => {
+                progress.done.add(entry, size);
+                progress.entries.push(entry.to_path_buf());
+            }
+            Ended::Skipped => progress.skipped += 1,
+            Ended::Failed => progress.failed += 1,
+        }
+        if progress.remaining > 0 {
+            return None;
+        }
+
+        let name = bucket.strip_prefix(&self.base).unwrap_or(bucket).display();
+        let (moved, bytes) = (progress.done.files, progress.done.bytes);
+        let mut left = String::new();
+        if progress.skipped > 0 {
+            left.push_str(&format!(", {} skipped", progress.skipped));
+        }
+        if progress.failed > 0 {
+            left.push_str(&format!(", {} failed", progress.failed));
+        }
+        if self.dry_run {
+            status!("🪣 {}: {} entries, {}{}", name, moved, format_size(bytes), left);
+        } else {
+            let elapsed = progress.started.map(|t| t.elapsed()).unwrap_or_default();
+            status!("🪣 {} complete: {} entries, {} in {:.1?}{}",
+                name, moved, format_size(bytes), elapsed, left);
+        }
+        if self.dry_run {
+            return None;
+        }
+        // Duplicates deleted under --dedupe never arrived
+        let entries = std::mem::take(&mut progress.entries);
+        Some(entries.into_iter().filter(|e| e.symlink_metadata().is_ok()).collect())
+    }
+
+    /// Totals for every bucket that received something, in bucket order.
+    fn breakdown(self) -> Vec<BucketStats> {
+        let base = self.base;
+        let mut stats: Vec<BucketStats> = self
+            .buckets
+            .into_iter()
+            .filter(|(_, progress)| progress.done.files > 0)
+            .map(|(bucket, progress)| BucketStats {
+                name: bucket.strip_prefix(&base).unwrap_or(&bucket).display().to_string(),
+                ..progress.done
+            })
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+}
+
+/// Re-plan after a run and fail if anything would still move. A clean run
+/// must be a fixed point; a non-empty second plan means the rules or the date
+/// source are unstable.
+async fn assert_idempotent(base_path: &Path, sources: &[PathBuf], args: &Args, rules: &Rules) -> Result<()> {
+    let plan = plan_directory(base_path, sources, args, rules).await?;
+    let again = plan.moves().count();
+    if again == 0 {
+        status!("✅ Idempotency check passed: a second run would move nothing");
+        return Ok(());
+    }
+
+    for planned in plan.moves() {
+        stderr!("❌ Would move again: {} -> {}",
+            planned.source.display(), planned.destination.display());
+    }
+    anyhow::bail!(
+        "Idempotency check failed: {} entries would move on a second run",
+        again
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: Args,
+    }
+
+    /// A base directory for one test, removed afterwards.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir = std::env::temp_dir().join(format!("chronoban-organize-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn entry(base: &Path, name: &str) -> PlanEntry {
+        PlanEntry {
+            source: base.join(name),
+            destination: base.join("2022-03").join(name),
+            bucket: base.join("2022-03"),
+            date: SystemTime::UNIX_EPOCH,
+            date_source: DateSource::Modified,
+            size: 0,
+            modified: None,
+            accessed: None,
+            inode: None,
+            conflict: None,
+            duplicate_of: None,
+            sidecar_of: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn roll_back_puts_moves_back_and_ends_the_run() {
+        let scratch = Scratch::new("rollback");
+        let base = scratch.0.as_path();
+        let (bucket, kept) = (base.join("2022-03"), base.join("2022-02"));
+        std::fs::create_dir(&kept).unwrap();
+        std::fs::write(base.join("a.txt"), "a").unwrap();
+        std::fs::create_dir_all(base.join("album/raw")).unwrap();
+        std::fs::write(base.join("album/raw/b.cr2"), "b").unwrap();
+        std::fs::write(kept.join("c.txt"), "c").unwrap();
+        std::fs::write(base.join("c.txt"), "c").unwrap();
+
+        let journal = Journal::open(base, "run-1", &Identity::current()).unwrap();
+        let mut plan = Plan::new(base.to_path_buf());
+        plan.entries = vec![entry(base, "a.txt"), entry(base, "album"), entry(base, "c.txt")];
+        journal.record_plan(&plan).unwrap();
+        let retry = Retry { retries: 0, delay: Duration::ZERO };
+        for name in ["a.txt", "album"] {
+            let method = move_entry(&base.join(name), &bucket.join(name), retry, false).await.unwrap();
+            journal.record_move(&base.join(name), &bucket.join(name), method).unwrap();
+        }
+        // A duplicate removed in favour of the copy already in a bucket
+        std::fs::remove_file(base.join("c.txt")).unwrap();
+        journal.record_dedupe(&base.join("c.txt"), &kept.join("c.txt"), None).unwrap();
+
+        let args = Cli::parse_from(["chronoban"]).args;
+        let created = BTreeSet::from([bucket.clone()]);
+        assert_eq!(roll_back(&journal, &created, &args).await.unwrap(), 2);
+
+        assert_eq!(std::fs::read_to_string(base.join("a.txt")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(base.join("album/raw/b.cr2")).unwrap(), "b");
+        assert!(!bucket.exists());
+        // Dedupes aren't moves: the bucket's copy is what is left of them
+        assert!(kept.join("c.txt").exists());
+        assert!(journal::unfinished_run(base).unwrap().is_none());
+        let journaled = std::fs::read_to_string(journal::journal_path(base)).unwrap();
+        assert_eq!(journaled.matches("\"event\":\"rollback\"").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn roll_back_of_copies_removes_them() {
+        let scratch = Scratch::new("rollback-copy");
+        let base = scratch.0.as_path();
+        let bucket = base.join("2022-03");
+        std::fs::write(base.join("a.txt"), "a").unwrap();
+        let journal = Journal::open(base, "run-1", &Identity::current()).unwrap();
+        let retry = Retry { retries: 0, delay: Duration::ZERO };
+        let method = copy_entry(&base.join("a.txt"), &bucket.join("a.txt"), retry, true).await.unwrap();
+        journal.record_move(&base.join("a.txt"), &bucket.join("a.txt"), method).unwrap();
+
+        let args = Cli::parse_from(["chronoban", "--copy"]).args;
+        assert_eq!(roll_back(&journal, &BTreeSet::from([bucket.clone()]), &args).await.unwrap(), 1);
+        assert!(base.join("a.txt").exists());
+        assert!(!bucket.exists());
+    }
+
+    #[test]
+    fn resume_plan_skips_what_the_run_finished() {
+        let scratch = Scratch::new("resume");
+        let base = scratch.0.as_path();
+        assert!(resume_plan(base).unwrap().is_none());
+
+        for name in ["pending.txt", "moved.txt", "duplicate.txt", "conflict.txt"] {
+            std::fs::write(base.join(name), name).unwrap();
+        }
+        let mut plan = Plan::new(base.to_path_buf());
+        plan.entries = ["pending.txt", "moved.txt", "duplicate.txt", "gone.txt", "conflict.txt"]
+            .iter()
+            .map(|name| entry(base, name))
+            .collect();
+        plan.entries[4].conflict = Some(Conflict::TargetExists);
+        let journal = Journal::open(base, "run-1", &Identity::current()).unwrap();
+        journal.record_plan(&plan).unwrap();
+        journal.record_move(&base.join("moved.txt"), &base.join("2022-03/moved.txt"), MoveMethod::Renamed).unwrap();
+        journal.record_dedupe(&base.join("duplicate.txt"), &base.join("2022-03/other.txt"), None).unwrap();
+
+        let resumed = resume_plan(base).unwrap().unwrap();
+        assert_eq!(resumed.entries, vec![entry(base, "pending.txt")]);
+        assert_eq!(resumed.skipped, 1);
+
+        journal.record_finish().unwrap();
+        assert!(resume_plan(base).unwrap().is_none());
+    }
+}
@@ -0,0 +1,316 @@
+//! Where human-readable progress goes, and how much of it. When the
+//! machine-readable summary owns stdout, progress moves to stderr so the JSON
+//! stays parseable. Lines lose their emoji in plain mode, for logs read by
+//! systemd, cron mail and grep.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Mutex, OnceLock};
+use std::thread::JoinHandle;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// How much progress to print. Warnings and errors are always shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only the summary.
+    Quiet,
+    /// Per-entry progress.
+    Normal,
+    /// Also how each entry was dated and placed.
+    Verbose,
+    /// Also why each entry was skipped.
+    Debug,
+}
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: u8) -> Verbosity {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+}
+
+static PROGRESS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn init(format: OutputFormat, verbosity: Verbosity) {
+    PROGRESS_TO_STDERR.store(format == OutputFormat::Json, Ordering::Relaxed);
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn progress_to_stderr() -> bool {
+    PROGRESS_TO_STDERR.load(Ordering::Relaxed)
+}
+
+static FORCE_PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Strip decorations regardless of environment (`--plain`).
+pub fn force_plain(plain: bool) {
+    FORCE_PLAIN.store(plain, Ordering::Relaxed);
+}
+
+static JOURNALD: AtomicBool = AtomicBool::new(false);
+
+/// Write plain lines with a syslog priority prefix such as `<4>`, which
+/// journald reads off a service's output (`--service`).
+pub fn journald(enabled: bool) {
+    JOURNALD.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Plain unless the stream is a terminal and `NO_COLOR` is unset.
+fn is_plain(stream: Stream) -> bool {
+    static STDOUT: OnceLock<bool> = OnceLock::new();
+    static STDERR: OnceLock<bool> = OnceLock::new();
+    let no_color = || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    FORCE_PLAIN.load(Ordering::Relaxed)
+        || JOURNALD.load(Ordering::Relaxed)
+        || match stream {
+            Stream::Stdout => *STDOUT.get_or_init(|| no_color() || !std::io::stdout().is_terminal()),
+            Stream::Stderr => *STDERR.get_or_init(|| no_color() || !std::io::stderr().is_terminal()),
+        }
+}
+
+/// Prepare a line for `stream`: in plain mode, drop ANSI escapes and the
+/// emoji that lead a line (after any indentation), so file names are never
+/// touched.
+pub fn tidy(line: String, stream: Stream) -> String {
+    if !is_plain(stream) {
+        return line;
+    }
+    if JOURNALD.load(Ordering::Relaxed) {
+        // Errors lead with ❌; everything else on stderr is a warning
+        let priority = match stream {
+            Stream::Stdout => 6,
+            Stream::Stderr if line.trim_start().starts_with('❌') => 3,
+            Stream::Stderr => 4,
+        };
+        // Blank lines that space out a terminal are noise in the journal
+        let line = line.trim_start_matches('\n').to_string();
+        return format!("<{}>{}", priority, undecorate(line));
+    }
+    undecorate(line)
+}
+
+fn undecorate(line: String) -> String {
+    let line = strip_ansi(&line);
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+    let mut rest = body;
+    let mut stripped = false;
+    while let Some(c) = rest.chars().next().filter(|&c| is_decoration(c)) {
+        rest = &rest[c.len_utf8()..];
+        stripped = true;
+    }
+    if stripped {
+        format!("{}{}", indent, rest.trim_start_matches(' '))
+    } else {
+        line
+    }
+}
+
+fn is_decoration(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF | 0x2300..=0x23FF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0x200D)
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.clone().next() == Some('[') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+enum Message {
+    Line(Stream, String),
+    /// Write out everything before this, then answer.
+    Flush(SyncSender<()>),
+}
+
+/// Lines queued for the writer thread while one runs.
+static QUEUE: Mutex<Option<SyncSender<Message>>> = Mutex::new(None);
+
+/// How many lines may wait for the writer before printing blocks.
+const QUEUE_LINES: usize = 4096;
+
+/// A thread that owns stdout and stderr for as long as this is held, so
+/// moves running on many threads never wait on the terminal or interleave,
+/// and long runs write in blocks rather than a line at a time. Dropping it
+/// writes out whatever is still queued.
+pub struct Writer {
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Writer {
+    pub fn start() -> Writer {
+        let (queue, lines) = mpsc::sync_channel(QUEUE_LINES);
+        let thread = std::thread::Builder::new()
+            .name("output".to_string())
+            .spawn(move || write_lines(lines))
+            .ok();
+        if thread.is_some() {
+            *QUEUE.lock().unwrap() = Some(queue);
+        }
+        Writer { thread }
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        // The thread finishes once the queue is closed and drained
+        QUEUE.lock().unwrap().take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn write_lines(lines: Receiver<Message>) {
+    let mut out = std::io::BufWriter::new(std::io::stdout());
+    // Unbuffered, like stderr always is
+    let mut err = std::io::stderr();
+    while let Ok(message) = lines.recv() {
+        let mut next = Some(message);
+        while let Some(message) = next {
+            match message {
+                Message::Line(Stream::Stdout, line) => {
+                    let _ = writeln!(out, "{}", line);
+                }
+                Message::Line(Stream::Stderr, line) => {
+                    // Keep the two streams in order on a shared terminal
+                    let _ = out.flush();
+                    let _ = writeln!(err, "{}", line);
+                }
+                Message::Flush(done) => {
+                    let _ = out.flush();
+                    let _ = done.send(());
+                }
+            }
+            next = lines.try_recv().ok();
+        }
+        // Nothing more queued for now, so show what there is
+        let _ = out.flush();
+    }
+}
+
+/// Print a line, through the writer thread when one is running.
+pub fn emit(stream: Stream, line: String) {
+    let queue = QUEUE.lock().unwrap().clone();
+    let line = match queue {
+        Some(queue) => match queue.send(Message::Line(stream, line)) {
+            Ok(()) => return,
+            Err(mpsc::SendError(Message::Line(_, line))) => line,
+            Err(_) => return,
+        },
+        None => line,
+    };
+    match stream {
+        Stream::Stdout => println!("{}", line),
+        Stream::Stderr => eprintln!("{}", line),
+    }
+}
+
+/// Wait until every queued line is written, before prompting or printing
+/// directly.
+pub fn flush() {
+    let queue = QUEUE.lock().unwrap().clone();
+    if let Some(queue) = queue {
+        let (done, written) = mpsc::sync_channel(1);
+        if queue.send(Message::Flush(done)).is_ok() {
+            let _ = written.recv();
+        }
+    }
+}
+
+/// `println!` for human-readable lines.
+macro_rules! stdout {
+    () => {
+        $crate::output::emit($crate::output::Stream::Stdout, String::new())
+    };
+    ($($arg:tt)*) => {
+        $crate::output::emit(
+            $crate::output::Stream::Stdout,
+            $crate::output::tidy(format!($($arg)*), $crate::output::Stream::Stdout),
+        )
+    };
+}
+
+/// `eprintln!` for human-readable lines.
+macro_rules! stderr {
+    () => {
+        $crate::output::emit($crate::output::Stream::Stderr, String::new())
+    };
+    ($($arg:tt)*) => {
+        $crate::output::emit(
+            $crate::output::Stream::Stderr,
+            $crate::output::tidy(format!($($arg)*), $crate::output::Stream::Stderr),
+        )
+    };
+}
+
+pub fn enabled(level: Verbosity) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Print a progress line at a given verbosity.
+macro_rules! progress {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::output::enabled($level) {
+            if $crate::output::progress_to_stderr() {
+                $crate::output::stderr!($($arg)*)
+            } else {
+                $crate::output::stdout!($($arg)*)
+            }
+        }
+    };
+}
+
+/// Print a progress line.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        $crate::output::progress!($crate::output::Verbosity::Normal, $($arg)*)
+    };
+}
+
+/// Print a line shown with `-v`.
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        $crate::output::progress!($crate::output::Verbosity::Verbose, $($arg)*)
+    };
+}
+
+/// Print a line shown with `-vv`.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::output::progress!($crate::output::Verbosity::Debug, $($arg)*)
+    };
+}
+
+pub(crate) use {debug, progress, status, stderr, stdout, verbose};
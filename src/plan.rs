@@ -0,0 +1,388 @@
+//! The plan of a run: every entry chronoban decided to move, where it goes,
+//! and why. The CLI builds one before touching anything, and the same type
+//! round-trips through JSON for embedders and saved plans.
+
+use chrono::{DateTime, Local};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::json::{self, Value};
+use crate::schema::SCHEMA_VERSION;
+
+/// Names the document a plan is written as, so tools reading a file or a
+/// dry-run summary can tell what they have before looking further. The
+/// suffix changes only when the meaning of a field does.
+pub const PLAN_DOCUMENT: &str = "plan.v1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    /// The directory being organized.
+    pub base: PathBuf,
+    pub entries: Vec<PlanEntry>,
+    /// Entries left alone while planning (existing buckets, too young, ...).
+    pub skipped: usize,
+    /// Entries pinned in place with `--keep` or a `.chronokeep` marker.
+    pub kept: usize,
+    /// Entries left alone because `--owner` or `--group` excludes them.
+    pub not_owned: usize,
+    /// Entries that could not be inspected.
+    pub errors: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanEntry {
+    pub source: PathBuf,
+    /// Equal to `source` for entries deleted in place instead of moved.
+    pub destination: PathBuf,
+    /// The bucket directory the destination lies in, before any route subfolder.
+    pub bucket: PathBuf,
+    /// The date that decided the bucket.
+    pub date: SystemTime,
+    pub date_source: DateSource,
+    /// Size in bytes; for directories only measured when a size limit applies.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    /// The inode when planned, so an entry replaced before it is moved can
+    /// be told apart; `None` where the platform has none.
+    pub inode: Option<u64>,
+    /// Set when the entry cannot be moved as planned.
+    pub conflict: Option<Conflict>,
+    /// An existing file in the bucket with identical contents.
+    pub duplicate_of: Option<PathBuf>,
+    /// For a sidecar under `--sidecars`, the file it belongs to; it is only
+    /// moved once that file has been.
+    pub sidecar_of: Option<PathBuf>,
+}
+
+/// Where an entry's date came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateSource {
+    Modified,
+    Accessed,
+    /// When the file was created, where the filesystem records it.
+    Created,
+    /// A date encoded in the file name.
+    Filename,
+    /// The recording date inside a video or audio file.
+    Media,
+    /// The Date header of an email.
+    Email,
+    /// When a photo was taken, from its EXIF data.
+    Exif,
+    /// When a document was created, from its metadata.
+    Document,
+}
+
+impl DateSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DateSource::Modified => "mtime",
+            DateSource::Accessed => "atime",
+            DateSource::Created => "btime",
+            DateSource::Filename => "filename",
+            DateSource::Media => "media",
+            DateSource::Email => "email",
+            DateSource::Exif => "exif",
+            DateSource::Document => "document",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<DateSource> {
+        match s {
+            "mtime" => Some(DateSource::Modified),
+            "atime" => Some(DateSource::Accessed),
+            "btime" => Some(DateSource::Created),
+            "filename" => Some(DateSource::Filename),
+            "media" => Some(DateSource::Media),
+            "email" => Some(DateSource::Email),
+            "exif" => Some(DateSource::Exif),
+            "document" => Some(DateSource::Document),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    /// Something with the destination's name is already there.
+    TargetExists,
+    /// The contents already exist in the bucket and duplicates are skipped.
+    Duplicate,
+    /// The file name encodes a date too far from the metadata date, and
+    /// nothing says which to trust.
+    ConflictingDates,
+    /// Part of a file and its sidecars, another of which can't be moved.
+    Sidecar,
+}
+
+impl Conflict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Conflict::TargetExists => "target-exists",
+            Conflict::Duplicate => "duplicate",
+            Conflict::ConflictingDates => "conflicting-dates",
+            Conflict::Sidecar => "sidecar",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Conflict> {
+        match s {
+            "target-exists" => Some(Conflict::TargetExists),
+            "duplicate" => Some(Conflict::Duplicate),
+            "conflicting-dates" => Some(Conflict::ConflictingDates),
+            "sidecar" => Some(Conflict::Sidecar),
+            _ => None,
+        }
+    }
+}
+
+impl Plan {
+    pub fn new(base: PathBuf) -> Plan {
+        Plan {
+            base,
+            entries: Vec::new(),
+            skipped: 0,
+            kept: 0,
+            not_owned: 0,
+            errors: 0,
+        }
+    }
+
+    /// Entries that will actually be moved.
+    pub fn moves(&self) -> impl Iterator<Item = &PlanEntry> {
+        self.entries.iter().filter(|e| e.conflict.is_none())
+    }
+
+    pub fn conflicts(&self) -> impl Iterator<Item = &PlanEntry> {
+        self.entries.iter().filter(|e| e.conflict.is_some())
+    }
+
+    pub fn to_json(&self) -> Value {
+        json::object([
+            ("schema_version", SCHEMA_VERSION.into()),
+            ("document", PLAN_DOCUMENT.into()),
+            ("base", self.base.as_path().into()),
+            ("skipped", self.skipped.into()),
+            ("kept", self.kept.into()),
+            ("not_owned", self.not_owned.into()),
+            ("errors", self.errors.into()),
+            ("entries", Value::Array(self.entries.iter().map(PlanEntry::to_json).collect())),
+        ])
+    }
+
+    /// Read a plan back, refusing one written by a newer chronoban whose
+    /// schema may mean something else. Plans without a version predate
+    /// versioning and are read as version 1, as are plans without a
+    /// document name.
+    pub fn from_json(value: &Value) -> Result<Plan, String> {
+        match value.get("document").map(Value::as_str) {
+            None | Some(Some(PLAN_DOCUMENT)) => {}
+            Some(Some(document)) => return Err(format!("expected a {} document, not {}", PLAN_DOCUMENT, document)),
+            Some(None) => return Err("plan's document name isn't a string".to_string()),
+        }
+        let version = value.get("schema_version").and_then(Value::as_u64).unwrap_or(1);
+        if version > SCHEMA_VERSION {
+            return Err(format!("plan has schema version {}, but this chronoban only reads up to {}",
+                version, SCHEMA_VERSION));
+        }
+        let entries = value
+            .get("entries")
+            .and_then(Value::as_array)
+            .ok_or("plan has no entries array")?
+            .iter()
+            .map(PlanEntry::from_json)
+            .collect::<Result<_, _>>()?;
+        Ok(Plan {
+            base: path_field(value, "base")?,
+            entries,
+            skipped: count_field(value, "skipped"),
+            kept: count_field(value, "kept"),
+            not_owned: count_field(value, "not_owned"),
+            errors: count_field(value, "errors"),
+        })
+    }
+}
+
+impl PlanEntry {
+    /// Whether the entry is removed rather than moved.
+    pub fn is_deletion(&self) -> bool {
+        self.destination == self.source
+    }
+
+    pub fn to_json(&self) -> Value {
+        json::object([
+            ("source", self.source.as_path().into()),
+            ("destination", self.destination.as_path().into()),
+            ("bucket", self.bucket.as_path().into()),
+            ("date", format_time(self.date).into()),
+            ("date_source", self.date_source.as_str().into()),
+            ("size", self.size.into()),
+            ("modified", self.modified.map(format_time).into()),
+            ("accessed", self.accessed.map(format_time).into()),
+            ("inode", self.inode.into()),
+            ("conflict", self.conflict.map(Conflict::as_str).into()),
+            ("duplicate_of", self.duplicate_of.as_deref().into()),
+            ("sidecar_of", self.sidecar_of.as_deref().into()),
+        ])
+    }
+
+    pub fn from_json(value: &Value) -> Result<PlanEntry, String> {
+        let date_source = value
+            .get("date_source")
+            .and_then(Value::as_str)
+            .and_then(DateSource::parse)
+            .ok_or("entry has no valid date_source")?;
+        let conflict = match value.get("conflict").and_then(Value::as_str) {
+            Some(s) => Some(Conflict::parse(s).ok_or_else(|| format!("unknown conflict {:?}", s))?),
+            None => None,
+        };
+        Ok(PlanEntry {
+            source: path_field(value, "source")?,
+            destination: path_field(value, "destination")?,
+            bucket: path_field(value, "bucket")?,
+            date: time_field(value, "date")?.ok_or("entry has no date")?,
+            date_source,
+            size: value.get("size").and_then(Value::as_u64).unwrap_or(0),
+            modified: time_field(value, "modified")?,
+            accessed: time_field(value, "accessed")?,
+            inode: value.get("inode").and_then(Value::as_u64),
+            conflict,
+            duplicate_of: value.get("duplicate_of").and_then(Value::as_str).map(PathBuf::from),
+            sidecar_of: value.get("sidecar_of").and_then(Value::as_str).map(PathBuf::from),
+        })
+    }
+}
+
+/// Times are written as RFC 3339 in local time, which is what users read.
+pub fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).to_rfc3339()
+}
+
+pub fn parse_time(s: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc3339(s).ok().map(SystemTime::from)
+}
+
+fn path_field(value: &Value, key: &str) -> Result<PathBuf, String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .ok_or_else(|| format!("missing {:?}", key))
+}
+
+fn time_field(value: &Value, key: &str) -> Result<Option<SystemTime>, String> {
+    match value.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v
+            .as_str()
+            .and_then(parse_time)
+            .map(Some)
+            .ok_or_else(|| format!("invalid time in {:?}", key)),
+    }
+}
+
+fn count_field(value: &Value, key: &str) -> usize {
+    value.get(key).and_then(Value::as_u64).unwrap_or(0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn plan() -> Plan {
+        let mut plan = Plan::new(PathBuf::from("/base"));
+        plan.skipped = 3;
+        plan.kept = 2;
+        plan.not_owned = 1;
+        plan.errors = 4;
+        plan.entries.push(PlanEntry {
+            source: PathBuf::from("/base/a.jpg"),
+            destination: PathBuf::from("/base/2024-03/a.jpg"),
+            bucket: PathBuf::from("/base/2024-03"),
+            date: at(1_710_000_000),
+            date_source: DateSource::Exif,
+            size: 1234,
+            modified: Some(at(1_710_000_100)),
+            accessed: Some(at(1_710_000_200)),
+            inode: Some(42),
+            conflict: None,
+            duplicate_of: None,
+            sidecar_of: None,
+        });
+        plan.entries.push(PlanEntry {
+            source: PathBuf::from("/base/a.xmp"),
+            destination: PathBuf::from("/base/2024-03/a.xmp"),
+            bucket: PathBuf::from("/base/2024-03"),
+            date: at(1_710_000_000),
+            date_source: DateSource::Modified,
+            size: 0,
+            modified: None,
+            accessed: None,
+            inode: None,
+            conflict: Some(Conflict::TargetExists),
+            duplicate_of: Some(PathBuf::from("/base/2024-03/b.xmp")),
+            sidecar_of: Some(PathBuf::from("/base/a.jpg")),
+        });
+        plan
+    }
+
+    #[test]
+    fn round_trips_as_plan_v1() {
+        assert_eq!(PLAN_DOCUMENT, "plan.v1");
+        assert_eq!(SCHEMA_VERSION, 1);
+        let plan = plan();
+        let written = plan.to_json();
+        assert_eq!(written.get("document").and_then(Value::as_str), Some("plan.v1"));
+        assert_eq!(written.get("schema_version").and_then(Value::as_u64), Some(1));
+        let read = json::parse(&written.to_string()).unwrap();
+        assert_eq!(Plan::from_json(&read), Ok(plan));
+    }
+
+    #[test]
+    fn rejects_other_documents_and_newer_versions() {
+        let with = |key: &str, value: Value| {
+            let Value::Object(mut fields) = plan().to_json() else { unreachable!() };
+            fields.iter_mut().find(|(k, _)| k == key).unwrap().1 = value;
+            Plan::from_json(&Value::Object(fields))
+        };
+        assert!(with("document", "plan.v2".into()).unwrap_err().contains("plan.v1"));
+        assert!(with("document", Value::UInt(1)).is_err());
+        assert!(with("schema_version", (SCHEMA_VERSION + 1).into()).unwrap_err().contains("schema version 2"));
+    }
+
+    #[test]
+    fn reads_unversioned_plans_as_version_1() {
+        let Value::Object(mut fields) = plan().to_json() else { unreachable!() };
+        fields.retain(|(k, _)| k != "document" && k != "schema_version");
+        assert_eq!(Plan::from_json(&Value::Object(fields)), Ok(plan()));
+    }
+
+    #[test]
+    fn rejects_unknown_entry_values() {
+        let entry = |key: &str, value: Value| {
+            let Value::Object(mut fields) = plan().entries[0].to_json() else { unreachable!() };
+            fields.iter_mut().find(|(k, _)| k == key).unwrap().1 = value;
+            PlanEntry::from_json(&Value::Object(fields))
+        };
+        assert!(entry("date_source", "sundial".into()).is_err());
+        assert!(entry("conflict", "cosmic-rays".into()).unwrap_err().contains("unknown conflict"));
+        assert!(entry("modified", "yesterday".into()).is_err());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for source in [DateSource::Modified, DateSource::Accessed, DateSource::Created, DateSource::Filename,
+            DateSource::Media, DateSource::Email, DateSource::Exif, DateSource::Document] {
+            assert_eq!(DateSource::parse(source.as_str()), Some(source));
+        }
+        for conflict in [Conflict::TargetExists, Conflict::Duplicate, Conflict::ConflictingDates, Conflict::Sidecar] {
+            assert_eq!(Conflict::parse(conflict.as_str()), Some(conflict));
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Checks made before anything moves, so a run that can't finish fails
+//! straight away instead of halfway through: every directory involved must
+//! be writable, and the destination must have room for whatever has to be
+//! copied rather than renamed. `--atomic` goes further, checking every
+//! entry. `--stop-at-free` keeps watching the destination while the run
+//! goes on.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::fsutil::{dir_size, free_inodes, free_space, is_other_filesystem, is_writable_dir};
+use crate::output::{status, stderr};
+use crate::plan::Plan;
+use crate::units::format_size;
+
+/// Fail unless entries can be taken out of each source and buckets created
+/// in the destination.
+pub fn check_writable(sources: &[PathBuf], dest: &Path) -> Result<()> {
+    for dir in sources.iter().map(PathBuf::as_path).chain([dest]) {
+        if !is_writable_dir(dir) {
+            anyhow::bail!("{} isn't writable, so nothing could be moved in or out of it; check its permissions",
+                dir.display());
+        }
+    }
+    Ok(())
+}
+
+/// What a run does once `--stop-at-free` is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LowSpace {
+    /// Start no more moves that copy, and finish
+    Stop,
+    /// Wait until space is freed, then carry on
+    Pause,
+}
+
+/// How much the destination must keep free (`--stop-at-free` and
+/// `--stop-at-free-inodes`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Floor {
+    pub bytes: Option<u64>,
+    pub inodes: Option<u64>,
+}
+
+impl Floor {
+    pub fn is_set(&self) -> bool {
+        self.bytes.is_some() || self.inodes.is_some()
+    }
+
+    /// Why `dest` is below the floor, if it is.
+    pub fn breached(&self, dest: &Path) -> Option<String> {
+        if let Some(floor) = self.bytes {
+            if let Some(free) = free_space(dest).filter(|free| *free < floor) {
+                return Some(format!("{} has {} free, below --stop-at-free {}",
+                    dest.display(), format_size(free), format_size(floor)));
+            }
+        }
+        if let Some(floor) = self.inodes {
+            if let Some(free) = free_inodes(dest).filter(|free| *free < floor) {
+                return Some(format!("{} has {} inodes free, below --stop-at-free-inodes {}",
+                    dest.display(), free, floor));
+            }
+        }
+        None
+    }
+
+    /// Wait until `dest` is back above the floor or the run is interrupted.
+    pub async fn wait(&self, dest: &Path, why: &str) {
+        stderr!("⏸️  {}; pausing until space is freed (Ctrl-C to stop)", why);
+        while self.breached(dest).is_some() {
+            if crate::exit::interrupted() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+        status!("▶️  Space freed on {}, carrying on", dest.display());
+    }
+}
+
+/// For `--atomic`: fail unless every entry of the plan looks movable, so a
+/// run that would only get partway is never started.
+pub fn check_atomic(plan: &Plan) -> Result<()> {
+    if plan.errors > 0 {
+        anyhow::bail!("--atomic: {} entries could not be inspected, not starting", plan.errors);
+    }
+    if let Some(entry) = plan.conflicts().next() {
+        anyhow::bail!("--atomic: {} can't be moved to {} ({}), and {} other entries conflict, not starting",
+            entry.source.display(), entry.destination.display(),
+            entry.conflict.map(|c| c.as_str()).unwrap_or_default(), plan.conflicts().count() - 1);
+    }
+    for entry in plan.moves() {
+        let from = entry.source.parent().unwrap_or(&entry.source);
+        if !is_writable_dir(from) {
+            anyhow::bail!("--atomic: {} isn't writable, so {} can't be moved out of it",
+                from.display(), entry.source.display());
+        }
+        // The bucket may not exist yet; whatever creates it must be writable
+        let to = entry.destination.ancestors().skip(1).find(|dir| dir.exists()).unwrap_or(&plan.base);
+        if !is_writable_dir(to) {
+            anyhow::bail!("--atomic: {} isn't writable, so {} can't be moved into it",
+                to.display(), entry.source.display());
+        }
+    }
+    Ok(())
+}
+
+/// Estimate how much the plan copies instead of renaming, either because of
+/// `--copy` or because the entry is on another filesystem than the
+/// destination, and fail when the destination hasn't that much free. In a
+/// dry run a shortfall is only reported.
+pub async fn check_space(plan: &Plan, dest: &Path, copy: bool, dry_run: bool) -> Result<()> {
+    let mut bytes = 0;
+    for entry in plan.moves().filter(|e| !e.is_deletion() && e.duplicate_of.is_none()) {
+        if !copy && !is_other_filesystem(&entry.source, dest) {
+            continue;
+        }
+        // Directories are only measured while planning when a size limit asks
+        bytes += match std::fs::symlink_metadata(&entry.source) {
+            Ok(m) if m.is_dir() => dir_size(&entry.source).await,
+            _ => entry.size,
+        };
+    }
+    if bytes == 0 {
+        return Ok(());
+    }
+    let Some(free) = free_space(dest) else {
+        return Ok(());
+    };
+    status!("💾 {} to copy, {} free on {}", format_size(bytes), format_size(free), dest.display());
+    if bytes > free {
+        let message = format!("Not enough space on {}: {} has to be copied but only {} is free",
+            dest.display(), format_size(bytes), format_size(free));
+        if !dry_run {
+            anyhow::bail!(message);
+        }
+        stderr!("⚠️  {}", message);
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! Bundles of options for common organization chores.
+
+use chrono::NaiveDateTime;
+
+use crate::bucket::parse_bucket_format;
+use crate::cli::Args;
+use crate::datesrc::{messenger_date, Prefer};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    /// WhatsApp and Telegram media exports: dated by the date in their
+    /// names, into day buckets
+    Messenger,
+}
+
+impl Preset {
+    /// Fill in the options this preset stands for. Options given explicitly
+    /// on the command line are left alone.
+    pub fn apply(self, args: &mut Args) {
+        match self {
+            Preset::Messenger => {
+                args.format = parse_bucket_format("%Y-%m-%d").expect("preset format is valid");
+                args.prefer.get_or_insert(Prefer::Filename);
+            }
+        }
+    }
+
+    /// The date a file name encodes, as this preset reads names.
+    pub fn date_from_name(self, name: &str) -> Option<NaiveDateTime> {
+        match self {
+            Preset::Messenger => messenger_date(name),
+        }
+    }
+}
+
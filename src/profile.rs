@@ -0,0 +1,96 @@
+//! `chronoban run`: organize the folders named by the config file's
+//! `[profile.NAME]` tables, one after another, so a single invocation (or
+//! timer) looks after all of them.
+//!
+//! ```sh
+//! chronoban run --profile photos
+//! chronoban run --all --dry-run
+//! ```
+//!
+//! Other options apply to every profile; a profile's path, dest and format
+//! take the place of `--dir`, `--dest` and `--format`.
+
+use anyhow::Result;
+
+use crate::cli::Args;
+use crate::config::{Config, Profile};
+use crate::exit::{self, Exit};
+use crate::organize;
+
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    /// Profile to run; repeat for several, run in the order given
+    #[arg(short = 'p', long = "profile", value_name = "NAME", required_unless_present = "all")]
+    profiles: Vec<String>,
+
+    /// Run every profile, in the order the config file gives them
+    #[arg(long, conflicts_with = "profiles")]
+    all: bool,
+
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+pub async fn run(args: RunArgs) -> Result<Exit> {
+    let config = Config::load(args.args.config.as_deref())?;
+    let profiles: Vec<&Profile> = if args.all {
+        config.profiles.iter().collect()
+    } else {
+        args.profiles.iter().map(|name| config.profile(name)).collect::<Result<_>>()?
+    };
+    if profiles.is_empty() {
+        anyhow::bail!("The config file has no [profile.NAME] tables to run");
+    }
+    if profiles.len() > 1 && args.args.watch.is_some() {
+        anyhow::bail!("--watch keeps running one profile; run several from a timer instead");
+    }
+
+    let mut exit = Exit::Success;
+    for profile in profiles {
+        if exit::interrupted() {
+            return Ok(Exit::Interrupted);
+        }
+        let outcome = match organize::run(with_profile(&args.args, profile)).await {
+            Ok(outcome) => outcome,
+            // The other profiles still run
+            Err(e) => {
+                eprintln!("Error: profile {}: {:?}", profile.name, e);
+                Exit::Fatal
+            }
+        };
+        exit = worse(exit, outcome);
+    }
+    Ok(exit)
+}
+
+/// The run's options with the profile's folder and layout in place.
+fn with_profile(args: &Args, profile: &Profile) -> Args {
+    let mut args = args.clone();
+    args.profile = Some(profile.name.clone());
+    match profile.paths.as_slice() {
+        [path] => {
+            args.path = path.clone();
+            args.paths.clear();
+        }
+        paths => args.paths = paths.to_vec(),
+    }
+    if let Some(dest) = &profile.dest {
+        args.dest = Some(dest.clone());
+    }
+    if let Some(format) = &profile.format {
+        args.format = format.clone();
+        args.granularity = None;
+    }
+    args
+}
+
+/// The outcome that says more about what went wrong.
+fn worse(a: Exit, b: Exit) -> Exit {
+    let rank = |exit| match exit {
+        Exit::Success => 0,
+        Exit::Partial => 1,
+        Exit::Fatal => 2,
+        Exit::Interrupted => 3,
+    };
+    if rank(b) > rank(a) { b } else { a }
+}
@@ -0,0 +1,252 @@
+//! Date providers and the fallback chains the config file builds from them:
+//! for files matching a pattern, try each provider in turn and bucket by
+//! the first date one finds.
+//!
+//! ```toml
+//! [dates]
+//! "*.jpg" = ["exif", "filename", "mtime"]
+//! "*.mov" = ["media", "mtime"]
+//! "*.pdf" = ["document", "filename", "mtime"]
+//! ```
+//!
+//! The first pattern that matches a file decides its chain. Files no
+//! pattern matches, and directories, are dated as usual.
+
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::datesrc::date_from_name;
+use crate::embedded;
+use crate::exif;
+use crate::ignore::Patterns;
+use crate::json::Value;
+use crate::plan::DateSource;
+use crate::preset::Preset;
+
+/// Something that can tell when a file is from.
+pub trait DateProvider: Send + Sync {
+    /// The name the config file uses.
+    fn name(&self) -> &'static str;
+
+    /// The file's date, or why this provider has none.
+    fn date(&self, path: &Path, metadata: &Metadata) -> Result<(SystemTime, DateSource), String>;
+}
+
+/// The names providers go by, for error messages.
+const NAMES: &str = "exif, media, email, document, filename, mtime, atime, btime";
+
+/// The provider called `name`. `preset` decides how file names are read.
+pub fn provider(name: &str, preset: Option<Preset>) -> Option<Box<dyn DateProvider>> {
+    Some(match name {
+        "exif" => Box::new(Exif),
+        "media" => Box::new(Media),
+        "email" => Box::new(Email),
+        "document" => Box::new(Document),
+        "filename" => Box::new(Filename { preset }),
+        "mtime" => Box::new(Modified),
+        "atime" => Box::new(Accessed),
+        "btime" => Box::new(Created),
+        _ => return None,
+    })
+}
+
+/// A provider name given on the command line.
+pub fn parse_provider_name(s: &str) -> Result<String, String> {
+    match provider(s, None) {
+        Some(_) => Ok(s.to_string()),
+        None => Err(format!("unknown date provider {:?} (expected one of {})", s, NAMES)),
+    }
+}
+
+struct Exif;
+
+impl DateProvider for Exif {
+    fn name(&self) -> &'static str {
+        "exif"
+    }
+
+    fn date(&self, path: &Path, _metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        let taken = exif::taken(path).ok_or("no EXIF date")?;
+        let local = Local.from_local_datetime(&taken).earliest().ok_or("EXIF date doesn't exist locally")?;
+        Ok((local.into(), DateSource::Exif))
+    }
+}
+
+struct Media;
+
+impl DateProvider for Media {
+    fn name(&self) -> &'static str {
+        "media"
+    }
+
+    fn date(&self, path: &Path, _metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        let recorded = embedded::media(path).ok_or("no recording date in MP4, ID3 or Vorbis metadata")?;
+        Ok((recorded, DateSource::Media))
+    }
+}
+
+struct Email;
+
+impl DateProvider for Email {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn date(&self, path: &Path, _metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        let sent = embedded::email(path).ok_or("not an .eml message with a Date header")?;
+        Ok((sent, DateSource::Email))
+    }
+}
+
+struct Document;
+
+impl DateProvider for Document {
+    fn name(&self) -> &'static str {
+        "document"
+    }
+
+    fn date(&self, path: &Path, _metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        let created = embedded::document(path).ok_or("no PDF creation date")?;
+        Ok((created, DateSource::Document))
+    }
+}
+
+struct Filename {
+    preset: Option<Preset>,
+}
+
+impl DateProvider for Filename {
+    fn name(&self) -> &'static str {
+        "filename"
+    }
+
+    fn date(&self, path: &Path, _metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        let name = path.file_name().and_then(|n| n.to_str()).ok_or("name isn't UTF-8")?;
+        let date = match self.preset {
+            Some(preset) => preset.date_from_name(name),
+            None => date_from_name(name),
+        };
+        let date = date.ok_or("no date in the name")?;
+        let local = Local.from_local_datetime(&date).earliest().ok_or("named date doesn't exist locally")?;
+        Ok((local.into(), DateSource::Filename))
+    }
+}
+
+struct Modified;
+
+impl DateProvider for Modified {
+    fn name(&self) -> &'static str {
+        "mtime"
+    }
+
+    fn date(&self, _path: &Path, metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        Ok((metadata.modified().map_err(|e| e.to_string())?, DateSource::Modified))
+    }
+}
+
+struct Accessed;
+
+impl DateProvider for Accessed {
+    fn name(&self) -> &'static str {
+        "atime"
+    }
+
+    fn date(&self, _path: &Path, metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        Ok((metadata.accessed().map_err(|e| e.to_string())?, DateSource::Accessed))
+    }
+}
+
+struct Created;
+
+impl DateProvider for Created {
+    fn name(&self) -> &'static str {
+        "btime"
+    }
+
+    fn date(&self, _path: &Path, metadata: &Metadata) -> Result<(SystemTime, DateSource), String> {
+        Ok((metadata.created().map_err(|e| e.to_string())?, DateSource::Created))
+    }
+}
+
+/// One `[dates]` entry as written in the config file.
+#[derive(Clone, Debug)]
+pub struct ChainConfig {
+    pub pattern: String,
+    pub providers: Vec<String>,
+}
+
+pub fn chains_from_config(table: &Value) -> Result<Vec<ChainConfig>> {
+    let table = table.as_object().context("[dates] must be a table of patterns")?;
+    let mut chains = Vec::new();
+    for (pattern, providers) in table {
+        let providers: Vec<String> = providers
+            .as_array()
+            .and_then(|items| items.iter().map(|v| v.as_str().map(String::from)).collect())
+            .with_context(|| format!("dates.{:?} must be a list of date providers", pattern))?;
+        if let Some(unknown) = providers.iter().find(|p| provider(p, None).is_none()) {
+            anyhow::bail!("dates.{:?}: unknown date provider {:?} (expected one of {})", pattern, unknown, NAMES);
+        }
+        chains.push(ChainConfig { pattern: pattern.clone(), providers });
+    }
+    Ok(chains)
+}
+
+/// The fallback chains, in the order the config gives them.
+#[derive(Default)]
+pub struct DateChains {
+    chains: Vec<DateChain>,
+}
+
+pub struct DateChain {
+    pub pattern: String,
+    matcher: Patterns,
+    providers: Vec<Box<dyn DateProvider>>,
+}
+
+/// What one provider in a chain made of a file.
+pub struct Attempt {
+    pub provider: &'static str,
+    pub outcome: Result<(SystemTime, DateSource), String>,
+}
+
+impl DateChains {
+    pub fn new(configs: &[ChainConfig], preset: Option<Preset>) -> DateChains {
+        let chains = configs
+            .iter()
+            .map(|config| DateChain {
+                pattern: config.pattern.clone(),
+                matcher: Patterns::new([config.pattern.as_str()]),
+                providers: config.providers.iter().filter_map(|name| provider(name, preset)).collect(),
+            })
+            .collect();
+        DateChains { chains }
+    }
+
+    /// The chain for the file at `relative` below the directory being
+    /// organized.
+    pub fn find(&self, relative: &Path) -> Option<&DateChain> {
+        self.chains.iter().find(|chain| chain.matcher.matches(relative, false))
+    }
+}
+
+impl DateChain {
+    pub fn provider_names(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+
+    /// The first date a provider finds, trying them in order.
+    pub fn resolve(&self, path: &Path, metadata: &Metadata) -> Option<(SystemTime, DateSource)> {
+        self.providers.iter().find_map(|provider| provider.date(path, metadata).ok())
+    }
+
+    /// Every provider's answer, for `--explain`.
+    pub fn attempts(&self, path: &Path, metadata: &Metadata) -> Vec<Attempt> {
+        self.providers
+            .iter()
+            .map(|provider| Attempt { provider: provider.name(), outcome: provider.date(path, metadata) })
+            .collect()
+    }
+}
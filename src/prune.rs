@@ -0,0 +1,256 @@
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate, TimeZone};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+
+use crate::archive::{self, ArchiveFormat};
+use crate::bucket::expired_buckets;
+use crate::exit::{self, Exit};
+use crate::fsutil::dir_size;
+use crate::identity::Identity;
+use crate::journal::{self, Journal, JournalOptOut, STATE_DIR};
+use crate::lock::LockArgs;
+use crate::output::{stderr, stdout};
+use crate::trash::trash;
+use crate::units::{format_size, parse_duration, parse_retention, Retention};
+
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("action").required(true).args(["trash", "delete", "archive"])))]
+pub struct PruneArgs {
+    /// Directory containing the month directories
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Prune months that ended more than this long ago (e.g. 90d, 12m, 3y)
+    #[arg(long, value_parser = parse_retention)]
+    older_than: Retention,
+
+    /// Move pruned months to the trash
+    #[arg(long)]
+    trash: bool,
+
+    /// Permanently delete pruned months, after the grace period
+    #[arg(long)]
+    delete: bool,
+
+    /// How long months removed with --delete wait in
+    /// .chronoban/pending-delete, where they can still be restored, before
+    /// a later run deletes them for good (0 deletes straight away)
+    #[arg(long, default_value = "7d", value_parser = parse_duration)]
+    grace_period: Duration,
+
+    /// Replace pruned months with a verified archive in this format
+    #[arg(long, value_name = "FORMAT")]
+    archive: Option<ArchiveFormat>,
+
+    /// Show what would be pruned without changing anything
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Record every pruned month in .chronoban/journal.jsonl inside the
+    /// directory
+    #[arg(long)]
+    journal: bool,
+
+    #[command(flatten)]
+    unjournaled: JournalOptOut,
+
+    #[command(flatten)]
+    lock: LockArgs,
+}
+
+enum Action {
+    Trash,
+    /// Delete, or with a grace period move into this pending-delete
+    /// directory first.
+    Delete(Option<PathBuf>),
+    Archive(ArchiveFormat),
+}
+
+/// Where months deleted with a grace period wait, in dated subdirectories.
+const PENDING_DIR: &str = "pending-delete";
+
+pub async fn run(args: PruneArgs) -> Result<Exit> {
+    let path = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+    let _lock = if args.dry_run { None } else { args.lock.acquire(&path).await? };
+
+    let action = match args.archive {
+        Some(format) => Action::Archive(format),
+        None if args.delete && args.grace_period.is_zero() => Action::Delete(None),
+        None if args.delete => {
+            let today = Local::now().format("%Y-%m-%d").to_string();
+            Action::Delete(Some(path.join(STATE_DIR).join(PENDING_DIR).join(today)))
+        }
+        None => Action::Trash,
+    };
+    // Trashing and archiving take the months away from where they were too
+    if !args.dry_run {
+        args.unjournaled.require(&format!("prune --{}", action.name()), args.journal)?;
+    }
+    let journal = if args.journal && !args.dry_run {
+        Some(Journal::open(&path, &journal::new_run_id(), &Identity::current())?)
+    } else {
+        None
+    };
+
+    let cutoff = args.older_than.cutoff(Local::now().date_naive()).context("--older-than reaches back past any date")?;
+    let buckets = expired_buckets(&path, cutoff).await?;
+
+    stdout!("Pruning month directories in: {}", path.display());
+    stdout!("Cutoff: months ending before {}", cutoff);
+    if args.dry_run {
+        stdout!("🔍 DRY RUN MODE - Nothing will be removed");
+    }
+    stdout!();
+
+    let mut pruned = 0;
+    let mut errors = 0;
+    let mut reclaimed = 0;
+
+    let (purged, purged_bytes, purge_errors) =
+        purge_pending(&path, args.grace_period, args.dry_run, journal.as_ref()).await?;
+    errors += purge_errors;
+    // Staged months only free their space once purged
+    let staging = matches!(action, Action::Delete(Some(_)));
+
+    for bucket in buckets {
+        if exit::interrupted() {
+            break;
+        }
+        let size = dir_size(&bucket).await;
+        if args.dry_run {
+            stdout!("🗑️  Would prune: {} ({})", bucket.display(), format_size(size));
+            pruned += 1;
+            reclaimed += if staging { 0 } else { size };
+            continue;
+        }
+
+        match prune_bucket(&bucket, &action).await {
+            Ok((note, target)) => {
+                if let Some(journal) = &journal {
+                    journal.record_prune(&bucket, action.name(), target.as_deref())?;
+                }
+                stdout!("🗑️  Pruned: {} ({}){}", bucket.display(), format_size(size), note);
+                pruned += 1;
+                reclaimed += if staging { 0 } else { size };
+            }
+            Err(e) => {
+                stderr!("❌ Error pruning {}: {:#}", bucket.display(), e);
+                errors += 1;
+            }
+        }
+    }
+
+    stdout!("\n📊 Summary:");
+    stdout!("  Months pruned: {}", pruned);
+    stdout!("  Space reclaimed: {}", format_size(reclaimed));
+    if purged > 0 {
+        stdout!("  Past their grace period and deleted: {} ({})", purged, format_size(purged_bytes));
+    }
+    stdout!("  Errors: {}", errors);
+
+    Ok(Exit::from_failures(errors))
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Trash => "trash",
+            Action::Delete(_) => "delete",
+            Action::Archive(_) => "archive",
+        }
+    }
+}
+
+/// Prune one month, returning a note for the output line and where it went,
+/// if anywhere.
+async fn prune_bucket(bucket: &Path, action: &Action) -> Result<(String, Option<PathBuf>)> {
+    match action {
+        Action::Trash => {
+            let bucket = bucket.to_path_buf();
+            let trashed = tokio::task::spawn_blocking(move || trash(&bucket)).await??;
+            Ok((" → trash".to_string(), Some(trashed)))
+        }
+        Action::Delete(Some(pending)) => {
+            fs::create_dir_all(pending)
+                .await
+                .with_context(|| format!("Failed to create directory: {:?}", pending))?;
+            let staged = pending.join(bucket.file_name().context("Bucket has no name")?);
+            if fs::try_exists(&staged).await? {
+                anyhow::bail!("{:?} is already waiting for deletion", staged);
+            }
+            fs::rename(bucket, &staged)
+                .await
+                .with_context(|| format!("Failed to move {:?} to {:?}", bucket, staged))?;
+            Ok((format!(" → {}", staged.display()), Some(staged)))
+        }
+        Action::Delete(None) => {
+            fs::remove_dir_all(bucket)
+                .await
+                .with_context(|| format!("Failed to delete {:?}", bucket))?;
+            Ok((String::new(), None))
+        }
+        Action::Archive(format) => {
+            let archive = archive::create_verified(bucket, *format).await?;
+            fs::remove_dir_all(bucket)
+                .await
+                .with_context(|| format!("Failed to delete {:?} after archiving", bucket))?;
+            Ok((format!(" → {}", archive.display()), Some(archive)))
+        }
+    }
+}
+
+/// Delete for good the months that `--delete` staged on a day more than
+/// `grace` ago. Returns how many staging days were purged, their size and
+/// how many failed.
+async fn purge_pending(
+    base: &Path,
+    grace: Duration,
+    dry_run: bool,
+    journal: Option<&Journal>,
+) -> Result<(usize, u64, usize)> {
+    let pending = base.join(STATE_DIR).join(PENDING_DIR);
+    let mut entries = match fs::read_dir(&pending).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0, 0)),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read directory: {:?}", pending)),
+    };
+
+    let now = Local::now();
+    let mut days = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let staged_on = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| NaiveDate::parse_from_str(name, "%Y-%m-%d").ok())
+            .and_then(|day| Local.from_local_datetime(&day.and_hms_opt(0, 0, 0)?).earliest());
+        if staged_on.is_some_and(|t| t + grace <= now) {
+            days.push(entry.path());
+        }
+    }
+    days.sort();
+
+    let (mut purged, mut bytes, mut errors) = (0, 0, 0);
+    for day in days {
+        let size = dir_size(&day).await;
+        if dry_run {
+            stdout!("🔥 Would delete for good: {} ({})", day.display(), format_size(size));
+        } else {
+            if let Err(e) = fs::remove_dir_all(&day).await {
+                stderr!("❌ Error deleting {}: {}", day.display(), e);
+                errors += 1;
+                continue;
+            }
+            stdout!("🔥 Deleted for good: {} ({})", day.display(), format_size(size));
+            if let Some(journal) = journal {
+                journal.record_prune(&day, "purge", None)?;
+            }
+        }
+        purged += 1;
+        bytes += size;
+    }
+    Ok((purged, bytes, errors))
+}
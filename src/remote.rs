@@ -0,0 +1,99 @@
+//! Destinations that aren't a local directory, such as `--dest
+//! s3://bucket/prefix` or `--dest user@host:/path`. The plan is worked out
+//! as if the buckets were local folders of the first source directory; each
+//! move then uploads its entry under the key its destination would have had,
+//! relative to that base, and removes the original unless `--copy` is given.
+//!
+//! Nothing already on the remote is overwritten, so with `--journal` each
+//! file is journaled once sent: an upload of a directory that was cut off
+//! skips those files when it is tried again rather than failing on them.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::journal::Journal;
+use crate::s3;
+use crate::ssh;
+
+#[derive(Clone, Debug)]
+pub enum Remote {
+    S3(s3::Bucket),
+    Ssh(ssh::Host),
+}
+
+impl Remote {
+    /// The remote `dest` names, or `None` for a local path. Like scp, a
+    /// colon before any `/` means a host.
+    pub fn parse(dest: &Path) -> Option<Result<Remote>> {
+        let dest = dest.to_str()?;
+        if dest.starts_with("s3://") {
+            return Some(s3::parse(dest).map(Remote::S3).map_err(anyhow::Error::msg));
+        }
+        ssh::parse(dest).map(|host| Ok(Remote::Ssh(host)))
+    }
+
+    /// Where `key` ends up, as recorded in the journal.
+    pub fn uri(&self, key: &str) -> String {
+        match self {
+            Remote::S3(bucket) => bucket.uri(key),
+            Remote::Ssh(host) => host.uri(key),
+        }
+    }
+
+    /// Upload the file or directory at `local` to `key`, returning the bytes
+    /// sent. A directory's files go under `key/`, by their relative paths;
+    /// those `journal` says an earlier run sent are left as they are.
+    pub async fn upload(&self, local: &Path, key: &str, journal: Option<&Journal>) -> Result<u64> {
+        let mut files = Vec::new();
+        for entry in WalkDir::new(local).follow_links(false) {
+            let entry = entry.with_context(|| format!("Failed to read {:?}", local))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(local).unwrap_or(entry.path());
+            let key = match key_of(relative).as_deref() {
+                Some("") => key.to_string(),
+                Some(relative) => format!("{}/{}", key, relative),
+                None => anyhow::bail!("{:?} isn't valid UTF-8, which remote keys need", entry.path()),
+            };
+            let size = entry.metadata()?.len();
+            files.push((entry.into_path(), key, size));
+        }
+        let mut sent = 0;
+        for (file, key, size) in files {
+            let uri = self.uri(&key);
+            if let Some(journal) = journal {
+                if journal.uploaded_before(&file, &uri, size)? {
+                    continue;
+                }
+            }
+            match self {
+                Remote::S3(bucket) => bucket.upload(&file, &key, size).await,
+                Remote::Ssh(host) => host.upload(&file, &key, size).await,
+            }
+            .with_context(|| format!("Failed to upload {:?} to {}", file, uri))?;
+            if let Some(journal) = journal {
+                journal.record_uploaded_file(&file, &uri, size)?;
+            }
+            sent += size;
+        }
+        Ok(sent)
+    }
+}
+
+/// The key for `relative`, its components joined with `/` whatever the
+/// platform separates them with, or `None` if it isn't valid UTF-8.
+pub fn key_of(relative: &Path) -> Option<String> {
+    let parts: Option<Vec<&str>> = relative.components().map(|part| part.as_os_str().to_str()).collect();
+    Some(parts?.join("/"))
+}
+
+impl std::fmt::Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Remote::S3(bucket) => write!(f, "{}", bucket.uri("").trim_end_matches('/')),
+            Remote::Ssh(host) => write!(f, "{}:{}", host.host, host.path),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! `chronoban plan`: work out what a run would do without doing it, and
+//! print the plan as JSON, or compare it against a plan saved earlier to
+//! see what a change of options or config would do before committing to it.
+//!
+//! ```sh
+//! chronoban plan -d ~/Downloads > before.json
+//! chronoban plan -d ~/Downloads --format %Y/%m --diff before.json
+//! ```
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::cli::Args;
+use crate::exit::Exit;
+use crate::json::{self, Value};
+use crate::output::{self, stdout, OutputFormat};
+use crate::plan::{Plan, PlanEntry};
+use crate::scan;
+use crate::schema::SCHEMA_VERSION;
+
+#[derive(clap::Args, Debug)]
+pub struct PlanArgs {
+    /// Compare against a saved plan, either `chronoban plan` output or a
+    /// `--dry-run --output json` summary, and report the moves that changed
+    #[arg(long, value_name = "PLAN")]
+    diff: Option<PathBuf>,
+
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+/// What `chronoban plan` does with the plan once it has one.
+#[derive(Clone, Debug)]
+pub enum Planning {
+    Print,
+    Compare(PathBuf),
+    /// Totals for `chronoban scan`.
+    Scan,
+}
+
+pub async fn run(args: PlanArgs) -> Result<Exit> {
+    let mut organize = *args.args;
+    if organize.watch.is_some() {
+        anyhow::bail!("plan works out a single run and can't be combined with --watch");
+    }
+    organize.dry_run = true;
+    organize.planning = Some(match args.diff {
+        Some(saved) => Planning::Compare(saved),
+        None => {
+            // The plan is JSON, which keeps progress off stdout
+            organize.output = OutputFormat::Json;
+            Planning::Print
+        }
+    });
+    crate::organize::run(organize).await
+}
+
+pub async fn report(planning: &Planning, plan: &Plan, args: &Args) -> Result<()> {
+    let saved = match planning {
+        Planning::Print => {
+            output::flush();
+            println!("{}", plan.to_json());
+            return Ok(());
+        }
+        Planning::Scan => {
+            scan::report(plan, args.now.unwrap_or_else(SystemTime::now), args.output).await;
+            return Ok(());
+        }
+        Planning::Compare(saved) => saved,
+    };
+    let text = std::fs::read_to_string(saved).with_context(|| format!("Failed to read plan {:?}", saved))?;
+    let value = json::parse(&text).map_err(|e| anyhow::anyhow!("{:?} isn't JSON: {}", saved, e))?;
+    // A dry-run summary carries its plan under "plan"
+    let value = value.get("plan").unwrap_or(&value);
+    let old = Plan::from_json(value).map_err(|e| anyhow::anyhow!("{:?} isn't a plan: {}", saved, e))?;
+    let changes = Changes::between(&old, plan);
+    match args.output {
+        OutputFormat::Text => changes.print(saved),
+        OutputFormat::Json => {
+            output::flush();
+            println!("{}", changes.to_json());
+        }
+    }
+    Ok(())
+}
+
+/// How the moves of two plans differ, by source path. Entries a plan
+/// can't move count as not moved.
+#[derive(Default)]
+struct Changes<'a> {
+    added: Vec<&'a PlanEntry>,
+    removed: Vec<&'a PlanEntry>,
+    /// The same source going somewhere else: before, then after.
+    changed: Vec<(&'a PlanEntry, &'a PlanEntry)>,
+    unchanged: usize,
+}
+
+impl<'a> Changes<'a> {
+    fn between(old: &'a Plan, new: &'a Plan) -> Changes<'a> {
+        let mut before: BTreeMap<&Path, &PlanEntry> = old.moves().map(|e| (e.source.as_path(), e)).collect();
+        let mut changes = Changes::default();
+        let mut after: Vec<&PlanEntry> = new.moves().collect();
+        after.sort_by(|a, b| a.source.cmp(&b.source));
+        for entry in after {
+            match before.remove(entry.source.as_path()) {
+                Some(was) if was.destination == entry.destination => changes.unchanged += 1,
+                Some(was) => changes.changed.push((was, entry)),
+                None => changes.added.push(entry),
+            }
+        }
+        changes.removed = before.into_values().collect();
+        changes
+    }
+
+    fn print(&self, saved: &Path) {
+        if self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() {
+            stdout!("✅ Same moves as {} ({} entries)", saved.display(), self.unchanged);
+            return;
+        }
+        stdout!("📋 Changes against {}:", saved.display());
+        for entry in &self.added {
+            stdout!("  + {} -> {}", entry.source.display(), entry.destination.display());
+        }
+        for entry in &self.removed {
+            stdout!("  - {} -> {}", entry.source.display(), entry.destination.display());
+        }
+        for (was, now) in &self.changed {
+            stdout!("  ~ {}: {} -> {}", now.source.display(), was.destination.display(), now.destination.display());
+        }
+        stdout!("\n📊 Summary:");
+        stdout!("  Added: {}", self.added.len());
+        stdout!("  Removed: {}", self.removed.len());
+        stdout!("  Changed: {}", self.changed.len());
+        stdout!("  Unchanged: {}", self.unchanged);
+    }
+
+    fn to_json(&self) -> Value {
+        let moves = |entries: &[&PlanEntry]| {
+            Value::Array(
+                entries
+                    .iter()
+                    .map(|e| json::object([("source", e.source.as_path().into()), ("destination", e.destination.as_path().into())]))
+                    .collect(),
+            )
+        };
+        let changed = self
+            .changed
+            .iter()
+            .map(|(was, now)| {
+                json::object([
+                    ("source", now.source.as_path().into()),
+                    ("before", was.destination.as_path().into()),
+                    ("after", now.destination.as_path().into()),
+                ])
+            })
+            .collect();
+        json::object([
+            ("schema_version", SCHEMA_VERSION.into()),
+            ("added", moves(&self.added)),
+            ("removed", moves(&self.removed)),
+            ("changed", Value::Array(changed)),
+            ("unchanged", self.unchanged.into()),
+        ])
+    }
+}
@@ -0,0 +1,78 @@
+//! `--report`: a CSV file with one row per entry a run dealt with, what
+//! happened to it and why, for auditing a large run in a spreadsheet or
+//! handing to whoever owns the data.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::plan::DateSource;
+
+const HEADER: &str = "path,action,target,date_source,size,error";
+
+struct Row {
+    path: PathBuf,
+    action: &'static str,
+    target: Option<PathBuf>,
+    date_source: DateSource,
+    size: u64,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Report {
+    rows: Vec<Row>,
+}
+
+impl Report {
+    pub fn add(
+        &mut self,
+        path: &Path,
+        action: &'static str,
+        target: Option<&Path>,
+        date_source: DateSource,
+        size: u64,
+        error: Option<String>,
+    ) {
+        self.rows.push(Row {
+            path: path.to_path_buf(),
+            action,
+            target: target.map(Path::to_path_buf),
+            date_source,
+            size,
+            error,
+        });
+    }
+
+    /// Write the rows, sorted by path, replacing whatever `file` held.
+    pub fn write(mut self, file: &Path) -> Result<()> {
+        self.rows.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut out = std::io::BufWriter::new(
+            std::fs::File::create(file).with_context(|| format!("Failed to create report {:?}", file))?,
+        );
+        writeln!(out, "{}", HEADER)?;
+        for row in &self.rows {
+            let target = row.target.as_deref().map(|t| t.to_string_lossy()).unwrap_or_default();
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                field(&row.path.to_string_lossy()),
+                row.action,
+                field(&target),
+                row.date_source.as_str(),
+                row.size,
+                field(row.error.as_deref().unwrap_or_default()),
+            )?;
+        }
+        out.flush().with_context(|| format!("Failed to write report {:?}", file))
+    }
+}
+
+/// `value` quoted for CSV when it needs to be.
+fn field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
@@ -0,0 +1,94 @@
+//! Routing entries into category subfolders within their bucket.
+
+use std::path::Path;
+
+use crate::filetype::FileType;
+
+/// Files matching one of `patterns` go into `folder` inside their bucket. A
+/// pattern is an extension (`jpg`) or a MIME type, optionally with a wildcard
+/// subtype (`image/*`), which only matches when types are detected.
+#[derive(Clone, Debug)]
+pub struct Route {
+    folder: String,
+    patterns: Vec<String>,
+}
+
+impl Route {
+    pub fn new(folder: &str, patterns: Vec<String>) -> Result<Route, String> {
+        let folder = folder.trim();
+        if folder.is_empty()
+            || Path::new(folder).is_absolute()
+            || folder.split('/').any(|part| part.is_empty() || part == "." || part == "..")
+        {
+            return Err(format!("invalid route folder: {:?}", folder));
+        }
+        let patterns: Vec<String> = patterns
+            .iter()
+            .map(|p| p.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|p| !p.is_empty())
+            .map(|p| canonical_extension(&p).to_string())
+            .collect();
+        if patterns.is_empty() {
+            return Err(format!("route {:?} lists no extensions", folder));
+        }
+        Ok(Route {
+            folder: folder.to_string(),
+            patterns,
+        })
+    }
+
+    fn matches(&self, ext: Option<&str>, detected: Option<&FileType>) -> bool {
+        self.patterns.iter().any(|pattern| match pattern.split_once('/') {
+            Some((kind, "*")) => detected.is_some_and(|t| t.mime.split('/').next() == Some(kind)),
+            Some(_) => detected.is_some_and(|t| t.mime == pattern),
+            None => ext == Some(pattern.as_str()),
+        })
+    }
+}
+
+/// Fold common spelling variants so `jpeg` routes also catch `.jpg` files.
+pub fn canonical_extension(ext: &str) -> &str {
+    match ext {
+        "jpeg" | "jpe" => "jpg",
+        "tiff" => "tif",
+        "htm" => "html",
+        "yml" => "yaml",
+        other => other,
+    }
+}
+
+/// Parse `--route 'jpg,png,heic=Photos'`.
+pub fn parse_route(s: &str) -> Result<Route, String> {
+    let (extensions, folder) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected EXT,EXT=FOLDER, found {:?}", s))?;
+    Route::new(folder, extensions.split(',').map(String::from).collect())
+}
+
+/// Routes in priority order: command-line routes first, then the config file's.
+#[derive(Debug, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new(routes: Vec<Route>) -> Router {
+        Router { routes }
+    }
+
+    /// The subfolder a file belongs in, if any route claims it. A detected
+    /// content type overrides the file's (possibly lying) extension.
+    pub fn folder_for(&self, path: &Path, detected: Option<&FileType>) -> Option<&str> {
+        let ext = match detected {
+            Some(t) => Some(t.extension.to_string()),
+            None => path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| canonical_extension(&e.to_ascii_lowercase()).to_string()),
+        };
+        self.routes
+            .iter()
+            .find(|r| r.matches(ext.as_deref(), detected))
+            .map(|r| r.folder.as_str())
+    }
+}
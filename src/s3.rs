@@ -0,0 +1,206 @@
+//! Uploading into an S3 bucket, or anything speaking the S3 API such as
+//! MinIO. Requests are signed with SigV4 by curl, as there is no TLS or
+//! signing here. Credentials come from the usual `AWS_ACCESS_KEY_ID`,
+//! `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`; `AWS_REGION` picks the
+//! region and `AWS_ENDPOINT_URL` another service.
+//!
+//! Files over [`MULTIPART_THRESHOLD`] go up in parts, so a dropped
+//! connection costs one part rather than the whole file. Keys that already
+//! exist are never overwritten.
+
+use anyhow::{Context, Result};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Where curl sends a part's response body, which only its headers matter.
+const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
+const MULTIPART_THRESHOLD: u64 = 64 * 1024 * 1024;
+const MIN_PART_SIZE: u64 = 16 * 1024 * 1024;
+/// S3 allows 10,000 parts; some room is left.
+const MAX_PARTS: u64 = 9000;
+
+/// Numbers the files parts are staged in, as several uploads can run at once.
+static STAGED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Debug)]
+pub struct Bucket {
+    pub name: String,
+    /// Key prefix everything goes under, without leading or trailing `/`.
+    pub prefix: String,
+    region: String,
+    /// A custom endpoint, addressed path-style.
+    endpoint: Option<String>,
+}
+
+/// Parse `s3://bucket/prefix`.
+pub fn parse(s: &str) -> Result<Bucket, String> {
+    let rest = s.strip_prefix("s3://").ok_or_else(|| format!("expected s3://bucket/prefix: {:?}", s))?;
+    let (name, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if name.is_empty() {
+        return Err(format!("no bucket in {:?}", s));
+    }
+    let region = ["AWS_REGION", "AWS_DEFAULT_REGION"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "us-east-1".to_string());
+    let endpoint = std::env::var("AWS_ENDPOINT_URL").ok().filter(|v| !v.is_empty()).map(|e| e.trim_end_matches('/').to_string());
+    Ok(Bucket { name: name.to_string(), prefix: prefix.trim_matches('/').to_string(), region, endpoint })
+}
+
+impl Bucket {
+    fn key(&self, key: &str) -> String {
+        if self.prefix.is_empty() { key.to_string() } else { format!("{}/{}", self.prefix, key) }
+    }
+
+    /// `s3://bucket/prefix/key`, as the journal records it.
+    pub fn uri(&self, key: &str) -> String {
+        format!("s3://{}/{}", self.name, self.key(key))
+    }
+
+    fn url(&self, key: &str) -> String {
+        let path = encode(&self.key(key));
+        match &self.endpoint {
+            Some(endpoint) => format!("{}/{}/{}", endpoint, self.name, path),
+            None => format!("https://{}.s3.{}.amazonaws.com/{}", self.name, self.region, path),
+        }
+    }
+
+    /// Upload the file at `local` to `key`, in parts if it is large.
+    pub async fn upload(&self, local: &Path, key: &str, size: u64) -> Result<()> {
+        if size <= MULTIPART_THRESHOLD {
+            return self.request(&["-X", "PUT", "-H", "If-None-Match: *", "-T"], Some(local), &self.url(key)).await.map(drop);
+        }
+
+        let url = self.url(key);
+        let created = self.request(&["-X", "POST", "-d", ""], None, &format!("{}?uploads", url)).await?;
+        let upload_id = between(&created, "<UploadId>", "</UploadId>")
+            .context("S3 didn't start a multipart upload")?
+            .to_string();
+        let result = self.upload_parts(local, &url, &upload_id, size).await;
+        if result.is_err() {
+            // Parts left behind are billed until the upload is aborted
+            let _ = self.request(&["-X", "DELETE"], None, &format!("{}?uploadId={}", url, upload_id)).await;
+        }
+        result
+    }
+
+    async fn upload_parts(&self, local: &Path, url: &str, upload_id: &str, size: u64) -> Result<()> {
+        let part_size = MIN_PART_SIZE.max(size.div_ceil(MAX_PARTS));
+        let staged = std::env::temp_dir().join(format!("chronoban-part-{}-{}", std::process::id(), STAGED.fetch_add(1, Ordering::Relaxed)));
+        let mut etags = Vec::new();
+        let result = async {
+            for (number, offset) in (0..size).step_by(part_size as usize).enumerate() {
+                let length = part_size.min(size - offset);
+                stage_part(local, &staged, offset, length)?;
+                let part_url = format!("{}?partNumber={}&uploadId={}", url, number + 1, upload_id);
+                let headers = self.request(&["-X", "PUT", "-D", "-", "-o", NULL_DEVICE, "-T"], Some(&staged), &part_url).await?;
+                let etag = headers
+                    .lines()
+                    .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("etag")))
+                    .map(|(_, value)| value.trim().to_string())
+                    .with_context(|| format!("S3 returned no ETag for part {}", number + 1))?;
+                etags.push(etag);
+            }
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+        let _ = std::fs::remove_file(&staged);
+        result?;
+
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        for (number, etag) in etags.iter().enumerate() {
+            complete.push_str(&format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", number + 1, etag));
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+        let finish_url = format!("{}?uploadId={}", url, upload_id);
+        let answer = self
+            .request(&["-X", "POST", "-H", "If-None-Match: *", "--data-binary", &complete], None, &finish_url)
+            .await?;
+        // Completion can fail after S3 has already answered 200
+        if answer.contains("<Error>") {
+            anyhow::bail!("S3 refused to complete the upload: {}", between(&answer, "<Message>", "</Message>").unwrap_or(&answer));
+        }
+        Ok(())
+    }
+
+    /// Run one signed request and return what curl printed. Credentials go
+    /// to curl on stdin, out of the process list.
+    async fn request(&self, args: &[&str], file: Option<&Path>, url: &str) -> Result<String> {
+        let key = std::env::var("AWS_ACCESS_KEY_ID").context("S3 needs AWS_ACCESS_KEY_ID set")?;
+        let secret = std::env::var("AWS_SECRET_ACCESS_KEY").context("S3 needs AWS_SECRET_ACCESS_KEY set")?;
+        let mut config = format!("aws-sigv4 = \"aws:amz:{}:s3\"\nuser = \"{}:{}\"\n", self.region, quote(&key), quote(&secret));
+        if let Ok(token) = std::env::var("AWS_SESSION_TOKEN") {
+            config.push_str(&format!("header = \"x-amz-security-token: {}\"\n", quote(&token)));
+        }
+
+        let mut command = Command::new("curl");
+        command.args(["-sS", "--fail-with-body", "-m", &TIMEOUT.as_secs().to_string(), "-K", "-"]).args(args);
+        if let Some(file) = file {
+            command.arg(file);
+        }
+        let mut child = command
+            .arg(url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start curl")?;
+        if let Some(mut pipe) = child.stdin.take() {
+            pipe.write_all(config.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !output.status.success() {
+            let why = between(&stdout, "<Message>", "</Message>").map(String::from).unwrap_or_else(|| {
+                String::from_utf8_lossy(&output.stderr).trim().to_string()
+            });
+            anyhow::bail!("S3 request failed: {}", why);
+        }
+        Ok(stdout)
+    }
+}
+
+/// Copy `length` bytes of `local` from `offset` into `staged`, for curl to
+/// send as one part.
+fn stage_part(local: &Path, staged: &PathBuf, offset: u64, length: u64) -> Result<()> {
+    let mut source = std::fs::File::open(local).with_context(|| format!("Failed to open {:?}", local))?;
+    source.seek(SeekFrom::Start(offset))?;
+    let mut part = std::fs::File::create(staged).with_context(|| format!("Failed to create {:?}", staged))?;
+    let copied = std::io::copy(&mut source.take(length), &mut part)?;
+    if copied != length {
+        anyhow::bail!("{:?} shrank while uploading", local);
+    }
+    Ok(())
+}
+
+fn between<'a>(text: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let start = text.find(open)? + open.len();
+    let end = text[start..].find(close)? + start;
+    Some(&text[start..end])
+}
+
+/// Percent-encode a key for the URL, leaving its `/` separators.
+fn encode(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Escape a value for a curl config file.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
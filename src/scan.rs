@@ -0,0 +1,114 @@
+//! `chronoban scan`: what a run would do to a directory, in numbers, for
+//! sizing up a large share before organizing it. Nothing is moved.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use crate::cli::Args;
+use crate::exit::Exit;
+use crate::fsutil::dir_size;
+use crate::json::{self, Value};
+use crate::output::{self, stdout, OutputFormat};
+use crate::plan::Plan;
+use crate::replan::Planning;
+use crate::schema::SCHEMA_VERSION;
+use crate::stats::BucketStats;
+use crate::units::format_size;
+
+#[derive(clap::Args, Debug)]
+pub struct ScanArgs {
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// The age histogram's bins, by upper bound.
+const AGES: &[(&str, u64)] = &[
+    ("under 1 day", DAY),
+    ("1 day to 1 week", 7 * DAY),
+    ("1 week to 1 month", 30 * DAY),
+    ("1 to 3 months", 91 * DAY),
+    ("3 months to 1 year", 365 * DAY),
+    ("1 to 2 years", 2 * 365 * DAY),
+    ("2 to 5 years", 5 * 365 * DAY),
+    ("over 5 years", u64::MAX),
+];
+
+pub async fn run(args: ScanArgs) -> Result<Exit> {
+    let mut organize = *args.args;
+    if organize.watch.is_some() {
+        anyhow::bail!("scan looks at a single run and can't be combined with --watch");
+    }
+    organize.dry_run = true;
+    organize.planning = Some(Planning::Scan);
+    crate::organize::run(organize).await
+}
+
+/// Totals of the moves in `plan`, aged from `now`.
+pub async fn report(plan: &Plan, now: SystemTime, output: OutputFormat) {
+    let mut buckets: BTreeMap<String, BucketStats> = BTreeMap::new();
+    let mut sources: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut ages = vec![0usize; AGES.len()];
+    let mut total = BucketStats::new(String::new());
+    for entry in plan.moves().filter(|e| !e.is_deletion()) {
+        // Planning only measures directories when a limit needs it
+        let size = if entry.source.is_dir() { dir_size(&entry.source).await } else { entry.size };
+        let name = entry.bucket.strip_prefix(&plan.base).unwrap_or(&entry.bucket).display().to_string();
+        buckets.entry(name.clone()).or_insert_with(|| BucketStats::new(name)).add(&entry.source, size);
+        total.add(&entry.source, size);
+        *sources.entry(entry.date_source.as_str()).or_default() += 1;
+        let age = now.duration_since(entry.date).unwrap_or(Duration::ZERO).as_secs();
+        ages[AGES.iter().position(|&(_, below)| age < below).unwrap_or(AGES.len() - 1)] += 1;
+    }
+    let conflicts = plan.conflicts().count();
+
+    match output {
+        OutputFormat::Text => {
+            stdout!("📊 Would organize {} entries, {}", total.files, format_size(total.bytes));
+            if conflicts > 0 {
+                stdout!("  Conflicts: {}", conflicts);
+            }
+            stdout!("  Left alone: {}", plan.skipped + plan.kept + plan.not_owned);
+            if !buckets.is_empty() {
+                stdout!("\n📅 Per bucket:");
+                for bucket in buckets.values() {
+                    stdout!("{}", bucket.line());
+                }
+            }
+            if !sources.is_empty() {
+                stdout!("\n🔎 Dated by:");
+                for (source, count) in &sources {
+                    stdout!("  {}: {} ({:.0}%)", source, count, *count as f64 * 100.0 / total.files as f64);
+                }
+                stdout!("\n⏳ Age:");
+                let widest = ages.iter().copied().max().unwrap_or(0).max(1);
+                for (&(label, _), &count) in AGES.iter().zip(&ages) {
+                    let bar = "#".repeat((count * 40).div_ceil(widest));
+                    stdout!("  {:>18}: {:>7} {}", label, count, bar);
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let document = json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("base", plan.base.as_path().into()),
+                ("files", total.files.into()),
+                ("bytes", total.bytes.into()),
+                ("conflicts", conflicts.into()),
+                ("skipped", (plan.skipped + plan.kept + plan.not_owned).into()),
+                ("buckets", Value::Array(buckets.values().map(BucketStats::to_json).collect())),
+                ("date_sources", json::object(sources.into_iter().map(|(source, count)| (source, count.into())))),
+                ("ages", Value::Array(
+                    AGES.iter()
+                        .zip(&ages)
+                        .map(|(&(label, _), &count)| json::object([("age", label.into()), ("files", count.into())]))
+                        .collect(),
+                )),
+            ]);
+            output::flush();
+            println!("{}", document);
+        }
+    }
+}
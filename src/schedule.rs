@@ -0,0 +1,211 @@
+//! When a watcher runs: every `--watch` interval, or at the times of a cron
+//! expression in the config file, each pushed back by a random jitter so a
+//! fleet of machines doesn't hit a shared NAS at the same second:
+//!
+//! ```toml
+//! [schedule]
+//! cron = "0 3 * * *"
+//! jitter = "10m"
+//! ```
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration as TimeDelta, Local, Timelike};
+use std::hash::{BuildHasher, Hasher as _};
+use std::time::Duration;
+
+use crate::json::Value;
+use crate::units::parse_duration;
+
+/// The `[schedule]` table of the config file.
+#[derive(Clone, Debug, Default)]
+pub struct ScheduleConfig {
+    pub cron: Option<Cron>,
+    pub jitter: Option<Duration>,
+}
+
+impl ScheduleConfig {
+    pub fn from_config(table: &Value) -> Result<ScheduleConfig> {
+        let cron = match table.get("cron") {
+            Some(v) => {
+                let expr = v.as_str().context("schedule.cron must be a cron expression")?;
+                Some(Cron::parse(expr).map_err(anyhow::Error::msg).context("schedule.cron")?)
+            }
+            None => None,
+        };
+        let jitter = match table.get("jitter") {
+            Some(Value::String(s)) => Some(parse_duration(s).map_err(anyhow::Error::msg).context("schedule.jitter")?),
+            Some(v) => Some(Duration::from_secs(v.as_u64().context("schedule.jitter must be a duration such as \"5m\"")?)),
+            None => None,
+        };
+        Ok(ScheduleConfig { cron, jitter })
+    }
+}
+
+/// When runs are due.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    pub period: Period,
+    pub jitter: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub enum Period {
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// When the first run is due: right away for an interval, at the next
+    /// matching time for cron.
+    pub fn first(&self, now: DateTime<Local>) -> DateTime<Local> {
+        match &self.period {
+            Period::Every(_) => now,
+            Period::Cron(_) => self.after(now),
+        }
+    }
+
+    /// When the run after one ending at `now` is due.
+    pub fn after(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let due = match &self.period {
+            Period::Every(interval) => now + TimeDelta::from_std(*interval).unwrap_or(TimeDelta::MAX),
+            Period::Cron(cron) => cron.next(now),
+        };
+        due + jitter(self.jitter)
+    }
+}
+
+/// A random delay below `max`.
+fn jitter(max: Duration) -> TimeDelta {
+    if max.is_zero() {
+        return TimeDelta::zero();
+    }
+    // Every RandomState is keyed afresh, which is all the randomness needed
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    let millis = random % max.as_millis().max(1) as u64;
+    TimeDelta::milliseconds(millis as i64)
+}
+
+/// A five-field cron expression (minute, hour, day of month, month, day of
+/// week) or one of `@hourly`, `@daily`, `@weekly` and `@monthly`. Fields
+/// take `*`, numbers, ranges, lists and `/` steps; as in cron, when both
+/// days are restricted a time matching either one is due.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Cron, String> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected five fields (minute hour day month weekday): {:?}", expr));
+        };
+        let mut weekdays = field(weekday, 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Cron {
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)?,
+            days: field(day, 1, 31)?,
+            months: field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// The first matching minute after `now`.
+    pub fn next(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let start = now.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(now);
+        let mut time = start + TimeDelta::minutes(1);
+        // Whole days and hours that can't match are skipped, so even a
+        // leap-day schedule is found in a few thousand steps
+        let limit = start + TimeDelta::days(366 * 5);
+        while time < limit {
+            if !self.day_matches(time) {
+                time += TimeDelta::days(1);
+                time = time.with_hour(0).and_then(|t| t.with_minute(0)).unwrap_or(time);
+            } else if !has(self.hours, time.hour()) {
+                time += TimeDelta::hours(1);
+                time = time.with_minute(0).unwrap_or(time);
+            } else if !has(self.minutes, time.minute()) {
+                time += TimeDelta::minutes(1);
+            } else {
+                return time;
+            }
+        }
+        limit
+    }
+
+    fn day_matches(&self, time: DateTime<Local>) -> bool {
+        if !has(self.months, time.month()) {
+            return false;
+        }
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parse one cron field into a bit set of the values it allows.
+fn field(spec: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut set = 0;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("invalid step in {:?}", spec))?;
+                if step == 0 {
+                    return Err(format!("step can't be 0 in {:?}", spec));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let number = |s: &str| -> Result<u32, String> {
+            s.parse()
+                .ok()
+                .filter(|n| (min..=max).contains(n))
+                .ok_or_else(|| format!("{:?} is not a number from {} to {} in {:?}", s, min, max, spec))
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((from, to)) => (number(from)?, number(to)?),
+                // A step after a single value runs to the end, as in cron
+                None if step > 1 => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if from > to {
+            return Err(format!("range {:?} runs backwards", range));
+        }
+        for value in (from..=to).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
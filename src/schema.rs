@@ -0,0 +1,471 @@
+//! The versioned shape of everything chronoban writes as JSON: the run
+//! summary of `--output json`, plans, journal lines and the reports of
+//! `stats` and `capabilities`. Each document carries `schema_version`.
+//!
+//! Within a version, fields are only ever added; a field that changes
+//! meaning or type, or goes away, bumps the version. Consumers should
+//! ignore fields they don't know and refuse versions newer than theirs.
+
+use anyhow::Result;
+
+use crate::json::{self, Value};
+use crate::plan::PLAN_DOCUMENT;
+
+/// Version of every JSON document chronoban writes.
+pub const SCHEMA_VERSION: u64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Document {
+    /// The run summary printed by `--output json`
+    Summary,
+    /// A plan, as embedded in dry-run summaries and journals
+    Plan,
+    /// One line of .chronoban/journal.jsonl
+    Journal,
+    /// One line of .chronoban/runs.jsonl, as listed by `chronoban history`
+    History,
+    /// `chronoban stats --output json`
+    Stats,
+    /// `chronoban capabilities --output json`
+    Capabilities,
+    /// `chronoban diff --output json`
+    Diff,
+    /// `chronoban plan --diff --output json`
+    PlanDiff,
+    /// `chronoban scan --output json`
+    Scan,
+    /// One line of a --catalog file
+    Catalog,
+    /// `chronoban find --output json`
+    Find,
+    /// `chronoban conflicts --output json`
+    Conflicts,
+    /// `chronoban bench --output json`
+    Bench,
+    /// Sent by --notify when a run fails
+    Failure,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SchemaArgs {
+    /// Which document to describe; all of them if omitted
+    #[arg(value_enum)]
+    document: Option<Document>,
+}
+
+pub fn run(args: SchemaArgs) -> Result<()> {
+    let schema = match args.document {
+        Some(document) => schema(document),
+        None => {
+            let all = [Document::Summary, Document::Plan, Document::Journal, Document::History, Document::Stats, Document::Capabilities,
+                Document::Diff, Document::PlanDiff, Document::Scan, Document::Catalog, Document::Find, Document::Conflicts,
+                Document::Bench, Document::Failure];
+            json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("documents", json::object(all.map(|d| (name(d), schema(d))))),
+            ])
+        }
+    };
+    println!("{}", schema);
+    Ok(())
+}
+
+fn name(document: Document) -> &'static str {
+    match document {
+        Document::Summary => "summary",
+        Document::Plan => "plan",
+        Document::Journal => "journal",
+        Document::History => "history",
+        Document::Stats => "stats",
+        Document::Capabilities => "capabilities",
+        Document::Diff => "diff",
+        Document::PlanDiff => "plan-diff",
+        Document::Scan => "scan",
+        Document::Catalog => "catalog",
+        Document::Find => "find",
+        Document::Conflicts => "conflicts",
+        Document::Bench => "bench",
+        Document::Failure => "failure",
+    }
+}
+
+/// A JSON Schema (draft 2020-12) for `document` at the current version.
+pub fn schema(document: Document) -> Value {
+    let (title, properties, required) = match document {
+        Document::Summary => summary(),
+        Document::Plan => ("A plan of moves", plan_properties(), &["schema_version", "document", "base", "entries"][..]),
+        Document::Journal => journal(),
+        Document::History => history(),
+        Document::Stats => (
+            "Bucket totals of an organized tree, or repeated names with --repeated-names",
+            vec![
+                ("base", field("string", "The directory examined")),
+                ("files", field("integer", "Files in all buckets")),
+                ("bytes", field("integer", "Bytes in all buckets")),
+                ("buckets", array(bucket_stats())),
+                ("min_buckets", field("integer", "With --repeated-names, the fewest buckets a name appears in")),
+                ("names", array(object("A name found in several buckets", vec![
+                    ("name", field("string", "The file name")),
+                    ("buckets", field("integer", "How many buckets hold it")),
+                    ("bytes", field("integer", "Bytes of all copies")),
+                    ("distinct", field("integer", "Distinct contents among the copies")),
+                    ("copies", array(object("One copy", vec![
+                        ("bucket", field("string", "Bucket holding the copy")),
+                        ("path", field("string", "Where the copy is")),
+                        ("bytes", field("integer", "Size of the copy")),
+                        ("sha256", nullable("string", "Hex SHA-256, null if unreadable")),
+                    ], &["bucket", "path", "bytes"]))),
+                ], &["name", "buckets", "copies"]))),
+            ],
+            &["schema_version", "base"][..],
+        ),
+        Document::Capabilities => (
+            "What the platform and a directory's filesystem support",
+            vec![
+                ("directory", field("string", "The directory whose filesystem was probed")),
+                ("os", field("string", "Operating system")),
+                ("arch", field("string", "CPU architecture")),
+                ("version", field("string", "chronoban version")),
+                ("capabilities", json::object([
+                    ("type", "object".into()),
+                    ("description", "Keyed by capability: birth_time, xattrs, reflink, renameat2, trash, notifications".into()),
+                    ("additionalProperties", object("One probed capability", vec![
+                        ("supported", field("boolean", "Whether it is available")),
+                        ("detail", field("string", "How it was found, or why it is missing")),
+                    ], &["supported", "detail"])),
+                ])),
+            ],
+            &["schema_version", "directory", "capabilities"][..],
+        ),
+        Document::Diff => (
+            "What the journal records since a time or run",
+            vec![
+                ("base", field("string", "The directory whose journal was read")),
+                ("since", field("string", "RFC 3339 time or run id the report starts after")),
+                ("runs", array(field("string", "A run in the report"))),
+                ("organized", field("integer", "Entries moved or copied into buckets")),
+                ("copied", field("integer", "Of those, entries copied rather than renamed")),
+                ("buckets", array(object("A bucket entries went into", vec![
+                    ("bucket", field("string", "Bucket path relative to the base")),
+                    ("entries", field("integer", "Entries organized into it")),
+                    ("created", field("boolean", "No earlier run had put anything in it")),
+                ], &["bucket", "entries", "created"]))),
+                ("deduplicated", field("integer", "Duplicates linked or deleted")),
+                ("deleted_empty", field("integer", "Empty files deleted")),
+                ("pruned", array(object("A month pruned", vec![
+                    ("bucket", field("string", "Bucket path relative to the base")),
+                    ("action", field("string", "trash, delete, archive or purge")),
+                ], &["bucket", "action"]))),
+                ("anomalies", array(object("Something that looks wrong now", vec![
+                    ("kind", enumeration(&["unfinished", "missing", "changed"], "A run that never finished, \
+                        an entry gone from where it was put, or one whose contents no longer match its checksum")),
+                    ("run_id", field("string", "unfinished: the run")),
+                    ("path", field("string", "missing, changed: where the entry was put")),
+                ], &["kind"]))),
+            ],
+            &["schema_version", "base", "since", "runs", "organized", "buckets", "anomalies"][..],
+        ),
+        Document::PlanDiff => (
+            "How the moves of a fresh plan differ from a saved one",
+            vec![
+                ("added", array(object("A move only the fresh plan makes", vec![
+                    ("source", field("string", "Where the entry is")),
+                    ("destination", field("string", "Where it would go")),
+                ], &["source", "destination"]))),
+                ("removed", array(object("A move only the saved plan made", vec![
+                    ("source", field("string", "Where the entry is")),
+                    ("destination", field("string", "Where it would have gone")),
+                ], &["source", "destination"]))),
+                ("changed", array(object("An entry the plans send to different places", vec![
+                    ("source", field("string", "Where the entry is")),
+                    ("before", field("string", "Where the saved plan sent it")),
+                    ("after", field("string", "Where the fresh plan sends it")),
+                ], &["source", "before", "after"]))),
+                ("unchanged", field("integer", "Moves both plans make alike")),
+            ],
+            &["schema_version", "added", "removed", "changed", "unchanged"][..],
+        ),
+        Document::Scan => (
+            "What a run would organize, in numbers",
+            vec![
+                ("base", field("string", "Where buckets would be created")),
+                ("files", field("integer", "Entries that would be organized")),
+                ("bytes", field("integer", "Bytes they hold")),
+                ("conflicts", field("integer", "Entries that couldn't be moved as planned")),
+                ("skipped", field("integer", "Entries left alone while planning")),
+                ("buckets", array(bucket_stats())),
+                ("date_sources", json::object([
+                    ("type", "object".into()),
+                    ("description", "Entries per date source, keyed as in plans".into()),
+                    ("additionalProperties", json::object([("type", "integer".into())])),
+                ])),
+                ("ages", array(object("One bin of the age histogram, youngest first", vec![
+                    ("age", field("string", "The range of ages, e.g. \"1 to 3 months\"")),
+                    ("files", field("integer", "Entries of that age")),
+                ], &["age", "files"]))),
+            ],
+            &["schema_version", "base", "files", "bytes", "buckets", "date_sources", "ages"][..],
+        ),
+        Document::Catalog => (
+            "One file organized, as recorded with --catalog",
+            vec![
+                ("run_id", field("string", "The run that moved it")),
+                ("time", field("string", "RFC 3339 time of the move")),
+                ("source", field("string", "Where it was")),
+                ("destination", field("string", "Where it went")),
+                ("size", field("integer", "Size in bytes")),
+                ("sha256", nullable("string", "Hex SHA-256 of its contents, null if unreadable")),
+                ("modified", nullable("string", "RFC 3339 modification time")),
+                ("accessed", nullable("string", "RFC 3339 access time")),
+            ],
+            &["schema_version", "run_id", "time", "source", "destination", "size"][..],
+        ),
+        Document::Find => (
+            "Files found by name",
+            vec![
+                ("pattern", field("string", "The pattern searched for")),
+                ("matches", array(object("A file found", vec![
+                    ("path", field("string", "Where it is, or was last put")),
+                    ("bucket", nullable("string", "The bucket it is in, relative to the base")),
+                    ("original", nullable("string", "Where it was before chronoban moved it, if recorded")),
+                    ("exists", field("boolean", "Whether it is still there")),
+                ], &["path", "bucket", "original", "exists"]))),
+            ],
+            &["schema_version", "pattern", "matches"][..],
+        ),
+        Document::Conflicts => (
+            "Entries journaled runs skipped for a conflict that are still waiting",
+            vec![
+                ("base", field("string", "The directory whose journal was read")),
+                ("conflicts", array(object("An outstanding conflict", vec![
+                    ("source", field("string", "The entry, still where it was")),
+                    ("target", field("string", "Where it would have gone")),
+                    ("conflict", field("string", "Why it was skipped, as in plans")),
+                    ("run_id", field("string", "The run that last skipped it")),
+                ], &["source", "target", "conflict", "run_id"]))),
+            ],
+            &["schema_version", "base", "conflicts"][..],
+        ),
+        Document::Bench => (
+            "Timings of runs at several job counts",
+            vec![
+                ("dir", field("string", "The directory benchmarked in")),
+                ("synthetic", field("boolean", "Whether a generated tree was moved, rather than the real one planned")),
+                ("trials", array(object("One run", vec![
+                    ("jobs", field("integer", "The --jobs value")),
+                    ("entries", field("integer", "Entries moved, or planned without a synthetic tree")),
+                    ("bytes", field("integer", "Bytes moved")),
+                    ("scan_secs", field("number", "Time spent before moving: starting, scanning and planning")),
+                    ("move_secs", field("number", "Time spent moving")),
+                    ("entries_per_sec", field("number", "Entries over the whole run time")),
+                ], &["jobs", "entries", "bytes", "scan_secs", "move_secs", "entries_per_sec"]))),
+                ("recommended_jobs", field("integer", "The fewest jobs within 5% of the fastest trial")),
+            ],
+            &["schema_version", "dir", "synthetic", "trials", "recommended_jobs"][..],
+        ),
+        Document::Failure => (
+            "A run that stopped with an error, as sent by --notify",
+            vec![
+                ("failed", json::object([("const", true.into())])),
+                ("base", field("string", "The directory being organized")),
+                ("error", field("string", "What went wrong")),
+            ],
+            &["schema_version", "failed", "base", "error"][..],
+        ),
+    };
+    document_schema(document, title, properties, required)
+}
+
+fn summary() -> (&'static str, Vec<(&'static str, Value)>, &'static [&'static str]) {
+    (
+        "Summary of an organize run",
+        vec![
+            ("run_id", field("string", "Identifies the run in the journal")),
+            ("base", field("string", "Where buckets were created")),
+            ("sources", array(field("string", "A directory organized"))),
+            ("dry_run", field("boolean", "Nothing was changed")),
+            ("interrupted", field("boolean", "The run was stopped before finishing its plan")),
+            ("stopped_low_space", nullable("string", "Why --stop-at-free stopped the run early")),
+            ("moved", field("integer", "Entries moved (or copied with --copy)")),
+            ("skipped", field("integer", "Entries left alone, including conflicts")),
+            ("kept", field("integer", "Entries pinned in place")),
+            ("not_owned", field("integer", "Entries owned by other users or groups (--owner, --group)")),
+            ("errors", field("integer", "Entries or hooks that failed")),
+            ("copied_across_devices", field("integer", "Moves that fell back to copying")),
+            ("copied_bytes", field("integer", "Bytes copied by those moves")),
+            ("deduplicated", field("integer", "Duplicates linked or deleted")),
+            ("reclaimed_bytes", field("integer", "Bytes freed by deduplication")),
+            ("deleted_empty", field("integer", "Empty files deleted")),
+            ("pruned_dirs", field("integer", "Directories left empty and removed (--prune-empty)")),
+            ("bytes_moved", field("integer", "Bytes moved")),
+            ("elapsed_secs", field("number", "Time spent moving")),
+            ("bytes_per_sec", field("integer", "Average throughput")),
+            ("buckets", array(bucket_stats())),
+            ("mover", identity()),
+            ("plan", json::object([("$ref", "#/$defs/plan".into())])),
+        ],
+        &["schema_version", "run_id", "base", "dry_run", "moved", "skipped", "errors"],
+    )
+}
+
+fn history() -> (&'static str, Vec<(&'static str, Value)>, &'static [&'static str]) {
+    let (_, mut properties, _) = summary();
+    properties.retain(|(key, _)| *key != "plan");
+    let at = properties.iter().position(|(key, _)| *key == "run_id").map_or(0, |i| i + 1);
+    properties.splice(at..at, [
+        ("started", field("string", "RFC 3339 time the run started")),
+        ("finished", field("string", "RFC 3339 time it finished")),
+        ("args", array(field("string", "One argument"))),
+    ]);
+    properties.push(("failures", array(object("An entry that failed", vec![
+        ("path", field("string", "The entry")),
+        ("error", field("string", "What went wrong")),
+    ], &["path", "error"]))));
+    (
+        "A finished run, as recorded in a journaled directory's history",
+        properties,
+        &["schema_version", "run_id", "started", "finished", "args", "base", "moved", "skipped", "errors"],
+    )
+}
+
+fn journal() -> (&'static str, Vec<(&'static str, Value)>, &'static [&'static str]) {
+    (
+        "One journal line; the fields present depend on the event",
+        vec![
+            ("run_id", field("string", "The run that wrote the line")),
+            ("time", field("string", "RFC 3339 time of the event")),
+            ("event", enumeration(&["move", "upload", "upload-file", "dedupe", "delete", "rollback", "conflict", "plan",
+                "finish", "prune"], "What happened")),
+            ("source", field("string", "move, upload, dedupe, delete, rollback, conflict: the entry's original path; \
+                upload-file: one file of an upload")),
+            ("target", nullable("string", "move: where the entry went; rollback: where it was put back from; \
+                conflict: where it would have gone; prune: where the month went")),
+            ("conflict", field("string", "conflict: why the entry was skipped, as in plans")),
+            ("method", field("string", "move: rename or copy")),
+            ("bytes_copied", nullable("integer", "move: bytes copied, null for a rename")),
+            ("sha256", nullable("string", "move: hex SHA-256 of a copy checked with --verify-checksum")),
+            ("uri", field("string", "upload, upload-file: where the entry or file went, such as s3://bucket/key")),
+            ("bytes_uploaded", field("integer", "upload: bytes sent; upload-file: the file's size")),
+            ("kept", field("boolean", "upload: whether the original was left in place")),
+            ("duplicate_of", field("string", "dedupe: the existing identical file")),
+            ("link", nullable("string", "dedupe: the hard link left in its place")),
+            ("plan", json::object([("$ref", "#/$defs/plan".into())])),
+            ("bucket", field("string", "prune: the month directory")),
+            ("action", field("string", "prune: trash, delete, archive or purge")),
+            ("mover", identity()),
+        ],
+        &["schema_version", "run_id", "time", "event"],
+    )
+}
+
+fn plan_properties() -> Vec<(&'static str, Value)> {
+    vec![
+        ("document", json::object([
+            ("const", PLAN_DOCUMENT.into()),
+            ("description", "Names the document and the version of its fields".into()),
+        ])),
+        ("base", field("string", "Where buckets are created")),
+        ("skipped", field("integer", "Entries left alone while planning")),
+        ("kept", field("integer", "Entries pinned in place")),
+        ("not_owned", field("integer", "Entries owned by other users or groups")),
+        ("errors", field("integer", "Entries that could not be inspected")),
+        ("entries", array(object("One planned entry", vec![
+            ("source", field("string", "Where the entry is")),
+            ("destination", field("string", "Where it goes; equal to source when it is deleted")),
+            ("bucket", field("string", "The bucket directory it goes into")),
+            ("date", field("string", "RFC 3339 date that decided the bucket")),
+            ("date_source", enumeration(&["mtime", "atime", "btime", "filename", "media", "email", "exif", "document"], "Where the date came from")),
+            ("size", field("integer", "Size in bytes")),
+            ("modified", nullable("string", "RFC 3339 modification time")),
+            ("accessed", nullable("string", "RFC 3339 access time")),
+            ("inode", nullable("integer", "Inode number when planned, null where there is none")),
+            ("conflict", json::object([
+                ("enum", Value::Array(vec!["target-exists".into(), "duplicate".into(), "conflicting-dates".into(), "sidecar".into(), Value::Null])),
+                ("description", "Why the entry can't be moved as planned".into()),
+            ])),
+            ("duplicate_of", nullable("string", "An identical file already in the bucket")),
+            ("sidecar_of", nullable("string", "The file this sidecar moves with")),
+        ], &["source", "destination", "bucket", "date", "date_source"]))),
+    ]
+}
+
+fn document_schema(
+    document: Document,
+    title: &str,
+    properties: Vec<(&str, Value)>,
+    required: &[&str],
+) -> Value {
+    let mut properties = properties;
+    properties.insert(0, ("schema_version", version()));
+    let mut fields = vec![
+        ("$schema".to_string(), Value::from("https://json-schema.org/draft/2020-12/schema")),
+        ("$id".to_string(), format!("chronoban:{}", name(document)).into()),
+        ("title".to_string(), title.into()),
+    ];
+    if let Value::Object(rest) = object(title, properties, required) {
+        fields.extend(rest.into_iter().filter(|(key, _)| key != "description"));
+    }
+    if matches!(document, Document::Summary | Document::Journal) {
+        let mut plan = plan_properties();
+        plan.insert(0, ("schema_version", version()));
+        let plan = object("A plan of moves", plan, &["base", "entries"]);
+        fields.push(("$defs".to_string(), json::object([("plan", plan)])));
+    }
+    Value::Object(fields)
+}
+
+fn version() -> Value {
+    json::object([
+        ("const", SCHEMA_VERSION.into()),
+        ("description", "Version of this schema".into()),
+    ])
+}
+
+fn bucket_stats() -> Value {
+    object("Totals for one bucket", vec![
+        ("bucket", field("string", "Bucket path relative to the base")),
+        ("files", field("integer", "Files in the bucket")),
+        ("bytes", field("integer", "Bytes in the bucket")),
+        ("largest", nullable("string", "The largest file")),
+        ("largest_bytes", nullable("integer", "Its size")),
+    ], &["bucket", "files", "bytes"])
+}
+
+fn identity() -> Value {
+    object("Who ran chronoban, where, and which version", vec![
+        ("user", field("string", "User name")),
+        ("host", field("string", "Host name")),
+        ("version", field("string", "chronoban version")),
+    ], &["user", "host", "version"])
+}
+
+fn field(ty: &str, description: &str) -> Value {
+    json::object([("type", ty.into()), ("description", description.into())])
+}
+
+fn nullable(ty: &str, description: &str) -> Value {
+    json::object([
+        ("type", Value::Array(vec![ty.into(), "null".into()])),
+        ("description", description.into()),
+    ])
+}
+
+fn enumeration(values: &[&str], description: &str) -> Value {
+    json::object([
+        ("enum", Value::Array(values.iter().map(|&v| v.into()).collect())),
+        ("description", description.into()),
+    ])
+}
+
+fn array(items: Value) -> Value {
+    json::object([("type", "array".into()), ("items", items)])
+}
+
+fn object(description: &str, properties: Vec<(&str, Value)>, required: &[&str]) -> Value {
+    json::object([
+        ("type", "object".into()),
+        ("description", description.into()),
+        ("properties", json::object(properties)),
+        ("required", Value::Array(required.iter().map(|&r| r.into()).collect())),
+    ])
+}
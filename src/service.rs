@@ -0,0 +1,45 @@
+//! Running the watcher as a systemd service (`--service`): readiness and
+//! status through the `sd_notify` protocol, so `Type=notify` units know
+//! when chronoban is up and what it last did. Without `NOTIFY_SOCKET` in
+//! the environment, as outside systemd, notifications go nowhere.
+
+/// Send `state`, newline-separated `KEY=value` assignments such as
+/// `READY=1`, to the service manager.
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy().into_owned();
+    // A leading @ names a socket in the abstract namespace
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(&path),
+    };
+    let sent = UnixDatagram::unbound().and_then(|socket| socket.send_to_addr(state.as_bytes(), &addr?));
+    if let Err(e) = sent {
+        crate::output::stderr!("⚠️  Could not notify the service manager: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) {}
+
+/// The `RELOADING=1` message, stamped with the monotonic clock as
+/// `Type=notify-reload` units require.
+#[cfg(target_os = "linux")]
+pub fn reloading() -> String {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: `now` is a valid timespec for the call to fill in.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let usec = now.tv_sec as u64 * 1_000_000 + now.tv_nsec as u64 / 1_000;
+    format!("RELOADING=1\nMONOTONIC_USEC={}", usec)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn reloading() -> String {
+    String::new()
+}
@@ -0,0 +1,79 @@
+//! Sidecar files: the `.xmp`, `.aae`, `.srt` and similar files cameras and
+//! editors leave next to a photo or video. Under `--sidecars` each goes
+//! into the bucket of the file it belongs to, dated by that file, and is
+//! only moved once that file has been.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::output::stderr;
+use crate::plan::{Conflict, Plan, PlanEntry};
+
+const EXTENSIONS: &[&str] = &["aae", "json", "lrv", "srt", "thm", "xmp"];
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    EXTENSIONS.iter().any(|e| has_extension(path, e))
+}
+
+/// The sidecar's path less its extension, which names its primary either
+/// in full (`IMG_0001.jpg.xmp`) or by stem (`IMG_0001.xmp`).
+pub fn primary_path(sidecar: &Path) -> PathBuf {
+    sidecar.with_extension("")
+}
+
+/// Whether the sidecar may name its primary by stem. JSON only counts when
+/// it names the whole file, which is how photo exports write it.
+pub fn names_stem(sidecar: &Path) -> bool {
+    !has_extension(sidecar, "json")
+}
+
+/// Hold back every file whose primary or fellow sidecars can't move, so
+/// none of them is moved without the others.
+pub fn keep_together(plan: &mut Plan) {
+    let mut groups: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    for (i, entry) in plan.entries.iter().enumerate() {
+        if let Some(primary) = &entry.sidecar_of {
+            groups.entry(primary.clone()).or_default().push(i);
+        }
+    }
+    let primaries: HashMap<&Path, usize> =
+        plan.entries.iter().enumerate().map(|(i, e)| (e.source.as_path(), i)).collect();
+    let mut held = Vec::new();
+    for (primary, sidecars) in &groups {
+        let members: Vec<usize> = primaries.get(primary.as_path()).copied().into_iter().chain(sidecars.iter().copied()).collect();
+        let stuck = if members.len() == sidecars.len() {
+            // The primary itself is no longer part of the plan
+            Some(primary.clone())
+        } else {
+            members.iter().find(|&&i| plan.entries[i].conflict.is_some()).map(|&i| plan.entries[i].source.clone())
+        };
+        if let Some(stuck) = stuck {
+            stderr!("⚠️  Not moving {} or its sidecars: {} can't be moved", primary.display(), stuck.display());
+            held.extend(members);
+        }
+    }
+    for i in held {
+        plan.entries[i].conflict.get_or_insert(Conflict::Sidecar);
+    }
+}
+
+/// The moves in the order to start them: each primary followed by its
+/// sidecars, then sidecars whose primary isn't being moved.
+pub fn in_order<'a>(moves: impl Iterator<Item = &'a PlanEntry>) -> Vec<&'a PlanEntry> {
+    let (sidecars, primaries): (Vec<_>, Vec<_>) = moves.partition(|m| m.sidecar_of.is_some());
+    let mut by_primary: BTreeMap<&Path, Vec<&PlanEntry>> = BTreeMap::new();
+    for sidecar in sidecars {
+        by_primary.entry(sidecar.sidecar_of.as_deref().unwrap()).or_default().push(sidecar);
+    }
+    let mut ordered = Vec::with_capacity(primaries.len());
+    for primary in primaries {
+        ordered.push(primary);
+        ordered.extend(by_primary.remove(primary.source.as_path()).unwrap_or_default());
+    }
+    ordered.extend(by_primary.into_values().flatten());
+    ordered
+}
@@ -0,0 +1,116 @@
+//! Uploading to another machine over SSH, for `--dest user@host:/path`.
+//! Files are streamed through `ssh` into a partial file next to their
+//! target, which is renamed into place once complete; a transfer that was
+//! cut off picks up where the partial file ends on the next run. Only a
+//! POSIX shell is needed on the far side.
+
+use anyhow::{Context, Result};
+use std::io::SeekFrom;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::process::Command;
+
+/// Suffix of the partial file a transfer writes before it is complete.
+const PARTIAL: &str = ".chronoban-part";
+
+/// What the far side exits with when the target already exists.
+const EXISTS: i32 = 17;
+
+#[derive(Clone, Debug)]
+pub struct Host {
+    /// `user@host` or `host`, as handed to ssh.
+    pub host: String,
+    /// The directory on the host, without a trailing `/`.
+    pub path: String,
+}
+
+/// Parse `[user@]host:path`, or `None` when `s` doesn't look like one: the
+/// part before the colon must be more than a drive letter and hold no `/`.
+pub fn parse(s: &str) -> Option<Host> {
+    let (host, path) = s.split_once(':')?;
+    if host.len() < 2 || host.contains(['/', '\\']) || host.ends_with('@') {
+        return None;
+    }
+    let path = match path.trim_end_matches('/') {
+        "" if path.starts_with('/') => "/",
+        "" => ".",
+        path => path,
+    };
+    Some(Host { host: host.to_string(), path: path.to_string() })
+}
+
+impl Host {
+    /// `user@host:/path/key`, as the journal records it.
+    pub fn uri(&self, key: &str) -> String {
+        format!("{}:{}", self.host, self.target(key))
+    }
+
+    fn target(&self, key: &str) -> String {
+        match self.path.as_str() {
+            "/" => format!("/{}", key),
+            path => format!("{}/{}", path, key),
+        }
+    }
+
+    /// Upload the file at `local` to `key`, resuming a partial copy an
+    /// earlier run left behind.
+    pub async fn upload(&self, local: &Path, key: &str, size: u64) -> Result<()> {
+        let target = quote(&self.target(key));
+        let partial = quote(&format!("{}{}", self.target(key), PARTIAL));
+
+        // Refuse to overwrite, make the directory and see how much of the
+        // file is already there
+        let script = format!(
+            "test -e {target} && exit {EXISTS}; mkdir -p \"$(dirname {target})\" || exit 1; \
+             if test -f {partial}; then wc -c < {partial}; else echo 0; fi",
+        );
+        let answer = self.run(&script, None).await?;
+        let have: u64 = answer.trim().parse().with_context(|| format!("Unexpected answer from {}: {:?}", self.host, answer))?;
+        let (offset, redirect) = if have > 0 && have <= size { (have, ">>") } else { (0, ">") };
+
+        if offset < size || size == 0 {
+            let mut file = tokio::fs::File::open(local).await.with_context(|| format!("Failed to open {:?}", local))?;
+            file.seek(SeekFrom::Start(offset)).await?;
+            self.run(&format!("cat {redirect} {partial}"), Some(file)).await?;
+        }
+
+        let script = format!(
+            "test -e {target} && exit {EXISTS}; test \"$(wc -c < {partial})\" -eq {size} || exit 1; mv {partial} {target}",
+        );
+        self.run(&script, None)
+            .await
+            .with_context(|| format!("{} doesn't hold all {} bytes of {:?}", self.host, size, local))?;
+        Ok(())
+    }
+
+    /// Run `script` on the host, feeding it `input`, and return what it
+    /// printed.
+    async fn run(&self, script: &str, input: Option<tokio::fs::File>) -> Result<String> {
+        let mut child = Command::new("ssh")
+            .args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=30", "--"])
+            .arg(&self.host)
+            .arg(script)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start ssh")?;
+        if let (Some(mut file), Some(mut pipe)) = (input, child.stdin.take()) {
+            tokio::io::copy(&mut file, &mut pipe).await.with_context(|| format!("Failed to send to {}", self.host))?;
+            pipe.shutdown().await?;
+        }
+        let output = child.wait_with_output().await?;
+        match output.status.code() {
+            Some(0) => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            Some(EXISTS) => anyhow::bail!("Already exists on {}, not overwriting", self.host),
+            _ => anyhow::bail!("ssh to {} failed: {}", self.host, String::from_utf8_lossy(&output.stderr).trim()),
+        }
+    }
+}
+
+/// Quote `s` for the remote shell.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
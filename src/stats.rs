@@ -0,0 +1,295 @@
+//! Per-bucket totals: files, bytes and the largest file. Shown in the
+//! organize summary and by `chronoban stats` for an existing tree.
+
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use walkdir::WalkDir;
+
+use crate::bucket::{find_buckets, parse_bucket_format, BucketFormat};
+use crate::hash::{hash_file, to_hex};
+use crate::json::{self, Value};
+use crate::marker::is_reserved;
+use crate::output::stdout;
+use crate::schema::SCHEMA_VERSION;
+use crate::units::format_size;
+
+#[derive(clap::Args, Debug)]
+pub struct StatsArgs {
+    /// Directory containing the buckets
+    #[arg(short = 'd', long = "dir", default_value = ".")]
+    path: PathBuf,
+
+    /// Bucket layout of the tree
+    #[arg(short = 'f', long, default_value = "%Y-%m", value_parser = parse_bucket_format)]
+    format: BucketFormat,
+
+    /// Largest buckets first instead of by date
+    #[arg(long)]
+    by_size: bool,
+
+    /// Instead of bucket totals, list file names found in at least this many
+    /// buckets, with each copy's size and hash
+    #[arg(long, value_name = "MIN_BUCKETS", num_args = 0..=1, default_missing_value = "3",
+        conflicts_with = "by_size", value_parser = clap::value_parser!(u64).range(2..))]
+    repeated_names: Option<u64>,
+
+    #[arg(long, value_enum, default_value = "text")]
+    output: ReportFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BucketStats {
+    /// Bucket path relative to the base.
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub largest: Option<(PathBuf, u64)>,
+}
+
+impl BucketStats {
+    pub fn new(name: String) -> Self {
+        BucketStats {
+            name,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, path: &Path, size: u64) {
+        self.files += 1;
+        self.bytes += size;
+        if self.largest.as_ref().is_none_or(|(_, largest)| size > *largest) {
+            self.largest = Some((path.to_path_buf(), size));
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (largest, largest_bytes) = match &self.largest {
+            Some((path, size)) => (Some(path.as_path()), Some(*size)),
+            None => (None, None),
+        };
+        json::object([
+            ("bucket", self.name.as_str().into()),
+            ("files", self.files.into()),
+            ("bytes", self.bytes.into()),
+            ("largest", largest.into()),
+            ("largest_bytes", largest_bytes.into()),
+        ])
+    }
+
+    /// One indented summary line.
+    pub fn line(&self) -> String {
+        let largest = match &self.largest {
+            Some((path, size)) => format!(" (largest: {}, {})",
+                path.file_name().unwrap_or_default().to_string_lossy(), format_size(*size)),
+            None => String::new(),
+        };
+        format!("  {}: {} files, {}{}", self.name, self.files, format_size(self.bytes), largest)
+    }
+}
+
+pub async fn run(args: StatsArgs) -> Result<()> {
+    let base = fs::canonicalize(&args.path)
+        .await
+        .with_context(|| format!("Failed to access directory: {:?}", args.path))?;
+
+    let buckets = find_buckets(&base, &args.format).await?;
+    if let Some(min_buckets) = args.repeated_names.map(|n| n as usize) {
+        let dirs = buckets.into_iter().map(|(dir, _)| dir).collect();
+        let repeated = tokio::task::spawn_blocking({
+            let base = base.clone();
+            move || repeated_names(&base, dirs, min_buckets)
+        })
+        .await?;
+        print_repeated(&base, &repeated, min_buckets, args.output);
+        return Ok(());
+    }
+
+    let mut stats = Vec::with_capacity(buckets.len());
+    for (dir, _) in buckets {
+        let name = dir.strip_prefix(&base).unwrap_or(&dir).display().to_string();
+        stats.push(tokio::task::spawn_blocking(move || measure(&dir, name)).await?);
+    }
+    if args.by_size {
+        stats.sort_by_key(|s| std::cmp::Reverse(s.bytes));
+    }
+
+    let files: usize = stats.iter().map(|s| s.files).sum();
+    let bytes: u64 = stats.iter().map(|s| s.bytes).sum();
+
+    match args.output {
+        ReportFormat::Text => {
+            stdout!("📅 Buckets in {}:", base.display());
+            for bucket in &stats {
+                stdout!("{}", bucket.line());
+            }
+            stdout!("\n📊 Total: {} buckets, {} files, {}", stats.len(), files, format_size(bytes));
+        }
+        ReportFormat::Json => {
+            println!("{}", json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("base", base.as_path().into()),
+                ("files", files.into()),
+                ("bytes", bytes.into()),
+                ("buckets", Value::Array(stats.iter().map(BucketStats::to_json).collect())),
+            ]));
+        }
+        ReportFormat::Csv => {
+            println!("bucket,files,bytes,largest,largest_bytes");
+            for bucket in &stats {
+                let (largest, largest_bytes) = match &bucket.largest {
+                    Some((path, size)) => (path.display().to_string(), size.to_string()),
+                    None => (String::new(), String::new()),
+                };
+                println!("{},{},{},{},{}",
+                    csv_field(&bucket.name), bucket.files, bucket.bytes, csv_field(&largest), largest_bytes);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A file name that recurs across buckets.
+struct RepeatedName {
+    name: String,
+    copies: Vec<Occurrence>,
+}
+
+struct Occurrence {
+    bucket: String,
+    path: PathBuf,
+    size: u64,
+    sha256: Option<String>,
+}
+
+impl RepeatedName {
+    fn bytes(&self) -> u64 {
+        self.copies.iter().map(|c| c.size).sum()
+    }
+
+    fn buckets(&self) -> usize {
+        self.copies.iter().map(|c| &c.bucket).collect::<HashSet<_>>().len()
+    }
+
+    /// How many different contents the copies have.
+    fn distinct(&self) -> usize {
+        self.copies.iter().map(|c| &c.sha256).collect::<HashSet<_>>().len()
+    }
+}
+
+/// File names present in at least `min_buckets` of the buckets, most
+/// widespread first. Only the recurring files are hashed.
+fn repeated_names(base: &Path, buckets: Vec<PathBuf>, min_buckets: usize) -> Vec<RepeatedName> {
+    let mut by_name: HashMap<String, Vec<Occurrence>> = HashMap::new();
+    for dir in buckets {
+        let bucket = dir.strip_prefix(base).unwrap_or(&dir).display().to_string();
+        for entry in WalkDir::new(&dir).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() || is_reserved(entry.file_name()) {
+                continue;
+            }
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            let name = entry.file_name().to_string_lossy().into_owned();
+            by_name.entry(name).or_default().push(Occurrence {
+                bucket: bucket.clone(),
+                path: entry.into_path(),
+                size,
+                sha256: None,
+            });
+        }
+    }
+
+    let mut repeated: Vec<RepeatedName> = by_name
+        .into_iter()
+        .map(|(name, copies)| RepeatedName { name, copies })
+        .filter(|r| r.buckets() >= min_buckets)
+        .collect();
+    for name in &mut repeated {
+        for copy in &mut name.copies {
+            copy.sha256 = hash_file(&copy.path).ok().map(|d| to_hex(&d));
+        }
+    }
+    repeated.sort_by(|a, b| b.buckets().cmp(&a.buckets()).then_with(|| a.name.cmp(&b.name)));
+    repeated
+}
+
+fn print_repeated(base: &Path, repeated: &[RepeatedName], min_buckets: usize, output: ReportFormat) {
+    match output {
+        ReportFormat::Text => {
+            stdout!("🔁 Names in {} or more buckets in {}:", min_buckets, base.display());
+            for name in repeated {
+                stdout!("  {} ×{}: {}, {} distinct",
+                    name.name, name.buckets(), format_size(name.bytes()), name.distinct());
+                for copy in &name.copies {
+                    let hash = copy.sha256.as_deref().map_or("unreadable", |h| &h[..12]);
+                    stdout!("    {}: {} {}", copy.bucket, format_size(copy.size), hash);
+                }
+            }
+            if repeated.is_empty() {
+                stdout!("  (none)");
+            }
+        }
+        ReportFormat::Json => {
+            let names = repeated.iter().map(|name| {
+                json::object([
+                    ("name", name.name.as_str().into()),
+                    ("buckets", name.buckets().into()),
+                    ("bytes", name.bytes().into()),
+                    ("distinct", name.distinct().into()),
+                    ("copies", Value::Array(name.copies.iter().map(|copy| json::object([
+                        ("bucket", copy.bucket.as_str().into()),
+                        ("path", copy.path.as_path().into()),
+                        ("bytes", copy.size.into()),
+                        ("sha256", copy.sha256.clone().into()),
+                    ])).collect())),
+                ])
+            });
+            println!("{}", json::object([
+                ("schema_version", SCHEMA_VERSION.into()),
+                ("base", base.into()),
+                ("min_buckets", min_buckets.into()),
+                ("names", Value::Array(names.collect())),
+            ]));
+        }
+        ReportFormat::Csv => {
+            println!("name,bucket,path,bytes,sha256");
+            for name in repeated {
+                for copy in &name.copies {
+                    println!("{},{},{},{},{}", csv_field(&name.name), csv_field(&copy.bucket),
+                        csv_field(&copy.path.display().to_string()), copy.size,
+                        copy.sha256.as_deref().unwrap_or_default());
+                }
+            }
+        }
+    }
+}
+
+/// Total up every file below a bucket, however deeply nested.
+fn measure(dir: &Path, name: String) -> BucketStats {
+    let mut stats = BucketStats::new(name);
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() || is_reserved(entry.file_name()) {
+            continue;
+        }
+        if let Ok(metadata) = entry.metadata() {
+            stats.add(entry.path(), metadata.len());
+        }
+    }
+    stats
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
@@ -0,0 +1,258 @@
+//! Destination templates such as `{year}/{month}/{ext}/{name}` or
+//! `%Y-%m/{ext}`, evaluated per file to a path relative to the base directory.
+//! Literal text is a strftime format, so date codes can be mixed with
+//! placeholders.
+
+use chrono::format::{self, Item, Parsed, StrftimeItems};
+use chrono::{DateTime, Datelike, Local};
+use std::path::{Path, PathBuf};
+
+use crate::filetype::Category;
+
+/// Stand-in when a file has no extension or no recognizable category, so a
+/// placeholder never renders an empty directory name.
+const NO_EXTENSION: &str = "no-extension";
+const NO_CATEGORY: &str = "other";
+const NO_CAMERA: &str = "Unknown camera";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    /// Literal text, possibly with strftime codes.
+    Text(String),
+    Year,
+    Month,
+    Day,
+    Ext,
+    Category,
+    /// Top-level MIME type: image, video, audio, font, text or application.
+    MimeCategory,
+    /// Camera make and model from EXIF.
+    Camera,
+    Owner,
+    Parent,
+    Name,
+    Stem,
+}
+
+impl Piece {
+    fn placeholder(name: &str) -> Option<Piece> {
+        Some(match name {
+            "year" => Piece::Year,
+            "month" => Piece::Month,
+            "day" => Piece::Day,
+            "ext" => Piece::Ext,
+            "category" => Piece::Category,
+            "mime_category" => Piece::MimeCategory,
+            "camera" => Piece::Camera,
+            "owner" => Piece::Owner,
+            "parent" => Piece::Parent,
+            "name" => Piece::Name,
+            "stem" => Piece::Stem,
+            _ => return None,
+        })
+    }
+}
+
+/// A parsed `--template`. Each `/` separates one directory level; unless the
+/// last level uses `{name}` or `{stem}`, the file's own name is appended.
+#[derive(Clone, Debug)]
+pub struct Template {
+    source: String,
+    segments: Vec<Vec<Piece>>,
+    names_file: bool,
+}
+
+/// What a template can refer to for one entry.
+pub struct Fields<'a> {
+    pub path: &'a Path,
+    pub datetime: &'a DateTime<Local>,
+    /// Canonical lowercase extension, preferring the detected one.
+    pub extension: Option<&'a str>,
+    pub category: Option<Category>,
+    /// Detected MIME type, if content was sniffed.
+    pub mime: Option<&'a str>,
+    /// Only looked up when the template uses `{owner}`.
+    pub owner: Option<&'a str>,
+    /// Only read when the template uses `{camera}`.
+    pub camera: Option<&'a str>,
+}
+
+impl Template {
+    /// The destination for an entry, relative to the base.
+    pub fn render(&self, fields: &Fields) -> PathBuf {
+        let mut out = PathBuf::new();
+        for segment in &self.segments {
+            let mut part = String::new();
+            for piece in segment {
+                match piece {
+                    Piece::Text(s) if s.contains('%') => {
+                        part.push_str(&fields.datetime.format(s).to_string())
+                    }
+                    Piece::Text(s) => part.push_str(s),
+                    Piece::Year => part.push_str(&format!("{:04}", fields.datetime.year())),
+                    Piece::Month => part.push_str(&format!("{:02}", fields.datetime.month())),
+                    Piece::Day => part.push_str(&format!("{:02}", fields.datetime.day())),
+                    Piece::Ext => part.push_str(fields.extension.unwrap_or(NO_EXTENSION)),
+                    Piece::Category => {
+                        part.push_str(fields.category.map_or(NO_CATEGORY, Category::folder_name))
+                    }
+                    Piece::MimeCategory => part.push_str(mime_category(fields)),
+                    Piece::Camera => part.push_str(fields.camera.unwrap_or(NO_CAMERA)),
+                    Piece::Owner => part.push_str(fields.owner.unwrap_or(NO_CATEGORY)),
+                    Piece::Parent => part.push_str(&file_name(fields.path.parent())),
+                    Piece::Name => part.push_str(&file_name(Some(fields.path))),
+                    Piece::Stem => part.push_str(
+                        &fields.path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default(),
+                    ),
+                }
+            }
+            out.push(part);
+        }
+        if !self.names_file {
+            out.push(fields.path.file_name().unwrap_or_default());
+        }
+        out
+    }
+
+    /// Whether a name directly below the base could have been produced by
+    /// the template's first level, so earlier output is not organized again.
+    pub fn is_bucket_name(&self, name: &str) -> bool {
+        matches_pieces(&self.segments[0], name)
+    }
+
+    /// Whether rendering needs the file's owner, which costs a user lookup.
+    pub fn needs_owner(&self) -> bool {
+        self.segments.iter().flatten().any(|p| *p == Piece::Owner)
+    }
+
+    /// Whether rendering needs EXIF data, which costs reading the file.
+    pub fn needs_camera(&self) -> bool {
+        self.segments.iter().flatten().any(|p| *p == Piece::Camera)
+    }
+}
+
+const MIME_CATEGORIES: [&str; 6] = ["image", "video", "audio", "font", "text", "application"];
+
+fn mime_category(fields: &Fields) -> &'static str {
+    if let Some(kind) = fields.mime.and_then(|m| m.split('/').next()) {
+        if let Some(known) = MIME_CATEGORIES.iter().find(|c| **c == kind) {
+            return known;
+        }
+    }
+    match fields.category {
+        Some(Category::Image) => "image",
+        Some(Category::Video) => "video",
+        Some(Category::Audio) => "audio",
+        Some(Category::Font) => "font",
+        Some(Category::Text) => "text",
+        Some(_) => "application",
+        None => NO_CATEGORY,
+    }
+}
+
+impl std::fmt::Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn file_name(path: Option<&Path>) -> String {
+    path.and_then(Path::file_name)
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Match a rendered name against template pieces. Free-form placeholders
+/// match any non-empty text, so this backtracks over where they end.
+fn matches_pieces(pieces: &[Piece], s: &str) -> bool {
+    let Some((piece, rest)) = pieces.split_first() else {
+        return s.is_empty();
+    };
+    let digits = |n: usize| {
+        s.len() >= n && s.as_bytes()[..n].iter().all(u8::is_ascii_digit) && matches_pieces(rest, &s[n..])
+    };
+    match piece {
+        Piece::Text(fmt) if fmt.contains('%') => (1..=s.len())
+            .filter(|&i| s.is_char_boundary(i))
+            .any(|i| {
+                let mut parsed = Parsed::new();
+                format::parse(&mut parsed, &s[..i], StrftimeItems::new(fmt)).is_ok()
+                    && matches_pieces(rest, &s[i..])
+            }),
+        Piece::Text(lit) => s.strip_prefix(lit.as_str()).is_some_and(|s| matches_pieces(rest, s)),
+        Piece::Year => digits(4),
+        Piece::Month | Piece::Day => digits(2),
+        Piece::Category => Category::ALL
+            .iter()
+            .map(|c| c.folder_name())
+            .chain([NO_CATEGORY])
+            .any(|c| s.strip_prefix(c).is_some_and(|s| matches_pieces(rest, s))),
+        Piece::MimeCategory => MIME_CATEGORIES
+            .iter()
+            .chain(&[NO_CATEGORY])
+            .any(|c| s.strip_prefix(c).is_some_and(|s| matches_pieces(rest, s))),
+        Piece::Ext | Piece::Camera | Piece::Owner | Piece::Parent | Piece::Name | Piece::Stem => (1..=s.len())
+            .filter(|&i| s.is_char_boundary(i))
+            .any(|i| matches_pieces(rest, &s[i..])),
+    }
+}
+
+/// Parse and validate `--template`.
+pub fn parse_template(s: &str) -> Result<Template, String> {
+    if s.starts_with('/') {
+        return Err(format!("template must be a relative path: {:?}", s));
+    }
+
+    let mut segments = Vec::new();
+    for raw in s.split('/') {
+        if raw.is_empty() || raw == "." || raw == ".." {
+            return Err(format!("template must be a relative path without empty parts: {:?}", s));
+        }
+        let mut pieces = Vec::new();
+        let mut rest = raw;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('{') {
+                let (name, tail) = after
+                    .split_once('}')
+                    .ok_or_else(|| format!("unclosed '{{' in template: {:?}", s))?;
+                pieces.push(Piece::placeholder(name).ok_or_else(|| {
+                    format!(
+                        "unknown placeholder {{{}}}; expected one of year, month, day, ext, \
+                         category, mime_category, camera, owner, parent, name, stem",
+                        name
+                    )
+                })?);
+                rest = tail;
+            } else {
+                let end = rest.find('{').unwrap_or(rest.len());
+                if rest[..end].contains('}') {
+                    return Err(format!("unmatched '}}' in template: {:?}", s));
+                }
+                let text = &rest[..end];
+                if StrftimeItems::new(text).any(|item| matches!(item, Item::Error)) {
+                    return Err(format!("invalid date format {:?} in template: {:?}", text, s));
+                }
+                pieces.push(Piece::Text(text.to_string()));
+                rest = &rest[end..];
+            }
+        }
+        segments.push(pieces);
+    }
+
+    let names_file = segments
+        .last()
+        .is_some_and(|last| last.iter().any(|p| matches!(p, Piece::Name | Piece::Stem)));
+    if segments[..segments.len() - 1]
+        .iter()
+        .flatten()
+        .any(|p| matches!(p, Piece::Name | Piece::Stem))
+    {
+        return Err(format!("{{name}} and {{stem}} may only appear in the last part: {:?}", s));
+    }
+
+    Ok(Template {
+        source: s.to_string(),
+        segments,
+        names_file,
+    })
+}
@@ -0,0 +1,292 @@
+//! A parser for the subset of TOML chronoban's config uses: tables
+//! (`[a.b]`), `key = value` pairs with strings, integers, floats, booleans
+//! and (possibly multi-line) arrays. Inline tables are refused rather than
+//! misread. Documents are returned as [`Value`] objects so config code can
+//! walk them like parsed JSON.
+
+use crate::json::Value;
+
+pub fn parse(input: &str) -> Result<Value, String> {
+    let mut root = Value::Object(Vec::new());
+    let mut table: Vec<String> = Vec::new();
+    let mut lines = input.lines().enumerate().peekable();
+
+    while let Some((n, line)) = lines.next() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |msg: String| format!("line {}: {}", n + 1, msg);
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| err("unterminated table header".into()))?;
+            table = split_key(header).map_err(err)?;
+            table_mut(&mut root, &table).map_err(err)?;
+            continue;
+        }
+
+        let (key, value) = split_assignment(line)
+            .ok_or_else(|| err(format!("expected `key = value`, found {:?}", line)))?;
+        let mut value = value.trim().to_string();
+        // Arrays may span lines until their brackets balance.
+        while value.starts_with('[') && !brackets_balanced(&value) {
+            let (_, next) = lines.next().ok_or_else(|| err("unterminated array".into()))?;
+            value.push(' ');
+            value.push_str(strip_comment(next).trim());
+        }
+
+        let key = split_key(key.trim()).map_err(err)?;
+        let (last, parents) = key.split_last().ok_or_else(|| err("empty key".into()))?;
+        let mut path = table.clone();
+        path.extend(parents.iter().cloned());
+
+        let parsed = parse_value(&value).map_err(err)?;
+        let Value::Object(fields) = table_mut(&mut root, &path).map_err(err)? else {
+            unreachable!()
+        };
+        if fields.iter().any(|(k, _)| k == last) {
+            return Err(err(format!("duplicate key {:?}", last)));
+        }
+        fields.push((last.clone(), parsed));
+    }
+
+    Ok(root)
+}
+
+/// Find or create the table at `path`.
+fn table_mut<'a>(root: &'a mut Value, path: &[String]) -> Result<&'a mut Value, String> {
+    let mut current = root;
+    for name in path {
+        let Value::Object(fields) = current else {
+            return Err(format!("{:?} is not a table", name));
+        };
+        let index = match fields.iter().position(|(k, _)| k == name) {
+            Some(i) => i,
+            None => {
+                fields.push((name.clone(), Value::Object(Vec::new())));
+                fields.len() - 1
+            }
+        };
+        current = &mut fields[index].1;
+        if !matches!(current, Value::Object(_)) {
+            return Err(format!("{:?} is not a table", name));
+        }
+    }
+    Ok(current)
+}
+
+fn split_key(key: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut rest = key.trim();
+    while !rest.is_empty() {
+        let (part, remainder) = if rest.starts_with(['"', '\'']) {
+            let mut remainder = rest;
+            let Value::String(part) = parse_inner(&mut remainder)? else {
+                unreachable!("a quote starts a string")
+            };
+            (part, remainder)
+        } else {
+            let end = rest.find('.').unwrap_or(rest.len());
+            let part = rest[..end].trim();
+            if part.is_empty()
+                || !part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(format!("invalid key {:?}", key));
+            }
+            (part.to_string(), &rest[end..])
+        };
+        parts.push(part);
+        rest = remainder.trim_start();
+        if let Some(r) = rest.strip_prefix('.') {
+            rest = r.trim_start();
+        } else if !rest.is_empty() {
+            return Err(format!("invalid key {:?}", key));
+        }
+    }
+    Ok(parts)
+}
+
+fn parse_value(s: &str) -> Result<Value, String> {
+    let mut chars = s.trim();
+    let value = parse_inner(&mut chars)?;
+    if !chars.trim().is_empty() {
+        return Err(format!("unexpected trailing characters: {:?}", chars));
+    }
+    Ok(value)
+}
+
+fn parse_inner(s: &mut &str) -> Result<Value, String> {
+    *s = s.trim_start();
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    *s = &rest[i + 1..];
+                    return Ok(Value::String(out));
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('\\') => out.push('\\'),
+                    Some('"') => out.push('"'),
+                    other => return Err(format!("unsupported escape: \\{}", other.unwrap_or(' '))),
+                },
+                c => out.push(c),
+            }
+        }
+        return Err("unterminated string".into());
+    }
+    if let Some(rest) = s.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or("unterminated string")?;
+        let value = rest[..end].to_string();
+        *s = &rest[end + 1..];
+        return Ok(Value::String(value));
+    }
+    if s.starts_with('{') {
+        return Err("inline tables aren't supported; use a [table] instead".into());
+    }
+    if let Some(rest) = s.strip_prefix('[') {
+        *s = rest;
+        let mut items = Vec::new();
+        loop {
+            *s = s.trim_start();
+            if let Some(rest) = s.strip_prefix(']') {
+                *s = rest;
+                return Ok(Value::Array(items));
+            }
+            items.push(parse_inner(s)?);
+            *s = s.trim_start();
+            if let Some(rest) = s.strip_prefix(',') {
+                *s = rest;
+            } else if !s.starts_with(']') {
+                return Err("expected `,` or `]` in array".into());
+            }
+        }
+    }
+
+    let end = s.find([',', ']']).unwrap_or(s.len());
+    let token = s[..end].trim();
+    *s = &s[end..];
+    match token {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => {
+            let digits = token.replace('_', "");
+            if let Ok(n) = digits.parse::<i64>() {
+                Ok(Value::Int(n))
+            } else if let Ok(f) = digits.parse::<f64>() {
+                Ok(Value::Float(f))
+            } else {
+                Err(format!("invalid value {:?} (strings must be quoted)", token))
+            }
+        }
+    }
+}
+
+/// The characters of `s` outside strings, with their byte offsets. Basic
+/// strings may hold escaped quotes; literal strings can't.
+fn outside_strings(s: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+    s.char_indices().filter(move |&(_, c)| {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => return true,
+        }
+        false
+    })
+}
+
+/// Split `key = value` at the first `=` outside a quoted key.
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let (i, _) = outside_strings(line).find(|&(_, c)| c == '=')?;
+    Some((&line[..i], &line[i + 1..]))
+}
+
+/// Drop a trailing `#` comment, ignoring `#` inside strings.
+fn strip_comment(line: &str) -> &str {
+    match outside_strings(line).find(|&(_, c)| c == '#') {
+        Some((i, _)) => &line[..i],
+        None => line,
+    }
+}
+
+fn brackets_balanced(s: &str) -> bool {
+    let depth = outside_strings(s).fold(0i32, |depth, (_, c)| match c {
+        '[' => depth + 1,
+        ']' => depth - 1,
+        _ => depth,
+    });
+    depth <= 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn tables_and_values() {
+        let doc = parse("top = 1\n[a.b]\nname = \"x\"\nratio = 1.5\non = true\nbig = 1_000\n").unwrap();
+        assert_eq!(doc.get("top"), Some(&Value::Int(1)));
+        let b = doc.get("a").and_then(|a| a.get("b")).unwrap();
+        assert_eq!(b.get("name").and_then(Value::as_str), Some("x"));
+        assert_eq!(b.get("ratio"), Some(&Value::Float(1.5)));
+        assert_eq!(b.get("on"), Some(&Value::Bool(true)));
+        assert_eq!(b.get("big"), Some(&Value::Int(1000)));
+    }
+
+    #[test]
+    fn escapes_and_comments_in_strings() {
+        let doc = parse(r##"a = "a\"#b" # a comment
+b = 'C:\dir #1'
+c = "tab\there\\" # "quoted" in a comment
+"##).unwrap();
+        assert_eq!(doc.get("a").and_then(Value::as_str), Some("a\"#b"));
+        assert_eq!(doc.get("b").and_then(Value::as_str), Some("C:\\dir #1"));
+        assert_eq!(doc.get("c").and_then(Value::as_str), Some("tab\there\\"));
+    }
+
+    #[test]
+    fn quoted_keys_may_hold_equals_and_dots() {
+        let doc = parse("[routes]\n\"a=b.c\" = 'x'\n'lit=' = 2\nplain.\"dotted.part\" = 3\n").unwrap();
+        let routes = doc.get("routes").unwrap();
+        assert_eq!(routes.get("a=b.c").and_then(Value::as_str), Some("x"));
+        assert_eq!(routes.get("lit="), Some(&Value::Int(2)));
+        assert_eq!(routes.get("plain").and_then(|p| p.get("dotted.part")), Some(&Value::Int(3)));
+    }
+
+    #[test]
+    fn multi_line_arrays() {
+        let doc = parse("list = [\n  \"a]\", # not the end\n  'b',\n  [1, 2],\n]\nafter = 1\n").unwrap();
+        let list = doc.get("list").and_then(Value::as_array).unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].as_str(), Some("a]"));
+        assert_eq!(list[2], Value::Array(vec![Value::Int(1), Value::Int(2)]));
+        assert_eq!(doc.get("after"), Some(&Value::Int(1)));
+        assert!(parse("list = [1,\n2\n").unwrap_err().contains("unterminated array"));
+    }
+
+    #[test]
+    fn rejects_duplicates_and_inline_tables() {
+        assert!(parse("a = 1\na = 2\n").unwrap_err().contains("duplicate key"));
+        assert!(parse("[t]\nx.y = 1\n[t.x]\ny = 2\n").unwrap_err().contains("duplicate key"));
+        assert!(parse("a = { b = 1 }\n").unwrap_err().contains("inline tables"));
+        assert!(parse("a = 1\n[a]\n").unwrap_err().contains("not a table"));
+        assert!(parse("a = bare\n").is_err());
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let doc = parse("name = \"caf\u{e9} \\\"#1\\\"\"\n[nested]\nlist = [-1, 2.5, false]\n").unwrap();
+        assert_eq!(json::parse(&doc.to_string()).unwrap(), doc);
+    }
+}
@@ -0,0 +1,138 @@
+//! `--trace-file`: timings of the parts of a run, written in the Chrome
+//! trace event format that chrome://tracing, Perfetto and speedscope open,
+//! for telling whether walking, reading metadata, dating or moving is what
+//! makes a run slow. Planning shows up as one lane and each worker as
+//! another. Spans cost next to nothing unless a trace was asked for.
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::json::{self, Value};
+
+static TRACE: OnceLock<Trace> = OnceLock::new();
+
+tokio::task_local! {
+    /// The worker a task is running on; planning is lane 0.
+    static LANE: usize;
+}
+
+struct Trace {
+    started: Instant,
+    events: Mutex<Vec<Event>>,
+}
+
+struct Event {
+    name: &'static str,
+    category: &'static str,
+    lane: usize,
+    /// Microseconds since tracing started.
+    start: u64,
+    duration: u64,
+    path: Option<String>,
+}
+
+/// Start recording spans.
+pub fn enable() {
+    TRACE.get_or_init(|| Trace { started: Instant::now(), events: Mutex::new(Vec::new()) });
+}
+
+/// Timing of one piece of work, recorded when dropped.
+#[must_use = "a span times until it is dropped"]
+pub struct Span(Option<Open>);
+
+struct Open {
+    name: &'static str,
+    category: &'static str,
+    path: Option<String>,
+    lane: usize,
+    start: Instant,
+}
+
+pub fn span(name: &'static str, category: &'static str) -> Span {
+    open(name, category, None)
+}
+
+/// A span about one entry, which the trace shows when it is selected.
+pub fn span_for(name: &'static str, category: &'static str, path: &Path) -> Span {
+    open(name, category, Some(path))
+}
+
+fn open(name: &'static str, category: &'static str, path: Option<&Path>) -> Span {
+    if TRACE.get().is_none() {
+        return Span(None);
+    }
+    Span(Some(Open {
+        name,
+        category,
+        path: path.map(|p| p.display().to_string()),
+        lane: LANE.try_with(|lane| *lane).unwrap_or(0),
+        start: Instant::now(),
+    }))
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let (Some(open), Some(trace)) = (self.0.take(), TRACE.get()) else {
+            return;
+        };
+        let start = open.start.saturating_duration_since(trace.started).as_micros() as u64;
+        let event = Event {
+            name: open.name,
+            category: open.category,
+            lane: open.lane,
+            start,
+            duration: open.start.elapsed().as_micros() as u64,
+            path: open.path,
+        };
+        trace.events.lock().unwrap().push(event);
+    }
+}
+
+/// Run `task` as worker `lane`, so its spans are drawn on that worker's lane.
+pub fn in_lane<F: Future>(lane: usize, task: F) -> impl Future<Output = F::Output> {
+    LANE.scope(lane, task)
+}
+
+/// Write everything recorded so far to `file`.
+pub fn write(file: &Path) -> Result<()> {
+    let Some(trace) = TRACE.get() else {
+        return Ok(());
+    };
+    let pid = u64::from(std::process::id());
+    let events = trace.events.lock().unwrap();
+    let lanes: std::collections::BTreeSet<usize> = events.iter().map(|e| e.lane).collect();
+    let mut out = Vec::with_capacity(events.len() + lanes.len());
+    for lane in lanes {
+        let name = if lane == 0 { "planning".to_string() } else { format!("worker {}", lane) };
+        out.push(json::object([
+            ("name", "thread_name".into()),
+            ("ph", "M".into()),
+            ("pid", pid.into()),
+            ("tid", lane.into()),
+            ("args", json::object([("name", name.into())])),
+        ]));
+    }
+    for event in events.iter() {
+        let args = match &event.path {
+            Some(path) => json::object([("path", path.as_str().into())]),
+            None => Value::Object(Vec::new()),
+        };
+        out.push(json::object([
+            ("name", event.name.into()),
+            ("cat", event.category.into()),
+            ("ph", "X".into()),
+            ("ts", event.start.into()),
+            ("dur", event.duration.into()),
+            ("pid", pid.into()),
+            ("tid", event.lane.into()),
+            ("args", args),
+        ]));
+    }
+    let document = json::object([("traceEvents", Value::Array(out)), ("displayTimeUnit", "ms".into())]);
+    let mut writer = std::fs::File::create(file).with_context(|| format!("Failed to create trace {:?}", file))?;
+    writeln!(writer, "{}", document).with_context(|| format!("Failed to write trace {:?}", file))
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Move `path` into the user's trash, following the freedesktop.org trash
+/// specification on Linux and using `~/.Trash` on macOS.
+pub fn trash(path: &Path) -> Result<PathBuf> {
+    let trash_dir = trash_dir()?;
+    let files = trash_dir.join("files");
+    std::fs::create_dir_all(&files)
+        .with_context(|| format!("Failed to create trash directory: {:?}", files))?;
+
+    let name = path.file_name().context("Path has no file name")?.to_string_lossy();
+    let info_dir = trash_dir.join("info");
+    if cfg!(target_os = "linux") {
+        std::fs::create_dir_all(&info_dir)
+            .with_context(|| format!("Failed to create trash directory: {:?}", info_dir))?;
+    }
+    let mut n = 1;
+    let (dest, info) = loop {
+        let candidate = match n {
+            1 => name.to_string(),
+            n => format!("{}.{}", name, n),
+        };
+        n += 1;
+        let dest = files.join(&candidate);
+        if dest.exists() {
+            continue;
+        }
+        if !cfg!(target_os = "linux") {
+            break (dest, None);
+        }
+        // Creating the info file is what reserves the name, so two runs
+        // trashing the same name at once each get their own
+        let info = info_dir.join(format!("{}.trashinfo", candidate));
+        match reserve(&info, path) {
+            Ok(()) => break (dest, Some(info)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to write {:?}", info)),
+        }
+    };
+
+    if let Err(e) = std::fs::rename(path, &dest) {
+        if let Some(info) = info {
+            let _ = std::fs::remove_file(info);
+        }
+        return Err(e).with_context(|| {
+            format!("Failed to move {:?} to trash (is the trash on another filesystem?)", path)
+        });
+    }
+    Ok(dest)
+}
+
+/// Create the `.trashinfo` file recording where `path` came from, failing
+/// if one of that name already exists.
+fn reserve(info: &Path, path: &Path) -> std::io::Result<()> {
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(path),
+        Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+    let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(info)?;
+    file.write_all(contents.as_bytes()).inspect_err(|_| {
+        let _ = std::fs::remove_file(info);
+    })
+}
+
+pub fn trash_dir() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "macos") {
+        return Ok(home.context("HOME is not set")?.join(".Trash"));
+    }
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.map(|h| h.join(".local/share")))
+        .context("Neither XDG_DATA_HOME nor HOME is set")?;
+    Ok(data.join("Trash"))
+}
+
+fn percent_encode(path: &Path) -> String {
+    let mut out = String::new();
+    for &b in path.as_os_str().as_encoded_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
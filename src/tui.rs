@@ -0,0 +1,440 @@
+//! `chronoban tui`: look over the planned moves, grouped by bucket, and
+//! pick which files or whole months to move before anything happens. Takes
+//! every option a plain run does.
+//!
+//! On a Unix terminal the review takes over the screen: arrow keys move,
+//! space includes or skips a month or file, and the months open to list
+//! their files. Elsewhere, and with `TERM=dumb`, it is a line-based prompt
+//! with the same choices.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::Args;
+use crate::exit::Exit;
+use crate::output::{self, stderr};
+use crate::plan::Plan;
+use crate::units::format_size;
+
+#[derive(clap::Args, Debug)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+pub async fn run(args: TuiArgs) -> Result<Exit> {
+    let mut args = *args.args;
+    if args.watch.is_some() {
+        anyhow::bail!("tui reviews a single run and can't be combined with --watch");
+    }
+    if !io::stdin().is_terminal() {
+        anyhow::bail!("tui needs a terminal to ask on; use --dry-run to see the plan instead");
+    }
+    args.review = true;
+    crate::organize::run(args).await
+}
+
+/// The moves into one bucket.
+struct Group {
+    bucket: PathBuf,
+    /// Indexes into the plan's entries.
+    entries: Vec<usize>,
+    expanded: bool,
+}
+
+/// Let the user choose which of the plan's moves to make. Moves left out
+/// count as skipped; conflicts are left for the run to report.
+pub fn review(mut plan: Plan) -> Result<Plan> {
+    let mut by_bucket: BTreeMap<&Path, Vec<usize>> = BTreeMap::new();
+    for (i, entry) in plan.entries.iter().enumerate() {
+        if entry.conflict.is_none() {
+            by_bucket.entry(&entry.bucket).or_default().push(i);
+        }
+    }
+    let mut groups: Vec<Group> = by_bucket
+        .into_iter()
+        .map(|(bucket, entries)| Group { bucket: bucket.to_path_buf(), entries, expanded: false })
+        .collect();
+    if groups.is_empty() {
+        stderr!("Nothing to review: no files would be moved");
+        return Ok(plan);
+    }
+
+    let mut selected: Vec<bool> = plan.entries.iter().map(|e| e.conflict.is_none()).collect();
+    let chosen = match screen::Raw::enter() {
+        Some(raw) => screen::review(raw, &plan, &mut groups, &mut selected)?,
+        None => prompt(&plan, &mut groups, &mut selected)?,
+    };
+    if !chosen {
+        selected.fill(false);
+    }
+
+    let before = plan.entries.len();
+    let mut keep = selected.iter();
+    plan.entries.retain(|entry| *keep.next().unwrap() || entry.conflict.is_some());
+    plan.skipped += before - plan.entries.len();
+    Ok(plan)
+}
+
+/// Ask on the line; false if the user quit, so nothing is to move.
+fn prompt(plan: &Plan, groups: &mut [Group], selected: &mut [bool]) -> Result<bool> {
+    show(plan, groups, selected);
+    stderr!("Type a number to skip or include a month, 'show 2' to list its files, '2.5' to skip or include \
+        one file, 'all' or 'none', then 'go' to move the chosen files or 'quit' to move nothing. '?' repeats this.");
+    loop {
+        output::flush();
+        eprint!("> ");
+        let _ = io::stderr().flush();
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            // End of input without 'go' moves nothing
+            return Ok(false);
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] => continue,
+            ["go" | "g" | "yes" | "y"] => return Ok(true),
+            ["quit" | "q" | "no" | "n"] => {
+                stderr!("Nothing will be moved");
+                return Ok(false);
+            }
+            ["all"] => select_all(groups, selected),
+            ["none"] => selected.fill(false),
+            ["list" | "l"] => {}
+            ["?" | "help" | "h"] => {
+                stderr!("  N       skip or include month N");
+                stderr!("  N.M     skip or include file M of month N");
+                stderr!("  show N  list the files of month N (again to hide them)");
+                stderr!("  all     include everything; none skips everything");
+                stderr!("  list    show the months again");
+                stderr!("  go      move what is included; quit moves nothing");
+                continue;
+            }
+            ["show" | "s", n] => match group_index(n, groups.len()) {
+                Some(g) => groups[g].expanded = !groups[g].expanded,
+                None => {
+                    stderr!("There is no month {}", n);
+                    continue;
+                }
+            },
+            [choice] => match choice.split_once('.') {
+                None => match group_index(choice, groups.len()) {
+                    Some(g) => toggle_group(&groups[g], selected),
+                    None => {
+                        stderr!("Not understood: {:?} ('?' for help)", choice);
+                        continue;
+                    }
+                },
+                Some((group, file)) => {
+                    let entry = group_index(group, groups.len())
+                        .and_then(|g| file.parse::<usize>().ok().and_then(|f| groups[g].entries.get(f.checked_sub(1)?)));
+                    match entry {
+                        Some(&i) => selected[i] = !selected[i],
+                        None => {
+                            stderr!("There is no file {}", choice);
+                            continue;
+                        }
+                    }
+                }
+            },
+            _ => {
+                stderr!("Not understood: {:?} ('?' for help)", line.trim());
+                continue;
+            }
+        }
+        show(plan, groups, selected);
+    }
+}
+
+fn select_all(groups: &[Group], selected: &mut [bool]) {
+    for &i in groups.iter().flat_map(|g| &g.entries) {
+        selected[i] = true;
+    }
+}
+
+/// Skip the whole group if it is all included, otherwise include all of it.
+fn toggle_group(group: &Group, selected: &mut [bool]) {
+    let include = !group.entries.iter().all(|&i| selected[i]);
+    for &i in &group.entries {
+        selected[i] = include;
+    }
+}
+
+/// `[x]` when all of the group is included, `[ ]` for none, `[-]` between.
+fn group_mark(group: &Group, selected: &[bool]) -> &'static str {
+    match group.entries.iter().filter(|&&i| selected[i]).count() {
+        0 => "[ ]",
+        n if n == group.entries.len() => "[x]",
+        _ => "[-]",
+    }
+}
+
+/// The bucket's name and how much of it is chosen, as listed.
+fn group_line(plan: &Plan, group: &Group, selected: &[bool]) -> String {
+    let chosen: Vec<usize> = group.entries.iter().copied().filter(|&i| selected[i]).collect();
+    let size: u64 = chosen.iter().map(|&i| plan.entries[i].size).sum();
+    let name = group.bucket.strip_prefix(&plan.base).unwrap_or(&group.bucket);
+    format!("{}: {} of {} files, {}", name.display(), chosen.len(), group.entries.len(), format_size(size))
+}
+
+fn file_line(plan: &Plan, i: usize) -> String {
+    let entry = &plan.entries[i];
+    let name = entry.source.file_name().map(Path::new).unwrap_or(&entry.source);
+    format!("{} ({})", name.display(), format_size(entry.size))
+}
+
+/// The 1-based month number `n`, as an index.
+fn group_index(n: &str, count: usize) -> Option<usize> {
+    n.parse::<usize>().ok().filter(|n| (1..=count).contains(n)).map(|n| n - 1)
+}
+
+fn show(plan: &Plan, groups: &[Group], selected: &[bool]) {
+    stderr!();
+    for (g, group) in groups.iter().enumerate() {
+        stderr!("📅 {} {:>3}. {}", group_mark(group, selected), g + 1, group_line(plan, group, selected));
+        if group.expanded {
+            for (f, &i) in group.entries.iter().enumerate() {
+                stderr!("      {} {}.{} {}", if selected[i] { "[x]" } else { "[ ]" }, g + 1, f + 1, file_line(plan, i));
+            }
+        }
+    }
+    let total = selected.iter().filter(|s| **s).count();
+    stderr!("{} files chosen", total);
+}
+
+/// The full-screen review: the terminal in raw mode on the alternate
+/// screen, redrawn with ANSI escapes after every key.
+#[cfg(unix)]
+mod screen {
+    use std::io::{self, IsTerminal, Read, Write};
+
+    use super::{file_line, group_line, group_mark, select_all, toggle_group, Group};
+    use crate::output;
+    use crate::plan::Plan;
+    use crate::units::format_size;
+
+    /// The terminal settings to put back when the review ends, however it
+    /// ends.
+    pub struct Raw {
+        saved: libc::termios,
+    }
+
+    impl Raw {
+        /// Take over the terminal, or `None` where it can't be drawn on.
+        pub fn enter() -> Option<Raw> {
+            if !io::stderr().is_terminal() || std::env::var_os("TERM").is_some_and(|term| term == "dumb") {
+                return None;
+            }
+            // SAFETY: termios is plain data, filled in by tcgetattr before use.
+            let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+            // SAFETY: both calls only read and write the termios passed in.
+            let raw = unsafe {
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+                    return None;
+                }
+                let mut raw = saved;
+                libc::cfmakeraw(&mut raw);
+                raw
+            };
+            // SAFETY: `raw` is a complete termios derived from the current one.
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+                return None;
+            }
+            output::flush();
+            eprint!("\x1b[?1049h\x1b[?25l");
+            Some(Raw { saved })
+        }
+    }
+
+    impl Drop for Raw {
+        fn drop(&mut self) {
+            eprint!("\x1b[?25h\x1b[?1049l");
+            let _ = io::stderr().flush();
+            // SAFETY: `saved` is what tcgetattr returned for this terminal.
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.saved) };
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Row {
+        Group(usize),
+        /// A group and the file's place in it.
+        File(usize, usize),
+    }
+
+    enum Key {
+        Up,
+        Down,
+        PageUp,
+        PageDown,
+        Home,
+        End,
+        Left,
+        Right,
+        Space,
+        Enter,
+        Quit,
+        Char(u8),
+    }
+
+    const HELP: &str = "↑↓ move  space include/skip  → ← open/close  a all  n none  g move chosen  q quit";
+
+    /// Review on the screen; false if the user quit, so nothing is to move.
+    pub fn review(_raw: Raw, plan: &Plan, groups: &mut [Group], selected: &mut [bool]) -> io::Result<bool> {
+        let (mut cursor, mut top) = (0, 0);
+        loop {
+            let rows = rows(groups);
+            cursor = cursor.min(rows.len() - 1);
+            let (height, width) = size();
+            let body = height.saturating_sub(2).max(1);
+            if cursor < top {
+                top = cursor;
+            } else if cursor >= top + body {
+                top = cursor + 1 - body;
+            }
+            draw(plan, groups, selected, &rows, cursor, top, (height, width))?;
+
+            match key()? {
+                Key::Up | Key::Char(b'k') => cursor = cursor.saturating_sub(1),
+                Key::Down | Key::Char(b'j') => cursor += 1,
+                Key::PageUp => cursor = cursor.saturating_sub(body),
+                Key::PageDown => cursor += body,
+                Key::Home => cursor = 0,
+                Key::End => cursor = rows.len() - 1,
+                Key::Right | Key::Char(b'l') => {
+                    if let Row::Group(g) = rows[cursor] {
+                        groups[g].expanded = true;
+                    }
+                }
+                Key::Left | Key::Char(b'h') => {
+                    let (Row::Group(g) | Row::File(g, _)) = rows[cursor];
+                    groups[g].expanded = false;
+                    cursor = rows.iter().position(|row| *row == Row::Group(g)).unwrap_or(0);
+                }
+                Key::Enter => match rows[cursor] {
+                    Row::Group(g) => groups[g].expanded = !groups[g].expanded,
+                    Row::File(g, f) => selected[groups[g].entries[f]] ^= true,
+                },
+                Key::Space => match rows[cursor] {
+                    Row::Group(g) => toggle_group(&groups[g], selected),
+                    Row::File(g, f) => selected[groups[g].entries[f]] ^= true,
+                },
+                Key::Char(b'a') => select_all(groups, selected),
+                Key::Char(b'n') => selected.fill(false),
+                Key::Char(b'g' | b'y') => return Ok(true),
+                Key::Quit => return Ok(false),
+                Key::Char(_) => {}
+            }
+        }
+    }
+
+    /// The groups, each followed by its files when it is open.
+    fn rows(groups: &[Group]) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (g, group) in groups.iter().enumerate() {
+            rows.push(Row::Group(g));
+            if group.expanded {
+                rows.extend((0..group.entries.len()).map(|f| Row::File(g, f)));
+            }
+        }
+        rows
+    }
+
+    fn draw(
+        plan: &Plan,
+        groups: &[Group],
+        selected: &[bool],
+        rows: &[Row],
+        cursor: usize,
+        top: usize,
+        (height, width): (usize, usize),
+    ) -> io::Result<()> {
+        let chosen: Vec<usize> = (0..selected.len()).filter(|&i| selected[i]).collect();
+        let size: u64 = chosen.iter().map(|&i| plan.entries[i].size).sum();
+        let title = format!("Moves in {}: {} files chosen, {}", plan.base.display(), chosen.len(), format_size(size));
+
+        let mut frame = format!("\x1b[H\x1b[2J\x1b[1m{}\x1b[0m\r\n", clip(&title, width));
+        for (r, row) in rows.iter().enumerate().skip(top).take(height.saturating_sub(2).max(1)) {
+            let line = match *row {
+                Row::Group(g) => {
+                    let group = &groups[g];
+                    let arrow = if group.expanded { "▾" } else { "▸" };
+                    format!("{} {} {}", arrow, group_mark(group, selected), group_line(plan, group, selected))
+                }
+                Row::File(g, f) => {
+                    let i = groups[g].entries[f];
+                    format!("    {} {}", if selected[i] { "[x]" } else { "[ ]" }, file_line(plan, i))
+                }
+            };
+            match r == cursor {
+                true => frame.push_str(&format!("\x1b[7m{}\x1b[0m\r\n", clip(&line, width))),
+                false => frame.push_str(&format!("{}\r\n", clip(&line, width))),
+            }
+        }
+        frame.push_str(&format!("\x1b[{};1H\x1b[7m{}\x1b[0m", height, clip(HELP, width)));
+
+        let mut stderr = io::stderr().lock();
+        stderr.write_all(frame.as_bytes())?;
+        stderr.flush()
+    }
+
+    /// `line` cut to the width of the terminal, so nothing wraps.
+    fn clip(line: &str, width: usize) -> String {
+        line.chars().take(width).collect()
+    }
+
+    /// Rows and columns of the terminal.
+    fn size() -> (usize, usize) {
+        // SAFETY: winsize is plain data for the ioctl to fill in.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        // SAFETY: TIOCGWINSZ only writes the winsize passed in.
+        match unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) } {
+            0 if size.ws_row > 0 && size.ws_col > 0 => (size.ws_row.into(), size.ws_col.into()),
+            _ => (24, 80),
+        }
+    }
+
+    /// The next key pressed. Raw mode delivers an escape sequence in one
+    /// read, and Ctrl-C as a plain byte.
+    fn key() -> io::Result<Key> {
+        let mut buf = [0u8; 8];
+        let n = io::stdin().lock().read(&mut buf)?;
+        Ok(match &buf[..n] {
+            [0x1b, b'[' | b'O', b'A'] => Key::Up,
+            [0x1b, b'[' | b'O', b'B'] => Key::Down,
+            [0x1b, b'[' | b'O', b'C'] => Key::Right,
+            [0x1b, b'[' | b'O', b'D'] => Key::Left,
+            [0x1b, b'[' | b'O', b'H'] | [0x1b, b'[', b'1' | b'7', b'~'] => Key::Home,
+            [0x1b, b'[' | b'O', b'F'] | [0x1b, b'[', b'4' | b'8', b'~'] => Key::End,
+            [0x1b, b'[', b'5', b'~'] => Key::PageUp,
+            [0x1b, b'[', b'6', b'~'] => Key::PageDown,
+            [b' '] => Key::Space,
+            [b'\r' | b'\n'] => Key::Enter,
+            // End of input, Escape, Ctrl-C or Ctrl-D
+            [] | [0x1b] | [3] | [4] | [b'q'] => Key::Quit,
+            [c] => Key::Char(*c),
+            _ => Key::Char(0),
+        })
+    }
+}
+
+/// Only Unix terminals are taken over; elsewhere the review is the prompt.
+#[cfg(not(unix))]
+mod screen {
+    use super::Group;
+    use crate::plan::Plan;
+
+    pub enum Raw {}
+
+    impl Raw {
+        pub fn enter() -> Option<Raw> {
+            None
+        }
+    }
+
+    pub fn review(raw: Raw, _plan: &Plan, _groups: &mut [Group], _selected: &mut [bool]) -> std::io::Result<bool> {
+        match raw {}
+    }
+}
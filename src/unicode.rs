@@ -0,0 +1,231 @@
+//! Unicode normalization of file names, for `--normalize`. macOS writes
+//! names decomposed (NFD) where most Linux tools write them composed (NFC),
+//! so one name can arrive in two spellings that look identical.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::unicode_tables::{COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Form {
+    /// Composed, as Linux and Windows tools write names
+    Nfc,
+    /// Decomposed, as macOS writes names
+    Nfd,
+}
+
+impl Form {
+    pub fn apply(self, s: &str) -> String {
+        match self {
+            Form::Nfc => nfc(s),
+            Form::Nfd => nfd(s),
+        }
+    }
+}
+
+// Hangul syllables compose and decompose by formula
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+fn combining_class(c: char) -> u8 {
+    let cp = c as u32;
+    match COMBINING_CLASSES.binary_search_by(|&(start, end, _)| {
+        if end < cp {
+            std::cmp::Ordering::Less
+        } else if start > cp {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }) {
+        Ok(i) => COMBINING_CLASSES[i].2,
+        Err(_) => 0,
+    }
+}
+
+fn decompose(c: char, out: &mut Vec<char>) {
+    let cp = c as u32;
+    if (S_BASE..S_BASE + S_COUNT).contains(&cp) {
+        let s = cp - S_BASE;
+        out.extend(char::from_u32(L_BASE + s / N_COUNT));
+        out.extend(char::from_u32(V_BASE + (s % N_COUNT) / T_COUNT));
+        if !s.is_multiple_of(T_COUNT) {
+            out.extend(char::from_u32(T_BASE + s % T_COUNT));
+        }
+        return;
+    }
+    match DECOMPOSITIONS.binary_search_by_key(&cp, |&(c, _, _)| c) {
+        Ok(i) => {
+            let (_, first, second) = DECOMPOSITIONS[i];
+            for part in [first, second].into_iter().filter(|&p| p != 0) {
+                decompose(char::from_u32(part).unwrap(), out);
+            }
+        }
+        Err(_) => out.push(c),
+    }
+}
+
+/// Fully decomposed, with each run of combining marks in canonical order.
+fn decomposed(s: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        decompose(c, &mut chars);
+    }
+    let mut i = 0;
+    while i < chars.len() {
+        let run = chars[i..].iter().take_while(|&&c| combining_class(c) != 0).count();
+        if run > 1 {
+            chars[i..i + run].sort_by_key(|&c| combining_class(c));
+        }
+        i += run.max(1);
+    }
+    chars
+}
+
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (first as u32, second as u32);
+    if (L_BASE..L_BASE + L_COUNT).contains(&a) && (V_BASE..V_BASE + V_COUNT).contains(&b) {
+        return char::from_u32(S_BASE + ((a - L_BASE) * V_COUNT + (b - V_BASE)) * T_COUNT);
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&a)
+        && (a - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&b)
+    {
+        return char::from_u32(a + (b - T_BASE));
+    }
+    let i = COMPOSITIONS.binary_search_by_key(&(a, b), |&(x, y, _)| (x, y)).ok()?;
+    char::from_u32(COMPOSITIONS[i].2)
+}
+
+pub fn nfd(s: &str) -> String {
+    decomposed(s).into_iter().collect()
+}
+
+pub fn nfc(s: &str) -> String {
+    let mut out: Vec<char> = Vec::with_capacity(s.len());
+    let mut starter = None;
+    // The class of the last mark kept since the starter, if any
+    let mut last_class = None;
+    for c in decomposed(s) {
+        let class = combining_class(c);
+        if let Some(at) = starter {
+            let unblocked = last_class.is_none_or(|last| last < class);
+            if unblocked {
+                if let Some(composed) = compose_pair(out[at], c) {
+                    out[at] = composed;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+            last_class = None;
+        } else {
+            last_class = Some(class);
+        }
+        out.push(c);
+    }
+    out.into_iter().collect()
+}
+
+/// `target` with every component below `base` in `form`. Names that aren't
+/// UTF-8 are left alone.
+pub fn normalize_path(base: &Path, target: PathBuf, form: Form) -> PathBuf {
+    let Ok(relative) = target.strip_prefix(base) else {
+        return target;
+    };
+    let normalized: PathBuf = relative
+        .iter()
+        .map(|name| name.to_str().map_or_else(|| name.to_os_string(), |s| OsString::from(form.apply(s))))
+        .collect();
+    if normalized == relative {
+        return target;
+    }
+    base.join(normalized)
+}
+
+/// What two spellings of one path have in common, for telling them apart
+/// from different paths.
+pub fn equivalence_key(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) => PathBuf::from(nfc(s)),
+        None => path.to_path_buf(),
+    }
+}
+
+/// The names already in each destination directory, composed, so a new
+/// name can be checked against spellings that differ only in normalization.
+#[derive(Default)]
+pub struct ExistingNames {
+    dirs: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+impl ExistingNames {
+    pub fn contains_equivalent(&mut self, path: &Path) -> bool {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        let names = self.dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+            std::fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| equivalence_key(Path::new(&entry.file_name())))
+                .collect()
+        });
+        names.contains(&equivalence_key(Path::new(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hangul_round_trips() {
+        assert_eq!(nfd("\u{AC00}"), "\u{1100}\u{1161}");
+        assert_eq!(nfc("\u{1100}\u{1161}"), "\u{AC00}");
+        assert_eq!(nfd("\u{AC01}"), "\u{1100}\u{1161}\u{11A8}");
+        assert_eq!(nfc("\u{1100}\u{1161}\u{11A8}"), "\u{AC01}");
+    }
+
+    #[test]
+    fn excluded_compositions_stay_decomposed() {
+        // DEVANAGARI LETTER QA is excluded from composition
+        assert_eq!(nfd("\u{0958}"), "\u{0915}\u{093C}");
+        assert_eq!(nfc("\u{0958}"), "\u{0915}\u{093C}");
+    }
+
+    #[test]
+    fn combining_marks_are_reordered() {
+        // Dot below (class 220) goes before acute (class 230) either way round
+        assert_eq!(nfd("a\u{301}\u{323}"), "a\u{323}\u{301}");
+        assert_eq!(nfd("a\u{323}\u{301}"), "a\u{323}\u{301}");
+        assert_eq!(nfc("a\u{301}\u{323}"), "\u{1EA1}\u{301}");
+        assert_eq!(nfc("e\u{301}"), "\u{E9}");
+    }
+
+    #[test]
+    fn existing_names_match_other_spellings() {
+        let dir = std::env::temp_dir().join(format!("chronoban-unicode-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cafe\u{301}.txt"), "").unwrap();
+
+        assert_eq!(equivalence_key(Path::new("caf\u{E9}.txt")), equivalence_key(Path::new("cafe\u{301}.txt")));
+        let mut existing = ExistingNames::default();
+        assert!(existing.contains_equivalent(&dir.join("caf\u{E9}.txt")));
+        assert!(existing.contains_equivalent(&dir.join("cafe\u{301}.txt")));
+        assert!(!existing.contains_equivalent(&dir.join("cafe.txt")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+/// Parse durations like `500ms`, `30s`, `5m`, `2h` or `1d`. A bare number is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let value: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid duration: {:?}", s))?;
+    let secs = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" | "min" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        "w" => value * 7.0 * 86400.0,
+        other => return Err(format!("unknown duration unit: {:?}", other)),
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("duration out of range: {:?}", s))
+}
+
+/// Parse a point in time: RFC 3339 (`2024-03-31T23:59:00+02:00`), or a
+/// local `2024-03-31 23:59:00` or `2024-03-31` (midnight).
+pub fn parse_instant(s: &str) -> Result<std::time::SystemTime, String> {
+    use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+    let s = s.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.into());
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap()))
+        .map_err(|_| format!("invalid time: {:?}", s))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(Into::into)
+        .ok_or_else(|| format!("time does not exist locally: {:?}", s))
+}
+
+/// Parse sizes like `500`, `10K`, `1.5G` or `2TiB`. Units are binary (1K = 1024).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let value: f64 = num.parse().map_err(|_| format!("invalid size: {:?}", s))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let prefix = unit.strip_suffix("IB").or_else(|| unit.strip_suffix('B')).unwrap_or(&unit);
+    let exponent = match (prefix, unit.as_str()) {
+        (_, "" | "B") => 0,
+        ("K", _) => 1,
+        ("M", _) => 2,
+        ("G", _) => 3,
+        ("T", _) => 4,
+        ("P", _) => 5,
+        _ => return Err(format!("unknown size unit: {:?}", unit)),
+    };
+    let bytes = value * 1024f64.powi(exponent);
+    // Casting would quietly clamp it to u64::MAX
+    if bytes >= u64::MAX as f64 {
+        return Err(format!("size too large: {:?}", s));
+    }
+    Ok(bytes as u64)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// A calendar-aware retention period such as `90d`, `2w`, `12m` or `3y`.
+#[derive(Clone, Copy, Debug)]
+pub enum Retention {
+    Days(u64),
+    Months(u32),
+}
+
+impl Retention {
+    /// The date before which something counts as older than this period, or
+    /// `None` if that is further back than the calendar goes.
+    pub fn cutoff(&self, today: chrono::NaiveDate) -> Option<chrono::NaiveDate> {
+        match *self {
+            Retention::Days(days) => today.checked_sub_days(chrono::Days::new(days)),
+            Retention::Months(months) => today.checked_sub_months(chrono::Months::new(months)),
+        }
+    }
+}
+
+/// Parse retention periods. Unlike [`parse_duration`], `m` means months here.
+pub fn parse_retention(s: &str) -> Result<Retention, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let value: u32 = num.parse().map_err(|_| format!("invalid period: {:?}", s))?;
+    let too_long = || format!("period too long: {:?}", s);
+    let retention = match unit.trim() {
+        "d" => Retention::Days(value.into()),
+        "w" => Retention::Days(u64::from(value) * 7),
+        "m" => Retention::Months(value),
+        "y" => Retention::Months(value.checked_mul(12).ok_or_else(too_long)?),
+        other => return Err(format!("unknown period unit {:?} (use d, w, m or y)", other)),
+    };
+    retention.cutoff(chrono::Local::now().date_naive()).ok_or_else(too_long)?;
+    Ok(retention)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse_duration("0s"), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn bad_durations_are_errors() {
+        assert!(parse_duration("99999999999999999999999999d").is_err());
+        assert!(parse_duration("99999999999999999999y").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("5 fortnights").is_err());
+        assert!(parse_duration("1.2.3s").is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("500B"), Ok(500));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("10kb"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size("2TiB"), Ok(2 << 40));
+        assert_eq!(parse_size("1 MiB"), Ok(1 << 20));
+    }
+
+    #[test]
+    fn bad_sizes_are_errors() {
+        assert!(parse_size("99999999999999999999P").is_err());
+        assert!(parse_size("16EiB").is_err());
+        assert!(parse_size("18446744073709551616").is_err());
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("iB").is_err());
+        assert!(parse_size("1KiBiB").is_err());
+        assert!(parse_size("3 bananas").is_err());
+    }
+
+    #[test]
+    fn formatted_sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(u64::MAX), "16384.0 PiB");
+    }
+
+    #[test]
+    fn retention_periods() {
+        let today = chrono::NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let cutoff = |s| parse_retention(s).unwrap().cutoff(today).unwrap().to_string();
+        assert_eq!(cutoff("90d"), "2024-01-01");
+        assert_eq!(cutoff("2w"), "2024-03-17");
+        // Months keep the calendar, clamping to the end of a shorter month
+        assert_eq!(cutoff("1m"), "2024-02-29");
+        assert_eq!(cutoff("3y"), "2021-03-31");
+        assert_eq!(cutoff("0d"), "2024-03-31");
+    }
+
+    #[test]
+    fn bad_retention_periods_are_errors() {
+        assert!(parse_retention("99999999999999999999y").is_err());
+        assert!(parse_retention("4294967295y").is_err());
+        assert!(parse_retention("4294967295d").is_err());
+        assert!(parse_retention("400000000m").is_err());
+        assert!(parse_retention("").is_err());
+        assert!(parse_retention("30").is_err());
+        assert!(parse_retention("d").is_err());
+        assert!(parse_retention("2 fortnights").is_err());
+        assert!(parse_retention("1.5y").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Windows path rules: names the Win32 layer refuses or treats as devices
+//! (`CON`, `aux.txt`, trailing dots, `:`), and the 260 character limit on
+//! ordinary paths, which the `\\?\` form lifts.
+
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Device names Windows reserves in every directory, with any extension.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// `name` made safe to create on Windows: characters Windows forbids and
+/// trailing dots and spaces become `_`, and a reserved device name gets a
+/// `_` after its stem (`aux.txt` becomes `aux_.txt`).
+pub fn sanitize_name(name: &OsStr) -> Cow<'_, OsStr> {
+    let Some(text) = name.to_str() else {
+        return Cow::Borrowed(name);
+    };
+    let mut safe: String = text
+        .chars()
+        .map(|c| if c < ' ' || "<>:\"|?*\\".contains(c) { '_' } else { c })
+        .collect();
+    let kept = safe.trim_end_matches(['.', ' ']).len();
+    let trailing = safe.len() - kept;
+    safe.truncate(kept);
+    safe.extend(std::iter::repeat_n('_', trailing));
+
+    let stem_len = safe.find('.').unwrap_or(safe.len());
+    let stem = safe[..stem_len].trim_end();
+    if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        safe.insert(stem.len(), '_');
+    }
+    if safe == text {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(OsString::from(safe))
+    }
+}
+
+/// `target` with every component below `base` sanitized for Windows.
+pub fn sanitize(base: &Path, target: PathBuf) -> PathBuf {
+    let Ok(relative) = target.strip_prefix(base) else {
+        return target;
+    };
+    let safe: PathBuf = relative.iter().map(sanitize_name).collect();
+    if safe == relative {
+        return target;
+    }
+    base.join(safe)
+}
+
+/// `path` in the extended `\\?\` form, which Windows lets exceed 260
+/// characters, so deep buckets and long names move like anywhere else.
+#[cfg(windows)]
+pub fn extended(path: &Path) -> Cow<'_, Path> {
+    use std::path::{Component, Prefix};
+
+    // Extended paths are taken literally, so `.` and `..` must go first
+    let Ok(absolute) = std::path::absolute(path) else {
+        return Cow::Borrowed(path);
+    };
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return Cow::Borrowed(path);
+    };
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(_) => {
+            let mut s = OsString::from(r"\\?\");
+            s.push(prefix.as_os_str());
+            s
+        }
+        Prefix::UNC(server, share) => {
+            let mut s = OsString::from(r"\\?\UNC\");
+            s.push(server);
+            s.push(r"\");
+            s.push(share);
+            s
+        }
+        // Already verbatim, or a device path
+        _ => return Cow::Borrowed(path),
+    };
+    let mut named = false;
+    for component in components {
+        if let Component::Normal(name) = component {
+            extended.push(r"\");
+            extended.push(name);
+            named = true;
+        }
+    }
+    if !named {
+        extended.push(r"\");
+    }
+    Cow::Owned(PathBuf::from(extended))
+}
+
+#[cfg(not(windows))]
+pub fn extended(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
@@ -0,0 +1,164 @@
+//! Runs against NTFS paths: times on read-only files and directories,
+//! deleting past the read-only attribute, and dating by creation time.
+
+#![cfg(windows)]
+
+use std::fs::{self, FileTimes};
+use std::os::windows::fs::{FileTimesExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::SystemTime;
+
+/// A directory for one test, removed afterwards even if it holds read-only
+/// files.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Scratch {
+        let dir = std::env::temp_dir().join(format!("chronoban-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        for entry in walkdir::WalkDir::new(&self.0).into_iter().flatten() {
+            set_readonly(entry.path(), false);
+        }
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn chronoban(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chronoban"))
+        .args(["--no-lock", "--config"])
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .arg("-d")
+        .arg(dir.join("base"))
+        .output()
+        .unwrap()
+}
+
+/// Noon UTC on the day, well clear of any bucket boundary.
+fn day(year: i32, month: u32, date: u32) -> SystemTime {
+    chrono::NaiveDate::from_ymd_opt(year, month, date).unwrap().and_hms_opt(12, 0, 0).unwrap().and_utc().into()
+}
+
+/// Set times on a file or directory; directories only open with backup
+/// semantics.
+fn set_times(path: &Path, times: FileTimes) {
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    fs::OpenOptions::new()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+        .unwrap()
+        .set_times(times)
+        .unwrap();
+}
+
+fn set_readonly(path: &Path, readonly: bool) {
+    let Ok(metadata) = fs::metadata(path) else { return };
+    let mut permissions = metadata.permissions();
+    permissions.set_readonly(readonly);
+    let _ = fs::set_permissions(path, permissions);
+}
+
+/// A base directory to organize, with an empty config beside it so no
+/// user configuration leaks in.
+fn setup(name: &str) -> (Scratch, PathBuf) {
+    let scratch = Scratch::new(name);
+    let base = scratch.0.join("base");
+    fs::create_dir(&base).unwrap();
+    fs::write(scratch.0.join("config.toml"), "").unwrap();
+    (scratch, base)
+}
+
+fn assert_success(output: &Output) {
+    assert!(output.status.success(), "chronoban failed: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn copy_keeps_times_of_read_only_file() {
+    let (scratch, base) = setup("readonly-copy");
+    let file = base.join("report.txt");
+    fs::write(&file, "figures").unwrap();
+    set_times(&file, FileTimes::new().set_modified(day(2022, 3, 15)).set_created(day(2021, 11, 2)));
+    set_readonly(&file, true);
+
+    assert_success(&chronoban(&["--copy"], &scratch.0));
+
+    let copy = fs::metadata(base.join("2022-03").join("report.txt")).unwrap();
+    assert!(copy.permissions().readonly());
+    assert_eq!(copy.modified().unwrap(), day(2022, 3, 15));
+    assert_eq!(copy.created().unwrap(), day(2021, 11, 2));
+    assert!(file.exists());
+}
+
+#[test]
+fn copy_keeps_times_of_directory() {
+    let (scratch, base) = setup("dir-copy");
+    let album = base.join("album");
+    fs::create_dir(&album).unwrap();
+    fs::write(album.join("photo.jpg"), "jpeg").unwrap();
+    set_times(&album, FileTimes::new().set_modified(day(2022, 4, 10)).set_created(day(2022, 4, 9)));
+
+    assert_success(&chronoban(&["--copy", "--only", "dirs"], &scratch.0));
+
+    let copy = fs::metadata(base.join("2022-04").join("album")).unwrap();
+    assert_eq!(copy.modified().unwrap(), day(2022, 4, 10));
+    assert_eq!(copy.created().unwrap(), day(2022, 4, 9));
+    assert!(base.join("2022-04").join("album").join("photo.jpg").exists());
+}
+
+#[test]
+fn deletes_read_only_empty_file() {
+    let (scratch, base) = setup("readonly-delete");
+    let file = base.join("download.part");
+    fs::write(&file, "").unwrap();
+    set_times(&file, FileTimes::new().set_modified(day(2022, 5, 20)));
+    set_readonly(&file, true);
+
+    assert_success(&chronoban(&["--empty", "delete"], &scratch.0));
+
+    assert!(!file.exists());
+}
+
+#[test]
+fn rollback_removes_read_only_tree() {
+    let (scratch, base) = setup("readonly-rollback");
+    let tree = base.join("project");
+    fs::create_dir_all(tree.join("docs")).unwrap();
+    let notes = tree.join("docs").join("notes.txt");
+    fs::write(&notes, "notes").unwrap();
+    set_readonly(&notes, true);
+    set_times(&tree, FileTimes::new().set_modified(day(2022, 6, 14)));
+    let failing = base.join("refused-by-hook.txt");
+    fs::write(&failing, "stays").unwrap();
+    set_times(&failing, FileTimes::new().set_modified(day(2022, 6, 15)));
+
+    // The hook refuses one entry, so --atomic has to undo the copied tree
+    let hook = "echo {src} | findstr /L /C:refused-by-hook >nul && exit 1 || exit 0";
+    let output = chronoban(&["--copy", "--atomic", "--pre-hook", hook], &scratch.0);
+
+    assert!(!output.status.success());
+    assert!(!base.join("2022-06").exists());
+    assert!(notes.exists());
+}
+
+#[test]
+fn btime_dates_by_creation_time() {
+    let (scratch, base) = setup("btime");
+    let file = base.join("scan.pdf");
+    fs::write(&file, "pdf").unwrap();
+    set_times(&file, FileTimes::new().set_modified(day(2023, 2, 14)).set_created(day(2021, 6, 15)));
+
+    assert_success(&chronoban(&["--time", "btime"], &scratch.0));
+
+    assert!(base.join("2021-06").join("scan.pdf").exists());
+    assert!(!file.exists());
+}